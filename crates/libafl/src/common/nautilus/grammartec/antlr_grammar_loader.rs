@@ -0,0 +1,618 @@
+//! Translates `ANTLR4` (`.g4`) grammars into a [`Context`].
+//!
+//! Parser rules become plain rules, lexer rules (and fragments) become regex rules.
+//! Actions, semantic predicates, labels, element options and lexer commands are ignored.
+//! Rule arguments, return values, locals, exception handlers and grammar imports are not supported.
+//! Tokens are concatenated without separators, as skipped lexer rules (such as whitespace) are not emitted.
+
+use alloc::{boxed::Box, format, string::String, vec, vec::Vec};
+
+use crate::{
+    Error,
+    common::nautilus::grammartec::{
+        context::Context,
+        grammar_builder::{GrammarExpr, GrammarRule, RuleKind, build_context},
+    },
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Ident(String),
+    Literal(String),
+    /// The raw content of a `[...]` block
+    CharSet(String),
+    /// A `{...}` block
+    Action,
+    Punct(&'static str),
+}
+
+const PUNCTS: [&str; 20] = [
+    "->", "+=", "..", "::", ":", ";", "|", "(", ")", "?", "*", "+", "~", ".", ",", "=", "#", "<",
+    ">", "@",
+];
+
+fn err(line: usize, msg: &str) -> Error {
+    Error::illegal_argument(format!("ANTLR grammar, line {line}: {msg}"))
+}
+
+/// Decodes a single escape sequence after a `\`, advancing `chars`
+fn unescape_char(
+    chars: &mut core::iter::Peekable<core::str::Chars>,
+    line: usize,
+) -> Result<char, Error> {
+    let c = chars
+        .next()
+        .ok_or_else(|| err(line, "unterminated escape sequence"))?;
+    Ok(match c {
+        'n' => '\n',
+        'r' => '\r',
+        't' => '\t',
+        'b' => '\u{8}',
+        'f' => '\u{c}',
+        'u' => {
+            let mut hex = String::new();
+            if chars.peek() == Some(&'{') {
+                chars.next();
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        break;
+                    }
+                    hex.push(c);
+                }
+            } else {
+                for _ in 0..4 {
+                    hex.extend(chars.next());
+                }
+            }
+            u32::from_str_radix(&hex, 16)
+                .ok()
+                .and_then(char::from_u32)
+                .ok_or_else(|| err(line, &format!("invalid unicode escape `\\u{hex}`")))?
+        }
+        'p' | 'P' => return Err(err(line, "unicode property escapes are not supported")),
+        c => c,
+    })
+}
+
+fn tokenize(grammar: &str) -> Result<Vec<(Token, usize)>, Error> {
+    let mut tokens = vec![];
+    let mut line = 1;
+    let mut chars = grammar.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        let start_line = line;
+        match c {
+            '\n' => {
+                line += 1;
+                chars.next();
+            }
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '/' if grammar_rest_starts_with(&chars, "//") => {
+                while chars.next_if(|&c| c != '\n').is_some() {}
+            }
+            '/' if grammar_rest_starts_with(&chars, "/*") => {
+                chars.next();
+                chars.next();
+                let mut last = ' ';
+                loop {
+                    let c = chars
+                        .next()
+                        .ok_or_else(|| err(start_line, "unterminated comment"))?;
+                    if c == '\n' {
+                        line += 1;
+                    }
+                    if last == '*' && c == '/' {
+                        break;
+                    }
+                    last = c;
+                }
+            }
+            '\'' => {
+                chars.next();
+                let mut literal = String::new();
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some('\\') => literal.push(unescape_char(&mut chars, line)?),
+                        Some('\n') | None => {
+                            return Err(err(start_line, "unterminated string literal"));
+                        }
+                        Some(c) => literal.push(c),
+                    }
+                }
+                tokens.push((Token::Literal(literal), start_line));
+            }
+            '[' => {
+                chars.next();
+                let mut set = String::new();
+                loop {
+                    match chars.next() {
+                        Some(']') => break,
+                        Some('\\') => {
+                            set.push('\\');
+                            set.extend(chars.next());
+                        }
+                        Some('\n') | None => {
+                            return Err(err(start_line, "unterminated character set"));
+                        }
+                        Some(c) => set.push(c),
+                    }
+                }
+                tokens.push((Token::CharSet(set), start_line));
+            }
+            '{' => {
+                // Actions may contain arbitrary target code, skip them by balancing braces
+                let mut depth = 0;
+                loop {
+                    match chars.next() {
+                        Some('{') => depth += 1,
+                        Some('}') => {
+                            depth -= 1;
+                            if depth == 0 {
+                                break;
+                            }
+                        }
+                        Some('\n') => line += 1,
+                        Some(_) => {}
+                        None => return Err(err(start_line, "unterminated action")),
+                    }
+                }
+                tokens.push((Token::Action, start_line));
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut ident = String::new();
+                while let Some(c) = chars.next_if(|&c| c.is_alphanumeric() || c == '_') {
+                    ident.push(c);
+                }
+                tokens.push((Token::Ident(ident), start_line));
+            }
+            _ => {
+                let Some(punct) = PUNCTS.iter().find(|p| grammar_rest_starts_with(&chars, p))
+                else {
+                    return Err(err(line, &format!("unexpected character `{c}`")));
+                };
+                for _ in 0..punct.len() {
+                    chars.next();
+                }
+                tokens.push((Token::Punct(punct), start_line));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn grammar_rest_starts_with(chars: &core::iter::Peekable<core::str::Chars>, prefix: &str) -> bool {
+    chars.clone().take(prefix.len()).eq(prefix.chars())
+}
+
+/// Parses the content of a `[...]` set into ranges
+fn parse_char_set(set: &str, line: usize) -> Result<Vec<(char, char)>, Error> {
+    let mut chars = set.chars().peekable();
+    let mut singles = vec![];
+    let mut ranges = vec![];
+    while let Some(c) = chars.next() {
+        let c = if c == '\\' {
+            unescape_char(&mut chars, line)?
+        } else {
+            c
+        };
+        if chars.peek() == Some(&'-') {
+            chars.next();
+            let to = match chars.next() {
+                Some('\\') => unescape_char(&mut chars, line)?,
+                Some(to) => to,
+                None => {
+                    // A trailing `-` is literal
+                    singles.push(c);
+                    singles.push('-');
+                    break;
+                }
+            };
+            if to < c {
+                return Err(err(line, &format!("invalid range `{c}-{to}`")));
+            }
+            ranges.push((c, to));
+        } else {
+            singles.push(c);
+        }
+    }
+    ranges.extend(singles.into_iter().map(|c| (c, c)));
+    Ok(ranges)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    /// If the rule currently parsed is a lexer rule
+    lexer: bool,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(t, _)| t)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.pos + offset).map(|(t, _)| t)
+    }
+
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or(self.tokens.last())
+            .map_or(0, |(_, l)| *l)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).map(|(t, _)| t.clone());
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Punct(p)) if *p == punct) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<(), Error> {
+        if self.eat(punct) {
+            Ok(())
+        } else {
+            Err(err(
+                self.line(),
+                &format!("expected `{punct}`, found {:?}", self.peek()),
+            ))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, Error> {
+        match self.next() {
+            Some(Token::Ident(ident)) => Ok(ident),
+            other => {
+                self.pos -= 1;
+                Err(err(
+                    self.line(),
+                    &format!("expected an identifier, found {other:?}"),
+                ))
+            }
+        }
+    }
+
+    /// Skips everything up to and including the next `;`
+    fn skip_statement(&mut self) {
+        while let Some(token) = self.next() {
+            if token == Token::Punct(";") {
+                break;
+            }
+        }
+    }
+
+    fn grammar(&mut self) -> Result<Vec<GrammarRule>, Error> {
+        let mut rules = vec![];
+        while let Some(token) = self.peek().cloned() {
+            let line = self.line();
+            match token {
+                Token::Ident(ident) => match ident.as_str() {
+                    "lexer" | "parser" | "grammar" | "mode" => self.skip_statement(),
+                    "import" => return Err(err(line, "grammar imports are not supported")),
+                    "options" | "tokens" | "channels"
+                        if self.peek_at(1) == Some(&Token::Action) =>
+                    {
+                        self.pos += 2;
+                    }
+                    _ => rules.push(self.rule()?),
+                },
+                Token::Punct("@") => {
+                    // Named actions, such as `@header {...}` or `@lexer::members {...}`
+                    while !matches!(self.next(), Some(Token::Action) | None) {}
+                }
+                other => return Err(err(line, &format!("unexpected {other:?}"))),
+            }
+        }
+        Ok(rules)
+    }
+
+    fn rule(&mut self) -> Result<GrammarRule, Error> {
+        while matches!(
+            self.peek(),
+            Some(Token::Ident(m)) if ["fragment", "public", "private", "protected"].contains(&m.as_str())
+        ) {
+            self.pos += 1;
+        }
+        let line = self.line();
+        let name = self.ident()?;
+        self.lexer = name.starts_with(|c: char| c.is_uppercase());
+
+        loop {
+            match self.peek() {
+                Some(Token::CharSet(_)) => {
+                    return Err(err(
+                        line,
+                        &format!("rule arguments of `{name}` are not supported"),
+                    ));
+                }
+                Some(Token::Ident(kw))
+                    if ["returns", "locals", "throws"].contains(&kw.as_str()) =>
+                {
+                    return Err(err(
+                        line,
+                        &format!("`{kw}` of rule `{name}` is not supported"),
+                    ));
+                }
+                Some(Token::Ident(kw)) if kw == "options" => self.pos += 2,
+                Some(Token::Punct("@")) => {
+                    while !matches!(self.next(), Some(Token::Action) | None) {}
+                }
+                _ => break,
+            }
+        }
+
+        self.expect(":")?;
+        let expr = self.alternatives()?;
+        self.expect(";")?;
+        if let Some(Token::Ident(kw)) = self.peek()
+            && (kw == "catch" || kw == "finally")
+        {
+            return Err(err(
+                self.line(),
+                &format!("exception handlers of rule `{name}` are not supported"),
+            ));
+        }
+
+        Ok(GrammarRule {
+            name,
+            kind: if self.lexer {
+                RuleKind::Regex
+            } else {
+                RuleKind::Grammar
+            },
+            expr,
+            line,
+        })
+    }
+
+    fn alternatives(&mut self) -> Result<GrammarExpr, Error> {
+        let mut alts = vec![self.sequence()?];
+        while self.eat("|") {
+            alts.push(self.sequence()?);
+        }
+        Ok(if alts.len() == 1 {
+            alts.pop().unwrap()
+        } else {
+            GrammarExpr::Alt(alts)
+        })
+    }
+
+    fn sequence(&mut self) -> Result<GrammarExpr, Error> {
+        let mut items = vec![];
+        loop {
+            match self.peek() {
+                None | Some(Token::Punct("|" | ";" | ")")) => break,
+                Some(Token::Punct("->")) => {
+                    // Lexer commands, such as `-> skip` or `-> channel(HIDDEN)`
+                    while !matches!(self.peek(), None | Some(Token::Punct("|" | ";" | ")"))) {
+                        if self.eat("(") {
+                            while !matches!(self.next(), Some(Token::Punct(")")) | None) {}
+                        } else {
+                            self.pos += 1;
+                        }
+                    }
+                }
+                Some(Token::Punct("#")) => {
+                    // Alternative labels
+                    self.pos += 1;
+                    self.ident()?;
+                }
+                Some(Token::Punct("<")) => self.skip_element_options(),
+                Some(Token::Action) => {
+                    self.pos += 1;
+                    self.eat("?");
+                }
+                _ => items.push(self.element()?),
+            }
+        }
+        Ok(if items.len() == 1 {
+            items.pop().unwrap()
+        } else {
+            GrammarExpr::Seq(items)
+        })
+    }
+
+    fn skip_element_options(&mut self) {
+        while !matches!(self.next(), Some(Token::Punct(">")) | None) {}
+    }
+
+    fn element(&mut self) -> Result<GrammarExpr, Error> {
+        // Labels, such as `x=expr` or `xs+=expr`
+        if matches!(self.peek(), Some(Token::Ident(_)))
+            && matches!(self.peek_at(1), Some(Token::Punct("=" | "+=")))
+        {
+            self.pos += 2;
+        }
+        let atom = self.atom()?;
+        if matches!(self.peek(), Some(Token::Punct("<"))) {
+            self.skip_element_options();
+        }
+        let expr = if self.eat("?") {
+            GrammarExpr::Opt(Box::new(atom))
+        } else if self.eat("*") {
+            GrammarExpr::Star(Box::new(atom))
+        } else if self.eat("+") {
+            GrammarExpr::Plus(Box::new(atom))
+        } else {
+            return Ok(atom);
+        };
+        // Non-greedy suffixes generate the same language
+        self.eat("?");
+        Ok(expr)
+    }
+
+    fn atom(&mut self) -> Result<GrammarExpr, Error> {
+        let line = self.line();
+        match self.next() {
+            Some(Token::Literal(from)) => {
+                if self.eat("..") {
+                    let Some(Token::Literal(to)) = self.next() else {
+                        return Err(err(line, "expected a literal after `..`"));
+                    };
+                    let (Some(from), Some(to)) = (single_char(&from), single_char(&to)) else {
+                        return Err(err(line, "ranges need single character bounds"));
+                    };
+                    Ok(GrammarExpr::CharSet {
+                        ranges: vec![(from, to)],
+                        negated: false,
+                    })
+                } else {
+                    Ok(GrammarExpr::Literal(from))
+                }
+            }
+            Some(Token::Ident(name)) if name == "EOF" => Ok(GrammarExpr::Seq(vec![])),
+            Some(Token::Ident(name)) => Ok(GrammarExpr::Ref { name, line }),
+            Some(Token::CharSet(set)) => Ok(GrammarExpr::CharSet {
+                ranges: parse_char_set(&set, line)?,
+                negated: false,
+            }),
+            Some(Token::Punct("(")) => {
+                if matches!(self.peek(), Some(Token::Ident(kw)) if kw == "options") {
+                    // Subrule options, `( options {...} : ... )`
+                    self.pos += 2;
+                    self.expect(":")?;
+                }
+                let expr = self.alternatives()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Some(Token::Punct(".")) => Ok(GrammarExpr::Any),
+            Some(Token::Punct("~")) => {
+                if !self.lexer {
+                    return Err(err(
+                        line,
+                        "token set negation in parser rules is not supported",
+                    ));
+                }
+                let inner = self.atom()?;
+                let ranges = negatable_ranges(&inner).ok_or_else(|| {
+                    err(
+                        line,
+                        "only character sets, ranges and single characters can be negated",
+                    )
+                })?;
+                Ok(GrammarExpr::CharSet {
+                    ranges,
+                    negated: true,
+                })
+            }
+            other => Err(err(line, &format!("unexpected {other:?}"))),
+        }
+    }
+}
+
+fn single_char(literal: &str) -> Option<char> {
+    let mut chars = literal.chars();
+    let c = chars.next()?;
+    chars.next().is_none().then_some(c)
+}
+
+/// The ranges of a set-like expression, for `~`
+fn negatable_ranges(expr: &GrammarExpr) -> Option<Vec<(char, char)>> {
+    match expr {
+        GrammarExpr::CharSet {
+            ranges,
+            negated: false,
+        } => Some(ranges.clone()),
+        GrammarExpr::Literal(literal) => single_char(literal).map(|c| vec![(c, c)]),
+        GrammarExpr::Alt(alts) => {
+            let mut ranges = vec![];
+            for alt in alts {
+                ranges.extend(negatable_ranges(alt)?);
+            }
+            Some(ranges)
+        }
+        _ => None,
+    }
+}
+
+/// Loads an `ANTLR4` grammar into a [`Context`], starting at the first parser rule.
+///
+/// If the grammar only has lexer rules, the first lexer rule is the start rule.
+pub fn load_antlr_grammar(grammar: &str) -> Result<Context, Error> {
+    let mut parser = Parser {
+        tokens: tokenize(grammar)?,
+        pos: 0,
+        lexer: false,
+    };
+    let rules = parser.grammar()?;
+    let start = rules
+        .iter()
+        .find(|r| r.kind == RuleKind::Grammar)
+        .or(rules.first())
+        .ok_or_else(|| Error::illegal_argument("ANTLR grammar contains no rules"))?
+        .name
+        .clone();
+    build_context(&rules, &start)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec};
+
+    use libafl_bolts::rands::StdRand;
+
+    use super::load_antlr_grammar;
+    use crate::common::nautilus::grammartec::tree::{Tree, TreeLike};
+
+    #[test]
+    fn antlr_expressions() {
+        let grammar = r"
+            grammar Expr;
+            options { language = Java; }
+            @header { package foo; }
+
+            prog : stat+ EOF ;
+            stat : e=expr ';' # printExpr
+                 | ID '=' expr ';' # assign
+                 ;
+            expr : <assoc=right> expr '^' expr
+                 | expr ('*'|'/') expr
+                 | INT
+                 | ID
+                 | '{' expr '}'
+                 ;
+            ID  : [a-z]+ ;
+            INT : DIGIT+ ('.' DIGIT*)? ;
+            fragment DIGIT : '0'..'9' ;
+            WS  : [ \t\r\n]+ -> skip ;
+        ";
+        let mut ctx = load_antlr_grammar(grammar).unwrap();
+        ctx.initialize(30);
+
+        let mut rand = StdRand::with_seed(0);
+        let start = ctx.nt_id("START");
+        for _ in 0..100 {
+            let mut tree = Tree::from_rule_vec(vec![], &ctx);
+            tree.generate_from_nt(&mut rand, start, 30, &ctx);
+            let output = String::from_utf8(tree.unparse_to_vec(&ctx)).unwrap();
+            assert!(output.ends_with(';'), "{output}");
+            assert!(
+                output
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "=;^*/{}.".contains(c)),
+                "{output}"
+            );
+        }
+    }
+
+    #[test]
+    fn antlr_unsupported() {
+        assert!(load_antlr_grammar("grammar A; import B; a : 'x' ;").is_err());
+        assert!(load_antlr_grammar("grammar A; a[int x] : 'x' ;").is_err());
+        assert!(load_antlr_grammar("grammar A; a : b ;").is_err());
+        assert!(load_antlr_grammar("grammar A; a : A ; A : 'x' A ;").is_err());
+        assert!(load_antlr_grammar("grammar A; a : 'x' a ;").is_err());
+    }
+}
@@ -69,6 +69,12 @@ impl Context {
         &self.rules[id]
     }
 
+    /// Get the number of rules
+    #[must_use]
+    pub fn num_rules(&self) -> usize {
+        self.rules.len()
+    }
+
     /// Get the nonterminal ID for a rule
     #[must_use]
     pub fn get_nt(&self, r: &RuleIdOrCustom) -> NTermId {
@@ -0,0 +1,364 @@
+//! An Earley parser turning concrete bytes back into a [`Tree`] of a [`Context`].
+//!
+//! Unlike greedy recursive descent, the Earley algorithm handles every context-free grammar,
+//! including ambiguous, left-recursive and nullable rules.
+//! Regex rules are matched with a lazy DFA, so every possible token length is considered.
+//! Script rules can not be parsed and are ignored.
+
+use alloc::{format, vec, vec::Vec};
+
+use hashbrown::{HashMap, HashSet};
+use regex_automata::{
+    Anchored, MatchKind,
+    hybrid::dfa::{Cache, DFA},
+    nfa::thompson::{self, WhichCaptures},
+    util::start,
+};
+
+use crate::{
+    Error,
+    common::nautilus::grammartec::{
+        context::Context,
+        newtypes::{NTermId, RuleId},
+        rule::{Rule, RuleChild, RuleIdOrCustom},
+        tree::Tree,
+    },
+};
+
+/// A rule with the number of children parsed so far (the dot) and the position it started at
+type Item = (RuleId, usize, usize);
+
+/// Parses bytes into [`Tree`]s of a [`Context`]
+pub struct EarleyParser<'a> {
+    ctx: &'a Context,
+    regexes: HashMap<RuleId, (DFA, Cache)>,
+}
+
+impl core::fmt::Debug for EarleyParser<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "EarleyParser {{}}")
+    }
+}
+
+impl<'a> EarleyParser<'a> {
+    /// Creates a new parser for the rules of `ctx`, compiling all regex rules
+    pub fn new(ctx: &'a Context) -> Result<Self, Error> {
+        let mut regexes = HashMap::new();
+        for rid in (0..ctx.num_rules()).map(RuleId::from) {
+            let Rule::RegExp(rule) = ctx.get_rule(rid) else {
+                continue;
+            };
+            let nfa = thompson::Compiler::new()
+                .configure(
+                    thompson::Config::new()
+                        .which_captures(WhichCaptures::None)
+                        .utf8(false),
+                )
+                .build_from_hir(&rule.hir)
+                .map_err(|err| {
+                    Error::illegal_argument(format!("Cannot compile regex rule: {err}"))
+                })?;
+            let dfa = DFA::builder()
+                .configure(DFA::config().match_kind(MatchKind::All))
+                .build_from_nfa(nfa)
+                .map_err(|err| {
+                    Error::illegal_argument(format!("Cannot compile regex rule: {err}"))
+                })?;
+            let cache = dfa.create_cache();
+            regexes.insert(rid, (dfa, cache));
+        }
+        Ok(Self { ctx, regexes })
+    }
+
+    /// Parses `input` as the nonterminal `nt`, returning `None` if it is not part of the language
+    pub fn parse(&mut self, nt: NTermId, input: &[u8]) -> Result<Option<Tree>, Error> {
+        let mut chart = Chart::new(input.len());
+        chart.predict(self, nt, 0, input)?;
+        for i in 0..=input.len() {
+            let mut next = 0;
+            while let Some(&item) = chart.queues[i].get(next) {
+                next += 1;
+                chart.process(self, item, i, input)?;
+            }
+        }
+
+        let mut stack = HashSet::new();
+        Ok(chart
+            .build_nt(self.ctx, nt, 0, input.len(), input, &mut stack)
+            .map(|rules| Tree::from_rule_vec(rules, self.ctx)))
+    }
+
+    /// All lengths of prefixes of `input` matched by the regex rule `rid`
+    fn regex_match_lens(&mut self, rid: RuleId, input: &[u8]) -> Result<Vec<usize>, Error> {
+        let (dfa, cache) = self
+            .regexes
+            .get_mut(&rid)
+            .ok_or_else(|| Error::key_not_found(format!("No regex for rule {rid:?}")))?;
+        let map_err = |err| Error::illegal_state(format!("Regex rule failed: {err}"));
+
+        let mut lens = vec![];
+        let mut sid = dfa
+            .start_state(cache, &start::Config::new().anchored(Anchored::Yes))
+            .map_err(|err| Error::illegal_state(format!("Regex rule failed: {err}")))?;
+        // Matches are reported one byte delayed
+        for (i, &b) in input.iter().enumerate() {
+            sid = dfa.next_state(cache, sid, b).map_err(map_err)?;
+            if sid.is_match() {
+                lens.push(i);
+            }
+            if sid.is_dead() || sid.is_quit() {
+                return Ok(lens);
+            }
+        }
+        sid = dfa.next_eoi_state(cache, sid).map_err(map_err)?;
+        if sid.is_match() {
+            lens.push(input.len());
+        }
+        Ok(lens)
+    }
+}
+
+/// The Earley chart of a single parse
+struct Chart {
+    /// All items of each position
+    sets: Vec<HashSet<Item>>,
+    /// The items of each position, in the order they are processed
+    queues: Vec<Vec<Item>>,
+    /// The items of each position waiting for a nonterminal
+    waiting: Vec<HashMap<NTermId, Vec<Item>>>,
+    /// The nonterminals already predicted at each position
+    predicted: HashSet<(NTermId, usize)>,
+    /// The ends of all parsed spans of a nonterminal, by start
+    ends: HashMap<(NTermId, usize), Vec<usize>>,
+    /// The starts of all parsed spans of a nonterminal, by end
+    starts: HashMap<(NTermId, usize), Vec<usize>>,
+    /// The rules deriving each parsed span
+    span_rules: HashMap<(NTermId, usize, usize), Vec<RuleId>>,
+}
+
+impl Chart {
+    fn new(len: usize) -> Self {
+        Self {
+            sets: vec![HashSet::new(); len + 1],
+            queues: vec![vec![]; len + 1],
+            waiting: vec![HashMap::new(); len + 1],
+            predicted: HashSet::new(),
+            ends: HashMap::new(),
+            starts: HashMap::new(),
+            span_rules: HashMap::new(),
+        }
+    }
+
+    fn add(&mut self, pos: usize, item: Item) {
+        if self.sets[pos].insert(item) {
+            self.queues[pos].push(item);
+        }
+    }
+
+    /// Records that `rid` derives `nt` from `start` to `end`, returns `true` if the span is new
+    fn complete(&mut self, nt: NTermId, rid: RuleId, start: usize, end: usize) -> bool {
+        let rules = self.span_rules.entry((nt, start, end)).or_default();
+        let new_span = rules.is_empty();
+        if !rules.contains(&rid) {
+            rules.push(rid);
+        }
+        if new_span {
+            self.ends.entry((nt, start)).or_default().push(end);
+            self.starts.entry((nt, end)).or_default().push(start);
+        }
+        new_span
+    }
+
+    fn predict(
+        &mut self,
+        parser: &mut EarleyParser,
+        nt: NTermId,
+        pos: usize,
+        input: &[u8],
+    ) -> Result<(), Error> {
+        if !self.predicted.insert((nt, pos)) {
+            return Ok(());
+        }
+        let ctx = parser.ctx;
+        for &rid in ctx.get_rules_for_nt(nt) {
+            match ctx.get_rule(rid) {
+                Rule::Plain(_) => self.add(pos, (rid, 0, pos)),
+                Rule::RegExp(_) => {
+                    for len in parser.regex_match_lens(rid, &input[pos..])? {
+                        self.complete(nt, rid, pos, pos + len);
+                    }
+                }
+                #[cfg(feature = "nautilus_py")]
+                Rule::Script(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn process(
+        &mut self,
+        parser: &mut EarleyParser,
+        item: Item,
+        pos: usize,
+        input: &[u8],
+    ) -> Result<(), Error> {
+        let (rid, dot, origin) = item;
+        let Rule::Plain(rule) = parser.ctx.get_rule(rid) else {
+            return Ok(());
+        };
+
+        match rule.children.get(dot) {
+            None => {
+                if self.complete(rule.nonterm, rid, origin, pos) {
+                    let waiting = self.waiting[origin]
+                        .get(&rule.nonterm)
+                        .cloned()
+                        .unwrap_or_default();
+                    for (w_rid, w_dot, w_origin) in waiting {
+                        self.add(pos, (w_rid, w_dot + 1, w_origin));
+                    }
+                }
+            }
+            Some(RuleChild::Term(term)) => {
+                if input[pos..].starts_with(term) {
+                    self.add(pos + term.len(), (rid, dot + 1, origin));
+                }
+            }
+            Some(RuleChild::NTerm(nt)) => {
+                self.waiting[pos].entry(*nt).or_default().push(item);
+                self.predict(parser, *nt, pos, input)?;
+                // Spans completed before this item was waiting: regex matches and nullable nonterminals
+                let ends = self.ends.get(&(*nt, pos)).cloned().unwrap_or_default();
+                for end in ends {
+                    self.add(end, (rid, dot + 1, origin));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Builds the preorder rules of a derivation of `nt` from `start` to `end`
+    fn build_nt(
+        &self,
+        ctx: &Context,
+        nt: NTermId,
+        start: usize,
+        end: usize,
+        input: &[u8],
+        stack: &mut HashSet<(NTermId, usize, usize)>,
+    ) -> Option<Vec<RuleIdOrCustom>> {
+        // Do not loop forever on cyclic derivations, such as `A -> B -> A`
+        if !stack.insert((nt, start, end)) {
+            return None;
+        }
+        let mut res = None;
+        for &rid in self.span_rules.get(&(nt, start, end))? {
+            match ctx.get_rule(rid) {
+                Rule::RegExp(_) => {
+                    res = Some(vec![RuleIdOrCustom::Custom(
+                        rid,
+                        input[start..end].to_vec(),
+                    )]);
+                }
+                Rule::Plain(rule) => {
+                    res = self
+                        .build_children(ctx, rid, &rule.children, start, end, input, stack)
+                        .map(|children| {
+                            let mut rules = vec![RuleIdOrCustom::Rule(rid)];
+                            rules.extend(children);
+                            rules
+                        });
+                }
+                #[cfg(feature = "nautilus_py")]
+                Rule::Script(_) => {}
+            }
+            if res.is_some() {
+                break;
+            }
+        }
+        stack.remove(&(nt, start, end));
+        res
+    }
+
+    /// Builds the preorder rules of `children` of the rule `rid` started at `origin`, ending at `end`.
+    ///
+    /// The children are matched from right to left: a prefix of `k` children ends at `pos`
+    /// if the item `(rid, k, origin)` is in the set of `pos`.
+    #[expect(clippy::too_many_arguments)]
+    fn build_children(
+        &self,
+        ctx: &Context,
+        rid: RuleId,
+        children: &[RuleChild],
+        origin: usize,
+        end: usize,
+        input: &[u8],
+        stack: &mut HashSet<(NTermId, usize, usize)>,
+    ) -> Option<Vec<RuleIdOrCustom>> {
+        let Some((last, prefix)) = children.split_last() else {
+            return (origin == end).then(Vec::new);
+        };
+        let prefix_ends_at = |pos: usize| self.sets[pos].contains(&(rid, prefix.len(), origin));
+
+        match last {
+            RuleChild::Term(term) => {
+                let start = end.checked_sub(term.len())?;
+                if &input[start..end] != term.as_slice() || !prefix_ends_at(start) {
+                    return None;
+                }
+                self.build_children(ctx, rid, prefix, origin, start, input, stack)
+            }
+            RuleChild::NTerm(nt) => {
+                for &start in self.starts.get(&(*nt, end))? {
+                    if start < origin || !prefix_ends_at(start) {
+                        continue;
+                    }
+                    let Some(child) = self.build_nt(ctx, *nt, start, end, input, stack) else {
+                        continue;
+                    };
+                    if let Some(mut rules) =
+                        self.build_children(ctx, rid, prefix, origin, start, input, stack)
+                    {
+                        rules.extend(child);
+                        return Some(rules);
+                    }
+                }
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::EarleyParser;
+    use crate::common::nautilus::grammartec::{context::Context, tree::TreeLike};
+
+    #[test]
+    fn earley_roundtrip() {
+        let mut ctx = Context::new();
+        // Left recursive, ambiguous and nullable rules, which greedy parsing can not handle
+        ctx.add_rule("START", b"{EXPR}");
+        ctx.add_rule("EXPR", b"{EXPR}+{EXPR}");
+        ctx.add_rule("EXPR", b"{EXPR}{WS}*{WS}{EXPR}");
+        ctx.add_rule("EXPR", b"({EXPR})");
+        ctx.add_rule("EXPR", b"{NUM}");
+        ctx.add_rule("WS", b"");
+        ctx.add_rule("WS", b" {WS}");
+        ctx.add_regex("NUM", "[0-9]+");
+        ctx.initialize(20);
+
+        let mut parser = EarleyParser::new(&ctx).unwrap();
+        let start = ctx.nt_id("START");
+        for input in [&b"1"[..], b"12+3", b"(1+2)*3", b"1 *  (2+33)*4+5", b"((7))"] {
+            let tree = parser.parse(start, input).unwrap().unwrap();
+            let unparsed: Vec<u8> = tree.unparse_to_vec(&ctx);
+            assert_eq!(unparsed, input);
+        }
+        for input in [&b""[..], b"1+", b"(1", b"1 + 2", b"a"] {
+            assert!(parser.parse(start, input).unwrap().is_none());
+        }
+    }
+}
@@ -0,0 +1,355 @@
+//! Translates ISO 14977 `EBNF` grammars into a [`Context`].
+//!
+//! Supported are rules (`name = ... ;` or `name = ... .`), terminal strings, concatenation (`,`),
+//! alternatives (`|`, `/`, `!`), optional (`[ ]`, `(/ /)`), repeated (`{ }`, `(: :)`) and grouped
+//! sequences, as well as repetition factors (`3 * x`).
+//! Exceptions (`a - b`) and special sequences (`? ... ?`) are not supported.
+
+use alloc::{boxed::Box, format, string::String, vec, vec::Vec};
+
+use crate::{
+    Error,
+    common::nautilus::grammartec::{
+        context::Context,
+        grammar_builder::{GrammarExpr, GrammarRule, RuleKind, build_context},
+    },
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    /// A meta identifier, whitespace inside of it is normalized to a single space
+    Ident(String),
+    Terminal(String),
+    Integer(usize),
+    Special,
+    Punct(&'static str),
+}
+
+const PUNCTS: [&str; 19] = [
+    "(/", "/)", "(:", ":)", "(", ")", "=", ";", ".", "|", "/", "!", ",", "-", "*", "[", "]", "{",
+    "}",
+];
+
+fn err(line: usize, msg: &str) -> Error {
+    Error::illegal_argument(format!("EBNF grammar, line {line}: {msg}"))
+}
+
+fn rest_starts_with(chars: &core::iter::Peekable<core::str::Chars>, prefix: &str) -> bool {
+    chars.clone().take(prefix.len()).eq(prefix.chars())
+}
+
+fn tokenize(grammar: &str) -> Result<Vec<(Token, usize)>, Error> {
+    let mut tokens = vec![];
+    let mut line = 1;
+    let mut chars = grammar.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        let start_line = line;
+        match c {
+            '\n' => {
+                line += 1;
+                chars.next();
+            }
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' if rest_starts_with(&chars, "(*") => {
+                chars.next();
+                chars.next();
+                let mut last = ' ';
+                loop {
+                    let c = chars
+                        .next()
+                        .ok_or_else(|| err(start_line, "unterminated comment"))?;
+                    if c == '\n' {
+                        line += 1;
+                    }
+                    if last == '*' && c == ')' {
+                        break;
+                    }
+                    last = c;
+                }
+            }
+            '\'' | '"' | '?' => {
+                chars.next();
+                let mut content = String::new();
+                loop {
+                    match chars.next() {
+                        Some(q) if q == c => break,
+                        Some('\n') if c != '?' => {
+                            return Err(err(start_line, "unterminated terminal string"));
+                        }
+                        Some(ch) => {
+                            if ch == '\n' {
+                                line += 1;
+                            }
+                            content.push(ch);
+                        }
+                        None => return Err(err(start_line, "unterminated terminal string")),
+                    }
+                }
+                let token = if c == '?' {
+                    Token::Special
+                } else {
+                    Token::Terminal(content)
+                };
+                tokens.push((token, start_line));
+            }
+            c if c.is_ascii_digit() => {
+                let mut digits = String::new();
+                while let Some(c) = chars.next_if(char::is_ascii_digit) {
+                    digits.push(c);
+                }
+                let count = digits
+                    .parse()
+                    .map_err(|_| err(line, &format!("invalid repetition count `{digits}`")))?;
+                tokens.push((Token::Integer(count), start_line));
+            }
+            c if c.is_alphabetic() => {
+                let mut ident = String::new();
+                loop {
+                    while let Some(c) = chars.next_if(|&c| c.is_alphanumeric() || c == '_') {
+                        ident.push(c);
+                    }
+                    // Meta identifiers may contain whitespace between their words
+                    let mut lookahead = chars.clone();
+                    let mut newlines = 0;
+                    while let Some(c) = lookahead.next_if(|c| c.is_whitespace()) {
+                        if c == '\n' {
+                            newlines += 1;
+                        }
+                    }
+                    match lookahead.peek() {
+                        Some(c) if c.is_alphanumeric() => {
+                            ident.push(' ');
+                            line += newlines;
+                            chars = lookahead;
+                        }
+                        _ => break,
+                    }
+                }
+                tokens.push((Token::Ident(ident), start_line));
+            }
+            _ => {
+                let Some(punct) = PUNCTS.iter().find(|p| rest_starts_with(&chars, p)) else {
+                    return Err(err(line, &format!("unexpected character `{c}`")));
+                };
+                for _ in 0..punct.len() {
+                    chars.next();
+                }
+                tokens.push((Token::Punct(punct), start_line));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(t, _)| t)
+    }
+
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or(self.tokens.last())
+            .map_or(0, |(_, l)| *l)
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Punct(p)) if *p == punct) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, puncts: &[&str]) -> Result<(), Error> {
+        if puncts.iter().any(|p| self.eat(p)) {
+            Ok(())
+        } else {
+            Err(err(
+                self.line(),
+                &format!(
+                    "expected `{}`, found {:?}",
+                    puncts.join("` or `"),
+                    self.peek()
+                ),
+            ))
+        }
+    }
+
+    fn syntax(&mut self) -> Result<Vec<GrammarRule>, Error> {
+        let mut rules = vec![];
+        while self.peek().is_some() {
+            let line = self.line();
+            let Some(Token::Ident(name)) = self.peek().cloned() else {
+                return Err(err(
+                    line,
+                    &format!("expected a rule name, found {:?}", self.peek()),
+                ));
+            };
+            self.pos += 1;
+            self.expect(&["="])?;
+            let expr = self.definitions()?;
+            self.expect(&[";", "."])?;
+            rules.push(GrammarRule {
+                name,
+                kind: RuleKind::Grammar,
+                expr,
+                line,
+            });
+        }
+        Ok(rules)
+    }
+
+    fn definitions(&mut self) -> Result<GrammarExpr, Error> {
+        let mut alts = vec![self.single_definition()?];
+        while self.eat("|") || self.eat("/") || self.eat("!") {
+            alts.push(self.single_definition()?);
+        }
+        Ok(if alts.len() == 1 {
+            alts.pop().unwrap()
+        } else {
+            GrammarExpr::Alt(alts)
+        })
+    }
+
+    fn single_definition(&mut self) -> Result<GrammarExpr, Error> {
+        let mut items = vec![];
+        items.extend(self.term()?);
+        while self.eat(",") {
+            items.extend(self.term()?);
+        }
+        Ok(if items.len() == 1 {
+            items.pop().unwrap()
+        } else {
+            GrammarExpr::Seq(items)
+        })
+    }
+
+    /// A factor, `None` for the empty sequence
+    fn term(&mut self) -> Result<Option<GrammarExpr>, Error> {
+        let line = self.line();
+        let factor = self.factor()?;
+        if self.eat("-") {
+            return Err(err(line, "exceptions (`a - b`) are not supported"));
+        }
+        Ok(factor)
+    }
+
+    fn factor(&mut self) -> Result<Option<GrammarExpr>, Error> {
+        let line = self.line();
+        let count = if let Some(&Token::Integer(count)) = self.peek() {
+            self.pos += 1;
+            self.expect(&["*"])?;
+            Some(count)
+        } else {
+            None
+        };
+        let primary = self.primary()?;
+        Ok(match (count, primary) {
+            (Some(_), None) => return Err(err(line, "repetition of an empty sequence")),
+            (Some(count), Some(primary)) => Some(GrammarExpr::Seq(vec![primary; count])),
+            (None, primary) => primary,
+        })
+    }
+
+    fn primary(&mut self) -> Result<Option<GrammarExpr>, Error> {
+        let line = self.line();
+        let Some(token) = self.peek().cloned() else {
+            return Ok(None);
+        };
+        let expr = match token {
+            Token::Ident(name) => GrammarExpr::Ref { name, line },
+            Token::Terminal(terminal) => GrammarExpr::Literal(terminal),
+            Token::Special => {
+                return Err(err(line, "special sequences (`? ... ?`) are not supported"));
+            }
+            Token::Punct(open @ ("[" | "(/" | "{" | "(:" | "(")) => {
+                self.pos += 1;
+                let inner = self.definitions()?;
+                let (close, expr): (&[&str], _) = match open {
+                    "[" | "(/" => (&["]", "/)"], GrammarExpr::Opt(Box::new(inner))),
+                    "{" | "(:" => (&["}", ":)"], GrammarExpr::Star(Box::new(inner))),
+                    _ => (&[")"], inner),
+                };
+                self.expect(close)?;
+                return Ok(Some(expr));
+            }
+            _ => return Ok(None),
+        };
+        self.pos += 1;
+        Ok(Some(expr))
+    }
+}
+
+/// Loads an ISO 14977 `EBNF` grammar into a [`Context`], starting at the first rule.
+pub fn load_ebnf_grammar(grammar: &str) -> Result<Context, Error> {
+    let mut parser = Parser {
+        tokens: tokenize(grammar)?,
+        pos: 0,
+    };
+    let rules = parser.syntax()?;
+    let start = rules
+        .first()
+        .ok_or_else(|| Error::illegal_argument("EBNF grammar contains no rules"))?
+        .name
+        .clone();
+    build_context(&rules, &start)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec};
+
+    use libafl_bolts::rands::StdRand;
+
+    use super::load_ebnf_grammar;
+    use crate::common::nautilus::grammartec::tree::{Tree, TreeLike};
+
+    #[test]
+    fn ebnf_assignments() {
+        let grammar = r#"
+            (* a list of assignments *)
+            program = assignment, { ";", assignment } ;
+            assignment = identifier, "=", ( number | identifier | "{}" ) ;
+            identifier = letter, [ letter | digit ] .
+            number = [ "-" ], digit excluding zero, { digit } | "0" ;
+            letter = "a" | "b" | "c" ;
+            digit excluding zero = "1" / "2" / "3" ;
+            digit = "0" | digit excluding zero | 2 * "9" ;
+        "#;
+        let mut ctx = load_ebnf_grammar(grammar).unwrap();
+        ctx.initialize(30);
+
+        let mut rand = StdRand::with_seed(0);
+        let start = ctx.nt_id("START");
+        for _ in 0..100 {
+            let mut tree = Tree::from_rule_vec(vec![], &ctx);
+            tree.generate_from_nt(&mut rand, start, 30, &ctx);
+            let output = String::from_utf8(tree.unparse_to_vec(&ctx)).unwrap();
+            for assignment in output.split(';') {
+                let (lhs, rhs) = assignment.split_once('=').unwrap();
+                assert!((1..=3).contains(&lhs.len()), "{output}");
+                assert!(
+                    rhs == "{}" || rhs.chars().all(|c| "abc0123-9".contains(c)),
+                    "{output}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn ebnf_unsupported() {
+        assert!(load_ebnf_grammar("a = ? anything ? ;").is_err());
+        assert!(load_ebnf_grammar("a = b - \"x\" ; b = \"y\" ;").is_err());
+        assert!(load_ebnf_grammar("a = b ;").is_err());
+        assert!(load_ebnf_grammar("a = \"x\", a ;").is_err());
+    }
+}
@@ -0,0 +1,639 @@
+//! Lowering of parsed textual grammars (`ANTLR4`, `EBNF`) into a [`Context`].
+//!
+//! The loaders parse their format into [`GrammarRule`]s, and [`build_context`] turns those into
+//! plain, regex and terminal rules. Nested constructs (groups, optionals, repetitions) become
+//! helper nonterminals named after the rule they appear in.
+
+use alloc::{
+    borrow::ToOwned,
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::fmt::Write;
+
+use hashbrown::{HashMap, HashSet};
+
+use crate::{Error, common::nautilus::grammartec::context::Context};
+
+/// The nonterminal every generated tree starts from
+const START_NT: &str = "START";
+
+/// An expression on the right hand side of a grammar rule
+#[derive(Debug, Clone)]
+pub(crate) enum GrammarExpr {
+    /// A literal string
+    Literal(String),
+    /// A reference to another rule
+    Ref {
+        /// The name of the rule, as written in the grammar
+        name: String,
+        /// The line of the reference, for error messages
+        line: usize,
+    },
+    /// A set of inclusive character ranges
+    CharSet {
+        /// The ranges of the set
+        ranges: Vec<(char, char)>,
+        /// If the set matches everything but the `ranges`
+        negated: bool,
+    },
+    /// Any single character
+    Any,
+    /// A sequence of expressions
+    Seq(Vec<GrammarExpr>),
+    /// A choice between expressions
+    Alt(Vec<GrammarExpr>),
+    /// Zero or one occurrences
+    Opt(Box<GrammarExpr>),
+    /// Zero or more occurrences
+    Star(Box<GrammarExpr>),
+    /// One or more occurrences
+    Plus(Box<GrammarExpr>),
+}
+
+/// How a rule is added to the [`Context`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RuleKind {
+    /// Each alternative becomes a plain rule, nested constructs become helper nonterminals
+    Grammar,
+    /// The whole rule is compiled into a single regex, like a lexer token
+    Regex,
+}
+
+/// A named rule of a textual grammar
+#[derive(Debug, Clone)]
+pub(crate) struct GrammarRule {
+    /// The name of the rule, as written in the grammar
+    pub name: String,
+    /// How the rule is lowered
+    pub kind: RuleKind,
+    /// The right hand side of the rule
+    pub expr: GrammarExpr,
+    /// The line the rule is defined at, for error messages
+    pub line: usize,
+}
+
+/// Builds a [`Context`] from `rules`, with a `START` rule deriving `start`.
+///
+/// Only rules reachable from `start` are added. Several rules with the same name are merged into alternatives.
+pub(crate) fn build_context(rules: &[GrammarRule], start: &str) -> Result<Context, Error> {
+    let mut builder = Builder::new(rules)?;
+    if !builder.rules.contains_key(start) {
+        return Err(Error::illegal_argument(format!(
+            "Start rule `{start}` is not defined in the grammar"
+        )));
+    }
+    builder.lower(start)?;
+    let start_format = format!("{{{}}}", builder.names[start]);
+    builder.ctx.add_rule(START_NT, start_format.as_bytes());
+    Ok(builder.ctx)
+}
+
+/// Turns a rule name into a valid nonterminal name, i.e., `[A-Z][a-zA-Z_\-0-9]*`
+fn nonterminal_name(raw: &str) -> String {
+    let mut name: String = raw
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    match name.chars().next() {
+        Some(c) if c.is_ascii_uppercase() => {}
+        Some(c) if c.is_ascii_lowercase() => {
+            name.replace_range(..1, &c.to_ascii_uppercase().to_string());
+        }
+        _ => name.insert(0, 'N'),
+    }
+    name
+}
+
+/// Escapes a literal for use in a rule format
+fn escape_format(literal: &str) -> Vec<u8> {
+    let mut res = Vec::with_capacity(literal.len());
+    for b in literal.bytes() {
+        if b == b'{' || b == b'}' {
+            res.push(b'\\');
+        }
+        res.push(b);
+    }
+    res
+}
+
+fn push_nonterminal(format: &mut Vec<u8>, nt: &str) {
+    format.push(b'{');
+    format.extend_from_slice(nt.as_bytes());
+    format.push(b'}');
+}
+
+/// The alternatives at the top of an expression
+fn alternatives(expr: &GrammarExpr) -> &[GrammarExpr] {
+    match expr {
+        GrammarExpr::Alt(alts) => alts,
+        _ => core::slice::from_ref(expr),
+    }
+}
+
+/// If `expr` can derive a finite string, given the set of rules that can
+fn is_productive(expr: &GrammarExpr, productive: &HashSet<&str>) -> bool {
+    match expr {
+        GrammarExpr::Literal(_)
+        | GrammarExpr::CharSet { .. }
+        | GrammarExpr::Any
+        | GrammarExpr::Opt(_)
+        | GrammarExpr::Star(_) => true,
+        GrammarExpr::Ref { name, .. } => productive.contains(name.as_str()),
+        GrammarExpr::Seq(items) => items.iter().all(|e| is_productive(e, productive)),
+        GrammarExpr::Alt(alts) => alts.iter().any(|e| is_productive(e, productive)),
+        GrammarExpr::Plus(e) => is_productive(e, productive),
+    }
+}
+
+/// Calls `f` for every rule reference in `expr`
+fn for_each_ref<'e, F>(expr: &'e GrammarExpr, f: &mut F) -> Result<(), Error>
+where
+    F: FnMut(&'e str, usize) -> Result<(), Error>,
+{
+    match expr {
+        GrammarExpr::Ref { name, line } => f(name, *line),
+        GrammarExpr::Seq(items) | GrammarExpr::Alt(items) => {
+            items.iter().try_for_each(|e| for_each_ref(e, f))
+        }
+        GrammarExpr::Opt(e) | GrammarExpr::Star(e) | GrammarExpr::Plus(e) => for_each_ref(e, f),
+        GrammarExpr::Literal(_) | GrammarExpr::CharSet { .. } | GrammarExpr::Any => Ok(()),
+    }
+}
+
+struct Builder<'r> {
+    ctx: Context,
+    /// All definitions of a rule, by name
+    rules: HashMap<&'r str, Vec<&'r GrammarRule>>,
+    /// The nonterminal name of each rule
+    names: HashMap<&'r str, String>,
+    /// All nonterminal names in use
+    used_names: HashSet<String>,
+    /// The rules that can derive a finite string
+    productive: HashSet<&'r str>,
+    /// Already compiled regexes of [`RuleKind::Regex`] rules
+    regexes: HashMap<&'r str, String>,
+    /// Counter for helper nonterminals
+    helpers: usize,
+}
+
+impl<'r> Builder<'r> {
+    fn new(rules: &'r [GrammarRule]) -> Result<Self, Error> {
+        let mut builder = Self {
+            ctx: Context::new(),
+            rules: HashMap::new(),
+            names: HashMap::new(),
+            used_names: HashSet::new(),
+            productive: HashSet::new(),
+            regexes: HashMap::new(),
+            helpers: 0,
+        };
+        builder.used_names.insert(START_NT.to_owned());
+
+        for rule in rules {
+            if let Some(first) = builder
+                .rules
+                .get(rule.name.as_str())
+                .and_then(|d| d.first())
+                && first.kind != rule.kind
+            {
+                return Err(Error::illegal_argument(format!(
+                    "line {}: rule `{}` is defined both as lexer and parser rule",
+                    rule.line, rule.name
+                )));
+            }
+            builder.rules.entry(&rule.name).or_default().push(rule);
+            if !builder.names.contains_key(rule.name.as_str()) {
+                let name = builder.unique_name(&nonterminal_name(&rule.name));
+                builder.names.insert(&rule.name, name);
+            }
+        }
+
+        for rule in rules {
+            for_each_ref(&rule.expr, &mut |name, line| {
+                if builder.rules.contains_key(name) {
+                    Ok(())
+                } else {
+                    Err(Error::illegal_argument(format!(
+                        "line {line}: rule `{name}` is referenced but never defined"
+                    )))
+                }
+            })?;
+        }
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for rule in rules {
+                if !builder.productive.contains(rule.name.as_str())
+                    && is_productive(&rule.expr, &builder.productive)
+                {
+                    builder.productive.insert(&rule.name);
+                    changed = true;
+                }
+            }
+        }
+
+        Ok(builder)
+    }
+
+    fn unique_name(&mut self, name: &str) -> String {
+        let mut candidate = name.to_owned();
+        let mut i = 1;
+        while self.used_names.contains(&candidate) {
+            candidate = format!("{name}_{i}");
+            i += 1;
+        }
+        self.used_names.insert(candidate.clone());
+        candidate
+    }
+
+    fn helper_name(&mut self, owner: &str) -> String {
+        self.helpers += 1;
+        let name = format!("{}_{}", self.names[owner], self.helpers);
+        self.unique_name(&name)
+    }
+
+    /// Adds `start` and all rules reachable from it to the context
+    fn lower(&mut self, start: &'r str) -> Result<(), Error> {
+        let mut queue = vec![start];
+        let mut seen: HashSet<&'r str> = queue.iter().copied().collect();
+        while let Some(name) = queue.pop() {
+            let defs = self.rules[name].clone();
+            if !self.productive.contains(name) {
+                return Err(Error::illegal_argument(format!(
+                    "line {}: rule `{name}` can never terminate (missing base case?)",
+                    defs[0].line
+                )));
+            }
+            let nt = self.names[name].clone();
+            if defs[0].kind == RuleKind::Regex {
+                let regex = self.rule_regex(name, &mut Vec::new())?;
+                self.ctx.add_regex(&nt, &regex);
+                continue;
+            }
+            for def in defs {
+                for_each_ref(&def.expr, &mut |referenced, _| {
+                    let referenced: &'r str = self.rules.get_key_value(referenced).unwrap().0;
+                    if seen.insert(referenced) {
+                        queue.push(referenced);
+                    }
+                    Ok(())
+                })?;
+                self.add_alternatives(name, &nt, &def.expr)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds one plain rule per productive alternative of `expr` to `nt`
+    fn add_alternatives(&mut self, owner: &str, nt: &str, expr: &GrammarExpr) -> Result<(), Error> {
+        for alt in alternatives(expr) {
+            if !is_productive(alt, &self.productive) {
+                continue;
+            }
+            let mut format = vec![];
+            self.append_format(owner, alt, &mut format)?;
+            self.ctx.add_rule(nt, &format);
+        }
+        Ok(())
+    }
+
+    /// Appends the format of `expr` to `format`, adding helper nonterminals as needed
+    fn append_format(
+        &mut self,
+        owner: &str,
+        expr: &GrammarExpr,
+        format: &mut Vec<u8>,
+    ) -> Result<(), Error> {
+        match expr {
+            GrammarExpr::Literal(literal) => {
+                if literal.contains('\\') {
+                    // Backslashes interfere with the escaping of the rule format
+                    let nt = self.helper_name(owner);
+                    self.ctx.add_term_rule(&nt, literal.as_bytes());
+                    push_nonterminal(format, &nt);
+                } else {
+                    format.extend_from_slice(&escape_format(literal));
+                }
+            }
+            GrammarExpr::Ref { name, .. } => push_nonterminal(format, &self.names[name.as_str()]),
+            GrammarExpr::Seq(items) => {
+                for item in items {
+                    self.append_format(owner, item, format)?;
+                }
+            }
+            GrammarExpr::Alt(_) => {
+                let nt = self.helper_name(owner);
+                self.add_alternatives(owner, &nt, expr)?;
+                push_nonterminal(format, &nt);
+            }
+            GrammarExpr::Opt(inner) | GrammarExpr::Star(inner) | GrammarExpr::Plus(inner) => {
+                let nt = self.helper_name(owner);
+                if !matches!(expr, GrammarExpr::Plus(_)) {
+                    self.ctx.add_rule(&nt, b"");
+                }
+                if is_productive(inner, &self.productive) {
+                    let mut once = vec![];
+                    self.append_format(owner, inner, &mut once)?;
+                    if !matches!(expr, GrammarExpr::Opt(_)) {
+                        let mut repeated = once.clone();
+                        push_nonterminal(&mut repeated, &nt);
+                        self.ctx.add_rule(&nt, &repeated);
+                    }
+                    if !matches!(expr, GrammarExpr::Star(_)) {
+                        self.ctx.add_rule(&nt, &once);
+                    }
+                }
+                push_nonterminal(format, &nt);
+            }
+            GrammarExpr::CharSet { .. } | GrammarExpr::Any => {
+                let nt = self.helper_name(owner);
+                let regex = self.regex(owner, expr, &mut Vec::new())?;
+                self.ctx.add_regex(&nt, &regex);
+                push_nonterminal(format, &nt);
+            }
+        }
+        Ok(())
+    }
+
+    /// Compiles all definitions of the [`RuleKind::Regex`] rule `name` into a single regex
+    fn rule_regex(&mut self, name: &'r str, stack: &mut Vec<&'r str>) -> Result<String, Error> {
+        if let Some(regex) = self.regexes.get(name) {
+            return Ok(regex.clone());
+        }
+        let defs = self.rules[name].clone();
+        if defs[0].kind != RuleKind::Regex {
+            return Err(Error::illegal_argument(format!(
+                "line {}: lexer rule `{}` references parser rule `{name}`",
+                defs[0].line,
+                stack.last().unwrap_or(&name)
+            )));
+        }
+        if stack.contains(&name) {
+            return Err(Error::illegal_argument(format!(
+                "line {}: recursive lexer rule `{name}` cannot be expressed as a regex",
+                defs[0].line
+            )));
+        }
+
+        stack.push(name);
+        let mut parts = vec![];
+        for def in &defs {
+            parts.push(self.regex(name, &def.expr, stack)?);
+        }
+        stack.pop();
+
+        let regex = if parts.len() == 1 {
+            parts.pop().unwrap()
+        } else {
+            format!("(?:{})", parts.join("|"))
+        };
+        regex_syntax::ParserBuilder::new()
+            .unicode(true)
+            .utf8(false)
+            .build()
+            .parse(&regex)
+            .map_err(|err| {
+                Error::illegal_argument(format!(
+                    "line {}: lexer rule `{name}` is not a valid regex: {err}",
+                    defs[0].line
+                ))
+            })?;
+        self.regexes.insert(name, regex.clone());
+        Ok(regex)
+    }
+
+    fn regex(
+        &mut self,
+        owner: &str,
+        expr: &GrammarExpr,
+        stack: &mut Vec<&'r str>,
+    ) -> Result<String, Error> {
+        Ok(match expr {
+            GrammarExpr::Literal(literal) => regex_syntax::escape(literal),
+            GrammarExpr::Ref { name, .. } => {
+                let name: &'r str = self.rules.get_key_value(name.as_str()).unwrap().0;
+                format!("(?:{})", self.rule_regex(name, stack)?)
+            }
+            GrammarExpr::CharSet { ranges, negated } => {
+                if ranges.is_empty() {
+                    if *negated {
+                        "(?s:.)".to_owned()
+                    } else {
+                        return Err(Error::illegal_argument(format!(
+                            "empty character set in rule `{owner}`"
+                        )));
+                    }
+                } else {
+                    let mut class = String::from(if *negated { "[^" } else { "[" });
+                    for (from, to) in ranges {
+                        write!(class, "\\x{{{:x}}}-\\x{{{:x}}}", *from as u32, *to as u32).unwrap();
+                    }
+                    class.push(']');
+                    class
+                }
+            }
+            GrammarExpr::Any => "(?s:.)".to_owned(),
+            GrammarExpr::Seq(items) => {
+                let mut regex = String::new();
+                for item in items {
+                    regex.push_str(&self.regex(owner, item, stack)?);
+                }
+                regex
+            }
+            GrammarExpr::Alt(alts) => {
+                let mut parts = vec![];
+                for alt in alts {
+                    parts.push(self.regex(owner, alt, stack)?);
+                }
+                format!("(?:{})", parts.join("|"))
+            }
+            GrammarExpr::Opt(inner) => format!("(?:{})?", self.regex(owner, inner, stack)?),
+            GrammarExpr::Star(inner) => format!("(?:{})*", self.regex(owner, inner, stack)?),
+            GrammarExpr::Plus(inner) => format!("(?:{})+", self.regex(owner, inner, stack)?),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{
+        boxed::Box,
+        string::{String, ToString},
+        vec,
+        vec::Vec,
+    };
+
+    use libafl_bolts::rands::StdRand;
+
+    use super::{
+        GrammarExpr, GrammarRule, RuleKind, build_context, escape_format, nonterminal_name,
+    };
+    use crate::common::nautilus::grammartec::tree::{Tree, TreeLike};
+
+    fn lit(s: &str) -> GrammarExpr {
+        GrammarExpr::Literal(s.to_string())
+    }
+
+    fn reference(name: &str) -> GrammarExpr {
+        GrammarExpr::Ref {
+            name: name.to_string(),
+            line: 1,
+        }
+    }
+
+    fn rule(name: &str, kind: RuleKind, expr: GrammarExpr) -> GrammarRule {
+        GrammarRule {
+            name: name.to_string(),
+            kind,
+            expr,
+            line: 1,
+        }
+    }
+
+    fn generate(rules: &[GrammarRule], start: &str) -> Vec<String> {
+        let mut ctx = build_context(rules, start).unwrap();
+        ctx.initialize(30);
+        let mut rand = StdRand::with_seed(0);
+        let start = ctx.nt_id("START");
+        (0..100)
+            .map(|_| {
+                let mut tree = Tree::from_rule_vec(vec![], &ctx);
+                tree.generate_from_nt(&mut rand, start, 30, &ctx);
+                String::from_utf8(tree.unparse_to_vec(&ctx)).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn names_and_escaping() {
+        assert_eq!(nonterminal_name("expr"), "Expr");
+        assert_eq!(nonterminal_name("Expr"), "Expr");
+        assert_eq!(
+            nonterminal_name("digit excluding zero"),
+            "Digit_excluding_zero"
+        );
+        assert_eq!(nonterminal_name("_hidden"), "N_hidden");
+        assert_eq!(escape_format("{a}"), b"\\{a\\}");
+    }
+
+    #[test]
+    fn lower_nested_constructs() {
+        // list = "[" , [ item , { "," , item } ] , "]" ;
+        // item = "x" | digits ;
+        // digits (lexer) = [0-9]+ ;
+        let rules = vec![
+            rule(
+                "list",
+                RuleKind::Grammar,
+                GrammarExpr::Seq(vec![
+                    lit("["),
+                    GrammarExpr::Opt(Box::new(GrammarExpr::Seq(vec![
+                        reference("item"),
+                        GrammarExpr::Star(Box::new(GrammarExpr::Seq(vec![
+                            lit(","),
+                            reference("item"),
+                        ]))),
+                    ]))),
+                    lit("]"),
+                ]),
+            ),
+            rule(
+                "item",
+                RuleKind::Grammar,
+                GrammarExpr::Alt(vec![lit("x"), reference("digits")]),
+            ),
+            rule(
+                "digits",
+                RuleKind::Regex,
+                GrammarExpr::Plus(Box::new(GrammarExpr::CharSet {
+                    ranges: vec![('0', '9')],
+                    negated: false,
+                })),
+            ),
+            // unreachable from the start rule, and never added
+            rule("unused", RuleKind::Grammar, reference("unused")),
+        ];
+
+        let mut saw_list = false;
+        for output in generate(&rules, "list") {
+            let inner = output
+                .strip_prefix('[')
+                .and_then(|o| o.strip_suffix(']'))
+                .unwrap_or_else(|| panic!("{output}"));
+            if inner.is_empty() {
+                continue;
+            }
+            saw_list |= inner.contains(',');
+            for item in inner.split(',') {
+                assert!(
+                    item == "x" || (!item.is_empty() && item.chars().all(|c| c.is_ascii_digit())),
+                    "{output}"
+                );
+            }
+        }
+        assert!(saw_list);
+    }
+
+    #[test]
+    fn lower_errors() {
+        let x = rule("a", RuleKind::Grammar, lit("x"));
+
+        // unknown start rule
+        assert!(build_context(core::slice::from_ref(&x), "b").is_err());
+        // undefined reference
+        assert!(build_context(&[rule("a", RuleKind::Grammar, reference("b"))], "a").is_err());
+        // no base case
+        assert!(
+            build_context(
+                &[rule(
+                    "a",
+                    RuleKind::Grammar,
+                    GrammarExpr::Seq(vec![lit("x"), reference("a")])
+                )],
+                "a"
+            )
+            .is_err()
+        );
+        // lexer and parser rule with the same name
+        assert!(build_context(&[x.clone(), rule("a", RuleKind::Regex, lit("y"))], "a").is_err());
+        // lexer rule referencing a parser rule
+        assert!(
+            build_context(
+                &[
+                    rule("a", RuleKind::Regex, reference("b")),
+                    rule("b", RuleKind::Grammar, lit("x"))
+                ],
+                "a"
+            )
+            .is_err()
+        );
+        // recursive lexer rule
+        assert!(
+            build_context(
+                &[rule(
+                    "a",
+                    RuleKind::Regex,
+                    GrammarExpr::Alt(vec![
+                        lit("x"),
+                        GrammarExpr::Seq(vec![lit("x"), reference("a")])
+                    ])
+                )],
+                "a"
+            )
+            .is_err()
+        );
+
+        assert!(build_context(&[x], "a").is_ok());
+    }
+}
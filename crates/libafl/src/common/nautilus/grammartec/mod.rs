@@ -1,8 +1,16 @@
 //! The grammartec module contains the grammar-based mutator and related structures.
+/// Module to load grammars from `ANTLR4` files
+pub mod antlr_grammar_loader;
 /// Chunkstore module
 pub mod chunkstore;
 /// Context module
 pub mod context;
+/// Earley parser module, turning bytes into trees
+pub mod earley;
+/// Module to load grammars from `EBNF` files
+pub mod ebnf_grammar_loader;
+/// Shared lowering of textual grammars into a [`context::Context`]
+mod grammar_builder;
 /// Mutator module
 pub mod mutator;
 /// Newtypes module
@@ -0,0 +1,454 @@
+//! A greedy whole corpus minimizer, which does not need `z3` and scales to very large corpora.
+//!
+//! Like `afl-cmin`, it keeps the cheapest testcase for every covered map entry and hit count.
+//! See the `MapCorpusMinimizer` of the `cmin` feature for the optimal, but much slower, `MaxSAT` based minimizer.
+
+use alloc::{borrow::Cow, string::ToString, vec::Vec};
+use core::{hash::Hash, marker::PhantomData, time::Duration};
+
+use hashbrown::HashSet;
+use libafl_bolts::{
+    AsIter, Named,
+    tuples::{Handle, Handled},
+};
+use num_traits::ToPrimitive;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Error, HasMetadata, HasScheduler,
+    corpus::{Corpus, CorpusId},
+    events::{Event, EventFirer, EventWithStats, LogSeverity},
+    executors::{Executor, ExitKind, HasObservers},
+    inputs::Input,
+    monitors::stats::{AggregatorOps, UserStats, UserStatsValue},
+    observers::{MapObserver, ObserversTuple},
+    schedulers::{LenTimeMulTestcasePenalty, RemovableScheduler, Scheduler, TestcasePenalty},
+    stages::run_target_with_timing,
+    state::{HasCorpus, HasExecutions},
+};
+
+/// The map entries covered by one testcase of the corpus
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeedCoverage {
+    /// The id of the testcase
+    pub id: CorpusId,
+    /// The indexes of the map entries the testcase covers
+    pub edges: Vec<usize>,
+}
+
+/// Which map entries each testcase of a (minimized) corpus covers
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CorpusCoverageReport {
+    /// The coverage of each testcase
+    pub seeds: Vec<SeedCoverage>,
+    /// The number of testcases removed by the minimization
+    pub removed: usize,
+    /// The number of removed testcases that did not exit normally, see [`GreedyCorpusMinimizer::with_remove_failing`]
+    pub failing: usize,
+}
+
+/// A testcase with its weight and the map entries it covers, with their hit counts
+type SeedFeatures<T> = (CorpusId, u64, Vec<(usize, T)>);
+
+/// Minimizes a corpus according to coverage maps, like `afl-cmin`.
+///
+/// Every testcase is executed once. Then, going from the lowest to the highest `TestcasePenalty`,
+/// a testcase is kept if it covers an entry of the map with a hit count no kept testcase covers.
+/// The result is not minimal like the `MaxSAT` solution of the `MapCorpusMinimizer`, but it takes
+/// linear time in the number of covered entries.
+/// Testcases that do not exit normally can be removed in the same pass, see [`Self::with_remove_failing`].
+#[derive(Debug)]
+pub struct GreedyCorpusMinimizer<C, E, I, O, S, T, TP> {
+    observer_handle: Handle<C>,
+    remove_failing: bool,
+    phantom: PhantomData<(E, I, O, S, T, TP)>,
+}
+
+/// Standard greedy corpus minimizer, which weights inputs by length and time.
+pub type StdGreedyCorpusMinimizer<C, E, I, O, S, T> =
+    GreedyCorpusMinimizer<C, E, I, O, S, T, LenTimeMulTestcasePenalty>;
+
+impl<C, E, I, O, S, T, TP> GreedyCorpusMinimizer<C, E, I, O, S, T, TP>
+where
+    C: Named,
+{
+    /// Constructs a new `GreedyCorpusMinimizer` from a provided observer. This observer will be used
+    /// to get observed maps from an executed input.
+    pub fn new(obs: &C) -> Self {
+        Self {
+            observer_handle: obs.handle(),
+            remove_failing: false,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Removes the testcases that crash or time out while they are executed, like `afl-cmin`.
+    ///
+    /// By default, they are kept and weighted as if they ran for one second.
+    #[must_use]
+    pub fn with_remove_failing(mut self, remove_failing: bool) -> Self {
+        self.remove_failing = remove_failing;
+        self
+    }
+}
+
+impl<C, E, I, O, S, T, TP> GreedyCorpusMinimizer<C, E, I, O, S, T, TP>
+where
+    for<'a> O: MapObserver<Entry = T> + AsIter<'a, Item = T>,
+    C: AsRef<O>,
+    I: Input,
+    S: HasMetadata + HasCorpus<I> + HasExecutions,
+    T: Copy + Hash + Eq,
+    TP: TestcasePenalty<I, S>,
+{
+    /// Executes every testcase of the corpus and collects the covered map entries with their hit counts,
+    /// and the weight of the testcase.
+    ///
+    /// If failing testcases are removed, they are returned separately.
+    fn collect<EM, Z>(
+        &self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        mgr: &mut EM,
+        state: &mut S,
+    ) -> Result<(Vec<SeedFeatures<T>>, Vec<CorpusId>), Error>
+    where
+        E: Executor<EM, I, S, Z> + HasObservers,
+        E::Observers: ObserversTuple<I, S>,
+        EM: EventFirer<I, S>,
+    {
+        mgr.log(
+            state,
+            LogSeverity::Info,
+            "Executing each input...".to_string(),
+        )?;
+
+        let total = state.corpus().count() as u64;
+        let mut seeds = Vec::with_capacity(state.corpus().count());
+        let mut failing = Vec::new();
+        let mut cur_id = state.corpus().first();
+        while let Some(id) = cur_id {
+            let input = state
+                .corpus()
+                .get(id)?
+                .borrow_mut()
+                .load_input(state.corpus())?
+                .clone();
+            let (exit_kind, mut total_time, _) =
+                run_target_with_timing(fuzzer, executor, state, mgr, &input, false)?;
+            if exit_kind != ExitKind::Ok {
+                if self.remove_failing {
+                    log::info!("Removing {id}: {exit_kind:?}");
+                    failing.push(id);
+                    cur_id = state.corpus().next(id);
+                    continue;
+                }
+                total_time = Duration::from_secs(1);
+            }
+
+            let weight = {
+                let mut testcase = state.corpus().get(id)?.borrow_mut();
+                testcase.set_exec_time(total_time);
+                TP::compute(state, &mut *testcase)?
+                    .to_u64()
+                    .expect("Weight must be computable.")
+            };
+
+            let observers = executor.observers();
+            let obs = observers[&self.observer_handle].as_ref();
+            let initial = obs.initial();
+            let features = obs
+                .as_iter()
+                .map(|x| *x)
+                .enumerate()
+                .filter(|(_, e)| *e != initial)
+                .collect();
+            seeds.push((id, weight, features));
+
+            mgr.fire(
+                state,
+                EventWithStats::with_current_time(
+                    Event::UpdateUserStats {
+                        name: Cow::from("minimisation exec pass"),
+                        value: UserStats::new(
+                            UserStatsValue::Ratio((seeds.len() + failing.len()) as u64, total),
+                            AggregatorOps::None,
+                        ),
+                        phantom: PhantomData,
+                    },
+                    *state.executions(),
+                ),
+            )?;
+
+            cur_id = state.corpus().next(id);
+        }
+        Ok((seeds, failing))
+    }
+
+    /// Removes the given testcases from the corpus and the scheduler
+    fn remove<CS, Z>(fuzzer: &mut Z, state: &mut S, mut ids: Vec<CorpusId>) -> Result<(), Error>
+    where
+        CS: RemovableScheduler<I, S>,
+        Z: HasScheduler<I, S, Scheduler = CS>,
+    {
+        // reverse order; if indexes are stored in a vec, we need to remove from back to front
+        ids.sort_unstable_by(|id1, id2| id2.cmp(id1));
+        for id in ids {
+            let removed = state.corpus_mut().remove(id)?;
+            // scheduler needs to know we've removed the input, or it will continue to try
+            // to use now-missing inputs
+            fuzzer
+                .scheduler_mut()
+                .on_remove(state, id, &Some(removed))?;
+        }
+        Ok(())
+    }
+
+    /// Executes every testcase and reports the map entries it covers.
+    ///
+    /// Only removes the failing testcases, if [`Self::with_remove_failing`] is set.
+    pub fn coverage_report<CS, EM, Z>(
+        &self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        mgr: &mut EM,
+        state: &mut S,
+    ) -> Result<CorpusCoverageReport, Error>
+    where
+        E: Executor<EM, I, S, Z> + HasObservers,
+        E::Observers: ObserversTuple<I, S>,
+        CS: RemovableScheduler<I, S>,
+        EM: EventFirer<I, S>,
+        Z: HasScheduler<I, S, Scheduler = CS>,
+    {
+        let (seeds, failing) = self.collect(fuzzer, executor, mgr, state)?;
+        let seeds = seeds
+            .into_iter()
+            .map(|(id, _, features)| SeedCoverage {
+                id,
+                edges: features.into_iter().map(|(idx, _)| idx).collect(),
+            })
+            .collect();
+        let report = CorpusCoverageReport {
+            seeds,
+            removed: failing.len(),
+            failing: failing.len(),
+        };
+        Self::remove(fuzzer, state, failing)?;
+        Ok(report)
+    }
+
+    /// Do the minimization, and report the map entries each kept testcase covers
+    pub fn minimize<CS, EM, Z>(
+        &self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        mgr: &mut EM,
+        state: &mut S,
+    ) -> Result<CorpusCoverageReport, Error>
+    where
+        E: Executor<EM, I, S, Z> + HasObservers,
+        E::Observers: ObserversTuple<I, S>,
+        CS: Scheduler<I, S> + RemovableScheduler<I, S>,
+        EM: EventFirer<I, S>,
+        Z: HasScheduler<I, S, Scheduler = CS>,
+    {
+        // don't delete this else it won't work after restart
+        let current = *state.corpus().current();
+
+        let (mut seeds, failing) = self.collect(fuzzer, executor, mgr, state)?;
+
+        mgr.log(state, LogSeverity::Info, "Selecting inputs...".to_string())?;
+
+        seeds.sort_unstable_by_key(|(id, weight, _)| (*weight, *id));
+        let mut covered = HashSet::new();
+        let mut report = CorpusCoverageReport {
+            failing: failing.len(),
+            ..CorpusCoverageReport::default()
+        };
+        let mut removed = failing;
+        for (id, _, features) in seeds {
+            let mut new_coverage = false;
+            for feature in &features {
+                new_coverage |= covered.insert(*feature);
+            }
+            if new_coverage || current == Some(id) {
+                report.seeds.push(SeedCoverage {
+                    id,
+                    edges: features.into_iter().map(|(idx, _)| idx).collect(),
+                });
+            } else {
+                removed.push(id);
+            }
+        }
+
+        report.removed = removed.len();
+        Self::remove(fuzzer, state, removed)?;
+        report.seeds.sort_unstable_by_key(|seed| seed.id);
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use libafl_bolts::{
+        Error,
+        rands::StdRand,
+        tuples::{RefIndexable, tuple_list, tuple_list_type},
+    };
+
+    use super::GreedyCorpusMinimizer;
+    use crate::{
+        StdFuzzer,
+        corpus::{Corpus, CorpusId, InMemoryCorpus, Testcase},
+        events::NopEventManager,
+        executors::{Executor, ExitKind, HasObservers},
+        inputs::{BytesInput, HasMutatorBytes},
+        observers::{MapObserver, StdMapObserver},
+        schedulers::{QueueScheduler, TestcasePenalty},
+        state::{HasCorpus, StdState},
+    };
+
+    /// Counts the occurrences of each byte value in the map, crashes on the byte 7
+    struct CountingExecutor {
+        observers: tuple_list_type!(StdMapObserver<'static, u8, false>),
+    }
+
+    impl<EM, S, Z> Executor<EM, BytesInput, S, Z> for CountingExecutor {
+        fn run_target(
+            &mut self,
+            _fuzzer: &mut Z,
+            _state: &mut S,
+            _mgr: &mut EM,
+            input: &BytesInput,
+        ) -> Result<ExitKind, Error> {
+            let map = &mut self.observers.0;
+            for byte in input.mutator_bytes() {
+                let idx = usize::from(*byte);
+                map.set(idx, map.get(idx) + 1);
+            }
+            if input.mutator_bytes().contains(&7) {
+                return Ok(ExitKind::Crash);
+            }
+            Ok(ExitKind::Ok)
+        }
+    }
+
+    impl HasObservers for CountingExecutor {
+        type Observers = tuple_list_type!(StdMapObserver<'static, u8, false>);
+
+        fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+            RefIndexable::from(&self.observers)
+        }
+
+        fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+            RefIndexable::from(&mut self.observers)
+        }
+    }
+
+    /// Weights testcases by length only, the execution times of the test are too short to compare
+    struct LenPenalty;
+
+    impl<S> TestcasePenalty<BytesInput, S> for LenPenalty {
+        #[expect(clippy::cast_precision_loss)]
+        fn compute(_state: &S, entry: &mut Testcase<BytesInput>) -> Result<f64, Error> {
+            Ok(entry.input().as_ref().unwrap().mutator_bytes().len() as f64)
+        }
+    }
+
+    #[test]
+    fn greedy_selection() -> Result<(), Error> {
+        let observer = StdMapObserver::owned("map", vec![0u8; 8]);
+        let minimizer: GreedyCorpusMinimizer<_, _, _, _, _, _, LenPenalty> =
+            GreedyCorpusMinimizer::new(&observer);
+        let mut executor = CountingExecutor {
+            observers: tuple_list!(observer),
+        };
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )?;
+        let mut fuzzer = StdFuzzer::new(QueueScheduler::new(), (), ());
+        let mut mgr = NopEventManager::new();
+
+        let mut ids = Vec::new();
+        for bytes in [
+            &[2, 3, 4][..],
+            &[1, 2],
+            &[1],
+            &[3, 4],
+            &[1, 1],
+            &[1, 2, 3, 4],
+        ] {
+            ids.push(
+                state
+                    .corpus_mut()
+                    .add(Testcase::new(BytesInput::new(bytes.to_vec())))?,
+            );
+        }
+
+        let report = minimizer.minimize(&mut fuzzer, &mut executor, &mut mgr, &mut state)?;
+
+        // From the shortest: [1] covers 1, [1, 2] adds 2, [3, 4] adds 3 and 4,
+        // [1, 1] adds 1 with a hit count of 2, and [2, 3, 4] and [1, 2, 3, 4] add nothing.
+        let kept: Vec<CorpusId> = report.seeds.iter().map(|seed| seed.id).collect();
+        assert_eq!(kept, vec![ids[1], ids[2], ids[3], ids[4]]);
+        assert_eq!(report.removed, 2);
+        assert_eq!(report.failing, 0);
+        assert_eq!(report.seeds[0].edges, vec![1, 2]);
+        assert_eq!(report.seeds[3].edges, vec![1]);
+        assert_eq!(state.corpus().count(), 4);
+        assert!(state.corpus().get(ids[0]).is_err());
+        assert!(state.corpus().get(ids[5]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn greedy_removes_failing() -> Result<(), Error> {
+        let observer = StdMapObserver::owned("map", vec![0u8; 8]);
+        let minimizer: GreedyCorpusMinimizer<_, _, _, _, _, _, LenPenalty> =
+            GreedyCorpusMinimizer::new(&observer).with_remove_failing(true);
+        let mut executor = CountingExecutor {
+            observers: tuple_list!(observer),
+        };
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )?;
+        let mut fuzzer = StdFuzzer::new(QueueScheduler::new(), (), ());
+        let mut mgr = NopEventManager::new();
+
+        let mut ids = Vec::new();
+        for bytes in [&[1][..], &[1, 7], &[7], &[2]] {
+            ids.push(
+                state
+                    .corpus_mut()
+                    .add(Testcase::new(BytesInput::new(bytes.to_vec())))?,
+            );
+        }
+
+        // The crashing inputs are removed, even though they cover the entry 7
+        let report = minimizer.minimize(&mut fuzzer, &mut executor, &mut mgr, &mut state)?;
+        let kept: Vec<CorpusId> = report.seeds.iter().map(|seed| seed.id).collect();
+        assert_eq!(kept, vec![ids[0], ids[3]]);
+        assert_eq!(report.removed, 2);
+        assert_eq!(report.failing, 2);
+        assert_eq!(state.corpus().count(), 2);
+
+        // Without failing inputs left, the coverage report keeps the corpus as it is
+        let report = minimizer.coverage_report(&mut fuzzer, &mut executor, &mut mgr, &mut state)?;
+        assert_eq!(report.seeds.len(), 2);
+        assert_eq!(report.removed, 0);
+        assert_eq!(state.corpus().count(), 2);
+        Ok(())
+    }
+}
@@ -248,6 +248,12 @@ impl<I> TestcaseStorage<I> {
         CorpusId::from(self.progressive_id)
     }
 
+    /// Make sure the next free id is at least `id`, i.e., ids below `id` will never be handed out again.
+    /// Used by corpora that restore [`Testcase`]s with their original ids from persistent storage.
+    pub fn reserve_ids_below(&mut self, id: CorpusId) {
+        self.progressive_id = self.progressive_id.max(id.into());
+    }
+
     /// Insert a testcase assigning a `CorpusId` to it
     pub fn insert_disabled(&mut self, testcase: RefCell<Testcase<I>>) -> CorpusId {
         self.insert_inner(testcase, true)
@@ -30,6 +30,16 @@ pub mod cached;
 #[cfg(feature = "std")]
 pub use cached::CachedOnDiskCorpus;
 
+#[cfg(feature = "sqlite_corpus")]
+pub mod sqlite;
+#[cfg(feature = "sqlite_corpus")]
+pub use sqlite::SqliteCorpus;
+
+pub mod greedy_minimizer;
+pub use greedy_minimizer::{
+    CorpusCoverageReport, GreedyCorpusMinimizer, SeedCoverage, StdGreedyCorpusMinimizer,
+};
+
 #[cfg(feature = "cmin")]
 pub mod minimizer;
 
@@ -0,0 +1,703 @@
+//! The [`SqliteCorpus`] stores all [`Testcase`]s, including their metadata, in a single embedded `SQLite` database.
+//!
+//! Compared to [`crate::corpus::OnDiskCorpus`] and [`crate::corpus::CachedOnDiskCorpus`], which write one file
+//! (plus one `.metadata` file) per entry, a single database file reloads quickly even for very large corpora,
+//! and can be queried, for example to find all [`Testcase`]s that were deemed interesting by a given feedback.
+//!
+//! All [`Testcase`]s are kept in memory, but their inputs are only loaded from the database when needed,
+//! and evicted in a FIFO manner, similar to [`crate::corpus::CachedOnDiskCorpus`].
+
+#[cfg(feature = "track_hit_feedbacks")]
+use alloc::{borrow::Cow, string::String};
+use alloc::{boxed::Box, collections::vec_deque::VecDeque, string::ToString, vec::Vec};
+use core::{
+    cell::{Ref, RefCell, RefMut},
+    fmt,
+    time::Duration,
+};
+use std::path::{Path, PathBuf};
+
+use libafl_bolts::serdeany::SerdeAnyMap;
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    Error, HasMetadata,
+    corpus::{
+        Corpus, CorpusId, EnableDisableCorpus, HasTestcase, Testcase, inmemory::TestcaseStorage,
+    },
+    inputs::Input,
+};
+
+/// The schema of the database backing a [`SqliteCorpus`].
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS testcases (
+        id INTEGER PRIMARY KEY,
+        disabled INTEGER NOT NULL,
+        input BLOB NOT NULL,
+        filename TEXT,
+        exec_time_ns INTEGER,
+        executions INTEGER NOT NULL,
+        scheduled_count INTEGER NOT NULL,
+        parent_id INTEGER,
+        objectives_found INTEGER NOT NULL,
+        metadata BLOB NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS testcases_parent_id ON testcases (parent_id);
+    CREATE TABLE IF NOT EXISTS hit_feedbacks (
+        id INTEGER NOT NULL REFERENCES testcases (id) ON DELETE CASCADE,
+        name TEXT NOT NULL,
+        objective INTEGER NOT NULL,
+        PRIMARY KEY (id, name, objective)
+    );
+    CREATE INDEX IF NOT EXISTS hit_feedbacks_name ON hit_feedbacks (name, objective);
+";
+
+/// Converts a [`rusqlite::Error`] into a `LibAFL` [`Error`]
+#[expect(clippy::needless_pass_by_value)]
+fn sql_err(err: rusqlite::Error) -> Error {
+    Error::illegal_state(format!("SQLite corpus error: {err}"))
+}
+
+/// A corpus that stores all [`Testcase`]s, and their metadata, in an `SQLite` database.
+///
+/// Opening an existing database restores all [`Testcase`]s with their original [`CorpusId`]s.
+/// Changes made to a [`Testcase`] through [`Corpus::get`] are only written back to the database
+/// on [`SqliteCorpus::flush`], [`SqliteCorpus::flush_testcase`], or when the corpus gets serialized.
+pub struct SqliteCorpus<I> {
+    storage: TestcaseStorage<I>,
+    current: Option<CorpusId>,
+    db_path: PathBuf,
+    conn: Connection,
+    cached_indexes: RefCell<VecDeque<CorpusId>>,
+    cache_max_len: usize,
+}
+
+impl<I> fmt::Debug for SqliteCorpus<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SqliteCorpus")
+            .field("db_path", &self.db_path)
+            .field("current", &self.current)
+            .field("count", &self.storage.enabled.map.len())
+            .field("count_disabled", &self.storage.disabled.map.len())
+            .field("cache_max_len", &self.cache_max_len)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<I> Corpus<I> for SqliteCorpus<I>
+where
+    I: Input,
+{
+    /// Returns the number of all enabled entries
+    #[inline]
+    fn count(&self) -> usize {
+        self.storage.enabled.map.len()
+    }
+
+    /// Returns the number of all disabled entries
+    fn count_disabled(&self) -> usize {
+        self.storage.disabled.map.len()
+    }
+
+    /// Returns the number of elements including disabled entries
+    #[inline]
+    fn count_all(&self) -> usize {
+        self.count().saturating_add(self.count_disabled())
+    }
+
+    /// Add an enabled testcase to the corpus and return its index
+    fn add(&mut self, testcase: Testcase<I>) -> Result<CorpusId, Error> {
+        let id = self.storage.insert(RefCell::new(testcase));
+        self.insert_row(id)?;
+        Ok(id)
+    }
+
+    /// Add a disabled testcase to the corpus and return its index
+    fn add_disabled(&mut self, testcase: Testcase<I>) -> Result<CorpusId, Error> {
+        let id = self.storage.insert_disabled(RefCell::new(testcase));
+        self.insert_row(id)?;
+        Ok(id)
+    }
+
+    /// Replaces the testcase at the given id
+    fn replace(&mut self, id: CorpusId, mut testcase: Testcase<I>) -> Result<Testcase<I>, Error> {
+        testcase.set_corpus_id(Some(id));
+        let old = self.storage.enabled.replace(id, testcase).ok_or_else(|| {
+            Error::key_not_found(format!("Index {id} not found, could not replace."))
+        })?;
+        self.cached_indexes.borrow_mut().retain(|e| *e != id);
+        self.insert_row(id)?;
+        Ok(old)
+    }
+
+    /// Removes an entry from the corpus, returning it if it was present; considers both enabled and disabled testcases
+    fn remove(&mut self, id: CorpusId) -> Result<Testcase<I>, Error> {
+        let mut testcase = self.storage.enabled.remove(id);
+        if testcase.is_none() {
+            testcase = self.storage.disabled.remove(id);
+        }
+        let testcase = testcase
+            .map(RefCell::into_inner)
+            .ok_or_else(|| Error::key_not_found(format!("Index {id} not found")))?;
+        self.cached_indexes.borrow_mut().retain(|e| *e != id);
+        self.conn
+            .execute("DELETE FROM testcases WHERE id = ?1", params![id.0])
+            .map_err(sql_err)?;
+        Ok(testcase)
+    }
+
+    /// Get by id; considers only enabled testcases
+    #[inline]
+    fn get(&self, id: CorpusId) -> Result<&RefCell<Testcase<I>>, Error> {
+        self.storage
+            .enabled
+            .get(id)
+            .ok_or_else(|| Error::key_not_found(format!("Index {id} not found")))
+    }
+
+    /// Get by id; considers both enabled and disabled testcases
+    #[inline]
+    fn get_from_all(&self, id: CorpusId) -> Result<&RefCell<Testcase<I>>, Error> {
+        self.storage
+            .enabled
+            .get(id)
+            .or_else(|| self.storage.disabled.get(id))
+            .ok_or_else(|| Error::key_not_found(format!("Index {id} not found")))
+    }
+
+    /// Current testcase scheduled
+    #[inline]
+    fn current(&self) -> &Option<CorpusId> {
+        &self.current
+    }
+
+    /// Current testcase scheduled (mutable)
+    #[inline]
+    fn current_mut(&mut self) -> &mut Option<CorpusId> {
+        &mut self.current
+    }
+
+    #[inline]
+    fn next(&self, id: CorpusId) -> Option<CorpusId> {
+        self.storage.enabled.next(id)
+    }
+
+    /// Peek the next free corpus id
+    #[inline]
+    fn peek_free_id(&self) -> CorpusId {
+        self.storage.peek_free_id()
+    }
+
+    #[inline]
+    fn prev(&self, id: CorpusId) -> Option<CorpusId> {
+        self.storage.enabled.prev(id)
+    }
+
+    #[inline]
+    fn first(&self) -> Option<CorpusId> {
+        self.storage.enabled.first()
+    }
+
+    #[inline]
+    fn last(&self) -> Option<CorpusId> {
+        self.storage.enabled.last()
+    }
+
+    /// Get the nth corpus id; considers only enabled testcases
+    #[inline]
+    fn nth(&self, nth: usize) -> CorpusId {
+        self.storage.enabled.keys[nth]
+    }
+
+    /// Get the nth corpus id; considers both enabled and disabled testcases
+    #[inline]
+    fn nth_from_all(&self, nth: usize) -> CorpusId {
+        let enabled_count = self.count();
+        if nth >= enabled_count {
+            return self.storage.disabled.keys[nth.saturating_sub(enabled_count)];
+        }
+        self.storage.enabled.keys[nth]
+    }
+
+    fn load_input_into(&self, testcase: &mut Testcase<I>) -> Result<(), Error> {
+        if testcase.input().is_some() {
+            return Ok(());
+        }
+        let id = testcase
+            .corpus_id()
+            .ok_or_else(|| Error::unknown("The testcase is not associated with an id"))?;
+        let bytes: Vec<u8> = self
+            .conn
+            .query_row(
+                "SELECT input FROM testcases WHERE id = ?1",
+                params![id.0],
+                |row| row.get(0),
+            )
+            .map_err(sql_err)?;
+        testcase.set_input(postcard::from_bytes(&bytes)?);
+
+        let mut borrowed_num = 0;
+        while self.cached_indexes.borrow().len() >= self.cache_max_len {
+            let to_be_evicted = self.cached_indexes.borrow_mut().pop_front().unwrap();
+
+            if let Ok(mut borrowed) = self.get_from_all(to_be_evicted)?.try_borrow_mut() {
+                *borrowed.input_mut() = None;
+            } else {
+                self.cached_indexes.borrow_mut().push_back(to_be_evicted);
+                borrowed_num += 1;
+                if self.cache_max_len == borrowed_num {
+                    break;
+                }
+            }
+        }
+        self.cached_indexes.borrow_mut().push_back(id);
+        Ok(())
+    }
+
+    fn store_input_from(&self, testcase: &Testcase<I>) -> Result<(), Error> {
+        let Some(id) = testcase.corpus_id() else {
+            return Err(Error::illegal_argument(
+                "The testcase is not associated with an id. Could not store input.",
+            ));
+        };
+        let Some(input) = testcase.input() else {
+            return Err(Error::illegal_argument(
+                "No input available for testcase. Could not store anything.",
+            ));
+        };
+        self.conn
+            .execute(
+                "UPDATE testcases SET input = ?2 WHERE id = ?1",
+                params![id.0, postcard::to_allocvec(input)?],
+            )
+            .map_err(sql_err)?;
+        Ok(())
+    }
+}
+
+impl<I> EnableDisableCorpus for SqliteCorpus<I>
+where
+    I: Input,
+{
+    fn disable(&mut self, id: CorpusId) -> Result<(), Error> {
+        let Some(testcase) = self.storage.enabled.remove(id) else {
+            return Err(Error::key_not_found(format!(
+                "Index {id} not found in enabled testcases"
+            )));
+        };
+        testcase.borrow_mut().set_disabled(true);
+        self.storage.insert_inner_with_id(testcase, true, id)?;
+        self.set_disabled_row(id, true)
+    }
+
+    fn enable(&mut self, id: CorpusId) -> Result<(), Error> {
+        let Some(testcase) = self.storage.disabled.remove(id) else {
+            return Err(Error::key_not_found(format!(
+                "Index {id} not found in disabled testcases"
+            )));
+        };
+        testcase.borrow_mut().set_disabled(false);
+        self.storage.insert_inner_with_id(testcase, false, id)?;
+        self.set_disabled_row(id, false)
+    }
+}
+
+impl<I> HasTestcase<I> for SqliteCorpus<I>
+where
+    I: Input,
+{
+    fn testcase(&self, id: CorpusId) -> Result<Ref<'_, Testcase<I>>, Error> {
+        Ok(self.get(id)?.borrow())
+    }
+
+    fn testcase_mut(&self, id: CorpusId) -> Result<RefMut<'_, Testcase<I>>, Error> {
+        Ok(self.get(id)?.borrow_mut())
+    }
+}
+
+impl<I> SqliteCorpus<I>
+where
+    I: Input,
+{
+    /// Opens (or creates) the [`SqliteCorpus`] database at `db_path`.
+    ///
+    /// All [`Testcase`]s already in the database are restored with their original ids.
+    /// At most `cache_max_len` inputs are kept in memory at the same time.
+    pub fn new<P>(db_path: P, cache_max_len: usize) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        if cache_max_len == 0 {
+            return Err(Error::illegal_argument(
+                "The max cache len in SqliteCorpus cannot be 0",
+            ));
+        }
+        let db_path = db_path.as_ref();
+        if let Some(parent) = db_path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            std::fs::create_dir_all(parent)?;
+        }
+        let conn = Connection::open(db_path).map_err(sql_err)?;
+        conn.pragma_update(None, "journal_mode", "WAL")
+            .map_err(sql_err)?;
+        conn.pragma_update(None, "foreign_keys", "ON")
+            .map_err(sql_err)?;
+        conn.execute_batch(SCHEMA).map_err(sql_err)?;
+
+        let mut corpus = Self {
+            storage: TestcaseStorage::new(),
+            current: None,
+            db_path: db_path.into(),
+            conn,
+            cached_indexes: RefCell::new(VecDeque::new()),
+            cache_max_len,
+        };
+        corpus.restore()?;
+        Ok(corpus)
+    }
+
+    /// Path to the database file backing this corpus
+    #[must_use]
+    pub fn db_path(&self) -> &PathBuf {
+        &self.db_path
+    }
+
+    /// Writes the current state of the [`Testcase`] at `id` back to the database.
+    ///
+    /// The input is only written if it is currently loaded.
+    pub fn flush_testcase(&self, id: CorpusId) -> Result<(), Error> {
+        let testcase = self.get_from_all(id)?.borrow();
+        self.update_row(id, &testcase)?;
+        if testcase.input().is_some() {
+            self.store_input_from(&testcase)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the current state of all [`Testcase`]s back to the database, in a single transaction.
+    pub fn flush(&self) -> Result<(), Error> {
+        let tx = self.conn.unchecked_transaction().map_err(sql_err)?;
+        for id in self
+            .storage
+            .enabled
+            .keys
+            .iter()
+            .chain(self.storage.disabled.keys.iter())
+        {
+            self.flush_testcase(*id)?;
+        }
+        tx.commit().map_err(sql_err)
+    }
+
+    /// Returns the ids of all [`Testcase`]s, enabled or disabled, that were deemed interesting by the feedback named `name`.
+    ///
+    /// Feedback names are only recorded with the `track_hit_feedbacks` feature.
+    pub fn ids_with_hit_feedback(&self, name: &str) -> Result<Vec<CorpusId>, Error> {
+        self.query_ids(
+            "SELECT id FROM hit_feedbacks WHERE name = ?1 AND objective = 0 ORDER BY id",
+            params![name],
+        )
+    }
+
+    /// Returns the ids of all [`Testcase`]s, enabled or disabled, that were deemed a solution by the objective named `name`.
+    ///
+    /// Objective names are only recorded with the `track_hit_feedbacks` feature.
+    pub fn ids_with_hit_objective(&self, name: &str) -> Result<Vec<CorpusId>, Error> {
+        self.query_ids(
+            "SELECT id FROM hit_feedbacks WHERE name = ?1 AND objective = 1 ORDER BY id",
+            params![name],
+        )
+    }
+
+    /// Returns the ids of all [`Testcase`]s, enabled or disabled, that were derived from `parent_id`
+    pub fn ids_with_parent(&self, parent_id: CorpusId) -> Result<Vec<CorpusId>, Error> {
+        self.query_ids(
+            "SELECT id FROM testcases WHERE parent_id = ?1 ORDER BY id",
+            params![parent_id.0],
+        )
+    }
+
+    /// Returns the ids of all [`Testcase`]s, enabled or disabled, matching the given SQL `condition`.
+    ///
+    /// The `condition` is used verbatim as the `WHERE` clause of a query over the `testcases` table,
+    /// with the columns `id`, `disabled`, `filename`, `exec_time_ns`, `executions`, `scheduled_count`,
+    /// `parent_id` and `objectives_found`. Use `?1`, `?2`, ... placeholders for the `params`.
+    /// Never pass untrusted strings as `condition`.
+    pub fn ids_where<P>(&self, condition: &str, params: P) -> Result<Vec<CorpusId>, Error>
+    where
+        P: rusqlite::Params,
+    {
+        self.query_ids(
+            &format!("SELECT id FROM testcases WHERE {condition} ORDER BY id"),
+            params,
+        )
+    }
+
+    fn query_ids<P>(&self, sql: &str, params: P) -> Result<Vec<CorpusId>, Error>
+    where
+        P: rusqlite::Params,
+    {
+        let mut stmt = self.conn.prepare_cached(sql).map_err(sql_err)?;
+        let rows = stmt
+            .query_map(params, |row| row.get::<_, usize>(0))
+            .map_err(sql_err)?;
+        rows.map(|id| id.map(CorpusId).map_err(sql_err)).collect()
+    }
+
+    /// Loads all rows from the database into memory, without their inputs.
+    fn restore(&mut self) -> Result<(), Error> {
+        let max_id: Option<usize> = self
+            .conn
+            .query_row("SELECT MAX(id) FROM testcases", [], |row| row.get(0))
+            .optional()
+            .map_err(sql_err)?
+            .flatten();
+        let Some(max_id) = max_id else {
+            return Ok(());
+        };
+        self.storage.reserve_ids_below(CorpusId(max_id + 1));
+
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT id, disabled, filename, exec_time_ns, executions, scheduled_count, \
+                 parent_id, objectives_found, metadata FROM testcases ORDER BY id",
+            )
+            .map_err(sql_err)?;
+        let mut rows = stmt.query([]).map_err(sql_err)?;
+        while let Some(row) = rows.next().map_err(sql_err)? {
+            let id = CorpusId(row.get(0).map_err(sql_err)?);
+            let disabled: bool = row.get(1).map_err(sql_err)?;
+            let metadata: Vec<u8> = row.get(8).map_err(sql_err)?;
+
+            let mut testcase = Testcase::default();
+            testcase.set_corpus_id(Some(id));
+            testcase.set_disabled(disabled);
+            *testcase.filename_mut() = row.get(2).map_err(sql_err)?;
+            *testcase.exec_time_mut() = row
+                .get::<_, Option<u64>>(3)
+                .map_err(sql_err)?
+                .map(Duration::from_nanos);
+            testcase.set_executions(row.get(4).map_err(sql_err)?);
+            testcase.set_scheduled_count(row.get(5).map_err(sql_err)?);
+            testcase.set_parent_id_optional(
+                row.get::<_, Option<usize>>(6)
+                    .map_err(sql_err)?
+                    .map(CorpusId),
+            );
+            testcase.set_objectives_found(row.get(7).map_err(sql_err)?);
+            *testcase.metadata_map_mut() = postcard::from_bytes::<SerdeAnyMap>(&metadata)?;
+
+            self.storage
+                .insert_inner_with_id(RefCell::new(testcase), disabled, id)?;
+        }
+
+        #[cfg(feature = "track_hit_feedbacks")]
+        {
+            let mut stmt = self
+                .conn
+                .prepare("SELECT id, name, objective FROM hit_feedbacks ORDER BY rowid")
+                .map_err(sql_err)?;
+            let mut rows = stmt.query([]).map_err(sql_err)?;
+            while let Some(row) = rows.next().map_err(sql_err)? {
+                let id = CorpusId(row.get(0).map_err(sql_err)?);
+                let name: String = row.get(1).map_err(sql_err)?;
+                let objective: bool = row.get(2).map_err(sql_err)?;
+                let mut testcase = self.get_from_all(id)?.borrow_mut();
+                if objective {
+                    testcase.hit_objectives_mut().push(Cow::Owned(name));
+                } else {
+                    testcase.hit_feedbacks_mut().push(Cow::Owned(name));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Inserts (or overwrites) the row for the freshly added [`Testcase`] at `id`, and drops its input from memory.
+    fn insert_row(&self, id: CorpusId) -> Result<(), Error> {
+        let mut testcase = self.get_from_all(id)?.borrow_mut();
+        let Some(input) = testcase.input() else {
+            return Err(Error::illegal_argument(
+                "No input available for testcase. Could not store anything.",
+            ));
+        };
+        let name = input.generate_name(Some(id));
+        let input = postcard::to_allocvec(input)?;
+        if testcase.filename().is_none() {
+            *testcase.filename_mut() = Some(name);
+        }
+        let tx = self.conn.unchecked_transaction().map_err(sql_err)?;
+        tx.execute(
+            "INSERT OR REPLACE INTO testcases \
+             (id, disabled, input, executions, scheduled_count, objectives_found, metadata) \
+             VALUES (?1, ?2, ?3, 0, 0, 0, x'')",
+            params![id.0, testcase.disabled(), input],
+        )
+        .map_err(sql_err)?;
+        self.update_row(id, &testcase)?;
+        tx.commit().map_err(sql_err)?;
+        *testcase.input_mut() = None;
+        Ok(())
+    }
+
+    /// Updates all columns of the row at `id`, except for the input.
+    fn update_row(&self, id: CorpusId, testcase: &Testcase<I>) -> Result<(), Error> {
+        let exec_time = testcase
+            .exec_time()
+            .map(|t| u64::try_from(t.as_nanos()).unwrap_or(u64::MAX));
+        self.conn
+            .prepare_cached(
+                "UPDATE testcases SET disabled = ?2, filename = ?3, exec_time_ns = ?4, \
+                 executions = ?5, scheduled_count = ?6, parent_id = ?7, objectives_found = ?8, \
+                 metadata = ?9 WHERE id = ?1",
+            )
+            .and_then(|mut stmt| {
+                stmt.execute(params![
+                    id.0,
+                    testcase.disabled(),
+                    testcase.filename(),
+                    exec_time,
+                    testcase.executions(),
+                    testcase.scheduled_count(),
+                    testcase.parent_id().map(|p| p.0),
+                    testcase.objectives_found(),
+                    postcard::to_allocvec(testcase.metadata_map())
+                        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?,
+                ])
+            })
+            .map_err(sql_err)?;
+
+        self.conn
+            .execute("DELETE FROM hit_feedbacks WHERE id = ?1", params![id.0])
+            .map_err(sql_err)?;
+        #[cfg(feature = "track_hit_feedbacks")]
+        {
+            let mut stmt = self
+                .conn
+                .prepare_cached(
+                    "INSERT OR IGNORE INTO hit_feedbacks (id, name, objective) VALUES (?1, ?2, ?3)",
+                )
+                .map_err(sql_err)?;
+            for name in testcase.hit_feedbacks() {
+                stmt.execute(params![id.0, name.as_ref(), false])
+                    .map_err(sql_err)?;
+            }
+            for name in testcase.hit_objectives() {
+                stmt.execute(params![id.0, name.as_ref(), true])
+                    .map_err(sql_err)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn set_disabled_row(&self, id: CorpusId, disabled: bool) -> Result<(), Error> {
+        self.conn
+            .execute(
+                "UPDATE testcases SET disabled = ?2 WHERE id = ?1",
+                params![id.0, disabled],
+            )
+            .map_err(sql_err)?;
+        Ok(())
+    }
+}
+
+/// The serialized form of a [`SqliteCorpus`]; all [`Testcase`]s live in the database itself.
+#[derive(Serialize, Deserialize)]
+struct SqliteCorpusRef {
+    db_path: PathBuf,
+    cache_max_len: usize,
+    current: Option<CorpusId>,
+}
+
+impl<I> Serialize for SqliteCorpus<I>
+where
+    I: Input,
+{
+    /// Flushes all [`Testcase`]s to the database, then serializes the path to the database.
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.flush().map_err(serde::ser::Error::custom)?;
+        SqliteCorpusRef {
+            db_path: self.db_path.clone(),
+            cache_max_len: self.cache_max_len,
+            current: self.current,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, I> Deserialize<'de> for SqliteCorpus<I>
+where
+    I: Input,
+{
+    /// Reopens the database and restores all [`Testcase`]s from it.
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let corpus_ref = SqliteCorpusRef::deserialize(deserializer)?;
+        let mut corpus = Self::new(&corpus_ref.db_path, corpus_ref.cache_max_len)
+            .map_err(|e| serde::de::Error::custom(e.to_string()))?;
+        corpus.current = corpus_ref.current;
+        Ok(corpus)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+    use std::fs;
+
+    use super::SqliteCorpus;
+    use crate::{
+        corpus::{Corpus, EnableDisableCorpus, Testcase},
+        inputs::BytesInput,
+    };
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn sqlite_corpus_restore() {
+        let dir = std::env::temp_dir().join("libafl_sqlite_corpus_restore_test");
+        let _ = fs::remove_dir_all(&dir);
+        let db = dir.join("corpus.sqlite");
+
+        let (first, second, third) = {
+            let mut corpus = SqliteCorpus::<BytesInput>::new(&db, 2).unwrap();
+            let first = corpus.add(Testcase::new(vec![0x41].into())).unwrap();
+            let mut child = Testcase::with_parent_id(vec![0x42].into(), first);
+            child.set_scheduled_count(3);
+            let second = corpus.add(child).unwrap();
+            let third = corpus.add(Testcase::new(vec![0x43].into())).unwrap();
+            corpus.remove(first).unwrap();
+            corpus.disable(third).unwrap();
+            (first, second, third)
+        };
+
+        let mut corpus = SqliteCorpus::<BytesInput>::new(&db, 2).unwrap();
+        assert_eq!(corpus.count(), 1);
+        assert_eq!(corpus.count_disabled(), 1);
+        assert!(corpus.get(first).is_err());
+        assert_eq!(corpus.ids_with_parent(first).unwrap(), vec![second]);
+        assert_eq!(
+            corpus.ids_where("scheduled_count = ?1", [3]).unwrap(),
+            vec![second]
+        );
+        assert_eq!(
+            corpus.cloned_input_for_id(second).unwrap(),
+            BytesInput::from(vec![0x42])
+        );
+
+        corpus.enable(third).unwrap();
+        let fourth = corpus.add(Testcase::new(vec![0x44].into())).unwrap();
+        assert!(fourth > third);
+        assert_eq!(corpus.count(), 3);
+
+        drop(corpus);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
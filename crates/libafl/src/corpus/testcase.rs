@@ -354,6 +354,11 @@ impl<I> Testcase<I> {
     pub fn found_objective(&mut self) {
         self.objectives_found = self.objectives_found.saturating_add(1);
     }
+
+    /// Sets the `objectives_found` counter, e.g., when restoring this testcase from persistent storage.
+    pub fn set_objectives_found(&mut self, objectives_found: usize) {
+        self.objectives_found = objectives_found;
+    }
 }
 
 impl<I> Default for Testcase<I> {
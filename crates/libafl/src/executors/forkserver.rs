@@ -611,6 +611,12 @@ impl Forkserver {
         self.child_pid = None;
     }
 
+    /// The signal used to kill the child and the forkserver
+    #[must_use]
+    pub fn kill_signal(&self) -> Signal {
+        self.kill_signal
+    }
+
     /// Read from the st pipe
     pub fn read_st(&mut self) -> Result<i32, Error> {
         let mut buf: [u8; 4] = [0_u8; 4];
@@ -0,0 +1,213 @@
+//! Leak detection for in-process targets, for example with `LeakSanitizer`.
+//!
+//! The allocation hooks of the target, like the sanitizer malloc hooks of `libafl_targets`, report each
+//! malloc and free with [`count_malloc`] and [`count_free`]. After each execution which allocated more than it freed,
+//! the [`LeakCheckHook`] runs the (expensive) leak check, and the [`LeakFeedback`] reports the leak, usually as objective.
+
+use alloc::borrow::Cow;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use libafl_bolts::Named;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Error,
+    executors::{ExitKind, hooks::ExecutorHook},
+    feedbacks::{Feedback, StateInitializer},
+};
+
+static TRACKING: AtomicBool = AtomicBool::new(false);
+static MALLOCS: AtomicUsize = AtomicUsize::new(0);
+static FREES: AtomicUsize = AtomicUsize::new(0);
+static LEAKED: AtomicBool = AtomicBool::new(false);
+
+/// The default number of leak checks after which the [`LeakCheckHook`] gives up, like libfuzzer
+pub const DEFAULT_MAX_LEAK_CHECKS: usize = 1000;
+
+/// Counts a malloc of the target, call this from its allocation hooks
+pub fn count_malloc() {
+    if TRACKING.load(Ordering::Relaxed) {
+        MALLOCS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Counts a free of the target, call this from its allocation hooks
+pub fn count_free() {
+    if TRACKING.load(Ordering::Relaxed) {
+        FREES.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Runs a leak check after each execution which allocated more than it freed
+#[derive(Debug, Clone, Copy)]
+pub struct LeakCheckHook {
+    check: fn() -> bool,
+    checks: usize,
+    max_checks: usize,
+}
+
+impl LeakCheckHook {
+    /// Creates a new [`LeakCheckHook`], `check` returns whether the target leaked memory,
+    /// like `__lsan_do_recoverable_leak_check`
+    #[must_use]
+    pub fn new(check: fn() -> bool) -> Self {
+        Self::with_max_checks(check, DEFAULT_MAX_LEAK_CHECKS)
+    }
+
+    /// Creates a new [`LeakCheckHook`], which is disabled after `max_checks` leak checks.
+    ///
+    /// Targets which keep allocations in a global state, without actually leaking them, would be checked after
+    /// every execution otherwise.
+    #[must_use]
+    pub fn with_max_checks(check: fn() -> bool, max_checks: usize) -> Self {
+        Self {
+            check,
+            checks: 0,
+            max_checks,
+        }
+    }
+
+    /// Whether the last execution leaked memory
+    #[must_use]
+    pub fn leaked() -> bool {
+        LEAKED.load(Ordering::Relaxed)
+    }
+}
+
+impl<I, S> ExecutorHook<I, S> for LeakCheckHook {
+    fn init(&mut self, _state: &mut S) {}
+
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) {
+        LEAKED.store(false, Ordering::Relaxed);
+        if self.checks < self.max_checks {
+            MALLOCS.store(0, Ordering::Relaxed);
+            FREES.store(0, Ordering::Relaxed);
+            TRACKING.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn post_exec(&mut self, _state: &mut S, _input: &I) {
+        if !TRACKING.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        // A leak is unlikely if every malloc was freed
+        if MALLOCS.load(Ordering::Relaxed) <= FREES.load(Ordering::Relaxed) {
+            return;
+        }
+
+        self.checks += 1;
+        if self.checks == self.max_checks {
+            log::info!(
+                "Disabled leak detection after {} checks, the target most likely accumulates memory in a global state without leaking it",
+                self.max_checks
+            );
+        }
+        LEAKED.store((self.check)(), Ordering::Relaxed);
+    }
+}
+
+/// Name used by `LeakFeedback`
+pub const LEAK_FEEDBACK_NAME: &str = "LeakFeedback";
+
+/// Reports the executions in which the [`LeakCheckHook`] found a leak as interesting
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, Default)]
+pub struct LeakFeedback;
+
+impl LeakFeedback {
+    /// Creates a new [`LeakFeedback`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Named for LeakFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed(LEAK_FEEDBACK_NAME);
+        &NAME
+    }
+}
+
+impl<S> StateInitializer<S> for LeakFeedback {}
+
+impl<EM, I, OT, S> Feedback<EM, I, OT, S> for LeakFeedback {
+    fn is_interesting(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        _observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        Ok(LeakCheckHook::leaked())
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        Ok(LeakCheckHook::leaked())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        executors::{
+            ExitKind,
+            hooks::{
+                ExecutorHook,
+                leak::{LeakCheckHook, LeakFeedback, count_free, count_malloc},
+            },
+        },
+        feedbacks::Feedback,
+    };
+
+    fn is_leak(feedback: &mut LeakFeedback) -> bool {
+        Feedback::<(), (), (), ()>::is_interesting(
+            feedback,
+            &mut (),
+            &mut (),
+            &(),
+            &(),
+            &ExitKind::Ok,
+        )
+        .unwrap()
+    }
+
+    // The hook and the feedback are tested together, as the leak tracking is global
+    #[test]
+    fn test_leak_check_hook() {
+        let mut feedback = LeakFeedback::new();
+        let mut hook = LeakCheckHook::with_max_checks(|| true, 2);
+        let mut state = ();
+
+        // Every malloc was freed, no need to check
+        ExecutorHook::<(), ()>::pre_exec(&mut hook, &mut state, &());
+        count_malloc();
+        count_free();
+        ExecutorHook::<(), ()>::post_exec(&mut hook, &mut state, &());
+        assert!(!LeakCheckHook::leaked());
+        assert!(!is_leak(&mut feedback));
+
+        for _ in 0..2 {
+            ExecutorHook::<(), ()>::pre_exec(&mut hook, &mut state, &());
+            count_malloc();
+            ExecutorHook::<(), ()>::post_exec(&mut hook, &mut state, &());
+            assert!(LeakCheckHook::leaked());
+            assert!(is_leak(&mut feedback));
+        }
+
+        // The hook gave up after two checks
+        ExecutorHook::<(), ()>::pre_exec(&mut hook, &mut state, &());
+        count_malloc();
+        ExecutorHook::<(), ()>::post_exec(&mut hook, &mut state, &());
+        assert!(!LeakCheckHook::leaked());
+        assert!(!is_leak(&mut feedback));
+
+        // The check itself found no leak
+        let mut hook = LeakCheckHook::new(|| false);
+        ExecutorHook::<(), ()>::pre_exec(&mut hook, &mut state, &());
+        count_malloc();
+        ExecutorHook::<(), ()>::post_exec(&mut hook, &mut state, &());
+        assert!(!is_leak(&mut feedback));
+    }
+}
@@ -16,6 +16,9 @@ pub mod inprocess_fork;
 /// The hook for inprocess executor
 pub mod inprocess;
 
+/// Leak detection, e.g. with `LeakSanitizer`
+pub mod leak;
+
 /// Timer-related stuff
 #[cfg(feature = "std")]
 pub mod timer;
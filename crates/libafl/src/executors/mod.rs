@@ -19,8 +19,12 @@ use libafl_bolts::os::unix_signals::Signal;
 use libafl_bolts::tuples::RefIndexable;
 #[cfg(feature = "std")]
 use libafl_bolts::{core_affinity::CoreId, tuples::Handle};
+#[cfg(all(feature = "std", unix))]
+pub use persistent_command::PersistentCommandExecutor;
 use serde::{Deserialize, Serialize};
 pub use shadow::ShadowExecutor;
+#[cfg(all(feature = "std", feature = "multipart_inputs"))]
+pub use socket::{CommandSessionTarget, SessionTarget, SocketExecutor, SocketTransport};
 pub use with_observers::WithObservers;
 
 use crate::Error;
@@ -35,6 +39,8 @@ pub mod differential;
 pub mod forkserver;
 pub mod inprocess;
 pub mod nop;
+#[cfg(all(feature = "std", unix))]
+pub mod persistent_command;
 /// SAND(<https://github.com/wtdcode/sand-aflpp>) implementation
 #[cfg(feature = "simd")]
 pub mod sand;
@@ -45,6 +51,10 @@ pub mod inprocess_fork;
 
 pub mod shadow;
 
+/// Executors delivering message sequences to network servers
+#[cfg(all(feature = "std", feature = "multipart_inputs"))]
+pub mod socket;
+
 pub mod with_observers;
 
 /// The module for all the hooks
@@ -0,0 +1,570 @@
+//! A persistent-mode executor for external targets that cannot be built with the forkserver runtime.
+//!
+//! The [`PersistentCommandExecutor`] keeps one target process alive over many executions.
+//! Inputs are written to a shared memory buffer, and each execution is triggered and acknowledged
+//! over a pair of pipes. The target side of the protocol is implemented by the client shim in
+//! `libafl_targets::persistent_command`, which can be linked into any binary and is also callable from C.
+//!
+//! The protocol, with all values being native endian `u32`s:
+//! 1. The client maps the shared memory named by [`PERSISTENT_SHM_ENV_VAR`] and writes [`PERSISTENT_HELLO`] to the status pipe.
+//! 2. For every execution, the executor writes the input length followed by the input to the shared memory
+//!    and sends [`PERSISTENT_RUN`] over the control pipe.
+//! 3. The client runs the harness on the input and answers with [`PERSISTENT_DONE`].
+//!
+//! If the status pipe closes during an execution, the target died and is restarted for the next execution.
+//! This is reported as a crash, also if the target exited normally, since the harness must not exit in persistent mode.
+//! If the target does not answer within the timeout, it is killed and restarted.
+
+use alloc::{string::ToString, vec::Vec};
+use core::{
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+    time::Duration,
+};
+use std::{
+    ffi::OsString,
+    io::{ErrorKind, Read, Write},
+    os::{
+        fd::{AsRawFd, BorrowedFd},
+        unix::process::ExitStatusExt,
+    },
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+};
+
+use libafl_bolts::{
+    AsSlice, AsSliceMut, InputLocation, StdTargetArgs, StdTargetArgsInner,
+    core_affinity::CoreId,
+    os::pipes::Pipe,
+    shmem::{ShMem, ShMemProvider},
+    tuples::RefIndexable,
+};
+use nix::sys::{
+    select::{FdSet, pselect},
+    signal::SigSet,
+    time::TimeSpec,
+};
+
+use super::{
+    HasTimeout, StdChildArgs, StdChildArgsInner,
+    forkserver::{ConfigTarget, MAX_INPUT_SIZE_DEFAULT},
+};
+#[cfg(feature = "regex")]
+use crate::observers::get_asan_runtime_flags;
+use crate::{
+    Error,
+    executors::{Executor, ExitKind, HasObservers, SetTimeout},
+    inputs::ToTargetBytesConverter,
+    observers::ObserversTuple,
+    state::HasExecutions,
+};
+
+/// The env variable holding the id of the input shared memory, its size is in `{PERSISTENT_SHM_ENV_VAR}_SIZE`
+pub const PERSISTENT_SHM_ENV_VAR: &str = "__LIBAFL_PERSISTENT_SHM_ID";
+/// The size of the input length header at the start of the input shared memory
+pub const PERSISTENT_HDR_SIZE: usize = 4;
+/// Sent by the client once it is ready to receive inputs
+pub const PERSISTENT_HELLO: u32 = 0x4C41_4650;
+/// Sent by the executor to start an execution
+pub const PERSISTENT_RUN: u32 = 0x5255_4E21;
+/// Sent by the client once the harness returned
+pub const PERSISTENT_DONE: u32 = 0x444F_4E45;
+
+/// What the client answered on the status pipe
+enum Reply {
+    Status(u32),
+    Closed,
+    TimedOut,
+}
+
+/// Reads the next message from the status pipe, waiting at most `timeout`
+fn read_reply(st_pipe: &mut Pipe, timeout: Duration) -> Result<Reply, Error> {
+    let Some(st_read) = st_pipe.read_end() else {
+        return Ok(Reply::Closed);
+    };
+    // # Safety
+    // The fd stays open as long as the pipe.
+    let st_read = unsafe { BorrowedFd::borrow_raw(st_read) };
+
+    let mut readfds = FdSet::new();
+    readfds.insert(st_read);
+    let sret = pselect(
+        Some(st_read.as_raw_fd() + 1),
+        &mut readfds,
+        None,
+        None,
+        Some(&TimeSpec::from_duration(timeout)),
+        Some(&SigSet::empty()),
+    )?;
+    if sret == 0 {
+        return Ok(Reply::TimedOut);
+    }
+
+    let mut buf = [0_u8; 4];
+    match st_pipe.read_exact(&mut buf) {
+        Ok(()) => Ok(Reply::Status(u32::from_ne_bytes(buf))),
+        Err(err) if err.kind() == ErrorKind::UnexpectedEof => Ok(Reply::Closed),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// The running target process and its pipes
+struct PersistentChild {
+    process: Child,
+    st_pipe: Pipe,
+    ctl_pipe: Pipe,
+    executions: u64,
+}
+
+impl PersistentChild {
+    /// Kills and reaps the process
+    fn kill(mut self) -> Result<(), Error> {
+        // The process may already be dead, which is fine.
+        let _ = self.process.kill();
+        self.process.wait()?;
+        Ok(())
+    }
+}
+
+/// An [`Executor`] running inputs in a long-lived external process, delivering them over shared memory.
+///
+/// The target has to link the client shim of `libafl_targets::persistent_command`, see the module documentation
+/// for the protocol. Targets that read their input from stdin can keep doing so, the shim resets stdin to the
+/// current input before every iteration.
+///
+/// Use [`PersistentCommandExecutor::builder`] to create it.
+pub struct PersistentCommandExecutor<I, OT, S, SHM> {
+    program: OsString,
+    arguments: Vec<OsString>,
+    envs: Vec<(OsString, OsString)>,
+    current_directory: Option<PathBuf>,
+    debug_child: bool,
+    core: Option<CoreId>,
+    child: Option<PersistentChild>,
+    input_shmem: SHM,
+    observers: OT,
+    timeout: Duration,
+    startup_timeout: Duration,
+    executions_per_process: Option<u64>,
+    phantom: PhantomData<fn() -> (I, S)>,
+}
+
+impl PersistentCommandExecutor<(), (), (), ()> {
+    /// Creates a builder for a new [`PersistentCommandExecutor`]
+    #[must_use]
+    pub fn builder() -> PersistentCommandExecutorBuilder {
+        PersistentCommandExecutorBuilder::new()
+    }
+}
+
+impl<I, OT, S, SHM> Debug for PersistentCommandExecutor<I, OT, S, SHM>
+where
+    OT: Debug,
+    SHM: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PersistentCommandExecutor")
+            .field("program", &self.program)
+            .field("arguments", &self.arguments)
+            .field("child", &self.child.as_ref().map(|c| c.process.id()))
+            .field("input_shmem", &self.input_shmem)
+            .field("observers", &self.observers)
+            .field("timeout", &self.timeout)
+            .field("executions_per_process", &self.executions_per_process)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<I, OT, S, SHM> PersistentCommandExecutor<I, OT, S, SHM>
+where
+    SHM: ShMem,
+{
+    /// The pid of the running target process, if any
+    #[must_use]
+    pub fn child_pid(&self) -> Option<u32> {
+        self.child.as_ref().map(|child| child.process.id())
+    }
+
+    /// Creates the [`Command`] for a fresh target process
+    fn command(&self) -> Command {
+        let mut command = Command::new(&self.program);
+        command
+            .args(&self.arguments)
+            .env(PERSISTENT_SHM_ENV_VAR, self.input_shmem.id().to_string())
+            .env(
+                format!("{PERSISTENT_SHM_ENV_VAR}_SIZE"),
+                self.input_shmem.len().to_string(),
+            )
+            .stdin(Stdio::null());
+        #[cfg(feature = "regex")]
+        command.env("ASAN_OPTIONS", get_asan_runtime_flags());
+        command.envs(self.envs.iter().map(|(k, v)| (k, v)));
+        if let Some(cwd) = &self.current_directory {
+            command.current_dir(cwd);
+        }
+        if self.debug_child {
+            command.stdout(Stdio::inherit()).stderr(Stdio::inherit());
+        } else {
+            command.stdout(Stdio::null()).stderr(Stdio::null());
+        }
+        if let Some(core) = self.core {
+            command.bind(core);
+        }
+        command
+    }
+
+    /// Starts a fresh target process and waits for the client to be ready
+    fn spawn_child(&mut self) -> Result<(), Error> {
+        let mut st_pipe = Pipe::new()?;
+        let mut ctl_pipe = Pipe::new()?;
+        let mut command = self.command();
+        // # Safety
+        // The pipe file descriptors are valid at this point.
+        let process = unsafe {
+            command.setpipe(
+                st_pipe.read_end().unwrap(),
+                st_pipe.write_end().unwrap(),
+                ctl_pipe.read_end().unwrap(),
+                ctl_pipe.write_end().unwrap(),
+            )
+        }
+        .spawn()
+        .map_err(|err| {
+            Error::illegal_state(format!("Could not spawn the persistent target: {err:#?}"))
+        })?;
+        ctl_pipe.close_read_end();
+        st_pipe.close_write_end();
+
+        let mut child = PersistentChild {
+            process,
+            st_pipe,
+            ctl_pipe,
+            executions: 0,
+        };
+        if let Reply::Status(PERSISTENT_HELLO) =
+            read_reply(&mut child.st_pipe, self.startup_timeout)?
+        {
+            self.child = Some(child);
+            Ok(())
+        } else {
+            child.kill()?;
+            Err(Error::illegal_state(
+                "The target did not complete the persistent handshake. Is the libafl_targets persistent_command client linked and called?",
+            ))
+        }
+    }
+
+    /// Kills the running target process, the next execution starts a fresh one
+    pub fn kill_child(&mut self) -> Result<(), Error> {
+        match self.child.take() {
+            Some(child) => child.kill(),
+            None => Ok(()),
+        }
+    }
+
+    /// Executes the input in the running target process, (re)starting it if needed
+    fn execute_input(&mut self, input: &[u8]) -> Result<ExitKind, Error> {
+        if self.child.is_none() {
+            self.spawn_child()?;
+        }
+
+        let len = input
+            .len()
+            .min(self.input_shmem.len() - PERSISTENT_HDR_SIZE);
+        let map = self.input_shmem.as_slice_mut();
+        #[expect(clippy::cast_possible_truncation)] // the shared memory is smaller than 4 GiB
+        map[..PERSISTENT_HDR_SIZE].copy_from_slice(&(len as u32).to_ne_bytes());
+        map[PERSISTENT_HDR_SIZE..PERSISTENT_HDR_SIZE + len].copy_from_slice(&input[..len]);
+
+        if self
+            .child
+            .as_mut()
+            .unwrap()
+            .ctl_pipe
+            .write_all(&PERSISTENT_RUN.to_ne_bytes())
+            .is_err()
+        {
+            // The target exited between two executions, start over.
+            self.kill_child()?;
+            self.spawn_child()?;
+            self.child
+                .as_mut()
+                .unwrap()
+                .ctl_pipe
+                .write_all(&PERSISTENT_RUN.to_ne_bytes())?;
+        }
+
+        let child = self.child.as_mut().unwrap();
+
+        match read_reply(&mut child.st_pipe, self.timeout)? {
+            Reply::Status(PERSISTENT_DONE) => {
+                child.executions += 1;
+                if self
+                    .executions_per_process
+                    .is_some_and(|max| child.executions >= max)
+                {
+                    self.kill_child()?;
+                }
+                Ok(ExitKind::Ok)
+            }
+            Reply::Status(status) => {
+                self.kill_child()?;
+                Err(Error::illegal_state(format!(
+                    "Unexpected status {status:#x} from the persistent target"
+                )))
+            }
+            Reply::Closed => {
+                // The next execution starts a fresh target process
+                let mut child = self.child.take().unwrap();
+                let status = child.process.wait()?;
+                if status.signal().is_none() {
+                    log::warn!("The persistent target exited during an execution with {status}");
+                }
+                Ok(ExitKind::Crash)
+            }
+            Reply::TimedOut => {
+                self.kill_child()?;
+                Ok(ExitKind::Timeout)
+            }
+        }
+    }
+}
+
+impl<I, OT, S, SHM> Drop for PersistentCommandExecutor<I, OT, S, SHM> {
+    fn drop(&mut self) {
+        if let Some(child) = self.child.take() {
+            let _ = child.kill();
+        }
+    }
+}
+
+impl<EM, I, OT, S, SHM, Z> Executor<EM, I, S, Z> for PersistentCommandExecutor<I, OT, S, SHM>
+where
+    OT: ObserversTuple<I, S>,
+    S: HasExecutions,
+    SHM: ShMem,
+    Z: ToTargetBytesConverter<I, S>,
+{
+    fn run_target(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut S,
+        _mgr: &mut EM,
+        input: &I,
+    ) -> Result<ExitKind, Error> {
+        *state.executions_mut() += 1;
+        let bytes = fuzzer.convert_to_target_bytes(state, input);
+        self.observers.pre_exec_child_all(state, input)?;
+        let exit_kind = self.execute_input(bytes.as_slice())?;
+        self.observers
+            .post_exec_child_all(state, input, &exit_kind)?;
+        Ok(exit_kind)
+    }
+}
+
+impl<I, OT, S, SHM> HasTimeout for PersistentCommandExecutor<I, OT, S, SHM> {
+    #[inline]
+    fn timeout(&self) -> Duration {
+        self.timeout
+    }
+}
+
+impl<I, OT, S, SHM> SetTimeout for PersistentCommandExecutor<I, OT, S, SHM> {
+    #[inline]
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+}
+
+impl<I, OT, S, SHM> HasObservers for PersistentCommandExecutor<I, OT, S, SHM>
+where
+    OT: ObserversTuple<I, S>,
+{
+    type Observers = OT;
+
+    #[inline]
+    fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+        RefIndexable::from(&self.observers)
+    }
+
+    #[inline]
+    fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+        RefIndexable::from(&mut self.observers)
+    }
+}
+
+/// The builder for the [`PersistentCommandExecutor`]
+#[derive(Debug, Clone)]
+pub struct PersistentCommandExecutorBuilder {
+    target_inner: StdTargetArgsInner,
+    child_env_inner: StdChildArgsInner,
+    max_input_size: usize,
+    startup_timeout: Duration,
+    executions_per_process: Option<u64>,
+}
+
+impl StdTargetArgs for PersistentCommandExecutorBuilder {
+    fn inner(&self) -> &StdTargetArgsInner {
+        &self.target_inner
+    }
+
+    fn inner_mut(&mut self) -> &mut StdTargetArgsInner {
+        &mut self.target_inner
+    }
+}
+
+impl StdChildArgs for PersistentCommandExecutorBuilder {
+    fn inner(&self) -> &StdChildArgsInner {
+        &self.child_env_inner
+    }
+
+    fn inner_mut(&mut self) -> &mut StdChildArgsInner {
+        &mut self.child_env_inner
+    }
+}
+
+impl Default for PersistentCommandExecutorBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PersistentCommandExecutorBuilder {
+    /// Creates a new [`PersistentCommandExecutorBuilder`]
+    #[must_use]
+    fn new() -> Self {
+        Self {
+            target_inner: StdTargetArgsInner::default(),
+            child_env_inner: StdChildArgsInner::default(),
+            max_input_size: MAX_INPUT_SIZE_DEFAULT,
+            startup_timeout: Duration::from_secs(10),
+            executions_per_process: None,
+        }
+    }
+
+    /// Sets the maximum input size, longer inputs are truncated
+    #[must_use]
+    pub fn max_input_size(mut self, size: usize) -> Self {
+        self.max_input_size = size;
+        self
+    }
+
+    /// Sets how long the target may take until the client is ready, 10 seconds by default
+    #[must_use]
+    pub fn startup_timeout(mut self, timeout: Duration) -> Self {
+        self.startup_timeout = timeout;
+        self
+    }
+
+    /// Restarts the target after this many executions, to get rid of state leaking between executions
+    #[must_use]
+    pub fn executions_per_process(mut self, executions: u64) -> Self {
+        self.executions_per_process = Some(executions);
+        self
+    }
+
+    /// Builds the [`PersistentCommandExecutor`] and starts the target
+    pub fn build<I, OT, S, SP>(
+        &self,
+        observers: OT,
+        shmem_provider: &mut SP,
+    ) -> Result<PersistentCommandExecutor<I, OT, S, SP::ShMem>, Error>
+    where
+        SP: ShMemProvider,
+    {
+        let Some(program) = &self.target_inner.program else {
+            return Err(Error::illegal_argument(
+                "PersistentCommandExecutor::builder: no program set!",
+            ));
+        };
+        if self.target_inner.input_location != InputLocation::default() {
+            return Err(Error::illegal_argument(
+                "PersistentCommandExecutor delivers inputs over shared memory, stdin is reset by the client",
+            ));
+        }
+        if self.child_env_inner.stdout_observer.is_some()
+            || self.child_env_inner.stderr_observer.is_some()
+        {
+            return Err(Error::illegal_argument(
+                "StdOut and StdErr observers are not supported by the PersistentCommandExecutor",
+            ));
+        }
+        if self.max_input_size > u32::MAX as usize - PERSISTENT_HDR_SIZE {
+            return Err(Error::illegal_argument(
+                "The maximum input size is too large",
+            ));
+        }
+
+        let input_shmem = shmem_provider.new_shmem(self.max_input_size + PERSISTENT_HDR_SIZE)?;
+        let mut executor = PersistentCommandExecutor {
+            program: program.clone(),
+            arguments: self.target_inner.arguments.clone(),
+            envs: self.target_inner.envs.clone(),
+            current_directory: self.child_env_inner.current_directory.clone(),
+            debug_child: self.child_env_inner.debug_child,
+            core: self.child_env_inner.core,
+            child: None,
+            input_shmem,
+            observers,
+            timeout: self.child_env_inner.timeout,
+            startup_timeout: self.startup_timeout,
+            executions_per_process: self.executions_per_process,
+            phantom: PhantomData,
+        };
+        executor.spawn_child()?;
+        Ok(executor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::{
+        StdTargetArgs,
+        shmem::{ShMemProvider, StdShMemProvider},
+    };
+
+    use super::PersistentCommandExecutor;
+    use crate::{
+        events::NopEventManager,
+        executors::{Executor, ExitKind},
+        inputs::{BytesInput, BytesInputConverter},
+        state::NopState,
+    };
+
+    #[test]
+    fn handshake_required() {
+        let mut shmem_provider = StdShMemProvider::new().unwrap();
+        let executor = PersistentCommandExecutor::builder()
+            .program("true")
+            .build::<BytesInput, (), (), _>((), &mut shmem_provider);
+        assert!(executor.is_err());
+    }
+
+    #[test]
+    #[cfg(target_endian = "little")]
+    fn exit_during_execution() {
+        // Completes the handshake, then exits normally in the first execution
+        let mut shmem_provider = StdShMemProvider::new().unwrap();
+        let mut executor = PersistentCommandExecutor::builder()
+            .program("bash")
+            .arg("-c")
+            .arg("printf PFAL >&199; head -c 4 <&198 >/dev/null; exit 0")
+            .build::<BytesInput, (), _, _>((), &mut shmem_provider)
+            .unwrap();
+
+        let mut state = NopState::<BytesInput>::new();
+        let input = BytesInput::new(b"input".to_vec());
+        for _ in 0..2 {
+            let exit_kind = executor
+                .run_target(
+                    &mut BytesInputConverter::new(),
+                    &mut state,
+                    &mut NopEventManager::new(),
+                    &input,
+                )
+                .unwrap();
+            assert_eq!(exit_kind, ExitKind::Crash);
+            assert!(executor.child_pid().is_none());
+        }
+    }
+}
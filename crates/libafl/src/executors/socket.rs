@@ -0,0 +1,771 @@
+//! The [`SocketExecutor`] fuzzes stateful network servers, `AFLNet`-style.
+//! See <https://github.com/aflnet/aflnet>
+//!
+//! For every execution, the executor starts a fresh server through a [`SessionTarget`],
+//! connects to it and delivers the messages of a [`ListInput`] one by one over a TCP, UDP
+//! or Unix socket, collecting the response to each message in a [`ResponsesObserver`].
+//! Together with the [`crate::feedbacks::ProtocolStateFeedback`], the responses drive the
+//! exploration of the server's state machine.
+
+use alloc::vec::Vec;
+use core::{
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    net::{TcpStream, UdpSocket},
+    process::{Child, Command, ExitStatus},
+    thread,
+    time::Instant,
+};
+#[cfg(unix)]
+use std::{
+    os::unix::{net::UnixStream, process::ExitStatusExt},
+    path::PathBuf,
+};
+
+#[cfg(unix)]
+use libafl_bolts::shmem::ShMem;
+use libafl_bolts::{
+    AsSlice,
+    tuples::{Handle, Handled, MatchNameRef, RefIndexable},
+};
+#[cfg(unix)]
+use nix::{
+    sys::{signal::kill, time::TimeSpec},
+    unistd::Pid,
+};
+
+#[cfg(unix)]
+use crate::executors::ForkserverExecutor;
+use crate::{
+    Error,
+    executors::{Executor, ExitKind, HasObservers},
+    inputs::{HasTargetBytes, ListInput},
+    observers::{ObserversTuple, ResponsesObserver},
+    state::HasExecutions,
+};
+
+/// The time between two connection attempts while the server starts up
+const CONNECT_RETRY_INTERVAL: Duration = Duration::from_millis(1);
+
+/// The maximum size of a single read from the socket
+const RECV_CHUNK_SIZE: usize = 4096;
+
+/// The transport used to talk to the server
+#[derive(Debug, Clone)]
+pub enum SocketTransport {
+    /// A TCP connection to the given address
+    Tcp(SocketAddr),
+    /// UDP datagrams to the given address, one per message
+    Udp(SocketAddr),
+    /// A Unix domain stream socket at the given path
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+/// An open connection to the server
+enum Connection {
+    Tcp(TcpStream),
+    Udp(UdpSocket),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Connection {
+    /// Connects to the server, retrying until the `deadline` while it is still starting up
+    fn open(transport: &SocketTransport, deadline: Instant) -> io::Result<Self> {
+        loop {
+            let attempt = match transport {
+                SocketTransport::Tcp(addr) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    TcpStream::connect_timeout(addr, remaining.max(CONNECT_RETRY_INTERVAL))
+                        .and_then(|stream| {
+                            stream.set_nodelay(true)?;
+                            Ok(Self::Tcp(stream))
+                        })
+                }
+                SocketTransport::Udp(addr) => {
+                    let local: SocketAddr = if addr.is_ipv4() {
+                        (Ipv4Addr::UNSPECIFIED, 0).into()
+                    } else {
+                        (Ipv6Addr::UNSPECIFIED, 0).into()
+                    };
+                    UdpSocket::bind(local).and_then(|socket| {
+                        socket.connect(addr)?;
+                        Ok(Self::Udp(socket))
+                    })
+                }
+                #[cfg(unix)]
+                SocketTransport::Unix(path) => UnixStream::connect(path).map(Self::Unix),
+            };
+            match attempt {
+                Err(_) if Instant::now() < deadline => thread::sleep(CONNECT_RETRY_INTERVAL),
+                attempt => return attempt,
+            }
+        }
+    }
+
+    fn set_read_timeout(&self, timeout: Duration) -> io::Result<()> {
+        match self {
+            Self::Tcp(stream) => stream.set_read_timeout(Some(timeout)),
+            Self::Udp(socket) => socket.set_read_timeout(Some(timeout)),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.set_read_timeout(Some(timeout)),
+        }
+    }
+
+    fn send(&mut self, message: &[u8]) -> io::Result<()> {
+        match self {
+            Self::Tcp(stream) => stream.write_all(message),
+            Self::Udp(socket) => socket.send(message).map(|_| ()),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.write_all(message),
+        }
+    }
+
+    fn recv(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Tcp(stream) => stream.read(buf),
+            Self::Udp(socket) => socket.recv(buf),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.read(buf),
+        }
+    }
+
+    /// Reads the response until the server stays silent for the read timeout, closes the connection,
+    /// or the `deadline` of the session passes.
+    ///
+    /// Returns `Ok(None)` if the connection was closed, or reset by a crashing server, before any data arrived,
+    /// and an [`ErrorKind::TimedOut`] error if the server stayed silent from the start.
+    fn recv_response(&mut self, deadline: Instant) -> io::Result<Option<Vec<u8>>> {
+        let mut response = vec![];
+        let mut buf = [0; RECV_CHUNK_SIZE];
+        loop {
+            match self.recv(&mut buf) {
+                Ok(0) => {
+                    return Ok(
+                        (!response.is_empty() || matches!(self, Self::Udp(_))).then_some(response)
+                    );
+                }
+                Ok(len) => {
+                    response.extend_from_slice(&buf[..len]);
+                    if Instant::now() >= deadline {
+                        return Ok(Some(response));
+                    }
+                }
+                Err(err) if is_timeout(&err) => {
+                    if response.is_empty() {
+                        return Err(io::Error::from(ErrorKind::TimedOut));
+                    }
+                    return Ok(Some(response));
+                }
+                Err(err)
+                    if matches!(
+                        err.kind(),
+                        ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted
+                    ) =>
+                {
+                    return Ok((!response.is_empty()).then_some(response));
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+/// If a socket operation failed because its timeout expired
+fn is_timeout(err: &io::Error) -> bool {
+    matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut)
+}
+
+/// The outcome of delivering the messages of one session
+struct Session {
+    responses: Vec<Vec<u8>>,
+    /// The session took longer than the timeout of the executor
+    timed_out: bool,
+}
+
+/// A server that is started freshly for every session of a [`SocketExecutor`]
+pub trait SessionTarget {
+    /// Starts the server
+    fn start(&mut self) -> Result<(), Error>;
+
+    /// Waits up to `grace` for the server to exit on its own, then kills it.
+    ///
+    /// Returns [`ExitKind::Crash`] if the server died from a signal during the session.
+    fn stop(&mut self, grace: Duration) -> Result<ExitKind, Error>;
+}
+
+/// Determines the [`ExitKind`] of a server that exited by itself
+fn exit_kind_of(status: ExitStatus) -> ExitKind {
+    #[cfg(unix)]
+    if status.signal().is_some() {
+        return ExitKind::Crash;
+    }
+    #[cfg(not(unix))]
+    let _ = status;
+    ExitKind::Ok
+}
+
+/// A [`SessionTarget`] spawning the server from a [`Command`] for every session
+pub struct CommandSessionTarget {
+    command: Command,
+    child: Option<Child>,
+}
+
+impl CommandSessionTarget {
+    /// Creates a new [`CommandSessionTarget`].
+    ///
+    /// The `command` should make the server listen on the address given to the [`SocketExecutor`].
+    #[must_use]
+    pub fn new(command: Command) -> Self {
+        Self {
+            command,
+            child: None,
+        }
+    }
+
+    /// Kills and reaps the current server, if it is still running
+    fn kill_child(&mut self) -> Result<(), Error> {
+        if let Some(mut child) = self.child.take() {
+            child.kill()?;
+            child.wait()?;
+        }
+        Ok(())
+    }
+}
+
+impl Debug for CommandSessionTarget {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CommandSessionTarget")
+            .field("command", &self.command)
+            .field("child", &self.child.as_ref().map(Child::id))
+            .finish()
+    }
+}
+
+impl SessionTarget for CommandSessionTarget {
+    fn start(&mut self) -> Result<(), Error> {
+        self.kill_child()?;
+        self.child = Some(self.command.spawn()?);
+        Ok(())
+    }
+
+    fn stop(&mut self, grace: Duration) -> Result<ExitKind, Error> {
+        let Some(child) = self.child.as_mut() else {
+            return Err(Error::illegal_state("The server was not started"));
+        };
+        let deadline = Instant::now() + grace;
+        loop {
+            if let Some(status) = child.try_wait()? {
+                self.child = None;
+                return Ok(exit_kind_of(status));
+            }
+            if Instant::now() >= deadline {
+                break;
+            }
+            thread::sleep(CONNECT_RETRY_INTERVAL);
+        }
+        self.kill_child()?;
+        Ok(ExitKind::Ok)
+    }
+}
+
+impl Drop for CommandSessionTarget {
+    fn drop(&mut self) {
+        let _ = self.kill_child();
+    }
+}
+
+/// Forks a fresh server from the forkserver for every session.
+///
+/// The messages are delivered over the socket only, the input file or shared memory of the
+/// [`ForkserverExecutor`] is left untouched.
+#[cfg(unix)]
+impl<I, OT, S, SHM> SessionTarget for ForkserverExecutor<I, OT, S, SHM>
+where
+    OT: ObserversTuple<I, S>,
+    SHM: ShMem,
+{
+    fn start(&mut self) -> Result<(), Error> {
+        let forkserver = self.forkserver_mut();
+        let last_run_timed_out = forkserver.last_run_timed_out_raw();
+        forkserver.set_last_run_timed_out(false);
+        forkserver.write_ctl(last_run_timed_out).map_err(|err| {
+            Error::unknown(format!(
+                "Unable to request new process from fork server (OOM?): {err:?}"
+            ))
+        })?;
+        let pid = forkserver.read_st()?;
+        if pid <= 0 {
+            return Err(Error::unknown("Fork server is misbehaving (OOM?)"));
+        }
+        forkserver.set_child_pid(Pid::from_raw(pid));
+        Ok(())
+    }
+
+    fn stop(&mut self, grace: Duration) -> Result<ExitKind, Error> {
+        let forkserver = self.forkserver_mut();
+        let exit_kind = if let Some(status) = forkserver.read_st_timed(&TimeSpec::from(grace))? {
+            forkserver.set_status(status);
+            if libc::WIFSIGNALED(status) {
+                ExitKind::Crash
+            } else {
+                ExitKind::Ok
+            }
+        } else {
+            // The server is still waiting for more messages, so end the session.
+            forkserver.set_last_run_timed_out(true);
+            let _ = kill(forkserver.child_pid(), forkserver.kill_signal());
+            let status = forkserver
+                .read_st()
+                .map_err(|err| Error::unknown(format!("Could not kill the server: {err:?}")))?;
+            forkserver.set_status(status);
+            ExitKind::Ok
+        };
+        forkserver.reset_child_pid();
+        Ok(exit_kind)
+    }
+}
+
+/// An [`Executor`] delivering each part of a [`ListInput`] as one message to a server over a socket.
+///
+/// Each execution starts a fresh server through the [`SessionTarget`], connects to it within the
+/// startup timeout, sends the messages in order and waits for each response until the server is
+/// silent for the message timeout. A greeting sent by the server right after connecting ends up in
+/// the response to the first message, and a message that is not answered within the message timeout
+/// ends the session. The responses are recorded in the [`ResponsesObserver`] set with
+/// [`SocketExecutor::with_responses_observer`]. A server dying from a signal is reported as
+/// [`ExitKind::Crash`]. A server that does not accept the connection within the startup timeout,
+/// or is still busy with the session after the timeout, is reported as [`ExitKind::Timeout`].
+pub struct SocketExecutor<I, OT, S, T> {
+    target: T,
+    transport: SocketTransport,
+    observers: OT,
+    responses_observer: Option<Handle<ResponsesObserver>>,
+    startup_timeout: Duration,
+    message_timeout: Duration,
+    timeout: Duration,
+    exit_grace: Duration,
+    phantom: PhantomData<fn() -> (I, S)>,
+}
+
+impl<I, OT, S, T> SocketExecutor<I, OT, S, T> {
+    /// Creates a new [`SocketExecutor`] talking to the server started by `target` over `transport`
+    pub fn new(target: T, transport: SocketTransport, observers: OT) -> Self {
+        Self {
+            target,
+            transport,
+            observers,
+            responses_observer: None,
+            startup_timeout: Duration::from_secs(1),
+            message_timeout: Duration::from_millis(50),
+            timeout: Duration::from_secs(1),
+            exit_grace: Duration::from_millis(10),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Records the responses of the server in the given observer, which must be part of the observers
+    #[must_use]
+    pub fn with_responses_observer(mut self, observer: &ResponsesObserver) -> Self {
+        self.responses_observer = Some(observer.handle());
+        self
+    }
+
+    /// Sets how long the server may take until it accepts connections, 1 second by default
+    #[must_use]
+    pub fn with_startup_timeout(mut self, timeout: Duration) -> Self {
+        self.startup_timeout = timeout;
+        self
+    }
+
+    /// Sets how long the server may stay silent before a response is considered complete, 50 milliseconds by default.
+    ///
+    /// If the server stays silent for this long after a message without answering it, the session ends.
+    #[must_use]
+    pub fn with_message_timeout(mut self, timeout: Duration) -> Self {
+        self.message_timeout = timeout;
+        self
+    }
+
+    /// Sets how long a session may take after connecting to the server before it hangs, 1 second by default
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets how long the server may take to exit on its own after the last message before it is killed,
+    /// 10 milliseconds by default
+    #[must_use]
+    pub fn with_exit_grace(mut self, grace: Duration) -> Self {
+        self.exit_grace = grace;
+        self
+    }
+
+    /// The [`SessionTarget`] starting the server
+    pub fn target(&self) -> &T {
+        &self.target
+    }
+
+    /// The [`SessionTarget`] starting the server (mutable)
+    pub fn target_mut(&mut self) -> &mut T {
+        &mut self.target
+    }
+
+    /// The transport used to talk to the server
+    pub fn transport(&self) -> &SocketTransport {
+        &self.transport
+    }
+
+    /// Delivers all messages and collects the responses.
+    ///
+    /// The session ends early if the server closes the connection, does not answer a message,
+    /// or the session takes longer than the timeout. Fails if no connection could be made within the startup timeout.
+    fn deliver<P>(&self, input: &ListInput<P>) -> Result<Session, io::Error>
+    where
+        P: HasTargetBytes,
+    {
+        let deadline = Instant::now() + self.startup_timeout;
+        let mut connection = Connection::open(&self.transport, deadline)?;
+        connection.set_read_timeout(self.message_timeout)?;
+        let session_deadline = Instant::now() + self.timeout;
+
+        let mut responses = Vec::with_capacity(input.len());
+        for (idx, part) in input.parts().iter().enumerate() {
+            if Instant::now() >= session_deadline {
+                log::debug!("Session timed out before message {idx}");
+                return Ok(Session {
+                    responses,
+                    timed_out: true,
+                });
+            }
+            let message = part.target_bytes();
+            let response = loop {
+                if let Err(err) = connection.send(message.as_slice()) {
+                    log::debug!("Session ended while sending message {idx}: {err}");
+                    return Ok(Session {
+                        responses,
+                        timed_out: false,
+                    });
+                }
+                match connection.recv_response(session_deadline) {
+                    // A UDP server that is not bound yet makes the first datagram bounce
+                    Err(err)
+                        if idx == 0
+                            && err.kind() == ErrorKind::ConnectionRefused
+                            && Instant::now() < deadline =>
+                    {
+                        thread::sleep(CONNECT_RETRY_INTERVAL);
+                    }
+                    Err(err) => {
+                        log::debug!("Session ended while receiving response {idx}: {err}");
+                        return Ok(Session {
+                            responses,
+                            timed_out: Instant::now() >= session_deadline,
+                        });
+                    }
+                    Ok(response) => break response,
+                }
+            };
+            let Some(response) = response else {
+                return Ok(Session {
+                    responses,
+                    timed_out: false,
+                });
+            };
+            responses.push(response);
+        }
+        Ok(Session {
+            responses,
+            timed_out: Instant::now() >= session_deadline,
+        })
+    }
+}
+
+impl<I, OT, S, T> Debug for SocketExecutor<I, OT, S, T>
+where
+    OT: Debug,
+    T: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SocketExecutor")
+            .field("target", &self.target)
+            .field("transport", &self.transport)
+            .field("observers", &self.observers)
+            .field("startup_timeout", &self.startup_timeout)
+            .field("message_timeout", &self.message_timeout)
+            .field("timeout", &self.timeout)
+            .field("exit_grace", &self.exit_grace)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<EM, OT, P, S, T, Z> Executor<EM, ListInput<P>, S, Z> for SocketExecutor<ListInput<P>, OT, S, T>
+where
+    OT: ObserversTuple<ListInput<P>, S>,
+    P: HasTargetBytes,
+    S: HasExecutions,
+    T: SessionTarget,
+{
+    fn run_target(
+        &mut self,
+        _fuzzer: &mut Z,
+        state: &mut S,
+        _mgr: &mut EM,
+        input: &ListInput<P>,
+    ) -> Result<ExitKind, Error> {
+        *state.executions_mut() += 1;
+        self.observers.pre_exec_child_all(state, input)?;
+
+        self.target.start()?;
+        let delivered = self.deliver(input);
+        let mut exit_kind = self.target.stop(self.exit_grace)?;
+
+        let responses = match delivered {
+            Ok(session) => {
+                if session.timed_out && exit_kind == ExitKind::Ok {
+                    exit_kind = ExitKind::Timeout;
+                }
+                session.responses
+            }
+            Err(err) => {
+                // Unless the server crashed before, it did not accept the connection in time
+                log::debug!("Could not connect to the server: {err}");
+                if exit_kind == ExitKind::Ok {
+                    exit_kind = ExitKind::Timeout;
+                }
+                vec![]
+            }
+        };
+        if let Some(handle) = &self.responses_observer {
+            let observer = self
+                .observers
+                .get_mut(handle)
+                .ok_or_else(|| Error::key_not_found("ResponsesObserver not found"))?;
+            for response in responses {
+                observer.observe(response);
+            }
+        }
+
+        self.observers
+            .post_exec_child_all(state, input, &exit_kind)?;
+        Ok(exit_kind)
+    }
+}
+
+impl<I, OT, S, T> HasObservers for SocketExecutor<I, OT, S, T>
+where
+    OT: ObserversTuple<I, S>,
+{
+    type Observers = OT;
+
+    #[inline]
+    fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+        RefIndexable::from(&self.observers)
+    }
+
+    #[inline]
+    fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+        RefIndexable::from(&mut self.observers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        thread::{self, JoinHandle},
+    };
+
+    use libafl_bolts::tuples::tuple_list;
+
+    use super::{SessionTarget, SocketExecutor, SocketTransport};
+    use crate::{
+        Error,
+        events::NopEventManager,
+        executors::{Executor, ExitKind, HasObservers},
+        feedbacks::{Feedback, ProtocolStateFeedback, StateInitializer},
+        fuzzer::NopFuzzer,
+        inputs::{BytesInput, MessageSequenceInput},
+        observers::ResponsesObserver,
+        state::NopState,
+    };
+
+    /// How the [`ThreadServer`] behaves
+    #[derive(Clone, Copy)]
+    enum Behavior {
+        /// Answers `USER` with 331 and everything else with 500
+        Answer,
+        /// Never answers
+        Mute,
+        /// Sends data without ever pausing
+        Flood,
+    }
+
+    /// A tiny line based server
+    struct ThreadServer {
+        listener: TcpListener,
+        behavior: Behavior,
+        handle: Option<JoinHandle<()>>,
+    }
+
+    impl SessionTarget for ThreadServer {
+        fn start(&mut self) -> Result<(), Error> {
+            let listener = self.listener.try_clone()?;
+            let behavior = self.behavior;
+            self.handle = Some(thread::spawn(move || {
+                let (stream, _) = listener.accept().unwrap();
+                let mut writer = stream.try_clone().unwrap();
+                match behavior {
+                    Behavior::Answer => {}
+                    Behavior::Mute => {
+                        for _ in BufReader::new(stream).lines() {}
+                        return;
+                    }
+                    Behavior::Flood => {
+                        while writer.write_all(b"flood\r\n").is_ok() {}
+                        return;
+                    }
+                }
+                writer.write_all(b"220 ready\r\n").unwrap();
+                for line in BufReader::new(stream).lines() {
+                    let Ok(line) = line else { break };
+                    let reply: &[u8] = if line.starts_with("USER") {
+                        b"331 password?\r\n"
+                    } else {
+                        b"500 unknown\r\n"
+                    };
+                    writer.write_all(reply).unwrap();
+                }
+            }));
+            Ok(())
+        }
+
+        fn stop(&mut self, _grace: Duration) -> Result<ExitKind, Error> {
+            self.handle.take().unwrap().join().unwrap();
+            Ok(ExitKind::Ok)
+        }
+    }
+
+    /// A server that never starts
+    struct NoServer;
+
+    impl SessionTarget for NoServer {
+        fn start(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn stop(&mut self, _grace: Duration) -> Result<ExitKind, Error> {
+            Ok(ExitKind::Ok)
+        }
+    }
+
+    #[test]
+    fn socket_session() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let target = ThreadServer {
+            listener,
+            behavior: Behavior::Answer,
+            handle: None,
+        };
+        let observer = ResponsesObserver::new("responses");
+        let mut feedback = ProtocolStateFeedback::new(&observer);
+        let mut executor = SocketExecutor::new(
+            target,
+            SocketTransport::Tcp(addr),
+            tuple_list!(observer.clone()),
+        )
+        .with_responses_observer(&observer)
+        .with_message_timeout(Duration::from_millis(200));
+
+        let mut state = NopState::<MessageSequenceInput>::new();
+        feedback.init_state(&mut state).unwrap();
+        let mut mgr = NopEventManager::new();
+        let input = MessageSequenceInput::new(vec![
+            BytesInput::new(b"USER x\r\n".to_vec()),
+            BytesInput::new(b"PASS y\r\n".to_vec()),
+        ]);
+
+        let exit_kind = executor
+            .run_target(&mut NopFuzzer::new(), &mut state, &mut mgr, &input)
+            .unwrap();
+        assert_eq!(exit_kind, ExitKind::Ok);
+
+        let observers = executor.observers();
+        let responses = observers.0.responses();
+        assert_eq!(responses.len(), 2);
+        assert!(responses[0].starts_with(b"220 ready\r\n331"));
+        assert!(responses[1].starts_with(b"500"));
+        assert!(
+            feedback
+                .is_interesting(&mut state, &mut mgr, &input, &*observers, &exit_kind)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn socket_timeouts() {
+        let mut state = NopState::<MessageSequenceInput>::new();
+        let mut mgr = NopEventManager::new();
+        let input = MessageSequenceInput::new(vec![BytesInput::new(b"USER x\r\n".to_vec())]);
+
+        // A server that accepts the connection, but never answers, only ends the session
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let target = ThreadServer {
+            listener,
+            behavior: Behavior::Mute,
+            handle: None,
+        };
+        let observer = ResponsesObserver::new("responses");
+        let mut executor = SocketExecutor::new(
+            target,
+            SocketTransport::Tcp(addr),
+            tuple_list!(observer.clone()),
+        )
+        .with_responses_observer(&observer)
+        .with_message_timeout(Duration::from_millis(20));
+        let exit_kind = executor
+            .run_target(&mut NopFuzzer::new(), &mut state, &mut mgr, &input)
+            .unwrap();
+        assert_eq!(exit_kind, ExitKind::Ok);
+        assert!(executor.observers().0.responses().is_empty());
+
+        // A server that is still busy after the timeout
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let target = ThreadServer {
+            listener,
+            behavior: Behavior::Flood,
+            handle: None,
+        };
+        let mut executor = SocketExecutor::new(target, SocketTransport::Tcp(addr), ())
+            .with_message_timeout(Duration::from_millis(20))
+            .with_timeout(Duration::from_millis(50));
+        let exit_kind = executor
+            .run_target(&mut NopFuzzer::new(), &mut state, &mut mgr, &input)
+            .unwrap();
+        assert_eq!(exit_kind, ExitKind::Timeout);
+
+        // A server that never accepts the connection
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let mut executor = SocketExecutor::new(NoServer, SocketTransport::Tcp(addr), ())
+            .with_startup_timeout(Duration::from_millis(20));
+        let exit_kind = executor
+            .run_target(&mut NopFuzzer::new(), &mut state, &mut mgr, &input)
+            .unwrap();
+        assert_eq!(exit_kind, ExitKind::Timeout);
+    }
+}
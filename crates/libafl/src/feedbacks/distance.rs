@@ -0,0 +1,282 @@
+//! The [`DistanceFeedback`] records the distance of new testcases to the targets of directed fuzzing.
+//!
+//! It never makes an input interesting on its own. Together with a directed
+//! [`crate::schedulers::powersched::PowerSchedule`], the recorded distances shift the energy of the
+//! power schedule towards testcases closer to the targets.
+
+use alloc::borrow::Cow;
+use core::time::Duration;
+
+use libafl_bolts::{
+    Error, Named, current_time, impl_serdeany,
+    tuples::{Handle, Handled, MatchName, MatchNameRef},
+};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "track_hit_feedbacks")]
+use crate::feedbacks::premature_last_result_err;
+use crate::{
+    HasMetadata,
+    corpus::Testcase,
+    executors::ExitKind,
+    feedbacks::{Feedback, StateInitializer},
+    observers::DistanceObserver,
+};
+
+/// The distance of a testcase to the targets of directed fuzzing
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    expect(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct DistanceMetadata {
+    /// The average distance of the executed blocks
+    pub distance: f64,
+}
+
+impl_serdeany!(DistanceMetadata);
+
+/// The distance range of the corpus and the start of the campaign, used for simulated annealing
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    expect(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct DistanceBoundsMetadata {
+    /// The smallest distance in the corpus
+    pub min_distance: f64,
+    /// The largest distance in the corpus
+    pub max_distance: f64,
+    /// When the campaign started, since the epoch
+    pub start_time: Duration,
+}
+
+impl_serdeany!(DistanceBoundsMetadata);
+
+impl DistanceBoundsMetadata {
+    /// Creates the metadata for a campaign starting now
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            min_distance: f64::INFINITY,
+            max_distance: f64::NEG_INFINITY,
+            start_time: current_time(),
+        }
+    }
+
+    /// Extends the range by the distance of a new testcase
+    pub fn update(&mut self, distance: f64) {
+        self.min_distance = self.min_distance.min(distance);
+        self.max_distance = self.max_distance.max(distance);
+    }
+
+    /// The distance normalized to `[0, 1]` within the range of the corpus, `0` is the closest
+    #[must_use]
+    pub fn normalize(&self, distance: f64) -> f64 {
+        if self.max_distance > self.min_distance {
+            ((distance - self.min_distance) / (self.max_distance - self.min_distance))
+                .clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Default for DistanceBoundsMetadata {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Attaches the [`DistanceMetadata`] measured by a [`DistanceObserver`] to new testcases.
+///
+/// Combine it with a coverage feedback using `feedback_or!`, it is never interesting by itself.
+#[derive(Debug, Clone)]
+pub struct DistanceFeedback {
+    observer_handle: Handle<DistanceObserver>,
+    #[cfg(feature = "track_hit_feedbacks")]
+    last_result: Option<bool>,
+}
+
+impl DistanceFeedback {
+    /// Creates a new [`DistanceFeedback`]
+    #[must_use]
+    pub fn new(observer: &DistanceObserver) -> Self {
+        Self {
+            observer_handle: observer.handle(),
+            #[cfg(feature = "track_hit_feedbacks")]
+            last_result: None,
+        }
+    }
+}
+
+impl Named for DistanceFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        self.observer_handle.name()
+    }
+}
+
+impl<S> StateInitializer<S> for DistanceFeedback
+where
+    S: HasMetadata,
+{
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        state.metadata_or_insert_with(DistanceBoundsMetadata::new);
+        Ok(())
+    }
+}
+
+impl<EM, I, OT, S> Feedback<EM, I, OT, S> for DistanceFeedback
+where
+    OT: MatchName,
+    S: HasMetadata,
+{
+    fn is_interesting(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        _observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        #[cfg(feature = "track_hit_feedbacks")]
+        {
+            self.last_result = Some(false);
+        }
+        Ok(false)
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        self.last_result.ok_or(premature_last_result_err())
+    }
+
+    fn append_metadata(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        observers: &OT,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        let observer = observers
+            .get(&self.observer_handle)
+            .ok_or_else(|| Error::key_not_found("DistanceObserver not found"))?;
+        if let Some(distance) = observer.last_distance() {
+            state
+                .metadata_or_insert_with(DistanceBoundsMetadata::new)
+                .update(distance);
+            testcase.add_metadata(DistanceMetadata { distance });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::tuples::tuple_list;
+
+    use super::{DistanceBoundsMetadata, DistanceFeedback, DistanceMetadata};
+    use crate::{
+        HasMetadata,
+        corpus::Testcase,
+        events::NopEventManager,
+        executors::ExitKind,
+        feedbacks::{Feedback, StateInitializer},
+        inputs::NopInput,
+        observers::{DistanceObserver, Observer},
+        state::NopState,
+    };
+
+    #[test]
+    fn distance_bounds() {
+        let mut bounds = DistanceBoundsMetadata::new();
+        assert!(bounds.normalize(5.0).abs() < f64::EPSILON);
+
+        bounds.update(10.0);
+        assert!(bounds.normalize(10.0).abs() < f64::EPSILON);
+
+        bounds.update(30.0);
+        bounds.update(20.0);
+        assert!((bounds.min_distance - 10.0).abs() < f64::EPSILON);
+        assert!((bounds.max_distance - 30.0).abs() < f64::EPSILON);
+        assert!(bounds.normalize(10.0).abs() < f64::EPSILON);
+        assert!((bounds.normalize(20.0) - 0.5).abs() < f64::EPSILON);
+        assert!((bounds.normalize(30.0) - 1.0).abs() < f64::EPSILON);
+        assert!(bounds.normalize(0.0).abs() < f64::EPSILON);
+        assert!((bounds.normalize(50.0) - 1.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn distance_feedback() {
+        let mut sum = 0;
+        let mut count = 0;
+        let mut observer =
+            unsafe { DistanceObserver::from_mut_ptr("distance", &raw mut sum, &raw mut count) };
+        let mut feedback = DistanceFeedback::new(&observer);
+        let mut state = NopState::<NopInput>::new();
+        let mut mgr = NopEventManager::new();
+        feedback.init_state(&mut state).unwrap();
+        assert!(state.has_metadata::<DistanceBoundsMetadata>());
+
+        // No block with a distance was executed, nothing is recorded
+        Observer::<NopInput, _>::pre_exec(&mut observer, &mut state, &NopInput {}).unwrap();
+        Observer::<NopInput, _>::post_exec(&mut observer, &mut state, &NopInput {}, &ExitKind::Ok)
+            .unwrap();
+        let observers = tuple_list!(observer);
+        assert!(
+            !feedback
+                .is_interesting(
+                    &mut state,
+                    &mut mgr,
+                    &NopInput {},
+                    &observers,
+                    &ExitKind::Ok
+                )
+                .unwrap()
+        );
+        let mut testcase = Testcase::new(NopInput {});
+        feedback
+            .append_metadata(&mut state, &mut mgr, &observers, &mut testcase)
+            .unwrap();
+        assert!(!testcase.has_metadata::<DistanceMetadata>());
+        let (mut observer, ()) = observers;
+
+        for (distance_sum, expected) in [(12, 3.0), (28, 7.0)] {
+            Observer::<NopInput, _>::pre_exec(&mut observer, &mut state, &NopInput {}).unwrap();
+            unsafe {
+                (&raw mut sum).write_volatile(distance_sum);
+                (&raw mut count).write_volatile(4);
+            }
+            Observer::<NopInput, _>::post_exec(
+                &mut observer,
+                &mut state,
+                &NopInput {},
+                &ExitKind::Ok,
+            )
+            .unwrap();
+            let observers = tuple_list!(observer);
+            assert!(
+                !feedback
+                    .is_interesting(
+                        &mut state,
+                        &mut mgr,
+                        &NopInput {},
+                        &observers,
+                        &ExitKind::Ok
+                    )
+                    .unwrap()
+            );
+            let mut testcase = Testcase::new(NopInput {});
+            feedback
+                .append_metadata(&mut state, &mut mgr, &observers, &mut testcase)
+                .unwrap();
+            let recorded = testcase.metadata::<DistanceMetadata>().unwrap().distance;
+            assert!((recorded - expected).abs() < f64::EPSILON);
+            (observer, ()) = observers;
+        }
+
+        let bounds = state.metadata::<DistanceBoundsMetadata>().unwrap();
+        assert!((bounds.min_distance - 3.0).abs() < f64::EPSILON);
+        assert!((bounds.max_distance - 7.0).abs() < f64::EPSILON);
+    }
+}
@@ -35,6 +35,8 @@ pub use bool::BoolValueFeedback;
 /// The module for `CustomFilenameToTestcaseFeedback`
 pub mod custom_filename;
 pub mod differential;
+pub mod distance;
+pub use distance::{DistanceBoundsMetadata, DistanceFeedback, DistanceMetadata};
 /// The module for list feedback
 pub mod list;
 pub mod map;
@@ -42,6 +44,10 @@ pub mod map;
 pub mod nautilus;
 #[cfg(feature = "std")]
 pub mod new_hash_feedback;
+pub mod protocol_state;
+pub use protocol_state::ProtocolStateFeedback;
+pub mod rare_edge;
+pub use rare_edge::{RareEdgeFeedback, RareEdgeMetadata, RareEdgesMetadata};
 #[cfg(feature = "simd")]
 pub mod simd;
 #[cfg(feature = "std")]
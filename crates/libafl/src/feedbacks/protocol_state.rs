@@ -0,0 +1,218 @@
+//! An `AFLNet`-style state feedback for stateful protocol fuzzing.
+//! See <https://github.com/aflnet/aflnet>
+//!
+//! The response codes of a server form the states of an implicitly learned state machine.
+//! An input is interesting if its message sequence reaches a new state or a new transition between states.
+
+use alloc::{borrow::Cow, vec::Vec};
+
+use hashbrown::HashSet;
+use libafl_bolts::{
+    Error, Named, impl_serdeany,
+    tuples::{Handle, Handled, MatchName, MatchNameRef},
+};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "track_hit_feedbacks")]
+use crate::feedbacks::premature_last_result_err;
+use crate::{
+    HasMetadata, HasNamedMetadata,
+    corpus::Testcase,
+    executors::ExitKind,
+    feedbacks::{Feedback, StateInitializer},
+    observers::ResponsesObserver,
+};
+
+/// The state every session starts in, before the first response
+pub const INITIAL_PROTOCOL_STATE: u32 = 0;
+
+/// The state machine learned from all sessions so far
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    expect(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct ProtocolStateMetadata {
+    /// All states seen
+    pub states: HashSet<u32>,
+    /// All transitions seen, as `(from, to)` pairs
+    pub transitions: HashSet<(u32, u32)>,
+}
+
+impl_serdeany!(ProtocolStateMetadata);
+
+/// The sequence of states a testcase goes through, starting with [`INITIAL_PROTOCOL_STATE`]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    expect(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct ProtocolStateSequenceMetadata {
+    /// The states, one entry per response code
+    pub states: Vec<u32>,
+}
+
+impl_serdeany!(ProtocolStateSequenceMetadata);
+
+/// Extracts the response codes of text based protocols from a server response.
+///
+/// Every line starting with a three digit code (`FTP`, `SMTP`) or with a protocol version followed by a
+/// three digit code (`HTTP/1.1 200 OK`, `RTSP/1.0 404 Not Found`) yields its code.
+#[must_use]
+pub fn extract_response_codes(response: &[u8]) -> Vec<u32> {
+    let three_digits = |bytes: &[u8]| -> Option<u32> {
+        let digits = bytes.get(..3)?;
+        if !digits.iter().all(u8::is_ascii_digit) || bytes.get(3).is_some_and(u8::is_ascii_digit) {
+            return None;
+        }
+        Some(
+            digits
+                .iter()
+                .fold(0, |code, digit| code * 10 + u32::from(digit - b'0')),
+        )
+    };
+
+    response
+        .split(|byte| *byte == b'\n')
+        .filter_map(|line| {
+            three_digits(line).or_else(|| {
+                let space = line.iter().position(|byte| *byte == b' ')?;
+                if line[..space].contains(&b'/') {
+                    three_digits(&line[space + 1..])
+                } else {
+                    None
+                }
+            })
+        })
+        .collect()
+}
+
+/// A feedback that learns the state machine of a server from its response codes, like `AFLNet`.
+///
+/// It considers an input interesting if the responses observed by the [`ResponsesObserver`] lead to
+/// a new state or a new transition. The state sequence is stored in the
+/// [`ProtocolStateSequenceMetadata`] of each new testcase.
+#[derive(Debug)]
+pub struct ProtocolStateFeedback {
+    name: Cow<'static, str>,
+    observer_handle: Handle<ResponsesObserver>,
+    extractor: fn(&[u8]) -> Vec<u32>,
+    last_states: Vec<u32>,
+    #[cfg(feature = "track_hit_feedbacks")]
+    last_result: Option<bool>,
+}
+
+impl ProtocolStateFeedback {
+    /// Creates a new [`ProtocolStateFeedback`] using [`extract_response_codes`] to find the states
+    #[must_use]
+    pub fn new(observer: &ResponsesObserver) -> Self {
+        Self::with_extractor(observer, extract_response_codes)
+    }
+
+    /// Creates a new [`ProtocolStateFeedback`] with a custom function extracting the response codes of a response
+    #[must_use]
+    pub fn with_extractor(observer: &ResponsesObserver, extractor: fn(&[u8]) -> Vec<u32>) -> Self {
+        Self {
+            name: Cow::Owned(format!("ProtocolStateFeedback<{}>", observer.name())),
+            observer_handle: observer.handle(),
+            extractor,
+            last_states: vec![],
+            #[cfg(feature = "track_hit_feedbacks")]
+            last_result: None,
+        }
+    }
+}
+
+impl Named for ProtocolStateFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<S> StateInitializer<S> for ProtocolStateFeedback
+where
+    S: HasNamedMetadata,
+{
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        state.add_named_metadata_checked(self.name(), ProtocolStateMetadata::default())?;
+        Ok(())
+    }
+}
+
+impl<EM, I, OT, S> Feedback<EM, I, OT, S> for ProtocolStateFeedback
+where
+    OT: MatchName,
+    S: HasNamedMetadata,
+{
+    fn is_interesting(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        let observer = observers
+            .get(&self.observer_handle)
+            .ok_or_else(|| Error::key_not_found("ResponsesObserver not found"))?;
+
+        self.last_states.clear();
+        self.last_states.push(INITIAL_PROTOCOL_STATE);
+        for response in observer.responses() {
+            self.last_states.extend((self.extractor)(response));
+        }
+
+        let known = state.named_metadata::<ProtocolStateMetadata>(&self.name)?;
+        let interesting = self.last_states.iter().any(|s| !known.states.contains(s))
+            || self
+                .last_states
+                .windows(2)
+                .any(|pair| !known.transitions.contains(&(pair[0], pair[1])));
+        #[cfg(feature = "track_hit_feedbacks")]
+        {
+            self.last_result = Some(interesting);
+        }
+        Ok(interesting)
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        self.last_result.ok_or(premature_last_result_err())
+    }
+
+    fn append_metadata(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        _observers: &OT,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        let known = state.named_metadata_mut::<ProtocolStateMetadata>(&self.name)?;
+        known.states.extend(self.last_states.iter().copied());
+        known
+            .transitions
+            .extend(self.last_states.windows(2).map(|pair| (pair[0], pair[1])));
+        testcase.add_metadata(ProtocolStateSequenceMetadata {
+            states: self.last_states.clone(),
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::extract_response_codes;
+
+    #[test]
+    fn response_codes() {
+        assert_eq!(
+            extract_response_codes(b"220 ready\r\n331 password?\r\n"),
+            [220, 331]
+        );
+        assert_eq!(
+            extract_response_codes(b"HTTP/1.1 404 Not Found\r\nContent-Length: 200\r\n\r\n"),
+            [404]
+        );
+        assert!(extract_response_codes(b"1234 no code\r\nhello 200\r\n").is_empty());
+    }
+}
@@ -0,0 +1,257 @@
+//! Rare edge tracking, like `FairFuzz`.
+//! See <https://github.com/carolemieux/afl-rb>
+//!
+//! Every execution counts which map entries it hits. Entries hit by only few inputs are rare,
+//! and testcases hitting rare edges get more attention from the schedulers wrapping their score with
+//! [`crate::schedulers::testcase_score::RareEdgeTestcaseScore`] or
+//! [`crate::schedulers::testcase_score::RareEdgeTestcasePenalty`].
+
+use alloc::{borrow::Cow, vec::Vec};
+use core::marker::PhantomData;
+
+use libafl_bolts::{
+    AsIter, Error, Named, impl_serdeany,
+    tuples::{Handle, Handled, MatchName, MatchNameRef},
+};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "track_hit_feedbacks")]
+use crate::feedbacks::premature_last_result_err;
+use crate::{
+    HasMetadata,
+    corpus::Testcase,
+    executors::ExitKind,
+    feedbacks::{Feedback, StateInitializer},
+    observers::MapObserver,
+};
+
+/// The smallest rarity exponent, entries hit by fewer than `2^4` inputs are always rare
+const MIN_RARITY_EXP: u32 = 4;
+
+/// The number of inputs hitting each map entry, over all executions
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    expect(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct RareEdgeMetadata {
+    /// The number of inputs that hit each map entry
+    pub hit_counts: Vec<u64>,
+    /// Entries hit by fewer inputs than this are rare
+    pub rarity_cutoff: u64,
+}
+
+impl_serdeany!(RareEdgeMetadata);
+
+impl RareEdgeMetadata {
+    /// Creates the metadata with all counts at zero
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            hit_counts: vec![],
+            rarity_cutoff: 1 << MIN_RARITY_EXP,
+        }
+    }
+
+    /// Whether the map entry is hit by few inputs
+    #[must_use]
+    pub fn is_rare(&self, idx: usize) -> bool {
+        self.hit_counts
+            .get(idx)
+            .is_some_and(|hits| *hits > 0 && *hits < self.rarity_cutoff)
+    }
+
+    /// Raises the cutoff above the hit count of the rarest entry, like `FairFuzz`.
+    ///
+    /// The cutoff never decreases, so the rarest entries stay rare once all entries are hit often.
+    pub fn update_cutoff(&mut self) {
+        if let Some(min) = self
+            .hit_counts
+            .iter()
+            .copied()
+            .filter(|hits| *hits > 0)
+            .min()
+        {
+            let exp = (min.ilog2() + 1).max(MIN_RARITY_EXP);
+            self.rarity_cutoff = self.rarity_cutoff.max(1_u64 << exp.min(63));
+        }
+    }
+}
+
+/// The rare edges a testcase hit when it was added to the corpus
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    expect(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct RareEdgesMetadata {
+    /// The map indexes of the rare edges
+    pub edges: Vec<usize>,
+}
+
+impl_serdeany!(RareEdgesMetadata);
+
+impl RareEdgesMetadata {
+    /// The number of edges of this testcase that are still rare
+    #[must_use]
+    pub fn count_rare(&self, meta: &RareEdgeMetadata) -> usize {
+        self.edges.iter().filter(|idx| meta.is_rare(**idx)).count()
+    }
+
+    /// The edge of this testcase hit by the fewest inputs, if any is still rare
+    #[must_use]
+    pub fn rarest(&self, meta: &RareEdgeMetadata) -> Option<usize> {
+        self.edges
+            .iter()
+            .copied()
+            .filter(|idx| meta.is_rare(*idx))
+            .min_by_key(|idx| meta.hit_counts[*idx])
+    }
+}
+
+/// Counts the inputs hitting each entry of a map and marks testcases hitting rare entries.
+///
+/// It never makes an input interesting on its own, combine it with the coverage feedback using `feedback_or!`.
+/// New testcases get a [`RareEdgesMetadata`] listing the rare entries they hit.
+#[derive(Debug, Clone)]
+pub struct RareEdgeFeedback<C, O> {
+    name: Cow<'static, str>,
+    map_ref: Handle<C>,
+    #[cfg(feature = "track_hit_feedbacks")]
+    last_result: Option<bool>,
+    phantom: PhantomData<O>,
+}
+
+impl<C, O> RareEdgeFeedback<C, O>
+where
+    C: Named,
+{
+    /// Creates a new [`RareEdgeFeedback`] for the given map observer
+    #[must_use]
+    pub fn new(map_observer: &C) -> Self {
+        Self {
+            name: Cow::Owned(format!("RareEdgeFeedback<{}>", map_observer.name())),
+            map_ref: map_observer.handle(),
+            #[cfg(feature = "track_hit_feedbacks")]
+            last_result: None,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<C, O> Named for RareEdgeFeedback<C, O> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<C, O, S> StateInitializer<S> for RareEdgeFeedback<C, O>
+where
+    S: HasMetadata,
+{
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        state.metadata_or_insert_with(RareEdgeMetadata::new);
+        Ok(())
+    }
+}
+
+impl<C, EM, I, O, OT, S> Feedback<EM, I, OT, S> for RareEdgeFeedback<C, O>
+where
+    C: AsRef<O>,
+    O: MapObserver + for<'it> AsIter<'it, Item = O::Entry>,
+    OT: MatchName,
+    S: HasMetadata,
+{
+    fn is_interesting(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        let observer = observers
+            .get(&self.map_ref)
+            .ok_or_else(|| Error::key_not_found("MapObserver not found"))?
+            .as_ref();
+        let initial = observer.initial();
+        let len = observer.usable_count();
+
+        let meta = state.metadata_or_insert_with(RareEdgeMetadata::new);
+        if meta.hit_counts.len() < len {
+            meta.hit_counts.resize(len, 0);
+        }
+        for (hits, entry) in meta.hit_counts.iter_mut().zip(observer.as_iter()).take(len) {
+            if *entry != initial {
+                *hits += 1;
+            }
+        }
+
+        #[cfg(feature = "track_hit_feedbacks")]
+        {
+            self.last_result = Some(false);
+        }
+        Ok(false)
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        self.last_result.ok_or(premature_last_result_err())
+    }
+
+    fn append_metadata(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        observers: &OT,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        let observer = observers
+            .get(&self.map_ref)
+            .ok_or_else(|| Error::key_not_found("MapObserver not found"))?
+            .as_ref();
+        let initial = observer.initial();
+
+        let meta = state.metadata_mut::<RareEdgeMetadata>()?;
+        meta.update_cutoff();
+        let edges: Vec<usize> = observer
+            .as_iter()
+            .take(observer.usable_count())
+            .enumerate()
+            .filter(|(idx, entry)| **entry != initial && meta.is_rare(*idx))
+            .map(|(idx, _)| idx)
+            .collect();
+        if !edges.is_empty() {
+            testcase.add_metadata(RareEdgesMetadata { edges });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RareEdgeMetadata, RareEdgesMetadata};
+
+    #[test]
+    fn rarity_cutoff() {
+        let mut meta = RareEdgeMetadata::new();
+        meta.hit_counts = vec![0, 3, 100, 40];
+        meta.update_cutoff();
+        assert_eq!(meta.rarity_cutoff, 16);
+        assert!(!meta.is_rare(0));
+        assert!(meta.is_rare(1));
+        assert!(!meta.is_rare(3));
+
+        meta.hit_counts = vec![0, 40, 100, 70];
+        meta.update_cutoff();
+        assert_eq!(meta.rarity_cutoff, 64);
+        assert!(meta.is_rare(1));
+        assert!(!meta.is_rare(3));
+
+        let edges = RareEdgesMetadata {
+            edges: vec![1, 2, 3],
+        };
+        assert_eq!(edges.count_rare(&meta), 1);
+        assert_eq!(edges.rarest(&meta), Some(1));
+    }
+}
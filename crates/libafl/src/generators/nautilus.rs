@@ -12,8 +12,14 @@ pub use crate::common::nautilus::grammartec::newtypes::NTermId;
 #[cfg(feature = "nautilus_py")]
 use crate::nautilus::grammartec::python_grammar_loader;
 use crate::{
-    Error, common::nautilus::grammartec::context::Context, generators::Generator,
-    inputs::nautilus::NautilusInput, state::HasRand,
+    Error,
+    common::nautilus::grammartec::{
+        antlr_grammar_loader::load_antlr_grammar, context::Context,
+        ebnf_grammar_loader::load_ebnf_grammar,
+    },
+    generators::Generator,
+    inputs::nautilus::NautilusInput,
+    state::HasRand,
 };
 
 /// The nautilus context for a generator
@@ -85,10 +91,40 @@ impl NautilusContext {
         Some(Self { ctx })
     }
 
+    /// Returns a new [`NautilusContext`] from an `ANTLR4` grammar, starting at its first parser rule.
+    ///
+    /// Lexer rules are turned into regex rules; unsupported constructs, such as rule arguments, result in an error.
+    pub fn from_antlr(tree_depth: usize, grammar: &str) -> Result<Self, Error> {
+        let mut ctx = load_antlr_grammar(grammar)?;
+        ctx.initialize(tree_depth);
+        Ok(Self { ctx })
+    }
+
+    /// Returns a new [`NautilusContext`] from an ISO `EBNF` grammar, starting at its first rule.
+    ///
+    /// Unsupported constructs, such as exceptions or special sequences, result in an error.
+    pub fn from_ebnf(tree_depth: usize, grammar: &str) -> Result<Self, Error> {
+        let mut ctx = load_ebnf_grammar(grammar)?;
+        ctx.initialize(tree_depth);
+        Ok(Self { ctx })
+    }
+
     /// Create a new [`NautilusContext`] from a file
+    ///
+    /// The format is picked by the extension: `.py` for python grammars (requires `nautilus_py`),
+    /// `.g4` for `ANTLR4`, `.ebnf` for `EBNF`, and the JSON rule list otherwise.
     pub fn from_file<P: AsRef<Path>>(tree_depth: usize, grammar_file: P) -> Result<Self, Error> {
         let grammar_file = grammar_file.as_ref();
-        if grammar_file.extension().unwrap_or_default() == "py" {
+        let extension = grammar_file.extension().unwrap_or_default();
+        if extension == "g4" {
+            log::debug!("Creating NautilusContext from ANTLR grammar");
+            return Self::from_antlr(tree_depth, &fs::read_to_string(grammar_file)?);
+        }
+        if extension == "ebnf" {
+            log::debug!("Creating NautilusContext from EBNF grammar");
+            return Self::from_ebnf(tree_depth, &fs::read_to_string(grammar_file)?);
+        }
+        if extension == "py" {
             #[cfg(feature = "nautilus_py")]
             {
                 log::debug!("Creating NautilusContext from python grammar");
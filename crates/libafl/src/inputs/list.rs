@@ -9,7 +9,7 @@ use alloc::{
     string::{String, ToString as _},
     vec::Vec,
 };
-use core::num::NonZero;
+use core::{num::NonZero, ops::RangeBounds};
 
 use arrayvec::ArrayVec;
 use libafl_bolts::{
@@ -21,7 +21,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     corpus::CorpusId,
-    inputs::Input,
+    inputs::{BytesInput, Input},
     mutators::{MutationResult, Mutator},
     state::HasRand,
 };
@@ -33,6 +33,12 @@ pub struct ListInput<I> {
     parts: Vec<I>,
 }
 
+/// An ordered sequence of messages, as sent to a server during one session in stateful protocol fuzzing.
+///
+/// Mutate single messages with [`ListInput::map_to_mutate_on_random_part`],
+/// and the sequence with the mutators in [`crate::mutators::list`].
+pub type MessageSequenceInput = ListInput<BytesInput>;
+
 impl<I> Default for ListInput<I> {
     fn default() -> Self {
         Self::empty()
@@ -140,6 +146,20 @@ impl<I> ListInput<I> {
         self.parts.remove(idx);
     }
 
+    /// Replaces the parts in `range` with the given parts, see [`Vec::splice`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds.
+    #[inline]
+    pub fn splice_parts<R, It>(&mut self, range: R, replace_with: It)
+    where
+        R: RangeBounds<usize>,
+        It: IntoIterator<Item = I>,
+    {
+        self.parts.splice(range, replace_with);
+    }
+
     /// Removes the last part from this input.
     ///
     /// Returns [`None`] if the input is empty.
@@ -18,6 +18,9 @@ pub use generalized::*;
 pub mod bytessub;
 pub use bytessub::BytesSubInput;
 
+pub mod schema;
+pub use schema::*;
+
 #[cfg(feature = "multipart_inputs")]
 pub mod multi;
 #[cfg(feature = "multipart_inputs")]
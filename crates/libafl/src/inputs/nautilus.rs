@@ -12,12 +12,13 @@ use serde::{Deserialize, Serialize};
 use crate::{
     common::nautilus::grammartec::{
         context::Context,
+        earley::EarleyParser,
         newtypes::{NTermId, NodeId, RuleId},
         rule::{Rule, RuleChild, RuleIdOrCustom},
         tree::{Tree, TreeLike},
     },
     generators::nautilus::NautilusContext,
-    inputs::{Input, ToTargetBytesConverter},
+    inputs::{BytesInput, FromTargetBytesConverter, Input, InputConverter, ToTargetBytesConverter},
 };
 
 /// An [`Input`] implementation for `Nautilus` grammar.
@@ -241,30 +242,71 @@ impl<'a> NautilusParser<'a> {
     }
 }
 
-impl<S> crate::inputs::FromTargetBytesConverter<NautilusInput, S> for NautilusBytesConverter<'_> {
+impl NautilusBytesConverter<'_> {
+    /// Parses `bytes` into a tree of the grammar, returns `None` if the bytes are not part of its language.
+    ///
+    /// The recursive descent [`NautilusParser`] is tried first. If it fails, e.g., for left-recursive
+    /// or ambiguous grammars, the bytes are parsed with the [`EarleyParser`].
+    fn parse(&self, bytes: &[u8]) -> Result<Option<NautilusInput>, libafl_bolts::Error> {
+        let ctx = &self.ctx.ctx;
+        let start_nt = ctx.nt_id("START");
+        if let Some((rules, consumed)) = NautilusParser::new(ctx, bytes).parse_nt(start_nt, 0)?
+            && consumed == bytes.len()
+        {
+            return Ok(Some(NautilusInput::new(Tree::from_rule_vec(rules, ctx))));
+        }
+        Ok(EarleyParser::new(ctx)?
+            .parse(start_nt, bytes)?
+            .map(NautilusInput::new))
+    }
+
+    /// Parses `bytes`, returning an empty input instead of any error if `on_error_return_empty` is set,
+    /// and `unparseable` if the bytes are not part of the language of the grammar.
+    fn parse_or_else<F>(
+        &self,
+        bytes: &[u8],
+        unparseable: F,
+    ) -> Result<NautilusInput, libafl_bolts::Error>
+    where
+        F: FnOnce() -> libafl_bolts::Error,
+    {
+        match self.parse(bytes) {
+            Ok(Some(input)) => Ok(input),
+            _ if self.on_error_return_empty => Ok(NautilusInput::empty()),
+            Ok(None) => Err(unparseable()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl<S> FromTargetBytesConverter<NautilusInput, S> for NautilusBytesConverter<'_> {
     fn convert_from_target_bytes(
         &mut self,
         _state: &mut S,
         bytes: &[u8],
     ) -> Result<NautilusInput, libafl_bolts::Error> {
-        let start_nt = self.ctx.ctx.nt_id("START");
-        let mut parser = NautilusParser::new(&self.ctx.ctx, bytes);
-        let res = parser.parse_nt(start_nt, 0);
-        match res {
-            Ok(Some((rules, consumed))) if consumed == bytes.len() => Ok(NautilusInput::new(
-                Tree::from_rule_vec(rules, &self.ctx.ctx),
-            )),
-            Err(e) if !self.on_error_return_empty => Err(e),
-            _ => {
-                if self.on_error_return_empty {
-                    Ok(NautilusInput::empty())
-                } else {
-                    Err(libafl_bolts::Error::illegal_argument(
-                        "Failed to parse bytes into NautilusInput",
-                    ))
-                }
-            }
-        }
+        self.parse_or_else(bytes, || {
+            libafl_bolts::Error::illegal_argument("Failed to parse bytes into NautilusInput")
+        })
+    }
+}
+
+/// Parses [`BytesInput`]s, such as seed files, into [`NautilusInput`]s,
+/// e.g., for [`crate::stages::SyncFromDiskStage::with_converter`].
+impl<S> InputConverter<S> for NautilusBytesConverter<'_> {
+    type From = BytesInput;
+    type To = NautilusInput;
+
+    /// Bytes that are not part of the language of the grammar are rejected with [`libafl_bolts::Error::invalid_input`],
+    /// so that [`crate::stages::SyncFromDiskStage`] skips them.
+    fn convert(
+        &mut self,
+        _state: &mut S,
+        input: BytesInput,
+    ) -> Result<NautilusInput, libafl_bolts::Error> {
+        self.parse_or_else(input.as_ref(), || {
+            libafl_bolts::Error::invalid_input("Failed to parse bytes into NautilusInput")
+        })
     }
 }
 
@@ -272,10 +314,12 @@ impl<S> crate::inputs::FromTargetBytesConverter<NautilusInput, S> for NautilusBy
 mod tests {
     use alloc::string::ToString;
 
-    use libafl_bolts::AsSlice;
+    use libafl_bolts::{AsSlice, Error};
 
     use super::{NautilusBytesConverter, NautilusContext};
-    use crate::inputs::{FromTargetBytesConverter, ToTargetBytesConverter};
+    use crate::inputs::{
+        BytesInput, FromTargetBytesConverter, InputConverter, ToTargetBytesConverter,
+    };
 
     #[test]
     #[cfg(feature = "nautilus")] // Nautilus parser requires nautilus feature (and regex)
@@ -300,6 +344,36 @@ mod tests {
 
         // Test invalid
         let bytes = b"aac";
-        assert!(converter.convert_from_target_bytes(&mut (), bytes).is_err());
+        assert!(matches!(
+            converter.convert_from_target_bytes(&mut (), bytes),
+            Err(Error::IllegalArgument(..))
+        ));
+        assert!(matches!(
+            converter.convert(&mut (), BytesInput::new(bytes.to_vec())),
+            Err(Error::InvalidInput(..))
+        ));
+
+        let mut converter = converter.on_error_return_empty(true);
+        let input = converter.convert_from_target_bytes(&mut (), bytes).unwrap();
+        assert!(input.tree.rules.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "nautilus")]
+    fn test_nautilus_parser_left_recursion() {
+        // The recursive descent parser gives up on left recursion, the Earley parser takes over
+        let rules = vec![
+            vec!["START".to_string(), "{A}".to_string()],
+            vec!["A".to_string(), "{A}+b".to_string()],
+            vec!["A".to_string(), "b".to_string()],
+        ];
+        let ctx = NautilusContext::new(10, &rules);
+        let mut converter = NautilusBytesConverter::new(&ctx);
+
+        let bytes = b"b+b+b";
+        let input = converter.convert_from_target_bytes(&mut (), bytes).unwrap();
+        let out_bytes = converter.convert_to_target_bytes(&mut (), &input);
+        assert_eq!(out_bytes.as_slice(), bytes.as_slice());
+        assert!(converter.convert_from_target_bytes(&mut (), b"b+").is_err());
     }
 }
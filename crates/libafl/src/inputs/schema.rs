@@ -0,0 +1,805 @@
+//! Structure-aware inputs for binary formats, described by a declarative [`Schema`].
+//!
+//! A [`Schema`] lists the fields of a format (integers of a given width and endianness, fixed or
+//! variable sized bytes, magic values, nested records and lists of records). Integer fields may be
+//! tied to other fields of the same record through a [`Relation`], such as the length of a payload
+//! or a CRC-32 over some other fields. The [`SchemaInput`] only stores the field values; after
+//! every mutation, [`SchemaInput::fixup`] recomputes the dependent fields, so the target always
+//! sees well-formed lengths and checksums.
+
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+pub use libafl_bolts::checksum::crc32;
+use libafl_bolts::{Error, ownedref::OwnedSlice};
+use serde::{Deserialize, Serialize};
+
+use crate::inputs::{
+    BytesInput, FromTargetBytesConverter, HasMutatorBytes, Input, InputConverter,
+    ToTargetBytesConverter,
+};
+
+/// The byte order of an integer field
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Endian {
+    /// Least significant byte first
+    Little,
+    /// Most significant byte first
+    Big,
+}
+
+/// The kind of a field in a [`Schema`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FieldKind {
+    /// An unsigned integer of `width` bytes (1 to 8)
+    Int {
+        /// The width in bytes
+        width: usize,
+        /// The byte order
+        endian: Endian,
+    },
+    /// Bytes of a fixed length
+    Bytes(usize),
+    /// Bytes of a variable length, bounded by a [`Relation::LengthOf`] field parsed before it,
+    /// or spanning the rest of the enclosing data otherwise
+    VarBytes,
+    /// A constant value, such as a file signature. It is never mutated.
+    Magic(Vec<u8>),
+    /// A nested record
+    Record(Vec<FieldSpec>),
+    /// A repeated field. Its length is given by a [`Relation::CountOf`] or a [`Relation::LengthOf`]
+    /// field parsed before it, otherwise it spans the rest of the enclosing data.
+    List(Box<FieldSpec>),
+}
+
+/// The relation of an integer field to its sibling fields, restored by [`SchemaInput::fixup`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Relation {
+    /// The summed length in bytes of the named fields
+    LengthOf(Vec<String>),
+    /// The number of elements in the named [`FieldKind::List`] field
+    CountOf(String),
+    /// The CRC-32 (IEEE, as used by `PNG` and `zlib`) over the named fields, in order
+    Crc32(Vec<String>),
+}
+
+/// A named field in a [`Schema`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FieldSpec {
+    /// The name, unique among its siblings
+    pub name: String,
+    /// The kind of this field
+    pub kind: FieldKind,
+    /// The relation of this field to its siblings, only valid for [`FieldKind::Int`] fields
+    pub relation: Option<Relation>,
+}
+
+impl FieldSpec {
+    fn new(name: &str, kind: FieldKind) -> Self {
+        Self {
+            name: name.to_string(),
+            kind,
+            relation: None,
+        }
+    }
+
+    /// An unsigned integer field of `width` bytes
+    #[must_use]
+    pub fn int(name: &str, width: usize, endian: Endian) -> Self {
+        Self::new(name, FieldKind::Int { width, endian })
+    }
+
+    /// A field of `len` bytes
+    #[must_use]
+    pub fn bytes(name: &str, len: usize) -> Self {
+        Self::new(name, FieldKind::Bytes(len))
+    }
+
+    /// A field of a variable number of bytes
+    #[must_use]
+    pub fn var_bytes(name: &str) -> Self {
+        Self::new(name, FieldKind::VarBytes)
+    }
+
+    /// A constant field
+    #[must_use]
+    pub fn magic(name: &str, value: &[u8]) -> Self {
+        Self::new(name, FieldKind::Magic(value.to_vec()))
+    }
+
+    /// A nested record
+    #[must_use]
+    pub fn record(name: &str, fields: Vec<FieldSpec>) -> Self {
+        Self::new(name, FieldKind::Record(fields))
+    }
+
+    /// A list of `element`s
+    #[must_use]
+    pub fn list(name: &str, element: FieldSpec) -> Self {
+        Self::new(name, FieldKind::List(Box::new(element)))
+    }
+
+    /// Make this field hold the summed length of the named sibling fields
+    #[must_use]
+    pub fn length_of(mut self, fields: &[&str]) -> Self {
+        self.relation = Some(Relation::LengthOf(
+            fields.iter().map(ToString::to_string).collect(),
+        ));
+        self
+    }
+
+    /// Make this field hold the number of elements of the named sibling list
+    #[must_use]
+    pub fn count_of(mut self, field: &str) -> Self {
+        self.relation = Some(Relation::CountOf(field.to_string()));
+        self
+    }
+
+    /// Make this field hold the CRC-32 of the named sibling fields
+    #[must_use]
+    pub fn crc32_of(mut self, fields: &[&str]) -> Self {
+        self.relation = Some(Relation::Crc32(
+            fields.iter().map(ToString::to_string).collect(),
+        ));
+        self
+    }
+
+    /// The serialized size of this field, if it does not depend on its value
+    #[must_use]
+    pub fn fixed_size(&self) -> Option<usize> {
+        match &self.kind {
+            FieldKind::Int { width, .. } => Some(*width),
+            FieldKind::Bytes(len) => Some(*len),
+            FieldKind::Magic(value) => Some(value.len()),
+            FieldKind::Record(fields) => fields.iter().map(Self::fixed_size).sum(),
+            FieldKind::VarBytes | FieldKind::List(_) => None,
+        }
+    }
+
+    /// The initial value of this field
+    #[must_use]
+    pub fn default_value(&self) -> FieldValue {
+        match &self.kind {
+            FieldKind::Int { .. } => FieldValue::Int(0),
+            FieldKind::Bytes(len) => FieldValue::Bytes(vec![0; *len]),
+            FieldKind::VarBytes => FieldValue::Bytes(vec![]),
+            FieldKind::Magic(value) => FieldValue::Bytes(value.clone()),
+            FieldKind::Record(fields) => {
+                FieldValue::Record(fields.iter().map(Self::default_value).collect())
+            }
+            FieldKind::List(_) => FieldValue::List(vec![]),
+        }
+    }
+}
+
+/// A declarative description of a binary format
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Schema {
+    fields: Vec<FieldSpec>,
+}
+
+impl Schema {
+    /// Creates a new [`Schema`] from the top-level fields, checking that it is consistent
+    pub fn new(fields: Vec<FieldSpec>) -> Result<Self, Error> {
+        let schema = Self { fields };
+        schema.validate()?;
+        Ok(schema)
+    }
+
+    /// The top-level fields
+    #[must_use]
+    pub fn fields(&self) -> &[FieldSpec] {
+        &self.fields
+    }
+
+    /// Checks the field names, integer widths and relations of this schema.
+    ///
+    /// Call this after deserializing a [`Schema`].
+    pub fn validate(&self) -> Result<(), Error> {
+        validate_record(&self.fields)
+    }
+
+    /// An input with every field set to its initial value and all relations fixed up
+    #[must_use]
+    pub fn default_input(&self) -> SchemaInput {
+        let mut input =
+            SchemaInput::new(self.fields.iter().map(FieldSpec::default_value).collect());
+        input.fixup(self);
+        input
+    }
+}
+
+fn validate_record(fields: &[FieldSpec]) -> Result<(), Error> {
+    let sibling = |name: &str| fields.iter().find(|f| f.name == name);
+    for (i, field) in fields.iter().enumerate() {
+        if fields[..i].iter().any(|f| f.name == field.name) {
+            return Err(Error::illegal_argument(format!(
+                "Duplicate schema field name `{}`",
+                field.name
+            )));
+        }
+        match &field.kind {
+            FieldKind::Int { width, .. } if !(1..=8).contains(width) => {
+                return Err(Error::illegal_argument(format!(
+                    "Integer field `{}` has unsupported width {width}",
+                    field.name
+                )));
+            }
+            FieldKind::Record(fields) => validate_record(fields)?,
+            FieldKind::List(element) => validate_record(core::slice::from_ref(element.as_ref()))?,
+            FieldKind::Int { .. }
+            | FieldKind::Bytes(_)
+            | FieldKind::VarBytes
+            | FieldKind::Magic(_) => {}
+        }
+
+        let Some(relation) = &field.relation else {
+            continue;
+        };
+        if !matches!(field.kind, FieldKind::Int { .. }) {
+            return Err(Error::illegal_argument(format!(
+                "Field `{}` has a relation but is not an integer",
+                field.name
+            )));
+        }
+        let targets = match relation {
+            Relation::LengthOf(targets) | Relation::Crc32(targets) => targets.as_slice(),
+            Relation::CountOf(target) => core::slice::from_ref(target),
+        };
+        for target in targets {
+            let Some(target_field) = sibling(target) else {
+                return Err(Error::illegal_argument(format!(
+                    "Field `{}` refers to unknown sibling `{target}`",
+                    field.name
+                )));
+            };
+            if *target == field.name {
+                return Err(Error::illegal_argument(format!(
+                    "Field `{}` refers to itself",
+                    field.name
+                )));
+            }
+            if matches!(relation, Relation::CountOf(_))
+                && !matches!(target_field.kind, FieldKind::List(_))
+            {
+                return Err(Error::illegal_argument(format!(
+                    "Field `{}` counts `{target}`, which is not a list",
+                    field.name
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The value of a field in a [`SchemaInput`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FieldValue {
+    /// The value of a [`FieldKind::Int`]
+    Int(u64),
+    /// The value of a [`FieldKind::Bytes`], [`FieldKind::VarBytes`] or [`FieldKind::Magic`]
+    Bytes(Vec<u8>),
+    /// The values of the fields of a [`FieldKind::Record`]
+    Record(Vec<FieldValue>),
+    /// The elements of a [`FieldKind::List`]
+    List(Vec<FieldValue>),
+}
+
+/// An input following a [`Schema`]. It only holds the values, the schema is needed to serialize it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SchemaInput {
+    fields: Vec<FieldValue>,
+}
+
+impl Input for SchemaInput {}
+
+impl SchemaInput {
+    /// Creates a new [`SchemaInput`] from the values of the top-level fields
+    #[must_use]
+    pub fn new(fields: Vec<FieldValue>) -> Self {
+        Self { fields }
+    }
+
+    /// The values of the top-level fields
+    #[must_use]
+    pub fn fields(&self) -> &[FieldValue] {
+        &self.fields
+    }
+
+    /// The values of the top-level fields, mutable
+    #[must_use]
+    pub fn fields_mut(&mut self) -> &mut Vec<FieldValue> {
+        &mut self.fields
+    }
+
+    /// Parses `bytes` according to `schema`.
+    ///
+    /// Returns [`Error::InvalidInput`] if the bytes do not follow the schema.
+    pub fn parse(schema: &Schema, bytes: &[u8]) -> Result<Self, Error> {
+        let (fields, consumed) = parse_record(&schema.fields, bytes)?;
+        if consumed != bytes.len() {
+            return Err(Error::invalid_input(format!(
+                "{} trailing bytes after the schema fields",
+                bytes.len() - consumed
+            )));
+        }
+        Ok(Self { fields })
+    }
+
+    /// Serializes this input according to `schema`
+    #[must_use]
+    pub fn to_bytes(&self, schema: &Schema) -> Vec<u8> {
+        let mut bytes = vec![];
+        serialize_record(&schema.fields, &self.fields, &mut bytes);
+        bytes
+    }
+
+    /// Recomputes all fields with a [`Relation`], innermost records first
+    pub fn fixup(&mut self, schema: &Schema) {
+        fixup_record(&schema.fields, &mut self.fields);
+    }
+
+    /// Calls `visitor` for every field value and its spec, depth first.
+    /// Records and lists are visited before their contents.
+    pub fn visit_fields_mut<F>(&mut self, schema: &Schema, visitor: &mut F)
+    where
+        F: FnMut(&FieldSpec, &mut FieldValue),
+    {
+        visit_record_mut(&schema.fields, &mut self.fields, visitor);
+    }
+}
+
+fn visit_field_mut<F>(spec: &FieldSpec, value: &mut FieldValue, visitor: &mut F)
+where
+    F: FnMut(&FieldSpec, &mut FieldValue),
+{
+    visitor(spec, value);
+    match (&spec.kind, value) {
+        (FieldKind::Record(fields), FieldValue::Record(values)) => {
+            visit_record_mut(fields, values, visitor);
+        }
+        (FieldKind::List(element), FieldValue::List(values)) => {
+            for value in values {
+                visit_field_mut(element, value, visitor);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn visit_record_mut<F>(specs: &[FieldSpec], values: &mut [FieldValue], visitor: &mut F)
+where
+    F: FnMut(&FieldSpec, &mut FieldValue),
+{
+    for (spec, value) in specs.iter().zip(values) {
+        visit_field_mut(spec, value, visitor);
+    }
+}
+
+fn serialize_field(spec: &FieldSpec, value: &FieldValue, out: &mut Vec<u8>) {
+    match (&spec.kind, value) {
+        (FieldKind::Int { width, endian }, FieldValue::Int(value)) => match endian {
+            Endian::Little => out.extend_from_slice(&value.to_le_bytes()[..*width]),
+            Endian::Big => out.extend_from_slice(&value.to_be_bytes()[8 - width..]),
+        },
+        (FieldKind::Bytes(len), FieldValue::Bytes(bytes)) => {
+            let start = out.len();
+            out.extend_from_slice(&bytes[..bytes.len().min(*len)]);
+            out.resize(start + len, 0);
+        }
+        (FieldKind::VarBytes, FieldValue::Bytes(bytes)) => out.extend_from_slice(bytes),
+        (FieldKind::Magic(magic), _) => out.extend_from_slice(magic),
+        (FieldKind::Record(fields), FieldValue::Record(values)) => {
+            serialize_record(fields, values, out);
+        }
+        (FieldKind::List(element), FieldValue::List(values)) => {
+            for value in values {
+                serialize_field(element, value, out);
+            }
+        }
+        // A value that does not match its spec, serialize the default instead
+        _ => serialize_field(spec, &spec.default_value(), out),
+    }
+}
+
+fn serialize_record(specs: &[FieldSpec], values: &[FieldValue], out: &mut Vec<u8>) {
+    for (i, spec) in specs.iter().enumerate() {
+        match values.get(i) {
+            Some(value) => serialize_field(spec, value, out),
+            None => serialize_field(spec, &spec.default_value(), out),
+        }
+    }
+}
+
+fn serialized_len(spec: &FieldSpec, value: &FieldValue) -> usize {
+    let mut bytes = vec![];
+    serialize_field(spec, value, &mut bytes);
+    bytes.len()
+}
+
+fn fixup_record(specs: &[FieldSpec], values: &mut [FieldValue]) {
+    for (spec, value) in specs.iter().zip(values.iter_mut()) {
+        match (&spec.kind, value) {
+            (FieldKind::Record(fields), FieldValue::Record(values)) => {
+                fixup_record(fields, values);
+            }
+            (FieldKind::List(element), FieldValue::List(values)) => {
+                for value in values {
+                    fixup_record(
+                        core::slice::from_ref(element.as_ref()),
+                        core::slice::from_mut(value),
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let position = |name: &str| specs.iter().position(|spec| spec.name == name);
+    // Lengths and counts first, checksums may cover them
+    for (i, spec) in specs.iter().enumerate() {
+        let new_value = match &spec.relation {
+            Some(Relation::LengthOf(targets)) => targets
+                .iter()
+                .filter_map(|target| position(target))
+                .filter_map(|idx| {
+                    values
+                        .get(idx)
+                        .map(|value| serialized_len(&specs[idx], value))
+                })
+                .sum::<usize>() as u64,
+            Some(Relation::CountOf(target)) => {
+                match position(target).and_then(|idx| values.get(idx)) {
+                    Some(FieldValue::List(elements)) => elements.len() as u64,
+                    _ => 0,
+                }
+            }
+            _ => continue,
+        };
+        if let Some(value) = values.get_mut(i) {
+            *value = FieldValue::Int(new_value);
+        }
+    }
+    for (i, spec) in specs.iter().enumerate() {
+        let Some(Relation::Crc32(targets)) = &spec.relation else {
+            continue;
+        };
+        let mut covered = vec![];
+        for idx in targets.iter().filter_map(|target| position(target)) {
+            if let Some(value) = values.get(idx) {
+                serialize_field(&specs[idx], value, &mut covered);
+            }
+        }
+        if let Some(value) = values.get_mut(i) {
+            *value = FieldValue::Int(u64::from(crc32(&covered)));
+        }
+    }
+}
+
+fn truncated(what: &str, name: &str) -> Error {
+    Error::invalid_input(format!("Input ends within {what} `{name}`"))
+}
+
+/// Parses a single field from the start of `data`, returning its value and the consumed length
+fn parse_field(spec: &FieldSpec, data: &[u8]) -> Result<(FieldValue, usize), Error> {
+    match &spec.kind {
+        FieldKind::Int { width, endian } => {
+            let bytes = data
+                .get(..*width)
+                .ok_or_else(|| truncated("integer", &spec.name))?;
+            let mut buf = [0; 8];
+            let value = match endian {
+                Endian::Little => {
+                    buf[..*width].copy_from_slice(bytes);
+                    u64::from_le_bytes(buf)
+                }
+                Endian::Big => {
+                    buf[8 - width..].copy_from_slice(bytes);
+                    u64::from_be_bytes(buf)
+                }
+            };
+            Ok((FieldValue::Int(value), *width))
+        }
+        FieldKind::Bytes(len) => {
+            let bytes = data
+                .get(..*len)
+                .ok_or_else(|| truncated("bytes", &spec.name))?;
+            Ok((FieldValue::Bytes(bytes.to_vec()), *len))
+        }
+        FieldKind::VarBytes => Ok((FieldValue::Bytes(data.to_vec()), data.len())),
+        FieldKind::Magic(magic) => {
+            if data.starts_with(magic) {
+                Ok((FieldValue::Bytes(magic.clone()), magic.len()))
+            } else {
+                Err(Error::invalid_input(format!(
+                    "Magic field `{}` does not match",
+                    spec.name
+                )))
+            }
+        }
+        FieldKind::Record(fields) => {
+            let (values, consumed) = parse_record(fields, data)?;
+            Ok((FieldValue::Record(values), consumed))
+        }
+        FieldKind::List(element) => {
+            let (elements, consumed) = parse_list(element, data, None)?;
+            Ok((FieldValue::List(elements), consumed))
+        }
+    }
+}
+
+fn parse_list(
+    element: &FieldSpec,
+    data: &[u8],
+    count: Option<usize>,
+) -> Result<(Vec<FieldValue>, usize), Error> {
+    let mut elements = vec![];
+    let mut consumed = 0;
+    while count.map_or(consumed < data.len(), |count| elements.len() < count) {
+        let (value, len) = parse_field(element, &data[consumed..])?;
+        if len == 0 {
+            // An empty element would repeat forever, or up to a (bogus) count without consuming any input
+            break;
+        }
+        elements.push(value);
+        consumed += len;
+    }
+    Ok((elements, consumed))
+}
+
+/// Parses the fields of a record from the start of `data`, returning the values and the consumed length
+fn parse_record(specs: &[FieldSpec], data: &[u8]) -> Result<(Vec<FieldValue>, usize), Error> {
+    let mut values: Vec<FieldValue> = Vec::with_capacity(specs.len());
+    let mut consumed = 0;
+    for spec in specs {
+        let rest = &data[consumed..];
+        // Bound a variable sized field by the length or count fields parsed so far
+        let mut count = None;
+        let mut bound = None;
+        let parsed = if spec.fixed_size().is_none() {
+            values.len()
+        } else {
+            0
+        };
+        for (parsed_spec, parsed_value) in specs.iter().zip(&values[..parsed]) {
+            let FieldValue::Int(value) = parsed_value else {
+                continue;
+            };
+            let value = usize::try_from(*value).unwrap_or(usize::MAX);
+            match &parsed_spec.relation {
+                Some(Relation::CountOf(target)) if *target == spec.name => count = Some(value),
+                Some(Relation::LengthOf(targets)) if targets.contains(&spec.name) => {
+                    let mut others = 0;
+                    for other in targets.iter().filter(|other| **other != spec.name) {
+                        let other_len = match specs.iter().position(|s| s.name == *other) {
+                            Some(idx) if idx < values.len() => serialized_len(&specs[idx], &values[idx]),
+                            Some(idx) => specs[idx].fixed_size().ok_or_else(|| {
+                                Error::invalid_input(format!(
+                                    "Cannot determine the length of `{}`, `{other}` has a variable size",
+                                    spec.name
+                                ))
+                            })?,
+                            None => 0,
+                        };
+                        others += other_len;
+                    }
+                    let len = value.checked_sub(others).ok_or_else(|| {
+                        Error::invalid_input(format!(
+                            "Length field `{}` is too small",
+                            parsed_spec.name
+                        ))
+                    })?;
+                    bound = Some(len);
+                }
+                _ => {}
+            }
+        }
+
+        let (value, len) = match (bound, count, &spec.kind) {
+            (_, Some(count), FieldKind::List(element)) => {
+                let (elements, len) = parse_list(element, rest, Some(count))?;
+                (FieldValue::List(elements), len)
+            }
+            (Some(bound), _, _) => {
+                let bounded = rest
+                    .get(..bound)
+                    .ok_or_else(|| truncated("field", &spec.name))?;
+                let (value, len) = parse_field(spec, bounded)?;
+                if len != bound {
+                    return Err(Error::invalid_input(format!(
+                        "Field `{}` does not fill its length of {bound} bytes",
+                        spec.name
+                    )));
+                }
+                (value, len)
+            }
+            _ => parse_field(spec, rest)?,
+        };
+        values.push(value);
+        consumed += len;
+    }
+    Ok((values, consumed))
+}
+
+/// Converts between [`SchemaInput`]s and the bytes of the described format
+#[derive(Debug, Clone, Copy)]
+pub struct SchemaBytesConverter<'a> {
+    schema: &'a Schema,
+}
+
+impl<'a> SchemaBytesConverter<'a> {
+    /// Creates a new [`SchemaBytesConverter`]
+    #[must_use]
+    pub fn new(schema: &'a Schema) -> Self {
+        Self { schema }
+    }
+}
+
+impl<S> ToTargetBytesConverter<SchemaInput, S> for SchemaBytesConverter<'_> {
+    fn convert_to_target_bytes<'a>(
+        &mut self,
+        _state: &mut S,
+        input: &'a SchemaInput,
+    ) -> OwnedSlice<'a, u8> {
+        let mut input = input.clone();
+        input.fixup(self.schema);
+        OwnedSlice::from(input.to_bytes(self.schema))
+    }
+}
+
+impl<S> FromTargetBytesConverter<SchemaInput, S> for SchemaBytesConverter<'_> {
+    fn convert_from_target_bytes(
+        &mut self,
+        _state: &mut S,
+        bytes: &[u8],
+    ) -> Result<SchemaInput, Error> {
+        let mut input = SchemaInput::parse(self.schema, bytes)?;
+        input.fixup(self.schema);
+        Ok(input)
+    }
+}
+
+impl<S> InputConverter<S> for SchemaBytesConverter<'_> {
+    type From = BytesInput;
+    type To = SchemaInput;
+
+    fn convert(&mut self, _state: &mut S, input: Self::From) -> Result<Self::To, Error> {
+        let mut input = SchemaInput::parse(self.schema, input.mutator_bytes())?;
+        input.fixup(self.schema);
+        Ok(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use libafl_bolts::AsSlice;
+
+    use super::{Endian, FieldSpec, FieldValue, Schema, SchemaBytesConverter, SchemaInput, crc32};
+    use crate::inputs::{FromTargetBytesConverter, ToTargetBytesConverter};
+
+    fn png_schema() -> Schema {
+        let chunk = FieldSpec::record(
+            "chunk",
+            vec![
+                FieldSpec::int("length", 4, Endian::Big).length_of(&["data"]),
+                FieldSpec::bytes("type", 4),
+                FieldSpec::var_bytes("data"),
+                FieldSpec::int("crc", 4, Endian::Big).crc32_of(&["type", "data"]),
+            ],
+        );
+        Schema::new(vec![
+            FieldSpec::magic("signature", b"\x89PNG\r\n\x1a\n"),
+            FieldSpec::list("chunks", chunk),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn schema_png_roundtrip_and_fixup() {
+        let schema = png_schema();
+        let mut bytes = b"\x89PNG\r\n\x1a\n".to_vec();
+        bytes.extend_from_slice(b"\x00\x00\x00\x03abcdXYZ\x00\x00\x00\x00");
+        bytes.extend_from_slice(b"\x00\x00\x00\x00IEND\xae\x42\x60\x82");
+
+        let mut input = SchemaInput::parse(&schema, &bytes).unwrap();
+        assert_eq!(input.to_bytes(&schema), bytes);
+
+        let FieldValue::List(chunks) = &mut input.fields_mut()[1] else {
+            panic!("chunks is not a list");
+        };
+        assert_eq!(chunks.len(), 2);
+        let FieldValue::Record(chunk) = &mut chunks[0] else {
+            panic!("chunk is not a record");
+        };
+        chunk[2] = FieldValue::Bytes(b"hello".to_vec());
+        input.fixup(&schema);
+
+        let FieldValue::List(chunks) = &input.fields()[1] else {
+            panic!("chunks is not a list");
+        };
+        let FieldValue::Record(chunk) = &chunks[0] else {
+            panic!("chunk is not a record");
+        };
+        assert_eq!(chunk[0], FieldValue::Int(5));
+        assert_eq!(chunk[3], FieldValue::Int(u64::from(crc32(b"abcdhello"))));
+
+        let reparsed = SchemaInput::parse(&schema, &input.to_bytes(&schema)).unwrap();
+        assert_eq!(reparsed, input);
+
+        assert!(SchemaInput::parse(&schema, b"\x89PNG").is_err());
+        assert!(Schema::new(vec![FieldSpec::int("x", 4, Endian::Big).count_of("y")]).is_err());
+    }
+
+    #[test]
+    fn schema_tlv_count() {
+        let schema = Schema::new(vec![
+            FieldSpec::int("count", 1, Endian::Little).count_of("items"),
+            FieldSpec::list(
+                "items",
+                FieldSpec::record(
+                    "tlv",
+                    vec![
+                        FieldSpec::int("tag", 1, Endian::Little),
+                        FieldSpec::int("len", 2, Endian::Little).length_of(&["value"]),
+                        FieldSpec::var_bytes("value"),
+                    ],
+                ),
+            ),
+            FieldSpec::var_bytes("trailer"),
+        ])
+        .unwrap();
+        let bytes = b"\x02\x01\x02\x00ab\x07\x00\x00rest";
+        let input = SchemaInput::parse(&schema, bytes).unwrap();
+        assert_eq!(input.fields()[2], FieldValue::Bytes(b"rest".to_vec()));
+        assert_eq!(input.to_bytes(&schema), bytes);
+        assert_eq!(schema.default_input().to_bytes(&schema), b"\x00");
+    }
+
+    #[test]
+    fn schema_bogus_count_of_empty_elements() {
+        let schema = Schema::new(vec![
+            FieldSpec::int("count", 4, Endian::Little).count_of("items"),
+            FieldSpec::list("items", FieldSpec::record("empty", vec![])),
+        ])
+        .unwrap();
+        // Must neither hang nor allocate `u32::MAX` elements
+        let input = SchemaInput::parse(&schema, b"\xff\xff\xff\xff").unwrap();
+        assert_eq!(input.fields()[1], FieldValue::List(vec![]));
+    }
+
+    #[test]
+    fn schema_converter_fixup() {
+        let schema = png_schema();
+        let mut converter = SchemaBytesConverter::new(&schema);
+        // A chunk with a broken crc
+        let bytes = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x00IEND\x00\x00\x00\x00";
+        let mut input = converter.convert_from_target_bytes(&mut (), bytes).unwrap();
+        assert_eq!(
+            converter
+                .convert_to_target_bytes(&mut (), &input)
+                .as_slice(),
+            b"\x89PNG\r\n\x1a\n\x00\x00\x00\x00IEND\xae\x42\x60\x82"
+        );
+
+        let FieldValue::List(chunks) = &mut input.fields_mut()[1] else {
+            panic!("chunks is not a list");
+        };
+        let FieldValue::Record(chunk) = &mut chunks[0] else {
+            panic!("chunk is not a record");
+        };
+        chunk[2] = FieldValue::Bytes(b"x".to_vec());
+        let mut expected = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x01IENDx".to_vec();
+        expected.extend_from_slice(&crc32(b"IENDx").to_be_bytes());
+        assert_eq!(
+            converter
+                .convert_to_target_bytes(&mut (), &input)
+                .as_slice(),
+            expected.as_slice()
+        );
+    }
+}
@@ -18,6 +18,11 @@ pub mod disk_aggregate;
 #[cfg(feature = "std")]
 pub use disk_aggregate::OnDiskJsonAggregateMonitor;
 
+#[cfg(feature = "std")]
+pub mod timeseries;
+#[cfg(feature = "std")]
+pub use timeseries::{OnDiskTimeSeriesMonitor, TimeSeries};
+
 #[cfg(all(feature = "tui_monitor", feature = "std"))]
 pub mod tui;
 #[cfg(all(feature = "tui_monitor", feature = "std"))]
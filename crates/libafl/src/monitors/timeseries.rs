@@ -0,0 +1,441 @@
+//! A monitor recording the stats of a campaign as time series, in a compact on-disk format.
+//!
+//! Read the recorded series back with [`TimeSeries::read`], for example to plot and compare campaigns.
+
+use alloc::{
+    borrow::ToOwned,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::time::Duration;
+use std::{
+    fs::{File, OpenOptions},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use hashbrown::HashMap;
+use libafl_bolts::{ClientId, Error, current_time};
+use serde::{Deserialize, Serialize};
+
+use crate::monitors::{
+    Monitor,
+    stats::{ClientStats, ClientStatsManager, UserStatsValue},
+};
+
+/// The first bytes of a time series file
+const TIME_SERIES_MAGIC: &[u8; 8] = b"LAFLTS01";
+
+/// A frame of a time series file, stored as a little endian `u32` length, followed by the `postcard` encoded frame
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum TimeSeriesFrame {
+    /// The name of the next series, series are referenced by the order of their names
+    Name(String),
+    /// The values of one client, or of all clients for `client: None`, at one point in time
+    Sample {
+        run_time_ms: u64,
+        client: Option<u32>,
+        values: Vec<(u32, f64)>,
+    },
+}
+
+fn write_frame(file: &mut File, frame: &TimeSeriesFrame) -> Result<(), Error> {
+    let bytes = postcard::to_allocvec(frame)?;
+    let len = u32::try_from(bytes.len())
+        .map_err(|_| Error::illegal_state("Time series frame too large"))?;
+    file.write_all(&len.to_le_bytes())?;
+    file.write_all(&bytes)?;
+    Ok(())
+}
+
+/// The values of one client, or of all clients, at one point in time
+#[derive(Debug, Clone)]
+pub struct TimeSeriesSample {
+    /// The run time of the campaign
+    pub run_time: Duration,
+    /// The client, or `None` for the global stats of all clients
+    pub client: Option<ClientId>,
+    /// The index of the series name, and the value
+    pub values: Vec<(usize, f64)>,
+}
+
+/// The time series of a campaign, as written by the [`OnDiskTimeSeriesMonitor`]
+#[derive(Debug, Clone, Default)]
+pub struct TimeSeries {
+    names: Vec<String>,
+    samples: Vec<TimeSeriesSample>,
+}
+
+impl TimeSeries {
+    /// Reads a time series file.
+    ///
+    /// A truncated last frame, for example of a campaign that is still running, is ignored.
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let mut data = Vec::new();
+        File::open(path)?.read_to_end(&mut data)?;
+        Self::parse(&data).map(|(series, _)| series)
+    }
+
+    /// Parses the contents of a time series file, and returns the series with the length of its complete frames
+    fn parse(data: &[u8]) -> Result<(Self, usize), Error> {
+        if !data.starts_with(TIME_SERIES_MAGIC) {
+            return Err(Error::illegal_argument("Not a time series file"));
+        }
+
+        let mut series = Self::default();
+        let mut rest = &data[TIME_SERIES_MAGIC.len()..];
+        while rest.len() >= 4 {
+            let len = u32::from_le_bytes(rest[..4].try_into().unwrap()) as usize;
+            let Some(bytes) = rest.get(4..4 + len) else {
+                break;
+            };
+            match postcard::from_bytes(bytes)? {
+                TimeSeriesFrame::Name(name) => series.names.push(name),
+                TimeSeriesFrame::Sample {
+                    run_time_ms,
+                    client,
+                    values,
+                } => series.samples.push(TimeSeriesSample {
+                    run_time: Duration::from_millis(run_time_ms),
+                    client: client.map(ClientId),
+                    values: values
+                        .into_iter()
+                        .map(|(idx, value)| (idx as usize, value))
+                        .collect(),
+                }),
+            }
+            rest = &rest[4 + len..];
+        }
+        Ok((series, data.len() - rest.len()))
+    }
+
+    /// The names of all recorded series
+    #[must_use]
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// All samples, in the order they were recorded
+    #[must_use]
+    pub fn samples(&self) -> &[TimeSeriesSample] {
+        &self.samples
+    }
+
+    /// The clients with recorded samples
+    #[must_use]
+    pub fn clients(&self) -> Vec<ClientId> {
+        let mut clients = self
+            .samples
+            .iter()
+            .filter_map(|sample| sample.client)
+            .collect::<Vec<_>>();
+        clients.sort_unstable();
+        clients.dedup();
+        clients
+    }
+
+    /// The values of the series `name` over time, for one client, or for all clients if `client` is `None`
+    #[must_use]
+    pub fn series(&self, name: &str, client: Option<ClientId>) -> Vec<(Duration, f64)> {
+        let Some(idx) = self.names.iter().position(|n| n == name) else {
+            return Vec::new();
+        };
+        self.samples
+            .iter()
+            .filter(|sample| sample.client == client)
+            .filter_map(|sample| {
+                sample
+                    .values
+                    .iter()
+                    .find(|(i, _)| *i == idx)
+                    .map(|(_, value)| (sample.run_time, *value))
+            })
+            .collect()
+    }
+}
+
+/// Records the global stats and the stats of each client at a fixed interval, into a compact time series file.
+///
+/// Series are named after the stats: `corpus`, `objectives`, `executions`, `exec_sec`, `clients` (global only),
+/// and the user stats, like `edges`. Ratios are recorded as their numerator, and their denominator as `<name>_total`.
+/// With the `introspection` feature, the share of time spent in the scheduler, the manager,
+/// each `PerfFeature` and each feedback is recorded as `perf_<name>`.
+///
+/// An existing file is appended to, after dropping a last frame cut off by a crash. Read it with [`TimeSeries::read`].
+#[derive(Debug)]
+pub struct OnDiskTimeSeriesMonitor {
+    path: PathBuf,
+    names: Option<HashMap<String, u32>>,
+    last_update: Duration,
+    update_interval: Duration,
+}
+
+impl OnDiskTimeSeriesMonitor {
+    /// Create a new [`OnDiskTimeSeriesMonitor`], recording every 10 seconds
+    #[must_use]
+    pub fn new<P>(path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self::with_update_interval(path, Duration::from_secs(10))
+    }
+
+    /// Create a new [`OnDiskTimeSeriesMonitor`] with a custom update interval
+    #[must_use]
+    pub fn with_update_interval<P>(path: P, update_interval: Duration) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self {
+            path: path.into(),
+            names: None,
+            last_update: current_time().saturating_sub(update_interval),
+            update_interval,
+        }
+    }
+
+    /// Opens the file, and gets the names of the series already recorded in it
+    fn open(&mut self) -> Result<File, Error> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&self.path)?;
+        if self.names.is_none() {
+            let mut data = Vec::new();
+            file.read_to_end(&mut data)?;
+            let names = if TIME_SERIES_MAGIC.starts_with(&data) {
+                // A new file, or one cut off while writing the magic
+                file.set_len(0)?;
+                file.write_all(TIME_SERIES_MAGIC)?;
+                Vec::new()
+            } else {
+                let (series, len) = TimeSeries::parse(&data)?;
+                // Drop a frame cut off by a crash, new frames would be read as part of it
+                file.set_len(len as u64)?;
+                series.names
+            };
+            self.names = Some(
+                names
+                    .into_iter()
+                    .enumerate()
+                    .map(|(idx, name)| (name, idx as u32))
+                    .collect(),
+            );
+        }
+        Ok(file)
+    }
+
+    /// Writes one sample, adding the names of new series first
+    fn write_sample(
+        &mut self,
+        file: &mut File,
+        run_time: Duration,
+        client: Option<ClientId>,
+        values: Vec<(String, f64)>,
+    ) -> Result<(), Error> {
+        let names = self.names.as_mut().unwrap();
+        let mut indexed = Vec::with_capacity(values.len());
+        for (name, value) in values {
+            let idx = if let Some(idx) = names.get(&name) {
+                *idx
+            } else {
+                let idx = names.len() as u32;
+                write_frame(file, &TimeSeriesFrame::Name(name.clone()))?;
+                names.insert(name, idx);
+                idx
+            };
+            indexed.push((idx, value));
+        }
+        write_frame(
+            file,
+            &TimeSeriesFrame::Sample {
+                run_time_ms: run_time.as_millis() as u64,
+                client: client.map(|client| client.0),
+                values: indexed,
+            },
+        )
+    }
+}
+
+/// Adds a user stat to the values of a sample
+#[expect(clippy::cast_precision_loss)]
+fn push_user_stat(values: &mut Vec<(String, f64)>, name: &str, value: &UserStatsValue) {
+    match value {
+        UserStatsValue::Ratio(a, b) => {
+            values.push((name.to_owned(), *a as f64));
+            values.push((format!("{name}_total"), *b as f64));
+        }
+        value => {
+            if let Some(value) = value.as_f64() {
+                values.push((name.to_owned(), value));
+            }
+        }
+    }
+}
+
+/// The values of one client
+#[expect(clippy::cast_precision_loss)]
+fn client_values(client: &mut ClientStats, cur_time: Duration) -> Vec<(String, f64)> {
+    let mut values = vec![
+        ("corpus".to_string(), client.corpus_size() as f64),
+        ("objectives".to_string(), client.objective_size() as f64),
+        ("executions".to_string(), client.executions() as f64),
+        ("exec_sec".to_string(), client.execs_per_sec(cur_time)),
+    ];
+    for (name, stat) in client.user_stats() {
+        push_user_stat(&mut values, name, stat.value());
+    }
+
+    #[cfg(feature = "introspection")]
+    {
+        use crate::monitors::stats::PerfFeature;
+
+        let perf = &client.introspection_stats;
+        let elapsed = perf.elapsed_cycles() as f64;
+        if elapsed > 0.0 {
+            values.push((
+                "perf_scheduler".to_string(),
+                perf.scheduler_cycles() as f64 / elapsed,
+            ));
+            values.push((
+                "perf_manager".to_string(),
+                perf.manager_cycles() as f64 / elapsed,
+            ));
+            let mut features = [0_u64; PerfFeature::Count as usize];
+            for (_, stage) in perf.used_stages() {
+                for (total, cycles) in features.iter_mut().zip(stage) {
+                    *total += cycles;
+                }
+            }
+            for (idx, cycles) in features.into_iter().enumerate() {
+                let feature: PerfFeature = idx.into();
+                values.push((format!("perf_{feature:?}"), cycles as f64 / elapsed));
+            }
+            for (name, cycles) in perf.feedbacks() {
+                values.push((format!("perf_{name}"), *cycles as f64 / elapsed));
+            }
+        }
+    }
+    values
+}
+
+impl Monitor for OnDiskTimeSeriesMonitor {
+    #[expect(clippy::cast_precision_loss)]
+    fn display(
+        &mut self,
+        client_stats_manager: &mut ClientStatsManager,
+        _event_msg: &str,
+        _sender_id: ClientId,
+    ) -> Result<(), Error> {
+        let cur_time = current_time();
+        if cur_time
+            .checked_sub(self.last_update)
+            .unwrap_or(self.update_interval)
+            < self.update_interval
+        {
+            return Ok(());
+        }
+        self.last_update = cur_time;
+
+        let mut file = self.open()?;
+
+        let global_stats = client_stats_manager.global_stats();
+        let run_time = global_stats.run_time;
+        let mut values = vec![
+            (
+                "clients".to_string(),
+                global_stats.client_stats_count as f64,
+            ),
+            ("corpus".to_string(), global_stats.corpus_size as f64),
+            ("objectives".to_string(), global_stats.objective_size as f64),
+            ("executions".to_string(), global_stats.total_execs as f64),
+            ("exec_sec".to_string(), global_stats.execs_per_sec),
+        ];
+        for (name, value) in client_stats_manager.aggregated() {
+            push_user_stat(&mut values, name, value);
+        }
+        self.write_sample(&mut file, run_time, None, values)?;
+
+        let mut clients = client_stats_manager
+            .client_stats()
+            .iter()
+            .filter(|(_, client)| client.enabled())
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        clients.sort_unstable();
+        for client_id in clients {
+            let values = client_stats_manager
+                .update_client_stats_for(client_id, |client| client_values(client, cur_time))?;
+            self.write_sample(&mut file, run_time, Some(client_id), values)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::borrow::Cow;
+    use core::time::Duration;
+    use std::{env, fs, io::Write, process};
+
+    use libafl_bolts::ClientId;
+
+    use super::{OnDiskTimeSeriesMonitor, TimeSeries};
+    use crate::monitors::{
+        Monitor,
+        stats::{AggregatorOps, ClientStatsManager, UserStats, UserStatsValue},
+    };
+
+    #[test]
+    #[expect(clippy::float_cmp)]
+    fn time_series_roundtrip() {
+        let path = env::temp_dir().join(format!("libafl_time_series_{}", process::id()));
+        let _ = fs::remove_file(&path);
+
+        let mut manager = ClientStatsManager::new();
+        manager.client_stats_insert(ClientId(1)).unwrap();
+        manager
+            .update_client_stats_for(ClientId(1), |client| {
+                client.update_corpus_size(3);
+                client.update_user_stats(
+                    Cow::Borrowed("edges"),
+                    UserStats::new(UserStatsValue::Ratio(5, 10), AggregatorOps::None),
+                );
+            })
+            .unwrap();
+
+        let mut monitor = OnDiskTimeSeriesMonitor::with_update_interval(&path, Duration::ZERO);
+        monitor.display(&mut manager, "", ClientId(1)).unwrap();
+        // a frame cut off by a crash is dropped
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(&[100, 0, 0, 0, 1, 2])
+            .unwrap();
+        // a new monitor appends to the existing file
+        let mut monitor = OnDiskTimeSeriesMonitor::with_update_interval(&path, Duration::ZERO);
+        monitor.display(&mut manager, "", ClientId(1)).unwrap();
+
+        let series = TimeSeries::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(series.clients(), vec![ClientId(1)]);
+        let corpus = series.series("corpus", None);
+        assert_eq!(corpus.len(), 2);
+        assert!(corpus.iter().all(|(_, value)| *value == 3.0));
+        let edges = series.series("edges", Some(ClientId(1)));
+        assert_eq!(edges.len(), 2);
+        assert_eq!(edges[0].1, 5.0);
+        assert_eq!(series.series("edges_total", Some(ClientId(1)))[1].1, 10.0);
+        assert_eq!(
+            series
+                .names()
+                .iter()
+                .filter(|name| *name == "corpus")
+                .count(),
+            1
+        );
+    }
+}
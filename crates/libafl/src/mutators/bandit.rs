@@ -0,0 +1,565 @@
+//! A [`ScheduledMutator`] learning which mutation operators pay off, using a multi-armed bandit.
+//!
+//! Every operator of the [`MutatorsTuple`] is an arm. An operator is rewarded whenever an input it helped to
+//! create is added to the corpus. The statistics are kept in the named [`BanditMutatorMetadata`] of the state,
+//! so each bandit mutator (for example one per input type) learns on its own and survives restarts.
+//! With [`BanditScheduledMutator::with_per_seed_learning`], each corpus entry additionally learns its own
+//! statistics, kept in its [`BanditSeedMetadata`].
+//! Use the [`crate::stages::MutationStatsStage`] to report the statistics as user stats.
+
+use alloc::{
+    borrow::Cow,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+
+use hashbrown::HashMap;
+use libafl_bolts::{Named, impl_serdeany, rands::Rand, tuples::NamedTuple};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Error, HasMetadata, HasNamedMetadata,
+    corpus::{Corpus, CorpusId, HasCurrentCorpusId},
+    mutators::{
+        ComposedByMutations, MutationId, MutationResult, Mutator, MutatorsTuple, ScheduledMutator,
+    },
+    state::{HasCorpus, HasRand},
+};
+
+/// The bandit algorithm used by the [`BanditScheduledMutator`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MutationBanditAlgorithm {
+    /// `EXP3`, for adversarial rewards, which suits the shifting payoffs of operators during a campaign
+    Exp3 {
+        /// The exploration rate `gamma` in `(0, 1]`
+        gamma: f64,
+    },
+    /// Discounted UCB, forgetting old rewards with the given discount factor
+    DiscountedUcb {
+        /// The discount factor in `(0, 1]`, `1.0` is plain UCB
+        discount: f64,
+        /// The weight of the exploration bonus
+        exploration: f64,
+    },
+}
+
+impl Default for MutationBanditAlgorithm {
+    fn default() -> Self {
+        Self::Exp3 { gamma: 0.1 }
+    }
+}
+
+/// The statistics of a single mutation operator
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MutationOperatorStats {
+    /// The name of the operator
+    pub name: String,
+    /// How often the operator mutated an input
+    pub selections: u64,
+    /// How often an input the operator mutated was added to the corpus
+    pub successes: u64,
+    /// The `EXP3` weight, in log space
+    pub log_weight: f64,
+    /// The discounted number of selections, for discounted UCB
+    pub discounted_selections: f64,
+    /// The discounted sum of rewards, for discounted UCB
+    pub discounted_rewards: f64,
+}
+
+/// The operator statistics of a [`BanditScheduledMutator`], stored as named metadata under its name
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    expect(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct BanditMutatorMetadata {
+    /// The statistics of each operator, indexed by [`MutationId`]
+    pub operators: Vec<MutationOperatorStats>,
+    /// The operators applied to the current input, with their selection probability
+    pub pending: Vec<(MutationId, f64)>,
+    /// With per-seed learning, the corpus entry being mutated and its own operator statistics.
+    ///
+    /// They are moved back to the [`BanditSeedMetadata`] of the entry once another entry is mutated.
+    pub seed: Option<(CorpusId, Vec<MutationOperatorStats>)>,
+}
+
+impl_serdeany!(BanditMutatorMetadata);
+
+/// The operator statistics learned for a single corpus entry by per-seed [`BanditScheduledMutator`]s
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    expect(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct BanditSeedMetadata {
+    /// The statistics of each operator, indexed by [`MutationId`], by the name of the mutator
+    pub mutators: HashMap<String, Vec<MutationOperatorStats>>,
+}
+
+impl_serdeany!(BanditSeedMetadata);
+
+impl BanditMutatorMetadata {
+    /// Creates the metadata for operators with the given names
+    #[must_use]
+    pub fn new(names: &[Cow<'static, str>]) -> Self {
+        Self {
+            operators: names
+                .iter()
+                .map(|name| MutationOperatorStats {
+                    name: name.to_string(),
+                    ..MutationOperatorStats::default()
+                })
+                .collect(),
+            pending: vec![],
+            seed: None,
+        }
+    }
+
+    /// The statistics the next operator is chosen from, the ones of the current seed if any
+    fn scheduling_operators(&self) -> &[MutationOperatorStats] {
+        self.seed
+            .as_ref()
+            .map_or(&self.operators, |(_, operators)| operators)
+    }
+
+    /// Rewards the pending operators and clears them
+    fn update(&mut self, algorithm: MutationBanditAlgorithm, success: bool) {
+        reward_operators(&mut self.operators, &self.pending, algorithm, success);
+        if let Some((_, operators)) = &mut self.seed {
+            reward_operators(operators, &self.pending, algorithm, success);
+        }
+        self.pending.clear();
+    }
+}
+
+/// Picks an operator by its `EXP3` selection probability, returning it with its probability.
+///
+/// `pick` is a random number in `[0, 1)`.
+fn exp3_choice(operators: &[MutationOperatorStats], gamma: f64, pick: f64) -> (usize, f64) {
+    #[expect(clippy::cast_precision_loss)]
+    let arms = operators.len() as f64;
+    let max = operators
+        .iter()
+        .map(|op| op.log_weight)
+        .fold(f64::NEG_INFINITY, f64::max);
+    let total: f64 = operators
+        .iter()
+        .map(|op| libm::exp(op.log_weight - max))
+        .sum();
+    let probability = |op: &MutationOperatorStats| {
+        (1.0 - gamma) * libm::exp(op.log_weight - max) / total + gamma / arms
+    };
+    let mut acc = 0.0;
+    for (idx, op) in operators.iter().enumerate() {
+        let probability = probability(op);
+        acc += probability;
+        if acc > pick {
+            return (idx, probability);
+        }
+    }
+    let last = operators.len() - 1;
+    (last, probability(&operators[last]))
+}
+
+/// Picks the operator with the highest discounted UCB index, untried operators first.
+///
+/// Ties are broken with `tie_pick`, a random number in `[0, 1)`.
+#[expect(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss
+)]
+fn ucb_choice(operators: &[MutationOperatorStats], exploration: f64, tie_pick: f64) -> usize {
+    let total: f64 = operators.iter().map(|op| op.discounted_selections).sum();
+    let log_total = libm::log(total.max(1.0));
+    let index = |op: &MutationOperatorStats| {
+        if op.discounted_selections == 0.0 {
+            f64::INFINITY
+        } else {
+            op.discounted_rewards / op.discounted_selections
+                + exploration * libm::sqrt(2.0 * log_total / op.discounted_selections)
+        }
+    };
+    let best_index = operators
+        .iter()
+        .map(index)
+        .fold(f64::NEG_INFINITY, f64::max);
+    let ties = operators
+        .iter()
+        .filter(|op| index(op) >= best_index)
+        .count();
+    let nth = ((tie_pick * ties as f64) as usize).min(ties.saturating_sub(1));
+    operators
+        .iter()
+        .enumerate()
+        .filter(|(_, op)| index(op) >= best_index)
+        .nth(nth)
+        .map_or(0, |(idx, _)| idx)
+}
+
+/// Rewards the `pending` operators, and counts their selections
+fn reward_operators(
+    operators: &mut [MutationOperatorStats],
+    pending: &[(MutationId, f64)],
+    algorithm: MutationBanditAlgorithm,
+    success: bool,
+) {
+    let reward = if success { 1.0 } else { 0.0 };
+    match algorithm {
+        MutationBanditAlgorithm::Exp3 { gamma } => {
+            #[expect(clippy::cast_precision_loss)]
+            let arms = operators.len() as f64;
+            for (id, probability) in pending {
+                operators[id.0].log_weight += gamma * (reward / probability) / arms;
+            }
+            // Keep the log weights bounded, only their differences matter
+            let max = operators
+                .iter()
+                .map(|op| op.log_weight)
+                .fold(f64::NEG_INFINITY, f64::max);
+            for op in operators.iter_mut() {
+                op.log_weight -= max;
+            }
+        }
+        MutationBanditAlgorithm::DiscountedUcb { discount, .. } => {
+            for op in operators.iter_mut() {
+                op.discounted_selections *= discount;
+                op.discounted_rewards *= discount;
+            }
+            for (id, _) in pending {
+                let op = &mut operators[id.0];
+                op.discounted_selections += 1.0;
+                op.discounted_rewards += reward;
+            }
+        }
+    }
+    for (id, _) in pending {
+        let op = &mut operators[id.0];
+        op.selections += 1;
+        op.successes += u64::from(success);
+    }
+}
+
+/// A [`ScheduledMutator`] that picks the stacked mutations with a bandit algorithm, learning from new corpus entries.
+///
+/// Works with any [`MutatorsTuple`], such as [`crate::mutators::havoc_mutations`] joined with
+/// [`crate::mutators::tokens_mutations`].
+#[derive(Debug)]
+pub struct BanditScheduledMutator<MT> {
+    name: Cow<'static, str>,
+    mutations: MT,
+    algorithm: MutationBanditAlgorithm,
+    max_stack_pow: usize,
+    per_seed: bool,
+}
+
+impl<MT> BanditScheduledMutator<MT>
+where
+    MT: NamedTuple,
+{
+    /// Creates a new [`BanditScheduledMutator`] using `EXP3`
+    pub fn new(mutations: MT) -> Self {
+        Self::with_algorithm(mutations, MutationBanditAlgorithm::default())
+    }
+
+    /// Creates a new [`BanditScheduledMutator`] using the given [`MutationBanditAlgorithm`]
+    pub fn with_algorithm(mutations: MT, algorithm: MutationBanditAlgorithm) -> Self {
+        Self {
+            name: Cow::from(format!(
+                "BanditScheduledMutator[{}]",
+                mutations.names().join(", ")
+            )),
+            mutations,
+            algorithm,
+            max_stack_pow: 7,
+            per_seed: false,
+        }
+    }
+
+    /// Sets the maximum number of stacked mutations to `2^max_stack_pow`
+    #[must_use]
+    pub fn with_max_stack_pow(mut self, max_stack_pow: usize) -> Self {
+        self.max_stack_pow = max_stack_pow;
+        self
+    }
+
+    /// Lets each corpus entry learn its own operator statistics, from scratch.
+    ///
+    /// The statistics over all entries are still updated, and reported by the [`crate::stages::MutationStatsStage`].
+    #[must_use]
+    pub fn with_per_seed_learning(mut self) -> Self {
+        self.per_seed = true;
+        self
+    }
+
+    /// The [`MutationBanditAlgorithm`] in use
+    pub fn algorithm(&self) -> MutationBanditAlgorithm {
+        self.algorithm
+    }
+
+    /// The metadata of this mutator, created on first use
+    fn metadata_mut<'a, S>(&self, state: &'a mut S) -> &'a mut BanditMutatorMetadata
+    where
+        S: HasNamedMetadata,
+    {
+        state.named_metadata_or_insert_with(&self.name, || {
+            BanditMutatorMetadata::new(&self.mutations.names())
+        })
+    }
+
+    /// Makes the statistics of the current corpus entry the ones to schedule with and update,
+    /// storing the ones of the previously mutated entry in its testcase
+    fn switch_seed<I, S>(&self, state: &mut S) -> Result<(), Error>
+    where
+        S: HasCorpus<I> + HasCurrentCorpusId + HasNamedMetadata,
+    {
+        let current = state.current_corpus_id()?;
+        let meta = self.metadata_mut(state);
+        if meta.seed.as_ref().map(|(id, _)| *id) == current {
+            return Ok(());
+        }
+        if let Some((id, operators)) = meta.seed.take()
+            && let Ok(testcase) = state.corpus().get_from_all(id)
+        {
+            testcase
+                .borrow_mut()
+                .metadata_or_insert_with(BanditSeedMetadata::default)
+                .mutators
+                .insert(self.name.to_string(), operators);
+        }
+
+        let Some(id) = current else {
+            return Ok(());
+        };
+        let operators = state
+            .corpus()
+            .get_from_all(id)?
+            .borrow_mut()
+            .metadata_mut::<BanditSeedMetadata>()
+            .ok()
+            .and_then(|seed| seed.mutators.remove(self.name.as_ref()));
+        let meta = self.metadata_mut(state);
+        let operators = operators.unwrap_or_else(|| {
+            meta.operators
+                .iter()
+                .map(|op| MutationOperatorStats {
+                    name: op.name.clone(),
+                    ..MutationOperatorStats::default()
+                })
+                .collect()
+        });
+        meta.seed = Some((id, operators));
+        Ok(())
+    }
+}
+
+impl<MT> Named for BanditScheduledMutator<MT> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<I, MT, S> Mutator<I, S> for BanditScheduledMutator<MT>
+where
+    MT: MutatorsTuple<I, S> + NamedTuple,
+    S: HasRand + HasNamedMetadata + HasCorpus<I> + HasCurrentCorpusId,
+{
+    #[inline]
+    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+        self.scheduled_mutate(state, input)
+    }
+
+    fn post_exec(&mut self, state: &mut S, new_corpus_id: Option<CorpusId>) -> Result<(), Error> {
+        let algorithm = self.algorithm;
+        self.metadata_mut(state)
+            .update(algorithm, new_corpus_id.is_some());
+        self.mutations.post_exec_all(state, new_corpus_id)
+    }
+}
+
+impl<MT> ComposedByMutations for BanditScheduledMutator<MT> {
+    type Mutations = MT;
+    /// Get the mutations
+    #[inline]
+    fn mutations(&self) -> &MT {
+        &self.mutations
+    }
+
+    // Get the mutations (mutable)
+    #[inline]
+    fn mutations_mut(&mut self) -> &mut MT {
+        &mut self.mutations
+    }
+}
+
+impl<I, MT, S> ScheduledMutator<I, S> for BanditScheduledMutator<MT>
+where
+    MT: MutatorsTuple<I, S> + NamedTuple,
+    S: HasRand + HasNamedMetadata + HasCorpus<I> + HasCurrentCorpusId,
+{
+    /// Compute the number of iterations used to apply stacked mutations
+    fn iterations(&self, state: &mut S, _: &I) -> u64 {
+        1 << (1 + state.rand_mut().below_or_zero(self.max_stack_pow))
+    }
+
+    /// Get the next mutation to apply, and remember it for the reward
+    fn schedule(&self, state: &mut S, _: &I) -> MutationId {
+        debug_assert_ne!(self.mutations.len(), 0);
+        let pick = state.rand_mut().next_float();
+        let meta = self.metadata_mut(state);
+        let operators = meta.scheduling_operators();
+        let (idx, probability) = match self.algorithm {
+            MutationBanditAlgorithm::Exp3 { gamma } => exp3_choice(operators, gamma, pick),
+            MutationBanditAlgorithm::DiscountedUcb { exploration, .. } => {
+                (ucb_choice(operators, exploration, pick), 1.0)
+            }
+        };
+        meta.pending.push((idx.into(), probability));
+        idx.into()
+    }
+
+    fn scheduled_mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+        if self.per_seed {
+            self.switch_seed(state)?;
+        }
+        self.metadata_mut(state).pending.clear();
+        let mut r = MutationResult::Skipped;
+        let num = self.iterations(state, input);
+        for _ in 0..num {
+            let idx = self.schedule(state, input);
+            let outcome = self.mutations_mut().get_and_mutate(idx, state, input)?;
+            if outcome == MutationResult::Mutated {
+                r = MutationResult::Mutated;
+            } else {
+                // Only operators that changed the input are rewarded
+                self.metadata_mut(state).pending.pop();
+            }
+        }
+        Ok(r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::{Named, rands::StdRand, tuples::tuple_list};
+
+    use super::{
+        BanditMutatorMetadata, BanditScheduledMutator, BanditSeedMetadata, MutationBanditAlgorithm,
+    };
+    use crate::{
+        HasMetadata, HasNamedMetadata,
+        corpus::{Corpus, CorpusId, HasCurrentCorpusId, InMemoryCorpus, Testcase},
+        feedbacks::ConstFeedback,
+        inputs::BytesInput,
+        mutators::{BitFlipMutator, ByteIncMutator, Mutator},
+        state::{HasCorpus, HasRand, NopState, StdState},
+    };
+
+    fn learns(algorithm: MutationBanditAlgorithm) {
+        let mut state = NopState::<BytesInput>::new();
+        *state.rand_mut() = StdRand::with_seed(1);
+        let mut mutator = BanditScheduledMutator::with_algorithm(
+            tuple_list!(BitFlipMutator::new(), ByteIncMutator::new()),
+            algorithm,
+        )
+        .with_max_stack_pow(0);
+        let mut input = BytesInput::new(vec![0; 8]);
+        for _ in 0..2000 {
+            mutator.mutate(&mut state, &mut input).unwrap();
+            // Only inputs mutated by `ByteIncMutator` alone are interesting
+            let rewarded = state
+                .named_metadata::<BanditMutatorMetadata>(mutator.name())
+                .unwrap()
+                .pending
+                .iter()
+                .all(|(id, _)| id.0 == 1);
+            Mutator::<BytesInput, _>::post_exec(
+                &mut mutator,
+                &mut state,
+                rewarded.then_some(CorpusId(0)),
+            )
+            .unwrap();
+        }
+        let meta = state
+            .named_metadata::<BanditMutatorMetadata>(mutator.name())
+            .unwrap();
+        assert!(
+            meta.operators[1].selections > 2 * meta.operators[0].selections,
+            "{meta:?}"
+        );
+    }
+
+    #[test]
+    fn exp3_learns() {
+        learns(MutationBanditAlgorithm::Exp3 { gamma: 0.1 });
+    }
+
+    #[test]
+    fn discounted_ucb_learns() {
+        learns(MutationBanditAlgorithm::DiscountedUcb {
+            discount: 0.99,
+            exploration: 0.5,
+        });
+    }
+
+    #[test]
+    fn per_seed_learning() {
+        let mut corpus = InMemoryCorpus::new();
+        let first = corpus
+            .add(Testcase::new(BytesInput::new(vec![0; 8])))
+            .unwrap();
+        let second = corpus
+            .add(Testcase::new(BytesInput::new(vec![0; 8])))
+            .unwrap();
+        let mut state = StdState::new(
+            StdRand::with_seed(1),
+            corpus,
+            InMemoryCorpus::new(),
+            &mut ConstFeedback::new(false),
+            &mut ConstFeedback::new(false),
+        )
+        .unwrap();
+        let mut mutator =
+            BanditScheduledMutator::new(tuple_list!(BitFlipMutator::new(), ByteIncMutator::new()))
+                .with_max_stack_pow(0)
+                .with_per_seed_learning();
+
+        // The first entry only pays off with `ByteIncMutator`, the second one with `BitFlipMutator`
+        for (id, paying) in [(first, 1), (second, 0)] {
+            state.set_corpus_id(id).unwrap();
+            for _ in 0..1000 {
+                let mut input = state.corpus().cloned_input_for_id(id).unwrap();
+                mutator.mutate(&mut state, &mut input).unwrap();
+                let rewarded = state
+                    .named_metadata::<BanditMutatorMetadata>(mutator.name())
+                    .unwrap()
+                    .pending
+                    .iter()
+                    .all(|(id, _)| id.0 == paying);
+                mutator
+                    .post_exec(&mut state, rewarded.then_some(CorpusId(0)))
+                    .unwrap();
+            }
+        }
+
+        let testcase = state.corpus().get(first).unwrap().borrow();
+        let operators =
+            &testcase.metadata::<BanditSeedMetadata>().unwrap().mutators[mutator.name().as_ref()];
+        assert!(operators[1].selections > 2 * operators[0].selections);
+        let meta = state
+            .named_metadata::<BanditMutatorMetadata>(mutator.name())
+            .unwrap();
+        let (id, operators) = meta.seed.as_ref().unwrap();
+        assert_eq!(*id, second);
+        assert!(operators[0].selections > 2 * operators[1].selections);
+        assert_eq!(
+            meta.operators[0].selections + meta.operators[1].selections,
+            testcase.metadata::<BanditSeedMetadata>().unwrap().mutators[mutator.name().as_ref()]
+                .iter()
+                .chain(operators)
+                .map(|op| op.selections)
+                .sum::<u64>()
+        );
+    }
+}
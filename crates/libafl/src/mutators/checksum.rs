@@ -0,0 +1,126 @@
+//! A mutator wrapper that fixes up the checksums found by the [`crate::stages::ChecksumDetectionStage`].
+
+use alloc::borrow::Cow;
+
+use libafl_bolts::Named;
+
+use crate::{
+    Error, HasMetadata,
+    corpus::CorpusId,
+    inputs::HasMutatorBytes,
+    mutators::{MutationResult, Mutator},
+    stages::checksum::ChecksumMetadata,
+    state::HasCurrentTestcase,
+};
+
+/// Runs the inner mutator and then recomputes the checksums in the [`ChecksumMetadata`] of the
+/// current testcase, so that mutated inputs still pass the checksum validation of the target.
+///
+/// Wrap the whole scheduled mutator, so the checksums are fixed once after all stacked mutations.
+#[derive(Debug)]
+pub struct ChecksumFixupMutator<M> {
+    inner: M,
+    name: Cow<'static, str>,
+}
+
+impl<M> ChecksumFixupMutator<M> {
+    /// Creates a new [`ChecksumFixupMutator`]
+    pub fn new(inner: M) -> Self
+    where
+        M: Named,
+    {
+        let name = Cow::Owned(format!("ChecksumFixupMutator<{}>", inner.name()));
+        Self { inner, name }
+    }
+}
+
+impl<I, M, S> Mutator<I, S> for ChecksumFixupMutator<M>
+where
+    I: HasMutatorBytes,
+    M: Mutator<I, S>,
+    S: HasCurrentTestcase<I>,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+        let result = self.inner.mutate(state, input)?;
+        if result == MutationResult::Mutated
+            && let Ok(testcase) = state.current_testcase()
+            && let Ok(meta) = testcase.metadata::<ChecksumMetadata>()
+        {
+            meta.fix(input.mutator_bytes_mut());
+        }
+        Ok(result)
+    }
+
+    #[inline]
+    fn post_exec(&mut self, state: &mut S, new_corpus_id: Option<CorpusId>) -> Result<(), Error> {
+        self.inner.post_exec(state, new_corpus_id)
+    }
+}
+
+impl<M> Named for ChecksumFixupMutator<M> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::rands::StdRand;
+
+    use super::ChecksumFixupMutator;
+    use crate::{
+        HasMetadata,
+        corpus::{Corpus, HasCurrentCorpusId, InMemoryCorpus, Testcase},
+        feedbacks::ConstFeedback,
+        inputs::{BytesInput, HasMutatorBytes},
+        mutators::{ByteRandMutator, MutationResult, Mutator},
+        stages::checksum::{ChecksumKind, ChecksumLocation, ChecksumMetadata},
+        state::{HasCorpus, StdState},
+    };
+
+    #[test]
+    fn fixup_after_mutation() {
+        // A payload followed by a little endian Adler-32 over it
+        let mut bytes = b"some payload".to_vec();
+        bytes.extend_from_slice(&ChecksumKind::Adler32.compute(&bytes, false));
+        let mut corpus = InMemoryCorpus::new();
+        let mut testcase = Testcase::new(BytesInput::new(bytes.clone()));
+        testcase.add_metadata(ChecksumMetadata {
+            input_len: bytes.len(),
+            checksums: vec![ChecksumLocation {
+                kind: ChecksumKind::Adler32,
+                offset: 12,
+                region: 0..12,
+                big_endian: false,
+            }],
+        });
+        let id = corpus.add(testcase).unwrap();
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            corpus,
+            InMemoryCorpus::new(),
+            &mut ConstFeedback::new(false),
+            &mut ConstFeedback::new(false),
+        )
+        .unwrap();
+
+        let mut mutator = ChecksumFixupMutator::new(ByteRandMutator::new());
+        // Without a current testcase, only the inner mutator runs
+        let mut input = state.corpus().cloned_input_for_id(id).unwrap();
+        assert_eq!(
+            mutator.mutate(&mut state, &mut input).unwrap(),
+            MutationResult::Mutated
+        );
+
+        state.set_corpus_id(id).unwrap();
+        for _ in 0..100 {
+            let mut input = state.corpus().cloned_input_for_id(id).unwrap();
+            mutator.mutate(&mut state, &mut input).unwrap();
+            let bytes = input.mutator_bytes();
+            assert_eq!(
+                bytes[12..],
+                ChecksumKind::Adler32.compute(&bytes[..12], false)[..]
+            );
+        }
+    }
+}
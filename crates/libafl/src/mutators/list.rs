@@ -1,6 +1,6 @@
 //! Mutator definitions for [`ListInput`]s. See [`crate::inputs::list`] for details.
 
-use alloc::borrow::Cow;
+use alloc::{borrow::Cow, vec::Vec};
 use core::num::NonZero;
 
 use libafl_bolts::{Error, Named, rands::Rand};
@@ -9,13 +9,15 @@ use tuple_list::{tuple_list, tuple_list_type};
 use crate::{
     corpus::Corpus,
     generators::Generator,
-    inputs::{Input, ListInput, multi::MultipartInput},
+    inputs::{Input, Keyed as _, ListInput, multi::MultipartInput},
     mutators::{MutationResult, Mutator},
     random_corpus_id,
     state::{HasCorpus, HasMaxSize, HasRand},
 };
 
-/// A list of mutators that can be used on a [`ListInput`].
+/// A list of mutators that can be used on a [`MultipartInput`], a [`ListInput`] of keyed parts.
+///
+/// For other [`ListInput`]s, use the [`ListInputEntryMutators`].
 pub type GenericListInputMutators = tuple_list_type!(
     RemoveLastEntryMutator,
     RemoveRandomEntryMutator,
@@ -23,9 +25,9 @@ pub type GenericListInputMutators = tuple_list_type!(
     CrossoverReplaceMutator
 );
 
-/// Create a list of mutators that can be used on a [`ListInput`].
+/// Create a list of mutators that can be used on a [`MultipartInput`], a [`ListInput`] of keyed parts.
 ///
-/// You may also want to use [`GenerateToAppendMutator`]
+/// You may also want to use [`GenerateToAppendMutator`] and the [`keyed_multipart_input_mutators`].
 #[must_use]
 pub fn generic_list_input_mutators() -> GenericListInputMutators {
     tuple_list!(
@@ -36,6 +38,36 @@ pub fn generic_list_input_mutators() -> GenericListInputMutators {
     )
 }
 
+/// A list of mutators that can be used on any [`ListInput`], moving whole entries within and between inputs.
+pub type ListInputEntryMutators = tuple_list_type!(
+    DuplicateRandomEntryMutator,
+    CrossoverInsertEntriesMutator,
+    CrossoverReplaceEntriesMutator
+);
+
+/// Create a list of mutators that can be used on any [`ListInput`], with at most `max_entries` entries per input.
+#[must_use]
+pub fn list_input_entry_mutators(max_entries: usize) -> ListInputEntryMutators {
+    tuple_list!(
+        DuplicateRandomEntryMutator::new().with_max_entries(max_entries),
+        CrossoverInsertEntriesMutator::new().with_max_entries(max_entries),
+        CrossoverReplaceEntriesMutator::new().with_max_entries(max_entries)
+    )
+}
+
+/// A list of mutators that can only be used on a [`MultipartInput`], as they rely on the keys of the parts.
+pub type KeyedMultipartInputMutators = tuple_list_type!(KeyedCrossoverReplaceMutator);
+
+/// Create a list of mutators that can only be used on a [`MultipartInput`], as they rely on the keys of the parts.
+#[must_use]
+pub fn keyed_multipart_input_mutators() -> KeyedMultipartInputMutators {
+    tuple_list!(KeyedCrossoverReplaceMutator)
+}
+
+/// The default maximum number of entries the [`DuplicateRandomEntryMutator`], the [`CrossoverInsertEntriesMutator`]
+/// and the [`CrossoverReplaceEntriesMutator`] grow an input to
+pub const DEFAULT_MAX_LIST_ENTRIES: usize = 128;
+
 /// Mutator that generates a new input and appends it to the list.
 #[derive(Debug)]
 pub struct GenerateToAppendMutator<G> {
@@ -155,6 +187,72 @@ impl Named for RemoveRandomEntryMutator {
     }
 }
 
+/// Mutator that duplicates a random entry of a [`ListInput`], inserting the copy at a random position.
+///
+/// For message sequences, this repeats a message, as needed to reach deeper protocol states.
+/// Returns [`MutationResult::Skipped`] if the input is empty, or already has the maximum number
+/// of entries, see [`Self::with_max_entries`].
+#[derive(Debug)]
+pub struct DuplicateRandomEntryMutator {
+    max_entries: usize,
+}
+
+impl DuplicateRandomEntryMutator {
+    /// Creates a new [`DuplicateRandomEntryMutator`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            max_entries: DEFAULT_MAX_LIST_ENTRIES,
+        }
+    }
+
+    /// Sets the number of entries the mutated input may have at most, [`DEFAULT_MAX_LIST_ENTRIES`] by default
+    #[must_use]
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+}
+
+impl Default for DuplicateRandomEntryMutator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<I, S> Mutator<ListInput<I>, S> for DuplicateRandomEntryMutator
+where
+    I: Clone,
+    S: HasRand,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut ListInput<I>) -> Result<MutationResult, Error> {
+        if input.len() >= self.max_entries {
+            return Ok(MutationResult::Skipped);
+        }
+        let Some(len) = NonZero::new(input.len()) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let part = input.parts()[state.rand_mut().below(len)].clone();
+        let index = state.rand_mut().between(0, len.get());
+        input.insert_part(index, part);
+        Ok(MutationResult::Mutated)
+    }
+    #[inline]
+    fn post_exec(
+        &mut self,
+        _state: &mut S,
+        _new_corpus_id: Option<crate::corpus::CorpusId>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl Named for DuplicateRandomEntryMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        &Cow::Borrowed("DuplicateRandomEntryMutator")
+    }
+}
+
 /// Mutator that inserts a random part from another [`MultipartInput`] into the current input.
 #[derive(Debug)]
 pub struct CrossoverInsertMutator;
@@ -261,3 +359,337 @@ impl Named for CrossoverReplaceMutator {
         &Cow::Borrowed("CrossoverReplaceMutator")
     }
 }
+
+/// Picks a random, non-empty range of at most `max_len` entries of a random [`ListInput`] from the corpus.
+///
+/// Returns [`None`] if the chosen input is empty or `max_len` is `0`.
+fn random_corpus_entries<I, S>(state: &mut S, max_len: usize) -> Result<Option<Vec<I>>, Error>
+where
+    S: HasCorpus<ListInput<I>> + HasRand,
+    I: Clone,
+{
+    // we can eat the slight bias; number of entries will be small
+    let start_raw = state.rand_mut().next() as usize;
+    let len_raw = state.rand_mut().next() as usize;
+
+    let id = random_corpus_id!(state.corpus(), state.rand_mut());
+    let mut testcase = state.corpus().get(id)?.borrow_mut();
+    let other = testcase.load_input(state.corpus())?;
+
+    if other.is_empty() || max_len == 0 {
+        return Ok(None);
+    }
+    let start = start_raw % other.len();
+    let len = 1 + len_raw % (other.len() - start).min(max_len);
+    Ok(Some(other.parts()[start..start + len].to_vec()))
+}
+
+/// Mutator that inserts a range of entries from another [`ListInput`] of the corpus into the current input.
+///
+/// For message sequences, this combines the messages of different sessions.
+/// Returns [`MutationResult::Skipped`] if the other input is empty, or the current input already has
+/// the maximum number of entries, see [`Self::with_max_entries`].
+#[derive(Debug)]
+pub struct CrossoverInsertEntriesMutator {
+    max_entries: usize,
+}
+
+impl CrossoverInsertEntriesMutator {
+    /// Creates a new [`CrossoverInsertEntriesMutator`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            max_entries: DEFAULT_MAX_LIST_ENTRIES,
+        }
+    }
+
+    /// Sets the number of entries the mutated input may have at most, [`DEFAULT_MAX_LIST_ENTRIES`] by default
+    #[must_use]
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+}
+
+impl Default for CrossoverInsertEntriesMutator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<I, S> Mutator<ListInput<I>, S> for CrossoverInsertEntriesMutator
+where
+    S: HasCorpus<ListInput<I>> + HasRand,
+    I: Clone,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut ListInput<I>) -> Result<MutationResult, Error> {
+        let room = self.max_entries.saturating_sub(input.len());
+        let index = state.rand_mut().between(0, input.len());
+        let Some(entries) = random_corpus_entries(state, room)? else {
+            return Ok(MutationResult::Skipped);
+        };
+        input.splice_parts(index..index, entries);
+        Ok(MutationResult::Mutated)
+    }
+    #[inline]
+    fn post_exec(
+        &mut self,
+        _state: &mut S,
+        _new_corpus_id: Option<crate::corpus::CorpusId>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl Named for CrossoverInsertEntriesMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        &Cow::Borrowed("CrossoverInsertEntriesMutator")
+    }
+}
+
+/// Mutator that replaces a range of entries of the current [`ListInput`] with a range of entries
+/// from another input of the corpus. The ranges may differ in length.
+///
+/// Returns [`MutationResult::Skipped`] if either input is empty. The mutated input has at most
+/// the maximum number of entries, see [`Self::with_max_entries`], unless it had more before.
+#[derive(Debug)]
+pub struct CrossoverReplaceEntriesMutator {
+    max_entries: usize,
+}
+
+impl CrossoverReplaceEntriesMutator {
+    /// Creates a new [`CrossoverReplaceEntriesMutator`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            max_entries: DEFAULT_MAX_LIST_ENTRIES,
+        }
+    }
+
+    /// Sets the number of entries the mutated input may have at most, [`DEFAULT_MAX_LIST_ENTRIES`] by default
+    #[must_use]
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+}
+
+impl Default for CrossoverReplaceEntriesMutator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<I, S> Mutator<ListInput<I>, S> for CrossoverReplaceEntriesMutator
+where
+    S: HasCorpus<ListInput<I>> + HasRand,
+    I: Clone,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut ListInput<I>) -> Result<MutationResult, Error> {
+        let Some(len) = NonZero::new(input.len()) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let start = state.rand_mut().below(len);
+        let end = state.rand_mut().between(start + 1, len.get());
+        let room = self.max_entries.saturating_sub(len.get() - (end - start));
+        let Some(entries) = random_corpus_entries(state, room.max(1))? else {
+            return Ok(MutationResult::Skipped);
+        };
+        input.splice_parts(start..end, entries);
+        Ok(MutationResult::Mutated)
+    }
+    #[inline]
+    fn post_exec(
+        &mut self,
+        _state: &mut S,
+        _new_corpus_id: Option<crate::corpus::CorpusId>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl Named for CrossoverReplaceEntriesMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        &Cow::Borrowed("CrossoverReplaceEntriesMutator")
+    }
+}
+
+/// Mutator that replaces a random part of the current [`MultipartInput`] with a part of the same name
+/// from another input of the corpus.
+///
+/// Unlike the [`CrossoverReplaceMutator`], the names of the parts stay the same, so each part only
+/// ever receives contents meant for it.
+/// Returns [`MutationResult::Skipped`] if the other input has no part with the chosen name.
+#[derive(Debug)]
+pub struct KeyedCrossoverReplaceMutator;
+
+impl<I, K, S> Mutator<MultipartInput<I, K>, S> for KeyedCrossoverReplaceMutator
+where
+    S: HasCorpus<MultipartInput<I, K>> + HasRand,
+    I: Clone,
+    K: Clone + PartialEq,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut MultipartInput<I, K>,
+    ) -> Result<MutationResult, Error> {
+        let Some(len) = NonZero::new(input.len()) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let current_idx = state.rand_mut().below(len);
+        let other_idx_raw = state.rand_mut().next() as usize;
+
+        let id = random_corpus_id!(state.corpus(), state.rand_mut());
+        let mut testcase = state.corpus().get(id)?.borrow_mut();
+        let other = testcase.load_input(state.corpus())?;
+
+        let (key, part) = &mut input.parts_mut()[current_idx];
+        let candidates = other.with_key(key).count();
+        if candidates == 0 {
+            return Ok(MutationResult::Skipped);
+        }
+        let (_, other_part) = other.with_key(key).nth(other_idx_raw % candidates).unwrap();
+        *part = other_part.clone();
+        Ok(MutationResult::Mutated)
+    }
+    #[inline]
+    fn post_exec(
+        &mut self,
+        _state: &mut S,
+        _new_corpus_id: Option<crate::corpus::CorpusId>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl Named for KeyedCrossoverReplaceMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        &Cow::Borrowed("KeyedCrossoverReplaceMutator")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::{String, ToString};
+
+    use libafl_bolts::rands::StdRand;
+
+    use super::{
+        CrossoverInsertEntriesMutator, CrossoverReplaceEntriesMutator, DuplicateRandomEntryMutator,
+        KeyedCrossoverReplaceMutator,
+    };
+    use crate::{
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        feedbacks::ConstFeedback,
+        inputs::{BytesInput, ListInput, multi::MultipartInput},
+        mutators::{MutationResult, Mutator},
+        state::{NopState, StdState},
+    };
+
+    fn part(key: &str, bytes: &[u8]) -> (String, BytesInput) {
+        (key.to_string(), BytesInput::new(bytes.to_vec()))
+    }
+
+    #[test]
+    fn keyed_crossover() {
+        let mut corpus = InMemoryCorpus::new();
+        corpus
+            .add(Testcase::new(MultipartInput::new(vec![
+                part("a", b"x"),
+                part("b", b"y"),
+            ])))
+            .unwrap();
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            corpus,
+            InMemoryCorpus::new(),
+            &mut ConstFeedback::new(false),
+            &mut ConstFeedback::new(false),
+        )
+        .unwrap();
+
+        let mut input = MultipartInput::new(vec![part("b", b"z")]);
+        let result = KeyedCrossoverReplaceMutator
+            .mutate(&mut state, &mut input)
+            .unwrap();
+        assert_eq!(result, MutationResult::Mutated);
+        assert_eq!(input.parts(), &[part("b", b"y")]);
+
+        let mut input = MultipartInput::new(vec![part("c", b"z")]);
+        let result = KeyedCrossoverReplaceMutator
+            .mutate(&mut state, &mut input)
+            .unwrap();
+        assert_eq!(result, MutationResult::Skipped);
+
+        let mut input: ListInput<_> = MultipartInput::new(vec![part("c", b"z"); 3]);
+        for _ in 0..10 {
+            CrossoverReplaceEntriesMutator::new()
+                .mutate(&mut state, &mut input)
+                .unwrap();
+        }
+        assert!(input.parts().iter().any(|(key, _)| key != "c"));
+    }
+
+    #[test]
+    fn insert_entries_capped() {
+        let entries = (0..5_u8).map(|byte| BytesInput::new(vec![byte])).collect();
+        let mut corpus = InMemoryCorpus::new();
+        corpus.add(Testcase::new(ListInput::new(entries))).unwrap();
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            corpus,
+            InMemoryCorpus::new(),
+            &mut ConstFeedback::new(false),
+            &mut ConstFeedback::new(false),
+        )
+        .unwrap();
+
+        let mut mutator = CrossoverInsertEntriesMutator::new().with_max_entries(4);
+        let mut input = ListInput::new(vec![BytesInput::new(vec![0xff]); 2]);
+        let mut inserted = false;
+        for _ in 0..20 {
+            let before = input.len();
+            match mutator.mutate(&mut state, &mut input).unwrap() {
+                MutationResult::Mutated => {
+                    inserted = true;
+                    assert!(input.len() > before);
+                }
+                MutationResult::Skipped => assert_eq!(before, 4),
+            }
+            assert!(input.len() <= 4);
+            assert_eq!(
+                input
+                    .parts()
+                    .iter()
+                    .filter(|part| **part == BytesInput::new(vec![0xff]))
+                    .count(),
+                2
+            );
+        }
+        assert!(inserted);
+        assert_eq!(input.len(), 4);
+    }
+
+    #[test]
+    fn duplicate_entry_capped() {
+        let mut state = NopState::<ListInput<BytesInput>>::new();
+        let mut mutator = DuplicateRandomEntryMutator::new().with_max_entries(3);
+        let mut input = ListInput::new(vec![BytesInput::new(vec![0])]);
+        assert_eq!(
+            mutator.mutate(&mut state, &mut input).unwrap(),
+            MutationResult::Mutated
+        );
+        assert_eq!(
+            mutator.mutate(&mut state, &mut input).unwrap(),
+            MutationResult::Mutated
+        );
+        for _ in 0..10 {
+            assert_eq!(
+                mutator.mutate(&mut state, &mut input).unwrap(),
+                MutationResult::Skipped
+            );
+        }
+        assert_eq!(input.len(), 3);
+    }
+}
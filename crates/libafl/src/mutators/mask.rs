@@ -0,0 +1,228 @@
+//! Mutation masks restrict mutations to the bytes of an input that may change.
+//!
+//! A stage computes a [`MutationMask`] metadata for a testcase, for example the
+//! [`crate::stages::RareEdgeMaskStage`] or the [`crate::stages::EffectorMapStage`], and the [`MaskedMutator`] keeps the masked bytes of inputs
+//! derived from it unchanged.
+
+use alloc::{borrow::Cow, vec::Vec};
+use core::marker::PhantomData;
+
+use libafl_bolts::{Named, impl_serdeany, rands::Rand, serdeany::SerdeAny};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Error, HasMetadata,
+    corpus::{CorpusId, HasCurrentCorpusId},
+    inputs::HasMutatorBytes,
+    mutators::{MutationResult, Mutator},
+    state::{HasCurrentTestcase, HasRand},
+};
+
+/// The default chance to ignore the mask and mutate the full input
+pub const DEFAULT_FULL_INPUT_CHANCE: f64 = 0.1;
+
+/// Testcase metadata marking the bytes that the [`MaskedMutator`] may change
+pub trait MutationMask: SerdeAny {
+    /// Whether each byte of the input may be mutated
+    fn mutable(&self) -> &[bool];
+}
+
+/// The bytes of a testcase that mutations may change, for masks computed by custom stages
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    expect(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct MutationMaskMetadata {
+    /// Whether each byte of the input may be mutated
+    pub mutable: Vec<bool>,
+}
+
+impl_serdeany!(MutationMaskMetadata);
+
+impl MutationMaskMetadata {
+    /// Creates a new [`MutationMaskMetadata`]
+    #[must_use]
+    pub fn new(mutable: Vec<bool>) -> Self {
+        Self { mutable }
+    }
+}
+
+impl MutationMask for MutationMaskMetadata {
+    fn mutable(&self) -> &[bool] {
+        &self.mutable
+    }
+}
+
+/// Wraps a [`Mutator`] for [`HasMutatorBytes`] inputs and reverts its changes to the bytes masked
+/// by the mask `K` of the current testcase, for example
+/// `MaskedMutator::<RareEdgeMaskMetadata, _>::new(mutator)`.
+///
+/// Mutations that change the length of the input are kept as they are, since the mask does not apply to them.
+/// Without a mask, or if the mask allows all or no bytes, the wrapped mutator runs unrestricted.
+/// To not miss paths the mask is wrong about, it also ignores the mask randomly, see [`Self::with_full_input_chance`].
+#[derive(Debug)]
+pub struct MaskedMutator<K, M> {
+    name: Cow<'static, str>,
+    inner: M,
+    full_input_chance: f64,
+    mask: Vec<bool>,
+    backup: Vec<u8>,
+    phantom: PhantomData<K>,
+}
+
+impl<K, M> MaskedMutator<K, M>
+where
+    M: Named,
+{
+    /// Creates a new [`MaskedMutator`] wrapping `inner`
+    pub fn new(inner: M) -> Self {
+        Self {
+            name: Cow::Owned(format!("MaskedMutator[{}]", inner.name())),
+            inner,
+            full_input_chance: DEFAULT_FULL_INPUT_CHANCE,
+            mask: vec![],
+            backup: vec![],
+            phantom: PhantomData,
+        }
+    }
+
+    /// Sets the chance to ignore the mask and mutate the full input, [`DEFAULT_FULL_INPUT_CHANCE`] by default.
+    ///
+    /// `FairFuzz` keeps mutating the full input in a separate stage, a chance of `0.1` approximates that.
+    /// With a chance of `0.0`, the mask always applies.
+    #[must_use]
+    pub fn with_full_input_chance(mut self, full_input_chance: f64) -> Self {
+        self.full_input_chance = full_input_chance;
+        self
+    }
+}
+
+impl<K, M> Named for MaskedMutator<K, M> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<I, K, M, S> Mutator<I, S> for MaskedMutator<K, M>
+where
+    I: HasMutatorBytes,
+    K: MutationMask,
+    M: Mutator<I, S>,
+    S: HasCurrentTestcase<I> + HasCurrentCorpusId + HasRand,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+        self.mask.clear();
+        // Only draw a random number if the fallback is enabled, to keep the random stream of the wrapped mutator
+        let full_input =
+            self.full_input_chance > 0.0 && state.rand_mut().coinflip(self.full_input_chance);
+        if !full_input
+            && state.current_corpus_id()?.is_some()
+            && let Ok(meta) = state.current_testcase()?.metadata::<K>()
+        {
+            self.mask.extend_from_slice(meta.mutable());
+        }
+        if self.mask.len() != input.mutator_bytes().len()
+            || self.mask.iter().all(|mutable| *mutable)
+            || !self.mask.iter().any(|mutable| *mutable)
+        {
+            return self.inner.mutate(state, input);
+        }
+
+        self.backup.clear();
+        self.backup.extend_from_slice(input.mutator_bytes());
+        let result = self.inner.mutate(state, input)?;
+        if result == MutationResult::Skipped || input.mutator_bytes().len() != self.backup.len() {
+            return Ok(result);
+        }
+
+        let bytes = input.mutator_bytes_mut();
+        for (idx, mutable) in self.mask.iter().enumerate() {
+            if !mutable {
+                bytes[idx] = self.backup[idx];
+            }
+        }
+        if *bytes == *self.backup {
+            Ok(MutationResult::Skipped)
+        } else {
+            Ok(MutationResult::Mutated)
+        }
+    }
+
+    #[inline]
+    fn post_exec(&mut self, state: &mut S, new_corpus_id: Option<CorpusId>) -> Result<(), Error> {
+        self.inner.post_exec(state, new_corpus_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::rands::StdRand;
+
+    use super::MaskedMutator;
+    use crate::{
+        HasMetadata,
+        corpus::{Corpus, HasCurrentCorpusId, InMemoryCorpus, Testcase},
+        feedbacks::ConstFeedback,
+        inputs::{BytesInput, HasMutatorBytes},
+        mutators::{ByteRandMutator, MutationResult, Mutator},
+        stages::RareEdgeMaskMetadata,
+        state::{HasCorpus, StdState},
+    };
+
+    #[test]
+    fn masked_bytes_stay() {
+        let mut corpus = InMemoryCorpus::new();
+        let mut testcase = Testcase::new(BytesInput::new(vec![0; 4]));
+        testcase.add_metadata(RareEdgeMaskMetadata::new(vec![false, true, false, false]));
+        let id = corpus.add(testcase).unwrap();
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            corpus,
+            InMemoryCorpus::new(),
+            &mut ConstFeedback::new(false),
+            &mut ConstFeedback::new(false),
+        )
+        .unwrap();
+        state.set_corpus_id(id).unwrap();
+
+        let mut mutator = MaskedMutator::<RareEdgeMaskMetadata, _>::new(ByteRandMutator::new())
+            .with_full_input_chance(0.0);
+        for _ in 0..100 {
+            let mut input = state.corpus().cloned_input_for_id(id).unwrap();
+            if mutator.mutate(&mut state, &mut input).unwrap() == MutationResult::Mutated {
+                let bytes = input.mutator_bytes();
+                assert_eq!((bytes[0], bytes[2], bytes[3]), (0, 0, 0));
+                assert_ne!(bytes[1], 0);
+            }
+        }
+    }
+
+    #[test]
+    fn full_input_fallback() {
+        let mut corpus = InMemoryCorpus::new();
+        let mut testcase = Testcase::new(BytesInput::new(vec![0; 4]));
+        testcase.add_metadata(RareEdgeMaskMetadata::new(vec![false, true, false, false]));
+        let id = corpus.add(testcase).unwrap();
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            corpus,
+            InMemoryCorpus::new(),
+            &mut ConstFeedback::new(false),
+            &mut ConstFeedback::new(false),
+        )
+        .unwrap();
+        state.set_corpus_id(id).unwrap();
+
+        let mut mutator = MaskedMutator::<RareEdgeMaskMetadata, _>::new(ByteRandMutator::new())
+            .with_full_input_chance(0.5);
+        let mut masked_changed = false;
+        for _ in 0..100 {
+            let mut input = state.corpus().cloned_input_for_id(id).unwrap();
+            mutator.mutate(&mut state, &mut input).unwrap();
+            let bytes = input.mutator_bytes();
+            masked_changed |= bytes[0] != 0 || bytes[2] != 0 || bytes[3] != 0;
+        }
+        assert!(masked_changed);
+    }
+}
@@ -19,12 +19,20 @@ pub mod encoded_mutations;
 pub use encoded_mutations::*;
 pub mod mopt_mutator;
 pub use mopt_mutator::*;
+pub mod bandit;
+pub use bandit::*;
+pub mod checksum;
+pub use checksum::*;
 pub mod gramatron;
 pub use gramatron::*;
 pub mod grimoire;
 pub use grimoire::*;
 pub mod mapping;
 pub use mapping::*;
+pub mod mask;
+pub use mask::*;
+pub mod schema;
+pub use schema::*;
 pub mod tuneable;
 pub use tuneable::*;
 
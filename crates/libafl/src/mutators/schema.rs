@@ -0,0 +1,420 @@
+//! Mutators for [`SchemaInput`]s. See [`crate::inputs::schema`] for details.
+//!
+//! Every mutator picks a random field of a fitting kind, mutates it and then calls
+//! [`SchemaInput::fixup`], so that length, count and checksum fields stay consistent.
+
+use alloc::{borrow::Cow, vec::Vec};
+use core::num::NonZero;
+
+use libafl_bolts::{
+    Named,
+    rands::Rand,
+    tuples::{tuple_list, tuple_list_type},
+};
+
+use crate::{
+    Error,
+    corpus::{Corpus, CorpusId},
+    inputs::schema::{FieldKind, FieldSpec, FieldValue, Schema, SchemaInput},
+    mutators::{ARITH_MAX, INTERESTING_32, MutationResult, Mutator},
+    nonzero, random_corpus_id,
+    state::{HasCorpus, HasRand},
+};
+
+/// The maximum number of bytes inserted into a [`FieldKind::VarBytes`] field at once
+const MAX_INSERT: usize = 16;
+
+/// The mutators for [`SchemaInput`]s
+pub type SchemaMutationsType<'a> = tuple_list_type!(
+    SchemaIntMutator<'a>,
+    SchemaBytesMutator<'a>,
+    SchemaListMutator<'a>,
+    SchemaSpliceMutator<'a>
+);
+
+/// Create all mutators for [`SchemaInput`]s following the given `schema`
+#[must_use]
+pub fn schema_mutations(schema: &Schema) -> SchemaMutationsType<'_> {
+    tuple_list!(
+        SchemaIntMutator::new(schema),
+        SchemaBytesMutator::new(schema),
+        SchemaListMutator::new(schema),
+        SchemaSpliceMutator::new(schema)
+    )
+}
+
+/// Calls `mutate` on a random field matching `filter`, returns `false` if there is none
+fn mutate_random_field<R, P, F>(
+    rand: &mut R,
+    schema: &Schema,
+    input: &mut SchemaInput,
+    filter: P,
+    mut mutate: F,
+) -> bool
+where
+    R: Rand,
+    P: Fn(&FieldSpec, &FieldValue) -> bool,
+    F: FnMut(&mut R, &FieldSpec, &mut FieldValue),
+{
+    let mut count = 0;
+    input.visit_fields_mut(schema, &mut |spec, value| {
+        if filter(spec, value) {
+            count += 1;
+        }
+    });
+    let Some(count) = NonZero::new(count) else {
+        return false;
+    };
+
+    let mut target = rand.below(count);
+    input.visit_fields_mut(schema, &mut |spec, value| {
+        if filter(spec, value) {
+            if target == 0 {
+                mutate(rand, spec, value);
+            }
+            target = target.wrapping_sub(1);
+        }
+    });
+    true
+}
+
+/// Mutates an integer field, without a relation, by flipping bits, arithmetics and interesting values.
+#[derive(Debug, Clone, Copy)]
+pub struct SchemaIntMutator<'a> {
+    schema: &'a Schema,
+}
+
+impl<'a> SchemaIntMutator<'a> {
+    /// Creates a new [`SchemaIntMutator`]
+    #[must_use]
+    pub fn new(schema: &'a Schema) -> Self {
+        Self { schema }
+    }
+}
+
+impl<S: HasRand> Mutator<SchemaInput, S> for SchemaIntMutator<'_> {
+    fn mutate(&mut self, state: &mut S, input: &mut SchemaInput) -> Result<MutationResult, Error> {
+        let mutated = mutate_random_field(
+            state.rand_mut(),
+            self.schema,
+            input,
+            |spec, value| spec.relation.is_none() && matches!(value, FieldValue::Int(_)),
+            |rand, spec, value| {
+                let (FieldKind::Int { width, .. }, FieldValue::Int(value)) = (&spec.kind, value)
+                else {
+                    return;
+                };
+                let bits = *width as u32 * 8;
+                let mask = u64::MAX >> (64 - bits);
+                *value = match rand.below(nonzero!(4)) {
+                    0 => *value ^ (1_u64 << rand.below_or_zero(bits as usize)),
+                    1 => {
+                        let delta = 1 + rand.below(nonzero!(ARITH_MAX)) as u64;
+                        if rand.coinflip(0.5) {
+                            value.wrapping_add(delta)
+                        } else {
+                            value.wrapping_sub(delta)
+                        }
+                    }
+                    2 => i64::from(*rand.choose(&INTERESTING_32).unwrap()).cast_unsigned(),
+                    _ => rand.next(),
+                } & mask;
+            },
+        );
+        if !mutated {
+            return Ok(MutationResult::Skipped);
+        }
+        input.fixup(self.schema);
+        Ok(MutationResult::Mutated)
+    }
+
+    #[inline]
+    fn post_exec(&mut self, _state: &mut S, _new_corpus_id: Option<CorpusId>) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl Named for SchemaIntMutator<'_> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("SchemaIntMutator");
+        &NAME
+    }
+}
+
+/// Mutates the bytes of a [`FieldKind::Bytes`] or [`FieldKind::VarBytes`] field.
+/// Variable sized fields may also grow or shrink.
+#[derive(Debug, Clone, Copy)]
+pub struct SchemaBytesMutator<'a> {
+    schema: &'a Schema,
+}
+
+impl<'a> SchemaBytesMutator<'a> {
+    /// Creates a new [`SchemaBytesMutator`]
+    #[must_use]
+    pub fn new(schema: &'a Schema) -> Self {
+        Self { schema }
+    }
+}
+
+impl<S: HasRand> Mutator<SchemaInput, S> for SchemaBytesMutator<'_> {
+    fn mutate(&mut self, state: &mut S, input: &mut SchemaInput) -> Result<MutationResult, Error> {
+        let mutated = mutate_random_field(
+            state.rand_mut(),
+            self.schema,
+            input,
+            |spec, _| match &spec.kind {
+                FieldKind::Bytes(len) => *len > 0,
+                FieldKind::VarBytes => true,
+                _ => false,
+            },
+            |rand, spec, value| {
+                let FieldValue::Bytes(bytes) = value else {
+                    return;
+                };
+                let resizable = matches!(spec.kind, FieldKind::VarBytes);
+                let Some(len) = NonZero::new(bytes.len()) else {
+                    if resizable {
+                        let insert = rand.between(1, MAX_INSERT);
+                        bytes.extend((0..insert).map(|_| rand.next() as u8));
+                    }
+                    return;
+                };
+                let ops = if resizable { 5 } else { 2 };
+                match rand.below_or_zero(ops) {
+                    0 => bytes[rand.below(len)] ^= 1 << rand.below(nonzero!(8)),
+                    1 => bytes[rand.below(len)] = rand.next() as u8,
+                    2 => {
+                        let pos = rand.between(0, len.get());
+                        let insert = rand.between(1, MAX_INSERT);
+                        let new_bytes: Vec<u8> = (0..insert).map(|_| rand.next() as u8).collect();
+                        bytes.splice(pos..pos, new_bytes);
+                    }
+                    3 => {
+                        let start = rand.below(len);
+                        let end = rand.between(start + 1, len.get());
+                        bytes.drain(start..end);
+                    }
+                    _ => {
+                        let start = rand.below(len);
+                        let end = rand.between(start + 1, len.get().min(start + MAX_INSERT));
+                        let pos = rand.between(0, len.get());
+                        let chunk = bytes[start..end].to_vec();
+                        bytes.splice(pos..pos, chunk);
+                    }
+                }
+            },
+        );
+        if !mutated {
+            return Ok(MutationResult::Skipped);
+        }
+        input.fixup(self.schema);
+        Ok(MutationResult::Mutated)
+    }
+
+    #[inline]
+    fn post_exec(&mut self, _state: &mut S, _new_corpus_id: Option<CorpusId>) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl Named for SchemaBytesMutator<'_> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("SchemaBytesMutator");
+        &NAME
+    }
+}
+
+/// Duplicates, removes or swaps elements of a [`FieldKind::List`] field.
+/// Empty lists get a default element.
+#[derive(Debug, Clone, Copy)]
+pub struct SchemaListMutator<'a> {
+    schema: &'a Schema,
+}
+
+impl<'a> SchemaListMutator<'a> {
+    /// Creates a new [`SchemaListMutator`]
+    #[must_use]
+    pub fn new(schema: &'a Schema) -> Self {
+        Self { schema }
+    }
+}
+
+impl<S: HasRand> Mutator<SchemaInput, S> for SchemaListMutator<'_> {
+    fn mutate(&mut self, state: &mut S, input: &mut SchemaInput) -> Result<MutationResult, Error> {
+        let mutated = mutate_random_field(
+            state.rand_mut(),
+            self.schema,
+            input,
+            |_, value| matches!(value, FieldValue::List(_)),
+            |rand, spec, value| {
+                let (FieldKind::List(element), FieldValue::List(elements)) = (&spec.kind, value)
+                else {
+                    return;
+                };
+                let Some(len) = NonZero::new(elements.len()) else {
+                    elements.push(element.default_value());
+                    return;
+                };
+                match rand.below(nonzero!(3)) {
+                    0 => {
+                        let duplicate = elements[rand.below(len)].clone();
+                        elements.insert(rand.between(0, len.get()), duplicate);
+                    }
+                    1 => {
+                        elements.remove(rand.below(len));
+                    }
+                    _ => elements.swap(rand.below(len), rand.below(len)),
+                }
+            },
+        );
+        if !mutated {
+            return Ok(MutationResult::Skipped);
+        }
+        input.fixup(self.schema);
+        Ok(MutationResult::Mutated)
+    }
+
+    #[inline]
+    fn post_exec(&mut self, _state: &mut S, _new_corpus_id: Option<CorpusId>) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl Named for SchemaListMutator<'_> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("SchemaListMutator");
+        &NAME
+    }
+}
+
+/// Replaces a random field with the value of the same field in another corpus entry
+#[derive(Debug, Clone, Copy)]
+pub struct SchemaSpliceMutator<'a> {
+    schema: &'a Schema,
+}
+
+impl<'a> SchemaSpliceMutator<'a> {
+    /// Creates a new [`SchemaSpliceMutator`]
+    #[must_use]
+    pub fn new(schema: &'a Schema) -> Self {
+        Self { schema }
+    }
+}
+
+impl<S> Mutator<SchemaInput, S> for SchemaSpliceMutator<'_>
+where
+    S: HasCorpus<SchemaInput> + HasRand,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut SchemaInput) -> Result<MutationResult, Error> {
+        let id = random_corpus_id!(state.corpus(), state.rand_mut());
+        let mut other = {
+            let mut other_testcase = state.corpus().get(id)?.borrow_mut();
+            state.corpus().load_input_into(&mut other_testcase)?;
+            other_testcase.input().as_ref().unwrap().clone()
+        };
+        if other == *input {
+            return Ok(MutationResult::Skipped);
+        }
+
+        // Collect the values of the other input, by the field they belong to
+        let mut donors: Vec<(*const FieldSpec, FieldValue)> = vec![];
+        other.visit_fields_mut(self.schema, &mut |spec, value| {
+            if !matches!(spec.kind, FieldKind::Magic(_)) && spec.relation.is_none() {
+                donors.push((spec, value.clone()));
+            }
+        });
+
+        let mutated = mutate_random_field(
+            state.rand_mut(),
+            self.schema,
+            input,
+            |spec, _| donors.iter().any(|(donor, _)| core::ptr::eq(*donor, spec)),
+            |rand, spec, value| {
+                let candidates: Vec<&FieldValue> = donors
+                    .iter()
+                    .filter(|(donor, _)| core::ptr::eq(*donor, spec))
+                    .map(|(_, donor_value)| donor_value)
+                    .collect();
+                if let Some(donor_value) = rand.choose(candidates) {
+                    value.clone_from(donor_value);
+                }
+            },
+        );
+        if !mutated {
+            return Ok(MutationResult::Skipped);
+        }
+        input.fixup(self.schema);
+        Ok(MutationResult::Mutated)
+    }
+
+    #[inline]
+    fn post_exec(&mut self, _state: &mut S, _new_corpus_id: Option<CorpusId>) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl Named for SchemaSpliceMutator<'_> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("SchemaSpliceMutator");
+        &NAME
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::rands::StdRand;
+
+    use super::schema_mutations;
+    use crate::{
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        feedbacks::ConstFeedback,
+        inputs::schema::{Endian, FieldSpec, Schema, SchemaInput},
+        mutators::MutatorsTuple,
+        state::StdState,
+    };
+
+    #[test]
+    fn schema_mutations_keep_format() {
+        let chunk = FieldSpec::record(
+            "chunk",
+            vec![
+                FieldSpec::int("length", 2, Endian::Little).length_of(&["type", "data"]),
+                FieldSpec::int("type", 1, Endian::Little),
+                FieldSpec::var_bytes("data"),
+                FieldSpec::int("crc", 4, Endian::Big).crc32_of(&["length", "type", "data"]),
+            ],
+        );
+        let schema = Schema::new(vec![
+            FieldSpec::magic("magic", b"SCHM"),
+            FieldSpec::int("count", 1, Endian::Little).count_of("chunks"),
+            FieldSpec::list("chunks", chunk),
+        ])
+        .unwrap();
+
+        let seed = schema.default_input();
+        let mut corpus = InMemoryCorpus::new();
+        corpus.add(Testcase::new(seed.clone())).unwrap();
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = ConstFeedback::new(false);
+        let mut state = StdState::new(
+            StdRand::with_seed(1337),
+            corpus,
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+
+        let mut mutations = schema_mutations(&schema);
+        let mut input = seed;
+        for i in 0..1000 {
+            let idx = i % 4;
+            mutations
+                .get_and_mutate(idx.into(), &mut state, &mut input)
+                .unwrap();
+            let bytes = input.to_bytes(&schema);
+            let reparsed = SchemaInput::parse(&schema, &bytes).unwrap();
+            assert_eq!(reparsed, input);
+        }
+    }
+}
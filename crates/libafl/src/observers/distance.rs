@@ -0,0 +1,114 @@
+//! The [`DistanceObserver`] reads the distance of an execution to the targets of directed fuzzing.
+//!
+//! The target is instrumented with the distance pass of `libafl_cc`, which adds the distance of every executed
+//! basic block to a sum and counts the blocks. The distance of an execution is the average over all blocks.
+
+use alloc::borrow::Cow;
+
+use libafl_bolts::{Named, ownedref::OwnedMutPtr};
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, executors::ExitKind, observers::Observer};
+
+/// An observer for the distance of an execution to the targets of directed fuzzing, like `AFLGo`.
+///
+/// Lower is closer, see [`crate::schedulers::powersched::PowerSchedule::set_directed`] to use it for scheduling.
+#[derive(Serialize, Deserialize, Debug)]
+#[expect(clippy::unsafe_derive_deserialize)]
+pub struct DistanceObserver {
+    name: Cow<'static, str>,
+    sum: OwnedMutPtr<u64>,
+    count: OwnedMutPtr<u64>,
+    last_distance: Option<f64>,
+}
+
+impl DistanceObserver {
+    /// Creates a new [`DistanceObserver`] reading the distance sum and the block count the target writes to.
+    ///
+    /// # Safety
+    /// Both pointers must stay valid for the lifetime of the observer.
+    #[must_use]
+    pub unsafe fn from_mut_ptr<S>(name: S, sum: *mut u64, count: *mut u64) -> Self
+    where
+        S: Into<Cow<'static, str>>,
+    {
+        Self {
+            name: name.into(),
+            sum: unsafe { OwnedMutPtr::from_raw_mut(sum) },
+            count: unsafe { OwnedMutPtr::from_raw_mut(count) },
+            last_distance: None,
+        }
+    }
+
+    /// The average distance of the last execution, `None` if it executed no block with a distance
+    #[must_use]
+    pub fn last_distance(&self) -> Option<f64> {
+        self.last_distance
+    }
+}
+
+impl Named for DistanceObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<I, S> Observer<I, S> for DistanceObserver {
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.last_distance = None;
+        // # Safety
+        // The pointers are valid, the target may write to them concurrently.
+        unsafe {
+            self.sum.as_mut_ptr().write_volatile(0);
+            self.count.as_mut_ptr().write_volatile(0);
+        }
+        Ok(())
+    }
+
+    #[expect(clippy::cast_precision_loss)]
+    fn post_exec(
+        &mut self,
+        _state: &mut S,
+        _input: &I,
+        _exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        // # Safety
+        // The pointers are valid, the target may write to them concurrently.
+        let (sum, count) = unsafe {
+            (
+                self.sum.as_ptr().read_volatile(),
+                self.count.as_ptr().read_volatile(),
+            )
+        };
+        self.last_distance = (count > 0).then(|| sum as f64 / count as f64);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DistanceObserver;
+    use crate::{executors::ExitKind, inputs::NopInput, observers::Observer, state::NopState};
+
+    #[test]
+    fn average_distance() {
+        let mut sum = 7;
+        let mut count = 3;
+        let mut observer =
+            unsafe { DistanceObserver::from_mut_ptr("distance", &raw mut sum, &raw mut count) };
+        let mut state = NopState::<NopInput>::new();
+
+        Observer::<NopInput, _>::pre_exec(&mut observer, &mut state, &NopInput {}).unwrap();
+        Observer::<NopInput, _>::post_exec(&mut observer, &mut state, &NopInput {}, &ExitKind::Ok)
+            .unwrap();
+        assert_eq!(observer.last_distance(), None);
+
+        unsafe {
+            (&raw mut sum).write_volatile(300);
+            (&raw mut count).write_volatile(4);
+        }
+        Observer::<NopInput, _>::post_exec(&mut observer, &mut state, &NopInput {}, &ExitKind::Ok)
+            .unwrap();
+        assert_eq!(observer.last_distance(), Some(75.0));
+    }
+}
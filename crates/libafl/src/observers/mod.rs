@@ -17,8 +17,14 @@ pub use stacktrace::*;
 pub mod map;
 pub use map::*;
 
+pub mod distance;
+pub use distance::DistanceObserver;
+
 pub mod value;
 
+pub mod responses;
+pub use responses::ResponsesObserver;
+
 /// List observer
 pub mod list;
 use core::{fmt::Debug, time::Duration};
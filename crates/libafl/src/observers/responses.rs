@@ -0,0 +1,53 @@
+//! An observer for the responses of a server to the messages of a session, used for stateful protocol fuzzing.
+
+use alloc::{borrow::Cow, vec::Vec};
+
+use libafl_bolts::{Error, Named};
+use serde::{Deserialize, Serialize};
+
+use crate::observers::Observer;
+
+/// Holds the responses of a server to each message of the last execution.
+///
+/// The observer is filled by the executor delivering the messages,
+/// such as the `SocketExecutor`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ResponsesObserver {
+    name: Cow<'static, str>,
+    responses: Vec<Vec<u8>>,
+}
+
+impl ResponsesObserver {
+    /// Creates a new [`ResponsesObserver`] with the given name
+    #[must_use]
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name: Cow::Borrowed(name),
+            responses: vec![],
+        }
+    }
+
+    /// The response to each delivered message, empty if the server did not answer in time
+    #[must_use]
+    pub fn responses(&self) -> &[Vec<u8>] {
+        &self.responses
+    }
+
+    /// Records the response to the next message
+    pub fn observe(&mut self, response: Vec<u8>) {
+        self.responses.push(response);
+    }
+}
+
+impl<I, S> Observer<I, S> for ResponsesObserver {
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.responses.clear();
+        Ok(())
+    }
+}
+
+impl Named for ResponsesObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
@@ -1,8 +1,10 @@
 //! the ``StacktraceObserver`` looks up the stacktrace on the execution thread and computes a hash for it for dedupe
 
-#[cfg(feature = "casr")]
-use alloc::string::ToString;
-use alloc::{borrow::Cow, string::String, vec::Vec};
+use alloc::{
+    borrow::Cow,
+    string::{String, ToString},
+    vec::Vec,
+};
 use core::fmt::Debug;
 #[cfg(feature = "casr")]
 use core::hash::{Hash, Hasher};
@@ -20,7 +22,7 @@ use libafl_bolts::{Named, ownedref::OwnedRefMut, shmem::ShMem};
 #[allow(unused_imports)] // expect breaks here for some reason
 #[cfg(feature = "casr")]
 use libcasr::{
-    asan::AsanStacktrace,
+    asan::{AsanContext, AsanStacktrace},
     constants::{
         STACK_FRAME_FILEPATH_IGNORE_REGEXES_CPP, STACK_FRAME_FILEPATH_IGNORE_REGEXES_GO,
         STACK_FRAME_FILEPATH_IGNORE_REGEXES_JAVA, STACK_FRAME_FILEPATH_IGNORE_REGEXES_PYTHON,
@@ -29,6 +31,7 @@ use libcasr::{
         STACK_FRAME_FUNCTION_IGNORE_REGEXES_PYTHON, STACK_FRAME_FUNCTION_IGNORE_REGEXES_RUST,
     },
     init_ignored_frames,
+    severity::Severity,
     stacktrace::{
         Filter, ParseStacktrace, STACK_FRAME_FILEPATH_IGNORE_REGEXES,
         STACK_FRAME_FUNCTION_IGNORE_REGEXES, Stacktrace, StacktraceEntry,
@@ -116,6 +119,7 @@ pub struct BacktraceObserver<'a> {
     observer_name: Cow<'static, str>,
     hash: OwnedRefMut<'a, Option<u64>>,
     harness_type: HarnessType,
+    report: Option<CrashReport>,
 }
 
 impl<'a> BacktraceObserver<'a> {
@@ -134,6 +138,7 @@ impl<'a> BacktraceObserver<'a> {
             observer_name: observer_name.into(),
             hash: backtrace_hash,
             harness_type,
+            report: None,
         }
     }
 
@@ -153,6 +158,7 @@ impl<'a> BacktraceObserver<'a> {
             observer_name: observer_name.into(),
             hash: backtrace_hash,
             harness_type,
+            report: None,
         }
     }
 
@@ -208,6 +214,18 @@ impl<'a> BacktraceObserver<'a> {
             }
         }
     }
+
+    /// Fill the crash report if the harness type is external, e.g., with a report parsed
+    /// from the sanitizer output of the target.
+    pub fn fill_external_report(&mut self, report: Option<CrashReport>, exit_kind: &ExitKind) {
+        if self.harness_type == HarnessType::External {
+            self.report = if *exit_kind == ExitKind::Crash {
+                report
+            } else {
+                None
+            };
+        }
+    }
 }
 
 impl ObserverWithHashField for BacktraceObserver<'_> {
@@ -217,13 +235,29 @@ impl ObserverWithHashField for BacktraceObserver<'_> {
     }
 }
 
+impl ObserverWithCrashReport for BacktraceObserver<'_> {
+    /// Gets the report of the last crash.
+    ///
+    /// For in-process harnesses, there is no sanitizer output to parse, so the report only
+    /// knows that the target crashed. External harnesses can fill in a full report with
+    /// [`BacktraceObserver::fill_external_report`].
+    fn crash_report(&self) -> Option<&CrashReport> {
+        self.report.as_ref()
+    }
+}
+
 impl<I, S> Observer<I, S> for BacktraceObserver<'_> {
     fn post_exec(&mut self, _state: &mut S, _input: &I, exit_kind: &ExitKind) -> Result<(), Error> {
         if self.harness_type == HarnessType::InProcess {
             if *exit_kind == ExitKind::Crash {
                 self.update_hash(collect_backtrace());
+                self.report = Some(CrashReport {
+                    error_type: "crash".to_string(),
+                    ..CrashReport::default()
+                });
             } else {
                 self.clear_hash();
+                self.report = None;
             }
         }
         Ok(())
@@ -245,6 +279,91 @@ impl Named for BacktraceObserver<'_> {
     }
 }
 
+/// A summary of a sanitizer crash report, used to triage crashes
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct CrashReport {
+    /// The error type reported by the sanitizer, e.g. `heap-buffer-overflow` or `SEGV`
+    pub error_type: String,
+    /// The faulting program counter, if reported
+    pub pc: Option<u64>,
+    /// The faulting address, if reported
+    pub address: Option<u64>,
+    /// `Some(true)` if the faulting access was a write, `Some(false)` if it was a read
+    pub is_write: Option<bool>,
+    /// The severity class assigned by CASR, e.g. `EXPLOITABLE`, only available with the `casr` feature
+    pub casr_severity: Option<String>,
+}
+
+impl CrashReport {
+    /// Parses the first error of an ASAN report, returns `None` if no ASAN error was found.
+    #[must_use]
+    pub fn parse_asan(output: &str) -> Option<Self> {
+        /// Parses the first hex number (`0x...`) following `key` in `line`
+        fn hex_after(line: &str, key: &str) -> Option<u64> {
+            let rest = &line[line.find(key)? + key.len()..];
+            let num = rest.trim_start().strip_prefix("0x")?;
+            let end = num
+                .find(|c: char| !c.is_ascii_hexdigit())
+                .unwrap_or(num.len());
+            u64::from_str_radix(&num[..end], 16).ok()
+        }
+
+        let mut lines = output.lines();
+        let error_line = lines.find(|line| line.contains("ERROR: AddressSanitizer: "))?;
+        let error = &error_line[error_line.find("ERROR: AddressSanitizer: ")? + 25..];
+        let error_type = error
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .trim_end_matches(':')
+            .to_string();
+
+        let mut is_write = None;
+        for line in lines {
+            let line = line.trim();
+            if line.starts_with("WRITE of size") || line.contains("The signal is caused by a WRITE")
+            {
+                is_write = Some(true);
+                break;
+            } else if line.starts_with("READ of size")
+                || line.contains("The signal is caused by a READ")
+            {
+                is_write = Some(false);
+                break;
+            }
+        }
+
+        #[cfg(feature = "casr")]
+        let casr_severity = {
+            let report = output
+                .lines()
+                .skip_while(|line| !line.contains("ERROR: AddressSanitizer: "))
+                .map(ToString::to_string)
+                .collect();
+            AsanContext(report)
+                .severity()
+                .ok()
+                .map(|class| class.severity)
+        };
+        #[cfg(not(feature = "casr"))]
+        let casr_severity = None;
+
+        Some(Self {
+            error_type,
+            pc: hex_after(error, "pc "),
+            address: hex_after(error, "address "),
+            is_write,
+            casr_severity,
+        })
+    }
+}
+
+/// An observer that, additionally to the backtrace hash, knows about the sanitizer report of the last crash.
+pub trait ObserverWithCrashReport: ObserverWithHashField {
+    /// The report of the last crash, if any
+    fn crash_report(&self) -> Option<&CrashReport>;
+}
+
 /// static variable of ASAN log path
 pub static ASAN_LOG_PATH: &str = "./asanlog"; // TODO make it unique
 
@@ -278,6 +397,7 @@ pub fn get_asan_runtime_flags() -> String {
 pub struct AsanBacktraceObserver {
     observer_name: Cow<'static, str>,
     hash: Option<u64>,
+    report: Option<CrashReport>,
 }
 
 impl AsanBacktraceObserver {
@@ -291,6 +411,7 @@ impl AsanBacktraceObserver {
         Self {
             observer_name: observer_name.into(),
             hash: None,
+            report: None,
         }
     }
 
@@ -305,6 +426,7 @@ impl AsanBacktraceObserver {
         Self {
             observer_name: observer_name.into(),
             hash: None,
+            report: None,
         }
     }
 
@@ -342,6 +464,7 @@ impl AsanBacktraceObserver {
             hash ^= u64::from_str_radix(g.as_str(), 16).unwrap();
         });
         self.update_hash(hash);
+        self.report = CrashReport::parse_asan(output);
     }
 
     #[cfg(feature = "casr")]
@@ -357,6 +480,7 @@ impl AsanBacktraceObserver {
             hash = s.finish();
         }
         self.update_hash(hash);
+        self.report = CrashReport::parse_asan(output);
     }
 
     /// Updates the hash value of this observer.
@@ -372,6 +496,13 @@ impl ObserverWithHashField for AsanBacktraceObserver {
     }
 }
 
+impl ObserverWithCrashReport for AsanBacktraceObserver {
+    /// Gets the report parsed from the last ASAN output.
+    fn crash_report(&self) -> Option<&CrashReport> {
+        self.report.as_ref()
+    }
+}
+
 impl Default for AsanBacktraceObserver {
     fn default() -> Self {
         Self::new("AsanBacktraceObserver")
@@ -0,0 +1,470 @@
+//! A multi-armed bandit scheduler, treating every corpus entry as an arm.
+//!
+//! Each time an entry is scheduled, the arm is pulled. The pull is rewarded if the mutants of the entry
+//! added new entries to the corpus or found new objectives before the next entry is scheduled.
+//! The next arm is chosen either by Thompson sampling over Beta posteriors, or by UCB1.
+//! The arm statistics are updated incrementally, only the finished pull changes an arm.
+
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::marker::PhantomData;
+
+use hashbrown::HashMap;
+use libafl_bolts::rands::Rand;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Error, HasMetadata,
+    corpus::{Corpus, CorpusId, SchedulerTestcaseMetadata, Testcase},
+    schedulers::{RemovableScheduler, Scheduler},
+    state::{HasCorpus, HasRand, HasSolutions},
+};
+
+/// How the [`BanditScheduler`] picks the next arm
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BanditStrategy {
+    /// Samples the success probability of each arm from its Beta posterior and picks the best sample.
+    ///
+    /// An arm is only sampled again once its posterior changed, after it was pulled.
+    ThompsonSampling,
+    /// Picks the arm with the highest upper confidence bound `mean + c * sqrt(2 ln(N) / n)`
+    Ucb1 {
+        /// The exploration factor `c`, `1.0` for the classic UCB1
+        exploration: f64,
+    },
+}
+
+/// The statistics of a single arm
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BanditArm {
+    /// How often the arm was pulled
+    pub pulls: u64,
+    /// How many pulls were rewarded
+    pub rewards: u64,
+    /// The latest sample of the success probability from the Beta posterior, for Thompson sampling
+    pub sample: f64,
+}
+
+impl BanditArm {
+    /// The `alpha` parameter of the Beta posterior, starting from a uniform prior
+    #[must_use]
+    #[expect(clippy::cast_precision_loss)]
+    pub fn alpha(&self) -> f64 {
+        (self.rewards + 1) as f64
+    }
+
+    /// The `beta` parameter of the Beta posterior, starting from a uniform prior
+    #[must_use]
+    #[expect(clippy::cast_precision_loss)]
+    pub fn beta(&self) -> f64 {
+        (self.pulls - self.rewards + 1) as f64
+    }
+
+    /// The fraction of rewarded pulls
+    #[must_use]
+    #[expect(clippy::cast_precision_loss)]
+    pub fn mean(&self) -> f64 {
+        if self.pulls == 0 {
+            0.0
+        } else {
+            self.rewards as f64 / self.pulls as f64
+        }
+    }
+}
+
+/// The pull currently in progress
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct BanditPull {
+    /// The pulled arm
+    pub id: CorpusId,
+    /// The number of solutions when the pull started
+    pub solutions_before: usize,
+    /// The corpus entries added by the mutants of the arm so far
+    pub new_entries: u64,
+}
+
+/// The posteriors of the [`BanditScheduler`], kept in the state so they survive restarts
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    expect(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct BanditSchedulerMetadata {
+    /// The statistics of each arm
+    pub arms: HashMap<CorpusId, BanditArm>,
+    /// The arms that were not pulled yet
+    pub unpulled: Vec<CorpusId>,
+    /// The total number of finished pulls
+    pub total_pulls: u64,
+    /// The pull in progress, if any
+    pub current: Option<BanditPull>,
+}
+
+libafl_bolts::impl_serdeany!(BanditSchedulerMetadata);
+
+impl BanditSchedulerMetadata {
+    /// Finishes the pull in progress, rewarding the arm if it found anything new.
+    ///
+    /// Returns the updated arm, if it still exists.
+    fn finish_pull(&mut self, solutions: usize) -> Option<&mut BanditArm> {
+        let pull = self.current.take()?;
+        self.total_pulls += 1;
+        let arm = self.arms.get_mut(&pull.id)?;
+        arm.pulls += 1;
+        if pull.new_entries > 0 || solutions > pull.solutions_before {
+            arm.rewards += 1;
+        }
+        if arm.pulls == 1
+            && let Some(pos) = self.unpulled.iter().position(|id| *id == pull.id)
+        {
+            self.unpulled.swap_remove(pos);
+        }
+        Some(arm)
+    }
+}
+
+/// Samples from the standard normal distribution, using the Box-Muller transform
+fn sample_normal<R: Rand>(rand: &mut R) -> f64 {
+    let u1 = 1.0 - rand.next_float();
+    let u2 = rand.next_float();
+    libm::sqrt(-2.0 * libm::log(u1)) * libm::cos(2.0 * core::f64::consts::PI * u2)
+}
+
+/// Samples from `Gamma(shape, 1)` for `shape >= 1`, using the method of Marsaglia and Tsang
+#[expect(clippy::many_single_char_names)] // names from the paper
+fn sample_gamma<R: Rand>(rand: &mut R, shape: f64) -> f64 {
+    debug_assert!(shape >= 1.0);
+    let d = shape - 1.0 / 3.0;
+    let c = 1.0 / libm::sqrt(9.0 * d);
+    loop {
+        let x = sample_normal(rand);
+        let v = 1.0 + c * x;
+        if v <= 0.0 {
+            continue;
+        }
+        let v = v * v * v;
+        let u = 1.0 - rand.next_float();
+        if libm::log(u) < 0.5 * x * x + d - d * v + d * libm::log(v) {
+            return d * v;
+        }
+    }
+}
+
+/// Samples from `Beta(alpha, beta)` for `alpha, beta >= 1`
+fn sample_beta<R: Rand>(rand: &mut R, alpha: f64, beta: f64) -> f64 {
+    let x = sample_gamma(rand, alpha);
+    let y = sample_gamma(rand, beta);
+    x / (x + y)
+}
+
+/// A scheduler treating each corpus entry as an arm of a multi-armed bandit.
+///
+/// A pull of an arm is rewarded if fuzzing the entry added new entries to the corpus (tracked through their
+/// `parent_id`) or new solutions. The posteriors are stored in the [`BanditSchedulerMetadata`] of the state.
+#[derive(Debug, Clone)]
+pub struct BanditScheduler<S> {
+    strategy: BanditStrategy,
+    phantom: PhantomData<S>,
+}
+
+impl<S> BanditScheduler<S> {
+    /// Creates a new [`BanditScheduler`] with the given [`BanditStrategy`]
+    #[must_use]
+    pub fn new(strategy: BanditStrategy) -> Self {
+        Self {
+            strategy,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Creates a new [`BanditScheduler`] using Thompson sampling
+    #[must_use]
+    pub fn thompson_sampling() -> Self {
+        Self::new(BanditStrategy::ThompsonSampling)
+    }
+
+    /// Creates a new [`BanditScheduler`] using UCB1 with the classic exploration factor
+    #[must_use]
+    pub fn ucb1() -> Self {
+        Self::new(BanditStrategy::Ucb1 { exploration: 1.0 })
+    }
+
+    /// The [`BanditStrategy`] in use
+    #[must_use]
+    pub fn strategy(&self) -> BanditStrategy {
+        self.strategy
+    }
+
+    /// Draws a new sample from the posterior of the arm, if the strategy needs it
+    fn resample<R: Rand>(&self, rand: &mut R, arm: &mut BanditArm) {
+        if self.strategy == BanditStrategy::ThompsonSampling {
+            arm.sample = sample_beta(rand, arm.alpha(), arm.beta());
+        }
+    }
+}
+
+impl<S> Default for BanditScheduler<S> {
+    fn default() -> Self {
+        Self::thompson_sampling()
+    }
+}
+
+impl<I, S> RemovableScheduler<I, S> for BanditScheduler<S>
+where
+    S: HasMetadata,
+{
+    fn on_remove(
+        &mut self,
+        state: &mut S,
+        id: CorpusId,
+        _testcase: &Option<Testcase<I>>,
+    ) -> Result<(), Error> {
+        if let Ok(meta) = state.metadata_mut::<BanditSchedulerMetadata>() {
+            meta.arms.remove(&id);
+            meta.unpulled.retain(|unpulled| *unpulled != id);
+            if meta.current.is_some_and(|pull| pull.id == id) {
+                meta.current = None;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<I, S> Scheduler<I, S> for BanditScheduler<S>
+where
+    S: HasCorpus<I> + HasSolutions<I> + HasMetadata + HasRand,
+{
+    fn on_add(&mut self, state: &mut S, id: CorpusId) -> Result<(), Error> {
+        let current_id = *state.corpus().current();
+        let depth = match current_id {
+            Some(parent_id) => state
+                .corpus()
+                .get_from_all(parent_id)
+                .ok()
+                .and_then(|parent| {
+                    parent
+                        .borrow()
+                        .metadata::<SchedulerTestcaseMetadata>()
+                        .map(SchedulerTestcaseMetadata::depth)
+                        .ok()
+                })
+                .unwrap_or(0),
+            None => 0,
+        };
+        {
+            let mut testcase = state.corpus().get_from_all(id)?.borrow_mut();
+            testcase.set_parent_id_optional(current_id);
+            if !testcase.has_metadata::<SchedulerTestcaseMetadata>() {
+                testcase.add_metadata(SchedulerTestcaseMetadata::new(depth + 1));
+            }
+        }
+
+        // Disabled entries are never scheduled, so they are no arms
+        let enabled = state.corpus().get(id).is_ok();
+        let mut arm = BanditArm::default();
+        self.resample(state.rand_mut(), &mut arm);
+        let meta = state.metadata_or_insert_with(BanditSchedulerMetadata::default);
+        if enabled && !meta.arms.contains_key(&id) {
+            meta.arms.insert(id, arm);
+            meta.unpulled.push(id);
+        }
+        if let Some(pull) = meta.current.as_mut()
+            && Some(pull.id) == current_id
+        {
+            pull.new_entries += 1;
+        }
+        Ok(())
+    }
+
+    fn next(&mut self, state: &mut S) -> Result<CorpusId, Error> {
+        if state.corpus().count() == 0 {
+            return Err(Error::empty(String::from(
+                "No entries in corpus. This often implies the target is not properly instrumented.",
+            )));
+        }
+
+        let solutions = state.solutions().count();
+        // Taken out of the state while the random number generator is in use
+        let mut meta: Box<BanditSchedulerMetadata> =
+            state.metadata_map_mut().remove().unwrap_or_default();
+        let rand = state.rand_mut();
+        if let Some(arm) = meta.finish_pull(solutions) {
+            self.resample(rand, arm);
+        }
+
+        let best = match self.strategy {
+            BanditStrategy::ThompsonSampling => meta
+                .arms
+                .iter()
+                .max_by(|(_, a), (_, b)| a.sample.total_cmp(&b.sample))
+                .map(|(id, _)| *id),
+            BanditStrategy::Ucb1 { exploration } => {
+                // Every arm is pulled once before the confidence bounds are used
+                if let Some(id) = rand.choose(&meta.unpulled) {
+                    Some(*id)
+                } else {
+                    #[expect(clippy::cast_precision_loss)]
+                    let log_total = libm::log(meta.total_pulls as f64);
+                    #[expect(clippy::cast_precision_loss)]
+                    let score = |arm: &BanditArm| {
+                        arm.mean() + exploration * libm::sqrt(2.0 * log_total / arm.pulls as f64)
+                    };
+                    meta.arms
+                        .iter()
+                        .max_by(|(_, a), (_, b)| score(a).total_cmp(&score(b)))
+                        .map(|(id, _)| *id)
+                }
+            }
+        };
+        state.metadata_map_mut().insert_boxed(meta);
+
+        // Without any arm, all entries were added before this scheduler was in use
+        let best = best
+            .or_else(|| state.corpus().first())
+            .ok_or_else(|| Error::empty("No enabled entries in corpus"))?;
+
+        self.set_current_scheduled(state, Some(best))?;
+        Ok(best)
+    }
+
+    fn set_current_scheduled(
+        &mut self,
+        state: &mut S,
+        next_id: Option<CorpusId>,
+    ) -> Result<(), Error> {
+        let solutions_before = state.solutions().count();
+        state
+            .metadata_or_insert_with(BanditSchedulerMetadata::default)
+            .current = next_id.map(|id| BanditPull {
+            id,
+            solutions_before,
+            new_entries: 0,
+        });
+        *state.corpus_mut().current_mut() = next_id;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use libafl_bolts::rands::StdRand;
+
+    use super::{BanditScheduler, BanditSchedulerMetadata, sample_beta};
+    use crate::{
+        HasMetadata,
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        feedbacks::ConstFeedback,
+        inputs::BytesInput,
+        schedulers::Scheduler,
+        state::{HasCorpus, StdState},
+    };
+
+    #[test]
+    fn beta_mean() {
+        let mut rand = StdRand::with_seed(1337);
+        let samples = 10_000;
+        let mean = (0..samples)
+            .map(|_| sample_beta(&mut rand, 8.0, 2.0))
+            .sum::<f64>()
+            / f64::from(samples);
+        assert!((mean - 0.8).abs() < 0.02, "mean {mean}");
+    }
+
+    #[test]
+    fn rewards_productive_arm() {
+        #[cfg(any(not(feature = "serdeany_autoreg"), miri))]
+        // # Safety
+        // No concurrency per testcase
+        unsafe {
+            BanditSchedulerMetadata::register();
+            crate::corpus::SchedulerTestcaseMetadata::register();
+        }
+
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = ConstFeedback::new(false);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+        let mut scheduler = BanditScheduler::thompson_sampling();
+        for byte in 0..2 {
+            let id = state
+                .corpus_mut()
+                .add(Testcase::new(BytesInput::new(vec![byte])))
+                .unwrap();
+            scheduler.on_add(&mut state, id).unwrap();
+        }
+
+        let productive = state.corpus().first().unwrap();
+        for _ in 0..200 {
+            let id = scheduler.next(&mut state).unwrap();
+            if id == productive {
+                // The mutant becomes a new corpus entry, but is not scheduled itself
+                let new_id = state
+                    .corpus_mut()
+                    .add_disabled(Testcase::new(BytesInput::new(vec![42])))
+                    .unwrap();
+                scheduler.on_add(&mut state, new_id).unwrap();
+            }
+        }
+
+        let meta = state.metadata::<BanditSchedulerMetadata>().unwrap();
+        let arm = meta.arms[&productive];
+        assert_eq!(arm.pulls, arm.rewards);
+        assert!(arm.pulls > 150, "{meta:?}");
+    }
+
+    #[test]
+    fn ucb1_pulls_every_arm_first() {
+        #[cfg(any(not(feature = "serdeany_autoreg"), miri))]
+        // # Safety
+        // No concurrency per testcase
+        unsafe {
+            BanditSchedulerMetadata::register();
+            crate::corpus::SchedulerTestcaseMetadata::register();
+        }
+
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::new(),
+            InMemoryCorpus::new(),
+            &mut ConstFeedback::new(false),
+            &mut ConstFeedback::new(false),
+        )
+        .unwrap();
+        let mut scheduler = BanditScheduler::ucb1();
+        for byte in 0..4 {
+            let id = state
+                .corpus_mut()
+                .add(Testcase::new(BytesInput::new(vec![byte])))
+                .unwrap();
+            scheduler.on_add(&mut state, id).unwrap();
+        }
+        // Disabled entries are no arms
+        let disabled = state
+            .corpus_mut()
+            .add_disabled(Testcase::new(BytesInput::new(vec![42])))
+            .unwrap();
+        scheduler.on_add(&mut state, disabled).unwrap();
+
+        let mut scheduled: Vec<_> = (0..4)
+            .map(|_| scheduler.next(&mut state).unwrap())
+            .collect();
+        scheduled.sort();
+        scheduled.dedup();
+        assert_eq!(scheduled.len(), 4);
+        for _ in 0..20 {
+            assert_ne!(scheduler.next(&mut state).unwrap(), disabled);
+        }
+
+        let meta = state.metadata::<BanditSchedulerMetadata>().unwrap();
+        assert!(meta.unpulled.is_empty());
+        assert_eq!(meta.arms.len(), 4);
+        assert_eq!(meta.total_pulls, 23);
+    }
+}
@@ -0,0 +1,516 @@
+//! An entropic scheduler, after the [entropic power schedule](https://mboehme.github.io/paper/FSE20.Entropy.pdf) of libFuzzer.
+//!
+//! Entropic prefers the [`Testcase`]s whose mutants hit the rare features of the target most evenly,
+//! as the information gained by fuzzing them, measured as the entropy over the rare features, is the highest.
+//! Here, the features are the indexes of a map observer.
+
+use alloc::{collections::BTreeMap, string::ToString, vec::Vec};
+use core::marker::PhantomData;
+
+use hashbrown::HashMap;
+use libafl_bolts::{
+    Named,
+    rands::Rand,
+    tuples::{Handle, Handled, MatchName, MatchNameRef},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Error, HasMetadata,
+    corpus::{Corpus, CorpusId, Testcase},
+    feedbacks::MapNoveltiesMetadata,
+    observers::{CanTrack, MapObserver},
+    require_novelties_tracking,
+    schedulers::{HasQueueCycles, RemovableScheduler, Scheduler},
+    state::{HasCorpus, HasRand},
+};
+
+/// The default number of rare features entropic keeps track of, like libFuzzer's `-entropic_number_of_rarest_features`
+pub const DEFAULT_NUMBER_OF_RARE_FEATURES: usize = 100;
+
+/// The default number of hits after which a feature is no longer rare,
+/// like libFuzzer's `-entropic_feature_frequency_threshold`
+pub const DEFAULT_FEATURE_FREQUENCY_THRESHOLD: u16 = 0xFF;
+
+/// What the [`EntropicScheduler`] knows about a corpus entry
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct EntropicTestcaseInfo {
+    /// How often the mutants of this entry hit each rare feature
+    feature_freqs: HashMap<usize, u16>,
+    /// The number of mutants of this entry executed so far
+    executed_mutations: u64,
+    energy: f64,
+}
+
+impl EntropicTestcaseInfo {
+    /// The energy of this entry, the higher the more likely it is scheduled
+    #[must_use]
+    pub fn energy(&self) -> f64 {
+        self.energy
+    }
+
+    /// The number of mutants of this entry executed so far
+    #[must_use]
+    pub fn executed_mutations(&self) -> u64 {
+        self.executed_mutations
+    }
+
+    /// Recomputes the energy, an estimate of the entropy over the rare features hit by the mutants of this entry.
+    ///
+    /// Add-one smoothing is applied to the rare features, including the ones never hit by the mutants of this entry,
+    /// and all other features count as a single, abundant, feature hit by every mutant.
+    #[expect(clippy::cast_precision_loss)]
+    fn update_energy(&mut self, rare_features: usize) {
+        let mut energy = 0.0;
+        let mut sum_incidence = 0.0;
+        for freq in self.feature_freqs.values() {
+            let incidence = f64::from(*freq) + 1.0;
+            energy -= incidence * incidence.ln();
+            sum_incidence += incidence;
+        }
+        sum_incidence += rare_features.saturating_sub(self.feature_freqs.len()) as f64;
+
+        let abundant_incidence = self.executed_mutations as f64 + 1.0;
+        energy -= abundant_incidence * abundant_incidence.ln();
+        sum_incidence += abundant_incidence;
+
+        self.energy = energy / sum_incidence + sum_incidence.ln();
+    }
+
+    /// How likely this entry is scheduled, relative to the other entries
+    fn weight(&self) -> f64 {
+        self.energy.max(0.0)
+    }
+}
+
+/// The metadata of the [`EntropicScheduler`]: the rare features and the entries of the corpus.
+///
+/// The energies are kept up to date as the rare features are hit, so picking an entry does not recompute them.
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    expect(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct EntropicMetadata {
+    /// The global number of hits of each rare feature
+    rare_features: HashMap<usize, u16>,
+    entries: BTreeMap<CorpusId, EntropicTestcaseInfo>,
+    /// The sum of the weights of all entries
+    total_weight: f64,
+}
+
+libafl_bolts::impl_serdeany!(EntropicMetadata);
+
+impl EntropicMetadata {
+    /// Creates a new [`EntropicMetadata`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The rare features and how often they were hit
+    #[must_use]
+    pub fn rare_features(&self) -> &HashMap<usize, u16> {
+        &self.rare_features
+    }
+
+    /// What the scheduler knows about a corpus entry
+    #[must_use]
+    pub fn entry(&self, id: CorpusId) -> Option<&EntropicTestcaseInfo> {
+        self.entries.get(&id)
+    }
+
+    /// The sum of the energies of all entries, ignoring negative ones
+    #[must_use]
+    pub fn total_weight(&self) -> f64 {
+        self.total_weight
+    }
+
+    /// Recomputes the energy of an entry whose mutants hit different rare features now
+    fn update_energy(&mut self, id: CorpusId) {
+        let rare_features = self.rare_features.len();
+        if let Some(entry) = self.entries.get_mut(&id) {
+            self.total_weight -= entry.weight();
+            entry.update_energy(rare_features);
+            self.total_weight += entry.weight();
+        }
+    }
+
+    /// Counts the rare features hit by a mutant of `parent`, and updates the energy of `parent`
+    pub fn update_frequencies<F>(&mut self, parent: Option<CorpusId>, is_hit: F)
+    where
+        F: Fn(usize) -> bool,
+    {
+        let mut entry = parent.and_then(|id| self.entries.get_mut(&id));
+        if let Some(entry) = entry.as_mut() {
+            entry.executed_mutations += 1;
+        }
+        for (feature, freq) in &mut self.rare_features {
+            if is_hit(*feature) {
+                *freq = freq.saturating_add(1);
+                if let Some(entry) = entry.as_mut() {
+                    let local = entry.feature_freqs.entry(*feature).or_default();
+                    *local = local.saturating_add(1);
+                }
+            }
+        }
+        if let Some(parent) = parent {
+            self.update_energy(parent);
+        }
+    }
+
+    /// Adds a corpus entry, a mutant of `parent` which discovered `new_features`.
+    ///
+    /// The new features are rare, until there are more than `number_of_rare_features` rare features:
+    /// then, the most abundant ones are dropped as long as they were hit more than `feature_frequency_threshold` times.
+    #[expect(clippy::cast_precision_loss)]
+    pub fn add_entry(
+        &mut self,
+        id: CorpusId,
+        parent: Option<CorpusId>,
+        new_features: &[usize],
+        number_of_rare_features: usize,
+        feature_frequency_threshold: u16,
+    ) {
+        let mut outdated = Vec::new();
+        for feature in new_features {
+            // Count the execution which discovered the feature
+            let freq = self.rare_features.entry(*feature).or_default();
+            *freq = freq.saturating_add(1);
+            if let Some(entry) = parent.and_then(|id| self.entries.get_mut(&id)) {
+                let local = entry.feature_freqs.entry(*feature).or_default();
+                *local = local.saturating_add(1);
+            }
+        }
+        outdated.extend(parent.filter(|_| !new_features.is_empty()));
+
+        while self.rare_features.len() > number_of_rare_features {
+            let Some((&most_abundant, &freq)) =
+                self.rare_features.iter().max_by_key(|(_, freq)| **freq)
+            else {
+                break;
+            };
+            if freq <= feature_frequency_threshold {
+                break;
+            }
+            self.rare_features.remove(&most_abundant);
+            for (id, entry) in &mut self.entries {
+                if entry.feature_freqs.remove(&most_abundant).is_some() {
+                    outdated.push(*id);
+                }
+            }
+        }
+        for id in outdated {
+            self.update_energy(id);
+        }
+
+        // New entries get the highest possible energy, until their first mutant is executed
+        let energy = if self.rare_features.is_empty() {
+            1.0
+        } else {
+            (self.rare_features.len() as f64).ln()
+        };
+        let entry = EntropicTestcaseInfo {
+            energy,
+            ..EntropicTestcaseInfo::default()
+        };
+        self.total_weight += entry.weight();
+        if let Some(prev) = self.entries.insert(id, entry) {
+            self.total_weight -= prev.weight();
+        }
+    }
+
+    /// Removes a corpus entry
+    pub fn remove_entry(&mut self, id: CorpusId) {
+        if let Some(entry) = self.entries.remove(&id) {
+            self.total_weight -= entry.weight();
+        }
+    }
+
+    /// Picks an entry weighted by energy, given a `threshold` in `0.0..1.0`, or `None` if no entry has any energy
+    fn pick(&self, threshold: f64) -> Option<CorpusId> {
+        if self.total_weight <= 0.0 || !self.total_weight.is_finite() {
+            return None;
+        }
+        let mut threshold = threshold * self.total_weight;
+        let mut last = None;
+        for (id, entry) in &self.entries {
+            let weight = entry.weight();
+            if weight <= 0.0 {
+                continue;
+            }
+            if threshold < weight {
+                return Some(*id);
+            }
+            threshold -= weight;
+            last = Some(*id);
+        }
+        // rounding errors of the total weight
+        last
+    }
+}
+
+/// The [`EntropicScheduler`] picks the corpus entries by their entropic energy, see the [module docs](self).
+///
+/// It wraps a `base` [`Scheduler`], which keeps track of the corpus as usual, so that for example the
+/// metadata of the power schedules stays available. As long as no rare feature is known, the `base`
+/// scheduler picks the next entry.
+///
+/// The features are the indexes of the map observer, which must track novelties.
+#[derive(Debug, Clone)]
+pub struct EntropicScheduler<CS, C, O> {
+    base: CS,
+    observer_handle: Handle<C>,
+    number_of_rare_features: usize,
+    feature_frequency_threshold: u16,
+    phantom: PhantomData<O>,
+}
+
+impl<CS, C, O> EntropicScheduler<CS, C, O>
+where
+    C: CanTrack + Named,
+{
+    /// Creates a new [`EntropicScheduler`] wrapping `base`, with libFuzzer's default parameters
+    pub fn new<S>(state: &mut S, observer: &C, base: CS) -> Self
+    where
+        S: HasMetadata,
+    {
+        Self::with_parameters(
+            state,
+            observer,
+            base,
+            DEFAULT_NUMBER_OF_RARE_FEATURES,
+            DEFAULT_FEATURE_FREQUENCY_THRESHOLD,
+        )
+    }
+
+    /// Creates a new [`EntropicScheduler`] wrapping `base`, which keeps track of at least `number_of_rare_features`
+    /// features, each until it was hit more than `feature_frequency_threshold` times.
+    ///
+    /// With `0` rare features, entropic is disabled and the `base` scheduler picks all entries.
+    pub fn with_parameters<S>(
+        state: &mut S,
+        observer: &C,
+        base: CS,
+        number_of_rare_features: usize,
+        feature_frequency_threshold: u16,
+    ) -> Self
+    where
+        S: HasMetadata,
+    {
+        require_novelties_tracking!("EntropicScheduler", C);
+        let _ = state.metadata_or_insert_with(EntropicMetadata::new);
+        Self {
+            base,
+            observer_handle: observer.handle(),
+            number_of_rare_features,
+            feature_frequency_threshold,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<CS, C, O> EntropicScheduler<CS, C, O> {
+    /// Get a reference to the base scheduler
+    pub fn base(&self) -> &CS {
+        &self.base
+    }
+
+    /// Get a reference to the base scheduler (mut)
+    pub fn base_mut(&mut self) -> &mut CS {
+        &mut self.base
+    }
+
+    /// Whether entropic scheduling is enabled
+    #[must_use]
+    pub fn enabled(&self) -> bool {
+        self.number_of_rare_features > 0
+    }
+
+    /// Picks an entry of the corpus, weighted by energy, or `None` if no entry has any energy
+    fn pick<S>(state: &mut S) -> Result<Option<CorpusId>, Error>
+    where
+        S: HasMetadata + HasRand,
+    {
+        if state
+            .metadata::<EntropicMetadata>()?
+            .rare_features
+            .is_empty()
+        {
+            return Ok(None);
+        }
+        let threshold = state.rand_mut().next_float();
+        Ok(state.metadata::<EntropicMetadata>()?.pick(threshold))
+    }
+}
+
+impl<CS, C, I, O, S> RemovableScheduler<I, S> for EntropicScheduler<CS, C, O>
+where
+    CS: RemovableScheduler<I, S>,
+    S: HasMetadata,
+{
+    fn on_remove(
+        &mut self,
+        state: &mut S,
+        id: CorpusId,
+        testcase: &Option<Testcase<I>>,
+    ) -> Result<(), Error> {
+        self.base.on_remove(state, id, testcase)?;
+        state.metadata_mut::<EntropicMetadata>()?.remove_entry(id);
+        Ok(())
+    }
+
+    fn on_replace(&mut self, state: &mut S, id: CorpusId, prev: &Testcase<I>) -> Result<(), Error> {
+        self.base.on_replace(state, id, prev)
+    }
+}
+
+impl<CS, C, O> HasQueueCycles for EntropicScheduler<CS, C, O>
+where
+    CS: HasQueueCycles,
+{
+    fn queue_cycles(&self) -> u64 {
+        self.base.queue_cycles()
+    }
+}
+
+impl<CS, C, I, O, S> Scheduler<I, S> for EntropicScheduler<CS, C, O>
+where
+    CS: Scheduler<I, S>,
+    C: AsRef<O>,
+    O: MapObserver,
+    S: HasCorpus<I> + HasMetadata + HasRand,
+{
+    fn on_add(&mut self, state: &mut S, id: CorpusId) -> Result<(), Error> {
+        self.base.on_add(state, id)?;
+        if !self.enabled() {
+            return Ok(());
+        }
+
+        let parent = *state.corpus().current();
+        let new_features = state
+            .corpus()
+            .get(id)?
+            .borrow()
+            .metadata_map()
+            .get::<MapNoveltiesMetadata>()
+            .map(|meta| meta.list.clone())
+            .unwrap_or_default();
+        state.metadata_mut::<EntropicMetadata>()?.add_entry(
+            id,
+            parent,
+            &new_features,
+            self.number_of_rare_features,
+            self.feature_frequency_threshold,
+        );
+        Ok(())
+    }
+
+    fn on_evaluation<OT>(&mut self, state: &mut S, input: &I, observers: &OT) -> Result<(), Error>
+    where
+        OT: MatchName,
+    {
+        self.base.on_evaluation(state, input, observers)?;
+        if !self.enabled() {
+            return Ok(());
+        }
+
+        let observer = observers
+            .get(&self.observer_handle)
+            .ok_or_else(|| Error::key_not_found("Observer not found".to_string()))?
+            .as_ref();
+        let initial = observer.initial();
+        let parent = *state.corpus().current();
+        state
+            .metadata_mut::<EntropicMetadata>()?
+            .update_frequencies(parent, |feature| observer.get(feature) != initial);
+        Ok(())
+    }
+
+    fn next(&mut self, state: &mut S) -> Result<CorpusId, Error> {
+        if self.enabled()
+            && let Some(id) = Self::pick(state)?
+        {
+            self.base.set_current_scheduled(state, Some(id))?;
+            return Ok(id);
+        }
+        self.base.next(state)
+    }
+
+    fn set_current_scheduled(
+        &mut self,
+        state: &mut S,
+        next_id: Option<CorpusId>,
+    ) -> Result<(), Error> {
+        self.base.set_current_scheduled(state, next_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use crate::{corpus::CorpusId, schedulers::entropic::EntropicMetadata};
+
+    #[test]
+    fn test_entropic_energy() {
+        let mut meta = EntropicMetadata::new();
+        meta.add_entry(CorpusId(0), None, &[1, 2, 3, 4], 100, 0xFF);
+        meta.add_entry(CorpusId(1), None, &[], 100, 0xFF);
+        meta.add_entry(CorpusId(2), None, &[], 100, 0xFF);
+        assert_eq!(meta.rare_features().len(), 4);
+
+        // The mutants of entry 1 hit all rare features evenly, the ones of entry 2 always the same feature
+        for _ in 0..10 {
+            meta.update_frequencies(Some(CorpusId(1)), |_| true);
+            meta.update_frequencies(Some(CorpusId(2)), |feature| feature == 1);
+        }
+        let even = meta.entry(CorpusId(1)).unwrap().energy();
+        let skewed = meta.entry(CorpusId(2)).unwrap().energy();
+        assert!(even > skewed, "{even} <= {skewed}");
+        assert_eq!(meta.entry(CorpusId(1)).unwrap().executed_mutations(), 10);
+        assert_eq!(meta.rare_features()[&1], 21);
+    }
+
+    #[test]
+    fn test_entropic_abundant_features() {
+        let mut meta = EntropicMetadata::new();
+        meta.add_entry(CorpusId(0), None, &[1, 2], 1, 4);
+        assert_eq!(meta.rare_features().len(), 2);
+        for _ in 0..5 {
+            meta.update_frequencies(Some(CorpusId(0)), |feature| feature == 1);
+        }
+
+        // Feature 1 is hit more than the threshold, so it is no longer rare once a feature is discovered
+        meta.add_entry(CorpusId(1), Some(CorpusId(0)), &[3], 1, 4);
+        assert!(!meta.rare_features().contains_key(&1));
+        assert_eq!(meta.rare_features().len(), 2);
+    }
+
+    #[test]
+    fn test_entropic_pick() {
+        let mut meta = EntropicMetadata::new();
+        assert_eq!(meta.pick(0.5), None);
+        meta.add_entry(CorpusId(0), None, &[1, 2, 3], 100, 0xFF);
+        meta.add_entry(CorpusId(1), None, &[], 100, 0xFF);
+        meta.add_entry(CorpusId(2), None, &[], 100, 0xFF);
+        for _ in 0..10 {
+            meta.update_frequencies(Some(CorpusId(1)), |feature| feature == 1);
+        }
+
+        // the cached total weight is the sum of the energies
+        let energies = (0..3)
+            .map(|id| meta.entry(CorpusId(id)).unwrap().energy())
+            .collect::<Vec<_>>();
+        let total = meta.total_weight();
+        assert!((total - energies.iter().sum::<f64>()).abs() < 1e-9);
+
+        assert_eq!(meta.pick(0.0), Some(CorpusId(0)));
+        assert_eq!(meta.pick(energies[0] / total + 1e-9), Some(CorpusId(1)));
+        assert_eq!(meta.pick(0.999_999), Some(CorpusId(2)));
+
+        meta.remove_entry(CorpusId(0));
+        assert!((meta.total_weight() - energies[1] - energies[2]).abs() < 1e-9);
+        assert_eq!(meta.pick(0.0), Some(CorpusId(1)));
+    }
+}
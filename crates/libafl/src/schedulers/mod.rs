@@ -26,6 +26,12 @@ pub use accounting::CoverageAccountingScheduler;
 pub mod weighted;
 pub use weighted::{StdWeightedScheduler, WeightedScheduler};
 
+pub mod bandit;
+pub use bandit::{BanditScheduler, BanditSchedulerMetadata, BanditStrategy};
+
+pub mod entropic;
+pub use entropic::{EntropicMetadata, EntropicScheduler};
+
 pub mod tuneable;
 use libafl_bolts::{
     generic_hash_std,
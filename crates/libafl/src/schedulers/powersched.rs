@@ -161,6 +161,9 @@ impl SchedulerMetadata {
 pub struct PowerSchedule {
     base: BaseSchedule,
     avoid_crash: bool,
+    /// The time to exploitation of the directed annealing, if directed
+    #[serde(default)]
+    directed: Option<Duration>,
 }
 
 impl PowerSchedule {
@@ -170,6 +173,7 @@ impl PowerSchedule {
         Self {
             base,
             avoid_crash: false,
+            directed: None,
         }
     }
 
@@ -179,6 +183,7 @@ impl PowerSchedule {
         Self {
             base: BaseSchedule::EXPLORE,
             avoid_crash: false,
+            directed: None,
         }
     }
 
@@ -188,6 +193,7 @@ impl PowerSchedule {
         Self {
             base: BaseSchedule::EXPLOIT,
             avoid_crash: false,
+            directed: None,
         }
     }
 
@@ -197,6 +203,7 @@ impl PowerSchedule {
         Self {
             base: BaseSchedule::FAST,
             avoid_crash: false,
+            directed: None,
         }
     }
 
@@ -206,6 +213,7 @@ impl PowerSchedule {
         Self {
             base: BaseSchedule::COE,
             avoid_crash: false,
+            directed: None,
         }
     }
 
@@ -215,6 +223,7 @@ impl PowerSchedule {
         Self {
             base: BaseSchedule::LIN,
             avoid_crash: false,
+            directed: None,
         }
     }
 
@@ -224,6 +233,7 @@ impl PowerSchedule {
         Self {
             base: BaseSchedule::QUAD,
             avoid_crash: false,
+            directed: None,
         }
     }
 
@@ -238,6 +248,22 @@ impl PowerSchedule {
         self.avoid_crash = true;
     }
 
+    /// The time to exploitation, if this schedule is directed
+    #[must_use]
+    pub fn directed(&self) -> Option<Duration> {
+        self.directed
+    }
+
+    /// Direct the schedule towards targets, like `AFLGo`, using the distances of
+    /// [`crate::feedbacks::DistanceFeedback`].
+    ///
+    /// The energy of each testcase is scaled by simulated annealing: at first all testcases get similar energy,
+    /// as the temperature cools down, testcases closer to the targets get up to 32 times more energy and distant ones
+    /// up to 32 times less. After `time_to_exploit` the temperature is down to 5%.
+    pub fn set_directed(&mut self, time_to_exploit: Duration) {
+        self.directed = Some(time_to_exploit);
+    }
+
     /// Getter to the base scheduler
     #[must_use]
     pub fn base(&self) -> &BaseSchedule {
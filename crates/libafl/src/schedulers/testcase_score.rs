@@ -1,13 +1,17 @@
 //! The `TestcaseScore` is an evaluator providing scores of corpus items.
 use alloc::string::String;
+use core::marker::PhantomData;
 
-use libafl_bolts::{HasLen, HasRefCnt};
+use libafl_bolts::{HasLen, HasRefCnt, current_time};
 use num_traits::Zero;
 
 use crate::{
     Error, HasMetadata,
     corpus::{Corpus, SchedulerTestcaseMetadata, Testcase},
-    feedbacks::MapIndexesMetadata,
+    feedbacks::{
+        DistanceBoundsMetadata, DistanceMetadata, MapIndexesMetadata, RareEdgeMetadata,
+        RareEdgesMetadata,
+    },
     schedulers::{
         minimizer::{IsFavoredMetadata, TopRatedsMetadata},
         powersched::{BaseSchedule, SchedulerMetadata},
@@ -244,6 +248,20 @@ where
             perf_score *= factor / POWER_BETA;
         }
 
+        // Directed fuzzing: simulated annealing from exploration to the testcases closest to the targets, like AFLGo
+        if let Some(time_to_exploit) = psmeta.strat().and_then(|s| s.directed())
+            && let Ok(distance) = entry.metadata::<DistanceMetadata>()
+            && let Ok(bounds) = state.metadata::<DistanceBoundsMetadata>()
+        {
+            let elapsed = current_time().saturating_sub(bounds.start_time);
+            let progress =
+                elapsed.as_secs_f64() / time_to_exploit.as_secs_f64().max(f64::MIN_POSITIVE);
+            let temperature = libm::pow(20.0, -progress);
+            let p = (1.0 - bounds.normalize(distance.distance)) * (1.0 - temperature)
+                + 0.5 * temperature;
+            perf_score *= libm::exp2(2.0 * libm::log2(MAX_FACTOR) * (p - 0.5));
+        }
+
         // Lower bound if the strat is not COE.
         if let Some(strat) = psmeta.strat()
             && *strat.base() == BaseSchedule::COE
@@ -353,3 +371,56 @@ where
         Ok(weight)
     }
 }
+
+/// The factor favoring a testcase for the rare edges it hits, `1.0` for none
+#[expect(clippy::cast_precision_loss)]
+fn rare_edge_factor<I, S>(state: &S, entry: &Testcase<I>) -> f64
+where
+    S: HasMetadata,
+{
+    match (
+        state.metadata::<RareEdgeMetadata>(),
+        entry.metadata::<RareEdgesMetadata>(),
+    ) {
+        (Ok(meta), Ok(edges)) => 1.0 + edges.count_rare(meta) as f64,
+        _ => 1.0,
+    }
+}
+
+/// Wraps a [`TestcaseScore`], multiplying it by one plus the number of still rare edges the testcase hits.
+///
+/// Use it with the [`crate::feedbacks::RareEdgeFeedback`], for example in a
+/// [`crate::schedulers::WeightedScheduler`], to focus on rare edges like `FairFuzz`.
+#[derive(Debug, Clone)]
+pub struct RareEdgeTestcaseScore<F> {
+    phantom: PhantomData<F>,
+}
+
+impl<F, I, S> TestcaseScore<I, S> for RareEdgeTestcaseScore<F>
+where
+    F: TestcaseScore<I, S>,
+    S: HasMetadata,
+{
+    fn compute(state: &S, entry: &mut Testcase<I>) -> Result<f64, Error> {
+        Ok(F::compute(state, entry)? * rare_edge_factor(state, entry))
+    }
+}
+
+/// Wraps a [`TestcasePenalty`], dividing it by one plus the number of still rare edges the testcase hits.
+///
+/// Use it with the [`crate::feedbacks::RareEdgeFeedback`] in a [`crate::schedulers::MinimizerScheduler`],
+/// so testcases hitting rare edges become favored.
+#[derive(Debug, Clone)]
+pub struct RareEdgeTestcasePenalty<F> {
+    phantom: PhantomData<F>,
+}
+
+impl<F, I, S> TestcasePenalty<I, S> for RareEdgeTestcasePenalty<F>
+where
+    F: TestcasePenalty<I, S>,
+    S: HasMetadata,
+{
+    fn compute(state: &S, entry: &mut Testcase<I>) -> Result<f64, Error> {
+        Ok(F::compute(state, entry)? / rare_edge_factor(state, entry))
+    }
+}
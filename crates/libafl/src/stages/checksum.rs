@@ -0,0 +1,548 @@
+//! Detection of checksums over the input, like the input-to-state checksum handling of `RedQueen`.
+//!
+//! Targets that validate a `CRC-32`, `Adler-32` or `MD5` over (parts of) the input reject almost every
+//! mutated input. The [`ChecksumDetectionStage`] traces an input with `CmpLog` and looks for comparisons
+//! where one operand is found in the input and the other one is a checksum over another region of the input.
+//! The found locations are stored in the [`ChecksumMetadata`] of the testcase, so that
+//! [`crate::mutators::ChecksumFixupMutator`] can recompute them after every mutation.
+
+use alloc::{borrow::Cow, vec::Vec};
+use core::ops::Range;
+
+use libafl_bolts::{
+    AsSlice, Named,
+    checksum::{Adler32, Crc32, Md5, md5},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Error, HasMetadata, HasNamedMetadata,
+    corpus::HasCurrentCorpusId,
+    executors::{Executor, HasObservers},
+    inputs::HasMutatorBytes,
+    observers::{CmpValues, CmpValuesMetadata, ObserversTuple},
+    stages::{Restartable, RetryCountRestartHelper, Stage, TracingStage},
+    state::{HasCorpus, HasCurrentTestcase, HasExecutions, MaybeHasClientPerfMonitor},
+};
+
+/// The default name of the [`ChecksumDetectionStage`]
+pub const CHECKSUM_DETECTION_STAGE_NAME: &str = "checksum_detection";
+
+/// Inputs up to this length are also searched for `MD5` digests over regions ending at the digest
+/// or the input end, which takes quadratic time. `CRC-32` and `Adler-32` regions are always searched.
+pub const DEFAULT_MAX_FULL_SEARCH_LEN: usize = 4096;
+
+/// How many occurrences of a compared value in the input are checked at most
+const MAX_OCCURRENCES: usize = 16;
+
+/// A checksum algorithm
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ChecksumKind {
+    /// `CRC-32` (IEEE 802.3), as used by `zlib`, `PNG` and `Ethernet`
+    Crc32,
+    /// `Adler-32`, as used by `zlib` streams
+    Adler32,
+    /// The `MD5` digest
+    Md5,
+}
+
+impl ChecksumKind {
+    /// The width of the stored checksum in bytes
+    #[must_use]
+    pub fn width(self) -> usize {
+        match self {
+            ChecksumKind::Crc32 | ChecksumKind::Adler32 => 4,
+            ChecksumKind::Md5 => 16,
+        }
+    }
+
+    /// Computes this checksum over `data`, encoded in the given byte order.
+    /// The byte order is ignored for digests.
+    #[must_use]
+    pub fn compute(self, data: &[u8], big_endian: bool) -> Vec<u8> {
+        let mut checksummer = Checksummer::new(self);
+        checksummer.update(data);
+        checksummer.finish(big_endian)
+    }
+}
+
+/// An incremental checksum computation
+#[derive(Debug, Clone)]
+enum Checksummer {
+    Crc32(Crc32),
+    Adler32(Adler32),
+    Md5(Md5),
+}
+
+impl Checksummer {
+    fn new(kind: ChecksumKind) -> Self {
+        match kind {
+            ChecksumKind::Crc32 => Checksummer::Crc32(Crc32::new()),
+            ChecksumKind::Adler32 => Checksummer::Adler32(Adler32::new()),
+            ChecksumKind::Md5 => Checksummer::Md5(Md5::new()),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Checksummer::Crc32(crc) => crc.update(data),
+            Checksummer::Adler32(adler) => adler.update(data),
+            Checksummer::Md5(md5) => md5.update(data),
+        }
+    }
+
+    fn finish(&self, big_endian: bool) -> Vec<u8> {
+        let value = match self {
+            Checksummer::Crc32(crc) => crc.finish(),
+            Checksummer::Adler32(adler) => adler.finish(),
+            Checksummer::Md5(md5) => return md5.finish().to_vec(),
+        };
+        encode_u32(value, big_endian)
+    }
+}
+
+fn encode_u32(value: u32, big_endian: bool) -> Vec<u8> {
+    if big_endian {
+        value.to_be_bytes().to_vec()
+    } else {
+        value.to_le_bytes().to_vec()
+    }
+}
+
+/// A checksum stored in the input
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ChecksumLocation {
+    /// The checksum algorithm
+    pub kind: ChecksumKind,
+    /// Where the checksum is stored in the input
+    pub offset: usize,
+    /// The checksummed region of the input
+    pub region: Range<usize>,
+    /// If integer checksums are stored in big endian byte order
+    pub big_endian: bool,
+}
+
+/// The checksums found in a testcase by the [`ChecksumDetectionStage`]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    expect(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct ChecksumMetadata {
+    /// The length of the input the checksums were found in
+    pub input_len: usize,
+    /// The checksums, inner (shorter) regions first
+    pub checksums: Vec<ChecksumLocation>,
+}
+
+libafl_bolts::impl_serdeany!(ChecksumMetadata);
+
+impl ChecksumMetadata {
+    /// Recomputes all checksums in `bytes`, a mutated version of the input they were found in.
+    ///
+    /// If the length changed, positions at the end of the original input move with the new end,
+    /// and a region that ended directly before its checksum follows the checksum. Checksums that
+    /// do not fit into the mutated input anymore are skipped.
+    pub fn fix(&self, bytes: &mut [u8]) {
+        let new_len = bytes.len();
+        for checksum in &self.checksums {
+            let width = checksum.kind.width();
+            let (offset, region) = if new_len == self.input_len {
+                (checksum.offset, checksum.region.clone())
+            } else {
+                let offset = if checksum.offset + width == self.input_len {
+                    new_len.wrapping_sub(width)
+                } else {
+                    checksum.offset
+                };
+                let end = if checksum.region.end == self.input_len {
+                    new_len
+                } else if checksum.region.end == checksum.offset {
+                    offset
+                } else {
+                    checksum.region.end
+                };
+                (offset, checksum.region.start..end)
+            };
+            if offset.checked_add(width).is_none_or(|end| end > new_len)
+                || region.start >= region.end
+                || region.end > new_len
+                || (region.start < offset + width && offset < region.end)
+            {
+                continue;
+            }
+            let value = checksum.kind.compute(&bytes[region], checksum.big_endian);
+            bytes[offset..offset + width].copy_from_slice(&value);
+        }
+    }
+}
+
+/// Returns the positions where `needle` occurs in `haystack`
+fn occurrences(haystack: &[u8], needle: &[u8]) -> Vec<usize> {
+    if needle.is_empty() || needle.len() > haystack.len() {
+        return vec![];
+    }
+    haystack
+        .windows(needle.len())
+        .enumerate()
+        .filter(|(_, window)| *window == needle)
+        .map(|(pos, _)| pos)
+        .take(MAX_OCCURRENCES)
+        .collect()
+}
+
+/// Searches a region of `input`, not overlapping the checksum at `offset`, whose checksum is `computed`
+fn find_region(
+    input: &[u8],
+    offset: usize,
+    kind: ChecksumKind,
+    big_endian: bool,
+    computed: &[u8],
+    max_full_search_len: usize,
+) -> Option<Range<usize>> {
+    let width = kind.width();
+    let after = offset + width;
+    let full_search = input.len() <= max_full_search_len;
+
+    // Regions starting at the input start or right after the checksum, with any end
+    for (start, limit) in [(0, offset), (after, input.len())] {
+        let mut checksummer = Checksummer::new(kind);
+        for end in start..limit {
+            checksummer.update(&input[end..=end]);
+            if checksummer.finish(big_endian) == computed {
+                return Some(start..end + 1);
+            }
+        }
+    }
+
+    // Regions ending right before the checksum or at the input end, with any start
+    for (lower, end) in [(0, offset), (after, input.len())] {
+        if let Some(start) =
+            find_suffix_start(&input[lower..end], kind, big_endian, computed, full_search)
+        {
+            return Some(lower + start..end);
+        }
+    }
+
+    None
+}
+
+/// Searches a suffix of `data` whose checksum is `computed`, and returns its start.
+///
+/// `CRC-32` and `Adler-32` of all suffixes are derived from each other, `MD5` suffixes are
+/// only searched if `full_search` is set, since each of them has to be hashed on its own.
+fn find_suffix_start(
+    data: &[u8],
+    kind: ChecksumKind,
+    big_endian: bool,
+    computed: &[u8],
+    full_search: bool,
+) -> Option<usize> {
+    let len = data.len();
+    match kind {
+        ChecksumKind::Crc32 => {
+            // crc(data[start..]) = crc(data) ^ crc(data[..start]) shifted over the suffix
+            let mut prefixes = Vec::with_capacity(len + 1);
+            let mut crc = Crc32::new();
+            prefixes.push(crc.finish());
+            for byte in data {
+                crc.update(core::slice::from_ref(byte));
+                prefixes.push(crc.finish());
+            }
+            let full = prefixes[len];
+            (0..len).rev().find(|&start| {
+                let suffix = full ^ Crc32::combine(prefixes[start], 0, len - start);
+                encode_u32(suffix, big_endian) == computed
+            })
+        }
+        ChecksumKind::Adler32 => {
+            let mut sum = 0_u64;
+            let mut weighted_sum = 0_u64;
+            (0..len).rev().find(|&start| {
+                sum += u64::from(data[start]);
+                weighted_sum += (len - start) as u64 * u64::from(data[start]);
+                encode_u32(
+                    Adler32::from_sums(len - start, sum, weighted_sum),
+                    big_endian,
+                ) == computed
+            })
+        }
+        ChecksumKind::Md5 => {
+            if !full_search {
+                return None;
+            }
+            (0..len)
+                .rev()
+                .find(|&start| md5(&data[start..]).as_slice() == computed)
+        }
+    }
+}
+
+/// Finds checksums over `input` that are compared to a value stored in `input`, using the logged comparisons
+#[must_use]
+pub fn detect_checksums(
+    input: &[u8],
+    cmps: &[CmpValues],
+    max_full_search_len: usize,
+) -> Vec<ChecksumLocation> {
+    let mut found: Vec<ChecksumLocation> = vec![];
+    for cmp in cmps {
+        // (stored, computed, candidate kinds with byte order)
+        let mut candidates: Vec<(Vec<u8>, Vec<u8>, ChecksumKind, bool)> = vec![];
+        let mut add_u32 = |stored: u32, computed: u32| {
+            for kind in [ChecksumKind::Crc32, ChecksumKind::Adler32] {
+                candidates.push((
+                    stored.to_le_bytes().to_vec(),
+                    computed.to_le_bytes().to_vec(),
+                    kind,
+                    false,
+                ));
+                candidates.push((
+                    stored.to_be_bytes().to_vec(),
+                    computed.to_be_bytes().to_vec(),
+                    kind,
+                    true,
+                ));
+            }
+        };
+        match cmp {
+            CmpValues::U32((v0, v1, _)) => {
+                add_u32(*v0, *v1);
+                if v0 != v1 {
+                    add_u32(*v1, *v0);
+                }
+            }
+            CmpValues::Bytes((v0, v1)) => {
+                let (v0, v1) = (v0.as_slice(), v1.as_slice());
+                if v0.len() != v1.len() {
+                    continue;
+                }
+                for (stored, computed) in [(v0, v1), (v1, v0)] {
+                    let kinds: &[(ChecksumKind, bool)] = match stored.len() {
+                        4 => &[
+                            (ChecksumKind::Crc32, false),
+                            (ChecksumKind::Crc32, true),
+                            (ChecksumKind::Adler32, false),
+                            (ChecksumKind::Adler32, true),
+                        ],
+                        16 => &[(ChecksumKind::Md5, false)],
+                        _ => &[],
+                    };
+                    for (kind, big_endian) in kinds {
+                        candidates.push((stored.to_vec(), computed.to_vec(), *kind, *big_endian));
+                    }
+                }
+            }
+            _ => continue,
+        }
+
+        for (stored, computed, kind, big_endian) in candidates {
+            for offset in occurrences(input, &stored) {
+                if found.iter().any(|c| c.offset == offset && c.kind == kind) {
+                    continue;
+                }
+                if let Some(region) = find_region(
+                    input,
+                    offset,
+                    kind,
+                    big_endian,
+                    &computed,
+                    max_full_search_len,
+                ) {
+                    found.push(ChecksumLocation {
+                        kind,
+                        offset,
+                        region,
+                        big_endian,
+                    });
+                }
+            }
+        }
+    }
+    found.sort_by_key(|checksum| checksum.region.len());
+    found
+}
+
+/// A stage that traces each new testcase with `CmpLog` once and records the checksums it validates
+/// in the [`ChecksumMetadata`] of the testcase.
+///
+/// The tracer executor needs a `CmpLog` observer that adds [`CmpValuesMetadata`] to the state.
+#[derive(Debug, Clone)]
+pub struct ChecksumDetectionStage<EM, I, TE, S, Z> {
+    name: Cow<'static, str>,
+    tracing: TracingStage<EM, I, TE, S, Z>,
+    max_full_search_len: usize,
+}
+
+impl<EM, I, TE, S, Z> ChecksumDetectionStage<EM, I, TE, S, Z> {
+    /// Creates a new [`ChecksumDetectionStage`] tracing with the given `CmpLog` executor
+    pub fn new(tracer_executor: TE) -> Self {
+        Self {
+            name: Cow::Borrowed(CHECKSUM_DETECTION_STAGE_NAME),
+            tracing: TracingStage::new(tracer_executor),
+            max_full_search_len: DEFAULT_MAX_FULL_SEARCH_LEN,
+        }
+    }
+
+    /// Sets the input length up to which `MD5` digests over regions with any start are searched
+    #[must_use]
+    pub fn with_max_full_search_len(mut self, max_full_search_len: usize) -> Self {
+        self.max_full_search_len = max_full_search_len;
+        self
+    }
+}
+
+impl<EM, I, TE, S, Z> Named for ChecksumDetectionStage<EM, I, TE, S, Z> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<E, EM, I, TE, S, Z> Stage<E, EM, S, Z> for ChecksumDetectionStage<EM, I, TE, S, Z>
+where
+    I: HasMutatorBytes + Clone,
+    TE: Executor<EM, I, S, Z> + HasObservers,
+    TE::Observers: ObserversTuple<I, S>,
+    S: HasExecutions
+        + HasCorpus<I>
+        + HasMetadata
+        + HasNamedMetadata
+        + HasCurrentTestcase<I>
+        + MaybeHasClientPerfMonitor,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        if state.current_testcase()?.has_metadata::<ChecksumMetadata>() {
+            return Ok(());
+        }
+
+        if let Ok(meta) = state.metadata_mut::<CmpValuesMetadata>() {
+            meta.list.clear();
+        }
+        self.tracing.trace(fuzzer, state, manager)?;
+
+        let input = state.current_input_cloned()?;
+        let bytes = input.mutator_bytes();
+        let checksums = state
+            .metadata::<CmpValuesMetadata>()
+            .map(|meta| detect_checksums(bytes, &meta.list, self.max_full_search_len))
+            .unwrap_or_default();
+
+        state
+            .current_testcase_mut()?
+            .add_metadata(ChecksumMetadata {
+                input_len: bytes.len(),
+                checksums,
+            });
+        Ok(())
+    }
+}
+
+impl<EM, I, TE, S, Z> Restartable<S> for ChecksumDetectionStage<EM, I, TE, S, Z>
+where
+    S: HasNamedMetadata + HasCurrentCorpusId,
+{
+    fn should_restart(&mut self, state: &mut S) -> Result<bool, Error> {
+        // Tracing crashes are not retried, like in the tracing stage
+        RetryCountRestartHelper::no_retry(state, &self.name)
+    }
+
+    fn clear_progress(&mut self, state: &mut S) -> Result<(), Error> {
+        RetryCountRestartHelper::clear_progress(state, &self.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::{
+        ChecksumKind, ChecksumMetadata, DEFAULT_MAX_FULL_SEARCH_LEN, detect_checksums, find_region,
+    };
+    use crate::{inputs::schema::crc32, observers::CmpValues};
+
+    #[test]
+    fn md5_digests() {
+        assert_eq!(
+            ChecksumKind::Md5.compute(b"", false),
+            b"\xd4\x1d\x8c\xd9\x8f\x00\xb2\x04\xe9\x80\x09\x98\xec\xf8\x42\x7e"
+        );
+        assert_eq!(
+            ChecksumKind::Md5.compute(
+                b"12345678901234567890123456789012345678901234567890123456789012345678901234567890",
+                false
+            ),
+            b"\x57\xed\xf4\xa2\x2b\xe3\xc9\x55\xac\x49\xda\x2e\x21\x07\xb6\x7a"
+        );
+        assert_eq!(
+            ChecksumKind::Adler32.compute(b"Wikipedia", true),
+            0x11E6_0398_u32.to_be_bytes()
+        );
+    }
+
+    #[test]
+    fn detect_and_fix_crc() {
+        // A header, a payload and a big endian CRC-32 over the payload, with a broken checksum
+        let mut input = b"HDR\x00payload data".to_vec();
+        let checksum_offset = input.len();
+        input.extend_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+        let computed = crc32(b"payload data");
+        let cmps = [CmpValues::U32((computed, 0xdead_beef, false))];
+
+        let checksums = detect_checksums(&input, &cmps, DEFAULT_MAX_FULL_SEARCH_LEN);
+        assert_eq!(checksums.len(), 1);
+        assert_eq!(checksums[0].kind, ChecksumKind::Crc32);
+        assert_eq!(checksums[0].offset, checksum_offset);
+        assert_eq!(checksums[0].region, 4..checksum_offset);
+        assert!(checksums[0].big_endian);
+
+        let meta = ChecksumMetadata {
+            input_len: input.len(),
+            checksums,
+        };
+        let mut mutated = b"HDR\x00PAYLOAD DATA, LONGER".to_vec();
+        mutated.extend_from_slice(&[0; 4]);
+        meta.fix(&mut mutated);
+        assert_eq!(
+            mutated[mutated.len() - 4..],
+            crc32(b"PAYLOAD DATA, LONGER").to_be_bytes()
+        );
+    }
+
+    #[test]
+    fn find_suffix_regions() {
+        // A checksum after a long input, over everything but a prefix of the input
+        let mut input: Vec<u8> = (0..=255_u8).cycle().take(3000).collect();
+        let offset = input.len();
+        input.extend_from_slice(&[0; 16]);
+        for kind in [
+            ChecksumKind::Crc32,
+            ChecksumKind::Adler32,
+            ChecksumKind::Md5,
+        ] {
+            for big_endian in [false, true] {
+                let computed = kind.compute(&input[1234..offset], big_endian);
+                assert_eq!(
+                    find_region(&input, offset, kind, big_endian, &computed, input.len()),
+                    Some(1234..offset)
+                );
+            }
+        }
+        // Without the full search, only `MD5` digests over suffixes are skipped
+        let computed = ChecksumKind::Md5.compute(&input[1234..offset], false);
+        assert_eq!(
+            find_region(&input, offset, ChecksumKind::Md5, false, &computed, 0),
+            None
+        );
+        let computed = ChecksumKind::Crc32.compute(&input[1234..offset], false);
+        assert_eq!(
+            find_region(&input, offset, ChecksumKind::Crc32, false, &computed, 0),
+            Some(1234..offset)
+        );
+    }
+}
@@ -0,0 +1,634 @@
+//! The [`DeterministicStage`] runs the deterministic mutations of AFL once per testcase:
+//! walking bit and byte flips, arithmetics, interesting values and dictionary overwrites and inserts.
+//!
+//! The progress is stored in the [`DeterministicProgressMetadata`] of the testcase before every execution,
+//! so that after a crash the restarted fuzzer continues right after the crashing input.
+
+use alloc::{borrow::Cow, vec::Vec};
+use core::marker::PhantomData;
+
+use libafl_bolts::{Named, impl_serdeany};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Error, Evaluator, HasMetadata, HasNamedMetadata,
+    corpus::HasCurrentCorpusId,
+    inputs::{HasMutatorBytes, ResizableMutator},
+    mutators::{ARITH_MAX, INTERESTING_8, INTERESTING_16, INTERESTING_32, Tokens},
+    schedulers::minimizer::IsFavoredMetadata,
+    stages::{EffectorMapMetadata, Restartable, RetryCountRestartHelper, Stage},
+    state::{HasCurrentTestcase, HasMaxSize},
+};
+
+/// The name for the deterministic stage
+pub static DETERMINISTIC_STAGE_NAME: &str = "deterministic";
+
+/// The default amount of restarts during the deterministic mutations of a single testcase
+pub const DEFAULT_DETERMINISTIC_MAX_RETRIES: usize = 10;
+
+/// A deterministic mutation of AFL, applied at every position of the input in turn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeterministicMutation {
+    /// Flips one bit
+    BitFlip1,
+    /// Flips two consecutive bits
+    BitFlip2,
+    /// Flips four consecutive bits
+    BitFlip4,
+    /// Flips one byte
+    ByteFlip1,
+    /// Flips two consecutive bytes
+    ByteFlip2,
+    /// Flips four consecutive bytes
+    ByteFlip4,
+    /// Adds and subtracts values up to [`ARITH_MAX`] to a byte
+    Arith8,
+    /// Adds and subtracts values up to [`ARITH_MAX`] to a word, in both byte orders
+    Arith16,
+    /// Adds and subtracts values up to [`ARITH_MAX`] to a dword, in both byte orders
+    Arith32,
+    /// Overwrites a byte with the values of [`INTERESTING_8`]
+    Interesting8,
+    /// Overwrites a word with the values of [`INTERESTING_16`], in both byte orders
+    Interesting16,
+    /// Overwrites a dword with the values of [`INTERESTING_32`], in both byte orders
+    Interesting32,
+    /// Overwrites bytes with the [`Tokens`] of the state
+    TokenOverwrite,
+    /// Inserts the [`Tokens`] of the state
+    TokenInsert,
+}
+
+impl DeterministicMutation {
+    /// All deterministic mutations, in the order of AFL
+    pub const ALL: [Self; 14] = [
+        Self::BitFlip1,
+        Self::BitFlip2,
+        Self::BitFlip4,
+        Self::ByteFlip1,
+        Self::ByteFlip2,
+        Self::ByteFlip4,
+        Self::Arith8,
+        Self::Arith16,
+        Self::Arith32,
+        Self::Interesting8,
+        Self::Interesting16,
+        Self::Interesting32,
+        Self::TokenOverwrite,
+        Self::TokenInsert,
+    ];
+
+    /// The amount of iterations of this mutation for an input of `len` bytes and `tokens` tokens
+    #[must_use]
+    pub fn iterations(self, len: usize, tokens: usize) -> usize {
+        match self {
+            Self::BitFlip1 => len * 8,
+            Self::BitFlip2 => (len * 8).saturating_sub(1),
+            Self::BitFlip4 => (len * 8).saturating_sub(3),
+            Self::ByteFlip1 => len,
+            Self::ByteFlip2 => len.saturating_sub(1),
+            Self::ByteFlip4 => len.saturating_sub(3),
+            Self::Arith8 => len * 2 * ARITH_MAX,
+            Self::Arith16 => len.saturating_sub(1) * 4 * ARITH_MAX,
+            Self::Arith32 => len.saturating_sub(3) * 4 * ARITH_MAX,
+            Self::Interesting8 => len * INTERESTING_8.len(),
+            Self::Interesting16 => len.saturating_sub(1) * 2 * INTERESTING_16.len(),
+            Self::Interesting32 => len.saturating_sub(3) * 2 * INTERESTING_32.len(),
+            Self::TokenOverwrite => len * tokens,
+            Self::TokenInsert => (len + 1) * tokens,
+        }
+    }
+
+    /// Applies the given iteration of this mutation to the input.
+    ///
+    /// Returns `false` if the iteration is skipped, because it does not change the input, would exceed
+    /// `max_size`, or only touches bytes the `mask` marks as not mutable. Like AFL, the bit flips and
+    /// single byte flips ignore the mask.
+    pub fn apply<I>(
+        self,
+        iteration: usize,
+        input: &mut I,
+        tokens: &[Vec<u8>],
+        mask: Option<&[bool]>,
+        max_size: usize,
+    ) -> bool
+    where
+        I: HasMutatorBytes + ResizableMutator<u8>,
+    {
+        let masked = |pos: usize, width: usize| {
+            mask.is_some_and(|mask| mask[pos..pos + width].iter().all(|mutable| !mutable))
+        };
+        let bytes = input.mutator_bytes_mut();
+        match self {
+            Self::BitFlip1 | Self::BitFlip2 | Self::BitFlip4 => {
+                let bits = match self {
+                    Self::BitFlip1 => 1,
+                    Self::BitFlip2 => 2,
+                    _ => 4,
+                };
+                for bit in iteration..iteration + bits {
+                    bytes[bit >> 3] ^= 0x80 >> (bit & 7);
+                }
+                true
+            }
+            Self::ByteFlip1 | Self::ByteFlip2 | Self::ByteFlip4 => {
+                let width = match self {
+                    Self::ByteFlip1 => 1,
+                    Self::ByteFlip2 => 2,
+                    _ => 4,
+                };
+                if width > 1 && masked(iteration, width) {
+                    return false;
+                }
+                for byte in &mut bytes[iteration..iteration + width] {
+                    *byte ^= 0xff;
+                }
+                true
+            }
+            Self::Arith8 => {
+                let pos = iteration / (2 * ARITH_MAX);
+                let step = iteration % (2 * ARITH_MAX);
+                #[expect(clippy::cast_possible_truncation)]
+                let delta = (step / 2 + 1) as u8;
+                if masked(pos, 1) {
+                    return false;
+                }
+                bytes[pos] = if step & 1 == 0 {
+                    bytes[pos].wrapping_add(delta)
+                } else {
+                    bytes[pos].wrapping_sub(delta)
+                };
+                true
+            }
+            Self::Arith16 => {
+                let pos = iteration / (4 * ARITH_MAX);
+                let step = iteration % (4 * ARITH_MAX);
+                #[expect(clippy::cast_possible_truncation)]
+                let delta = (step / 4 + 1) as u16;
+                if masked(pos, 2) {
+                    return false;
+                }
+                let target: &mut [u8; 2] = (&mut bytes[pos..pos + 2]).try_into().unwrap();
+                let value = if step & 2 == 0 {
+                    u16::from_le_bytes(*target)
+                } else {
+                    u16::from_be_bytes(*target)
+                };
+                let value = if step & 1 == 0 {
+                    value.wrapping_add(delta)
+                } else {
+                    value.wrapping_sub(delta)
+                };
+                *target = if step & 2 == 0 {
+                    value.to_le_bytes()
+                } else {
+                    value.to_be_bytes()
+                };
+                true
+            }
+            Self::Arith32 => {
+                let pos = iteration / (4 * ARITH_MAX);
+                let step = iteration % (4 * ARITH_MAX);
+                #[expect(clippy::cast_possible_truncation)]
+                let delta = (step / 4 + 1) as u32;
+                if masked(pos, 4) {
+                    return false;
+                }
+                let target: &mut [u8; 4] = (&mut bytes[pos..pos + 4]).try_into().unwrap();
+                let value = if step & 2 == 0 {
+                    u32::from_le_bytes(*target)
+                } else {
+                    u32::from_be_bytes(*target)
+                };
+                let value = if step & 1 == 0 {
+                    value.wrapping_add(delta)
+                } else {
+                    value.wrapping_sub(delta)
+                };
+                *target = if step & 2 == 0 {
+                    value.to_le_bytes()
+                } else {
+                    value.to_be_bytes()
+                };
+                true
+            }
+            Self::Interesting8 => {
+                let pos = iteration / INTERESTING_8.len();
+                #[expect(clippy::cast_sign_loss)]
+                let value = INTERESTING_8[iteration % INTERESTING_8.len()] as u8;
+                if masked(pos, 1) || bytes[pos] == value {
+                    return false;
+                }
+                bytes[pos] = value;
+                true
+            }
+            Self::Interesting16 => {
+                let pos = iteration / (2 * INTERESTING_16.len());
+                let step = iteration % (2 * INTERESTING_16.len());
+                #[expect(clippy::cast_sign_loss)]
+                let value = INTERESTING_16[step / 2] as u16;
+                let value = if step & 1 == 0 {
+                    value.to_le_bytes()
+                } else {
+                    value.to_be_bytes()
+                };
+                if masked(pos, 2) || bytes[pos..pos + 2] == value {
+                    return false;
+                }
+                bytes[pos..pos + 2].copy_from_slice(&value);
+                true
+            }
+            Self::Interesting32 => {
+                let pos = iteration / (2 * INTERESTING_32.len());
+                let step = iteration % (2 * INTERESTING_32.len());
+                #[expect(clippy::cast_sign_loss)]
+                let value = INTERESTING_32[step / 2] as u32;
+                let value = if step & 1 == 0 {
+                    value.to_le_bytes()
+                } else {
+                    value.to_be_bytes()
+                };
+                if masked(pos, 4) || bytes[pos..pos + 4] == value {
+                    return false;
+                }
+                bytes[pos..pos + 4].copy_from_slice(&value);
+                true
+            }
+            Self::TokenOverwrite => {
+                let pos = iteration / tokens.len();
+                let token = &tokens[iteration % tokens.len()];
+                if token.is_empty()
+                    || pos + token.len() > bytes.len()
+                    || masked(pos, token.len())
+                    || bytes[pos..pos + token.len()] == **token
+                {
+                    return false;
+                }
+                bytes[pos..pos + token.len()].copy_from_slice(token);
+                true
+            }
+            Self::TokenInsert => {
+                let pos = iteration / tokens.len();
+                let token = &tokens[iteration % tokens.len()];
+                if token.is_empty() || bytes.len() + token.len() > max_size {
+                    return false;
+                }
+                input.splice(pos..pos, token.iter().copied());
+                true
+            }
+        }
+    }
+}
+
+/// How far the [`DeterministicStage`] got for a testcase
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    expect(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct DeterministicProgressMetadata {
+    /// The index of the current mutation in [`DeterministicMutation::ALL`]
+    pub mutation: usize,
+    /// The next iteration of the current mutation
+    pub iteration: usize,
+    /// Whether all deterministic mutations are done for this testcase
+    pub done: bool,
+}
+
+impl_serdeany!(DeterministicProgressMetadata);
+
+/// Runs the deterministic mutations of AFL, see [`DeterministicMutation`], once for every testcase.
+///
+/// Mutated inputs are evaluated like in a mutational stage. The dictionary mutations use the
+/// [`Tokens`] of the state, and the byte level mutations skip bytes masked by the
+/// [`EffectorMapMetadata`] of the testcase computed by an [`crate::stages::EffectorMapStage`].
+///
+/// The stage is restartable: the progress is stored in the [`DeterministicProgressMetadata`] of the
+/// testcase before each execution, so a restart after a crash resumes after the crashing input.
+/// After `max_retries` restarts on the same testcase, it is skipped.
+/// The deterministic mutations are expensive, use [`Self::favored_only`] to only run them for favored
+/// testcases, or an [`crate::stages::IfStage`] for any other condition.
+#[derive(Debug, Clone)]
+pub struct DeterministicStage<E, EM, I, S, Z> {
+    name: Cow<'static, str>,
+    favored_only: bool,
+    max_retries: usize,
+    phantom: PhantomData<(E, EM, I, S, Z)>,
+}
+
+impl<E, EM, I, S, Z> DeterministicStage<E, EM, I, S, Z> {
+    /// Creates a new [`DeterministicStage`] running for all testcases
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            name: Cow::Borrowed(DETERMINISTIC_STAGE_NAME),
+            favored_only: false,
+            max_retries: DEFAULT_DETERMINISTIC_MAX_RETRIES,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Only runs for testcases marked with the [`IsFavoredMetadata`] by a minimizer scheduler.
+    ///
+    /// Testcases that become favored later run the deterministic mutations at that time.
+    #[must_use]
+    pub fn favored_only(mut self) -> Self {
+        self.favored_only = true;
+        self
+    }
+
+    /// Sets how often the stage may restart on the same testcase before it is skipped
+    #[must_use]
+    pub fn with_max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+}
+
+impl<E, EM, I, S, Z> Default for DeterministicStage<E, EM, I, S, Z> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E, EM, I, S, Z> Named for DeterministicStage<E, EM, I, S, Z> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<E, EM, I, S, Z> Stage<E, EM, S, Z> for DeterministicStage<E, EM, I, S, Z>
+where
+    I: HasMutatorBytes + ResizableMutator<u8> + Clone,
+    S: HasCurrentTestcase<I> + HasCurrentCorpusId + HasMetadata + HasMaxSize,
+    Z: Evaluator<E, EM, I, S>,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        let (mut progress, mask) = {
+            let testcase = state.current_testcase()?;
+            if self.favored_only && !testcase.has_metadata::<IsFavoredMetadata>() {
+                return Ok(());
+            }
+            let progress = testcase
+                .metadata::<DeterministicProgressMetadata>()
+                .cloned()
+                .unwrap_or_default();
+            if progress.done {
+                return Ok(());
+            }
+            let mask = testcase
+                .metadata::<EffectorMapMetadata>()
+                .map(|meta| meta.effective.clone())
+                .ok();
+            (progress, mask)
+        };
+
+        let original = state.current_input_cloned()?;
+        let len = original.mutator_bytes().len();
+        let mask = mask.filter(|mask| mask.len() == len);
+        let tokens = state
+            .metadata::<Tokens>()
+            .map(|tokens| tokens.tokens().to_vec())
+            .unwrap_or_default();
+        let max_size = state.max_size();
+
+        while let Some(mutation) = DeterministicMutation::ALL.get(progress.mutation) {
+            let iterations = mutation.iterations(len, tokens.len());
+            while progress.iteration < iterations {
+                let iteration = progress.iteration;
+                progress.iteration += 1;
+
+                let mut input = original.clone();
+                if !mutation.apply(iteration, &mut input, &tokens, mask.as_deref(), max_size) {
+                    continue;
+                }
+                // Store the progress first, so a crash on this input does not repeat it after the restart
+                *state
+                    .current_testcase_mut()?
+                    .metadata_or_insert_with(DeterministicProgressMetadata::default) =
+                    progress.clone();
+                fuzzer.evaluate_filtered(state, executor, manager, &input)?;
+            }
+            progress.mutation += 1;
+            progress.iteration = 0;
+        }
+
+        progress.done = true;
+        state.current_testcase_mut()?.add_metadata(progress);
+        Ok(())
+    }
+}
+
+impl<E, EM, I, S, Z> Restartable<S> for DeterministicStage<E, EM, I, S, Z>
+where
+    S: HasNamedMetadata + HasCurrentCorpusId,
+{
+    fn should_restart(&mut self, state: &mut S) -> Result<bool, Error> {
+        // The progress is stored in the testcase, so restarting continues after the input that crashed
+        RetryCountRestartHelper::should_restart(state, &self.name, self.max_retries)
+    }
+
+    fn clear_progress(&mut self, state: &mut S) -> Result<(), Error> {
+        RetryCountRestartHelper::clear_progress::<S>(state, &self.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{vec, vec::Vec};
+
+    use libafl_bolts::{Error, rands::StdRand, tuples::RefIndexable};
+
+    use super::{DeterministicMutation, DeterministicProgressMetadata, DeterministicStage};
+    use crate::{
+        HasMetadata, StdFuzzer,
+        corpus::{Corpus, CorpusId, HasCurrentCorpusId, InMemoryCorpus, Testcase},
+        events::NopEventManager,
+        executors::{Executor, ExitKind, HasObservers},
+        inputs::{BytesInput, HasMutatorBytes},
+        mutators::{ARITH_MAX, INTERESTING_8},
+        schedulers::{QueueScheduler, minimizer::IsFavoredMetadata},
+        stages::Stage,
+        state::{HasCorpus, HasMaxSize, StdState},
+    };
+
+    /// Records every executed input
+    #[derive(Default)]
+    struct RecordingExecutor {
+        executed: Vec<BytesInput>,
+        observers: (),
+    }
+
+    impl<EM, S, Z> Executor<EM, BytesInput, S, Z> for RecordingExecutor {
+        fn run_target(
+            &mut self,
+            _fuzzer: &mut Z,
+            _state: &mut S,
+            _mgr: &mut EM,
+            input: &BytesInput,
+        ) -> Result<ExitKind, Error> {
+            self.executed.push(input.clone());
+            Ok(ExitKind::Ok)
+        }
+    }
+
+    impl HasObservers for RecordingExecutor {
+        type Observers = ();
+
+        fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+            RefIndexable::from(&self.observers)
+        }
+
+        fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+            RefIndexable::from(&mut self.observers)
+        }
+    }
+
+    type TestState =
+        StdState<InMemoryCorpus<BytesInput>, BytesInput, StdRand, InMemoryCorpus<BytesInput>>;
+
+    /// A state whose current testcase is `testcase`
+    fn state_with(testcase: Testcase<BytesInput>) -> Result<(TestState, CorpusId), Error> {
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )?;
+        let id = state.corpus_mut().add(testcase)?;
+        state.set_corpus_id(id)?;
+        Ok((state, id))
+    }
+
+    /// The inputs the stage executes for `original` without tokens, starting at `mutation` and `iteration`
+    fn expected_inputs(
+        original: &BytesInput,
+        mutation: usize,
+        iteration: usize,
+    ) -> Vec<BytesInput> {
+        let len = original.mutator_bytes().len();
+        let mut expected = vec![];
+        for (idx, mutation_kind) in DeterministicMutation::ALL.iter().enumerate().skip(mutation) {
+            let start = if idx == mutation { iteration } else { 0 };
+            for iteration in start..mutation_kind.iterations(len, 0) {
+                let mut input = original.clone();
+                if mutation_kind.apply(iteration, &mut input, &[], None, 16) {
+                    expected.push(input);
+                }
+            }
+        }
+        expected
+    }
+
+    #[test]
+    fn resumes_from_progress() -> Result<(), Error> {
+        let original = BytesInput::new(vec![0, 0x10]);
+        let mut testcase = Testcase::new(original.clone());
+        // Resume in the middle of the arithmetics, as after a restart
+        testcase.add_metadata(DeterministicProgressMetadata {
+            mutation: 6,
+            iteration: 5,
+            done: false,
+        });
+        let (mut state, id) = state_with(testcase)?;
+        state.set_max_size(16);
+        let mut executor = RecordingExecutor::default();
+        let mut fuzzer = StdFuzzer::new(QueueScheduler::new(), (), ());
+        let mut mgr = NopEventManager::new();
+
+        let mut stage = DeterministicStage::new();
+        stage.perform(&mut fuzzer, &mut executor, &mut state, &mut mgr)?;
+
+        let expected = expected_inputs(&original, 6, 5);
+        assert!(expected.len() < expected_inputs(&original, 0, 0).len());
+        assert_eq!(executor.executed, expected);
+        assert!(
+            state
+                .corpus()
+                .get(id)?
+                .borrow()
+                .metadata::<DeterministicProgressMetadata>()?
+                .done
+        );
+
+        // Done testcases are not mutated again
+        executor.executed.clear();
+        stage.perform(&mut fuzzer, &mut executor, &mut state, &mut mgr)?;
+        assert!(executor.executed.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn favored_only() -> Result<(), Error> {
+        let (mut state, id) = state_with(Testcase::new(BytesInput::new(vec![0])))?;
+        let mut executor = RecordingExecutor::default();
+        let mut fuzzer = StdFuzzer::new(QueueScheduler::new(), (), ());
+        let mut mgr = NopEventManager::new();
+        let mut stage = DeterministicStage::new().favored_only();
+
+        stage.perform(&mut fuzzer, &mut executor, &mut state, &mut mgr)?;
+        assert!(executor.executed.is_empty());
+        assert!(
+            !state
+                .corpus()
+                .get(id)?
+                .borrow()
+                .has_metadata::<DeterministicProgressMetadata>()
+        );
+
+        // Once favored, the testcase runs the deterministic mutations
+        state
+            .corpus()
+            .get(id)?
+            .borrow_mut()
+            .add_metadata(IsFavoredMetadata {});
+        stage.perform(&mut fuzzer, &mut executor, &mut state, &mut mgr)?;
+        assert!(!executor.executed.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn deterministic_mutations() {
+        let original = BytesInput::new(vec![0, 0x10, 0, 0]);
+        let tokens = vec![b"ab".to_vec()];
+
+        let mut input = original.clone();
+        assert!(DeterministicMutation::BitFlip2.apply(7, &mut input, &tokens, None, 16));
+        assert_eq!(input.mutator_bytes(), &[1, 0x90, 0, 0]);
+
+        let mut input = original.clone();
+        assert!(DeterministicMutation::Arith16.apply(7, &mut input, &tokens, None, 16));
+        assert_eq!(input.mutator_bytes(), &[0, 0x10 - 2, 0, 0]);
+
+        // Interesting values equal to the input are skipped, and so are masked bytes
+        let mut input = original.clone();
+        assert!(!DeterministicMutation::Interesting8.apply(2, &mut input, &tokens, None, 16));
+        let mask = [true, false, false, false];
+        assert!(!DeterministicMutation::Arith8.apply(
+            2 * ARITH_MAX,
+            &mut input,
+            &tokens,
+            Some(&mask),
+            16
+        ));
+        assert!(DeterministicMutation::ByteFlip1.apply(1, &mut input, &tokens, Some(&mask), 16));
+
+        let mut input = original.clone();
+        assert!(DeterministicMutation::TokenInsert.apply(4, &mut input, &tokens, None, 16));
+        assert_eq!(input.mutator_bytes(), b"\0\x10\0\0ab");
+        assert!(!DeterministicMutation::TokenInsert.apply(0, &mut input, &tokens, None, 6));
+
+        assert_eq!(
+            DeterministicMutation::Interesting8.iterations(4, 1),
+            4 * INTERESTING_8.len()
+        );
+        assert_eq!(DeterministicMutation::BitFlip4.iterations(1, 1), 5);
+    }
+}
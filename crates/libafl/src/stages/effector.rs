@@ -0,0 +1,326 @@
+//! The [`EffectorMapStage`] finds the bytes of a testcase that affect the coverage map, like the effector map of AFL.
+
+use alloc::{
+    borrow::{Cow, ToOwned},
+    vec::Vec,
+};
+use core::marker::PhantomData;
+
+use libafl_bolts::{
+    Named, impl_serdeany,
+    tuples::{Handle, Handled, MatchName},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Error, ExecutesInput, HasMetadata, HasNamedMetadata,
+    corpus::HasCurrentCorpusId,
+    executors::HasObservers,
+    inputs::HasMutatorBytes,
+    mutators::MutationMask,
+    observers::MapObserver,
+    stages::{Restartable, RetryCountRestartHelper, Stage, calibrate::UnstableEntriesMetadata},
+    state::HasCurrentTestcase,
+};
+
+/// The name for the effector map stage
+pub static EFFECTOR_MAP_STAGE_NAME: &str = "effector_map";
+
+/// Inputs shorter than this are mutated everywhere, like `EFF_MIN_LEN` in AFL
+const EFFECTOR_MIN_LEN: usize = 128;
+
+/// If more than this percentage of bytes is effective, the whole input is, like `EFF_MAX_PERC` in AFL
+const EFFECTOR_MAX_PERCENT: usize = 90;
+
+/// The bytes of a testcase that change the coverage map when flipped, like the effector map of AFL
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    expect(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct EffectorMapMetadata {
+    /// Whether flipping each byte changes the coverage map
+    pub effective: Vec<bool>,
+}
+
+impl_serdeany!(EffectorMapMetadata);
+
+impl EffectorMapMetadata {
+    /// Creates a new [`EffectorMapMetadata`]
+    #[must_use]
+    pub fn new(effective: Vec<bool>) -> Self {
+        Self { effective }
+    }
+}
+
+impl MutationMask for EffectorMapMetadata {
+    fn mutable(&self) -> &[bool] {
+        &self.effective
+    }
+}
+
+/// Computes which bytes of a new testcase change the coverage map when flipped, and stores them
+/// as the [`EffectorMapMetadata`] of the testcase.
+///
+/// Place it after the [`crate::stages::CalibrationStage`], so that entries found unstable during
+/// calibration are ignored, and wrap the mutator of the following mutational stage in a
+/// [`crate::mutators::MaskedMutator`] for the [`EffectorMapMetadata`] to focus mutations on the effective bytes.
+///
+/// To keep it cheap, the input is split into blocks of `block_size` bytes, and each block is flipped
+/// at once, costing one execution per block. A block is effective if flipping it changes any stable map entry.
+/// Testcases that already have an effector map are skipped.
+#[derive(Debug, Clone)]
+pub struct EffectorMapStage<C, E, EM, I, O, S, Z> {
+    map_observer_handle: Handle<C>,
+    name: Cow<'static, str>,
+    block_size: usize,
+    max_len: usize,
+    phantom: PhantomData<(E, EM, I, O, S, Z)>,
+}
+
+impl<C, E, EM, I, O, S, Z> EffectorMapStage<C, E, EM, I, O, S, Z>
+where
+    C: Named,
+{
+    /// Creates a new [`EffectorMapStage`] for the given coverage map observer
+    #[must_use]
+    pub fn new(map_observer: &C) -> Self {
+        Self {
+            map_observer_handle: map_observer.handle(),
+            name: Cow::Owned(
+                EFFECTOR_MAP_STAGE_NAME.to_owned() + ":" + map_observer.name().as_ref(),
+            ),
+            block_size: 8,
+            max_len: 1 << 16,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Flips blocks of `block_size` bytes at once, larger blocks need fewer executions
+    #[must_use]
+    pub fn with_block_size(mut self, block_size: usize) -> Self {
+        self.block_size = block_size.max(1);
+        self
+    }
+
+    /// Skips testcases longer than `max_len` bytes
+    #[must_use]
+    pub fn with_max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+}
+
+impl<C, E, EM, I, O, S, Z> Named for EffectorMapStage<C, E, EM, I, O, S, Z> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<C, E, EM, I, O, S, Z> Stage<E, EM, S, Z> for EffectorMapStage<C, E, EM, I, O, S, Z>
+where
+    C: AsRef<O>,
+    E: HasObservers,
+    E::Observers: MatchName,
+    I: HasMutatorBytes + Clone,
+    O: MapObserver,
+    S: HasCurrentTestcase<I> + HasCurrentCorpusId + HasMetadata,
+    Z: ExecutesInput<E, EM, I, S>,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        if state
+            .current_testcase()?
+            .has_metadata::<EffectorMapMetadata>()
+        {
+            return Ok(());
+        }
+
+        let input = state.current_input_cloned()?;
+        let len = input.mutator_bytes().len();
+        if len > self.max_len {
+            return Ok(());
+        }
+        if len < EFFECTOR_MIN_LEN {
+            state
+                .current_testcase_mut()?
+                .add_metadata(EffectorMapMetadata::new(vec![true; len]));
+            return Ok(());
+        }
+
+        fuzzer.execute_input(state, executor, manager, &input)?;
+        let baseline = executor.observers()[&self.map_observer_handle]
+            .as_ref()
+            .to_vec();
+        let unstable = state
+            .metadata::<UnstableEntriesMetadata>()
+            .map(|meta| meta.unstable_entries().clone())
+            .unwrap_or_default();
+
+        let mut mutable = vec![false; len];
+        let mut flipped = input.clone();
+        for start in (0..len).step_by(self.block_size) {
+            let end = (start + self.block_size).min(len);
+            for byte in &mut flipped.mutator_bytes_mut()[start..end] {
+                *byte ^= 0xff;
+            }
+            fuzzer.execute_input(state, executor, manager, &flipped)?;
+            let observers = executor.observers();
+            let observer = observers[&self.map_observer_handle].as_ref();
+            let effective = baseline
+                .iter()
+                .enumerate()
+                .any(|(idx, entry)| !unstable.contains(&idx) && observer.get(idx) != *entry);
+            mutable[start..end].fill(effective);
+            flipped.mutator_bytes_mut()[start..end]
+                .copy_from_slice(&input.mutator_bytes()[start..end]);
+        }
+
+        if mutable.iter().filter(|mutable| **mutable).count() * 100 > len * EFFECTOR_MAX_PERCENT {
+            mutable.fill(true);
+        }
+        state
+            .current_testcase_mut()?
+            .add_metadata(EffectorMapMetadata::new(mutable));
+        Ok(())
+    }
+}
+
+impl<C, E, EM, I, O, S, Z> Restartable<S> for EffectorMapStage<C, E, EM, I, O, S, Z>
+where
+    S: HasNamedMetadata + HasCurrentCorpusId,
+{
+    fn should_restart(&mut self, state: &mut S) -> Result<bool, Error> {
+        // This is a deterministic stage
+        // Once it failed, then don't retry,
+        // It will just fail again
+        RetryCountRestartHelper::no_retry(state, &self.name)
+    }
+
+    fn clear_progress(&mut self, state: &mut S) -> Result<(), Error> {
+        RetryCountRestartHelper::clear_progress::<S>(state, &self.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use libafl_bolts::{
+        Error,
+        rands::StdRand,
+        tuples::{RefIndexable, tuple_list, tuple_list_type},
+    };
+
+    use super::{EffectorMapMetadata, EffectorMapStage};
+    use crate::{
+        HasMetadata, StdFuzzer,
+        corpus::{Corpus, CorpusId, HasCurrentCorpusId, InMemoryCorpus, Testcase},
+        events::NopEventManager,
+        executors::{Executor, ExitKind, HasObservers},
+        inputs::{BytesInput, HasMutatorBytes},
+        observers::{MapObserver, StdMapObserver},
+        schedulers::QueueScheduler,
+        stages::Stage,
+        state::{HasCorpus, StdState},
+    };
+
+    /// Sets map index 0 for every input, and index 1 and 2 if the bytes 10 and 200 are set
+    struct EffectorExecutor {
+        observers: tuple_list_type!(StdMapObserver<'static, u8, false>),
+    }
+
+    impl<EM, S, Z> Executor<EM, BytesInput, S, Z> for EffectorExecutor {
+        fn run_target(
+            &mut self,
+            _fuzzer: &mut Z,
+            _state: &mut S,
+            _mgr: &mut EM,
+            input: &BytesInput,
+        ) -> Result<ExitKind, Error> {
+            let bytes = input.mutator_bytes();
+            let map = &mut self.observers.0;
+            map.set(0, 1);
+            if bytes.get(10).is_some_and(|byte| *byte != 0) {
+                map.set(1, 1);
+            }
+            if bytes.get(200).is_some_and(|byte| *byte != 0) {
+                map.set(2, 1);
+            }
+            Ok(ExitKind::Ok)
+        }
+    }
+
+    impl HasObservers for EffectorExecutor {
+        type Observers = tuple_list_type!(StdMapObserver<'static, u8, false>);
+
+        fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+            RefIndexable::from(&self.observers)
+        }
+
+        fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+            RefIndexable::from(&mut self.observers)
+        }
+    }
+
+    fn effector_map<S>(state: &S, id: CorpusId) -> Vec<bool>
+    where
+        S: HasCorpus<BytesInput>,
+    {
+        state
+            .corpus()
+            .get(id)
+            .unwrap()
+            .borrow()
+            .metadata::<EffectorMapMetadata>()
+            .unwrap()
+            .effective
+            .clone()
+    }
+
+    #[test]
+    fn effective_blocks() -> Result<(), Error> {
+        let observer = StdMapObserver::owned("map", vec![0u8; 4]);
+        let mut stage = EffectorMapStage::new(&observer);
+        let mut executor = EffectorExecutor {
+            observers: tuple_list!(observer),
+        };
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )?;
+        let mut fuzzer = StdFuzzer::new(QueueScheduler::new(), (), ());
+        let mut mgr = NopEventManager::new();
+
+        // Short inputs are mutated everywhere
+        let short = state
+            .corpus_mut()
+            .add(Testcase::new(BytesInput::new(vec![0; 16])))?;
+        state.set_corpus_id(short)?;
+        stage.perform(&mut fuzzer, &mut executor, &mut state, &mut mgr)?;
+        assert_eq!(effector_map(&state, short), vec![true; 16]);
+
+        let long = state
+            .corpus_mut()
+            .add(Testcase::new(BytesInput::new(vec![0; 256])))?;
+        state.set_corpus_id(long)?;
+        stage.perform(&mut fuzzer, &mut executor, &mut state, &mut mgr)?;
+        let effective = effector_map(&state, long);
+        for (idx, effective) in effective.iter().enumerate() {
+            assert_eq!(
+                *effective,
+                (8..16).contains(&idx) || (200..208).contains(&idx),
+                "byte {idx}"
+            );
+        }
+        Ok(())
+    }
+}
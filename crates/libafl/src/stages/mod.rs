@@ -15,10 +15,13 @@ use core::{fmt, marker::PhantomData};
 #[cfg(feature = "std")]
 pub use afl_stats::{AflStatsStage, CalibrationTime, FuzzTime, SyncTime};
 pub use calibrate::{CalibrationStage, run_target_with_timing};
+pub use checksum::{ChecksumDetectionStage, ChecksumMetadata};
 pub use colorization::*;
+pub use deterministic::{DeterministicMutation, DeterministicProgressMetadata, DeterministicStage};
 #[cfg(all(feature = "std", unix))]
 #[cfg(feature = "std")]
 pub use dump::*;
+pub use effector::{EffectorMapMetadata, EffectorMapStage};
 pub use generalization::GeneralizationStage;
 use hashbrown::HashSet;
 use libafl_bolts::{
@@ -26,15 +29,24 @@ use libafl_bolts::{
     tuples::{HasConstLen, IntoVec},
 };
 pub use logics::*;
+pub use mutation_stats::MutationStatsStage;
 pub use mutational::{MutationalStage, StdMutationalStage};
+#[cfg(feature = "nautilus")]
+pub use nautilus::NautilusMinimizationStage;
 pub use power::{PowerMutationalStage, StdPowerMutationalStage};
+pub use rare_edge_mask::{RareEdgeMaskMetadata, RareEdgeMaskStage};
 use serde::{Deserialize, Serialize};
 #[cfg(feature = "std")]
 pub use sync::*;
 #[cfg(feature = "std")]
 pub use time_tracker::TimeTrackingStageWrapper;
-pub use tmin::{ObserverEqualityFactory, ObserverEqualityFeedback, StdTMinMutationalStage};
+pub use tmin::{
+    CrashSignatureFactory, CrashSignatureFeedback, ObserverEqualityFactory,
+    ObserverEqualityFeedback, SolutionTMinStage, StdTMinMutationalStage,
+};
 pub use tracing::TracingStage;
+#[cfg(feature = "regex")]
+pub use triage::{CrashTriage, CrashTriageFeedback, CrashTriageStage};
 pub use tuneable::*;
 use tuple_list::NonEmptyTuple;
 #[cfg(feature = "unicode")]
@@ -50,6 +62,7 @@ use crate::{
 };
 
 /// Mutational stage is the normal fuzzing stage.
+pub mod mutation_stats;
 pub mod mutational;
 pub mod push;
 pub mod tmin;
@@ -63,20 +76,28 @@ pub use replay::*;
 #[cfg(feature = "std")]
 pub mod afl_stats;
 pub mod calibrate;
+pub mod checksum;
 pub mod colorization;
+pub mod deterministic;
 #[cfg(feature = "std")]
 pub mod dump;
 pub mod dynamic;
+pub mod effector;
 pub mod generalization;
 pub mod generation;
 pub mod logics;
+#[cfg(feature = "nautilus")]
+pub mod nautilus;
 pub mod nop;
 pub mod power;
+pub mod rare_edge_mask;
 #[cfg(feature = "std")]
 pub mod sync;
 #[cfg(feature = "std")]
 pub mod time_tracker;
 pub mod tracing;
+#[cfg(feature = "regex")]
+pub mod triage;
 pub mod tuneable;
 #[cfg(feature = "unicode")]
 pub mod unicode;
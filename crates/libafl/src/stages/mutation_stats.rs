@@ -0,0 +1,208 @@
+//! The [`MutationStatsStage`] reports the operator statistics of a [`crate::mutators::BanditScheduledMutator`]
+//! to the monitors.
+
+use alloc::borrow::Cow;
+use core::{marker::PhantomData, time::Duration};
+
+use hashbrown::HashMap;
+use libafl_bolts::{Named, current_time};
+
+use crate::{
+    Error, HasNamedMetadata,
+    events::{Event, EventFirer, EventWithStats},
+    monitors::stats::{AggregatorOps, UserStats, UserStatsValue},
+    mutators::BanditMutatorMetadata,
+    stages::{Restartable, Stage},
+    state::HasExecutions,
+};
+
+/// Reports the success ratio of every operator of a [`crate::mutators::BanditScheduledMutator`] as user stats.
+///
+/// Each operator shows up as `<prefix>_<operator>`, with the number of new corpus entries out of its selections.
+#[derive(Debug, Clone)]
+pub struct MutationStatsStage<I> {
+    mutator_name: Cow<'static, str>,
+    prefix: Cow<'static, str>,
+    report_interval: Duration,
+    last_report: Duration,
+    phantom: PhantomData<I>,
+}
+
+impl<I> MutationStatsStage<I> {
+    /// Creates a new [`MutationStatsStage`] for the given bandit mutator, reporting every 15 seconds
+    pub fn new<M>(mutator: &M) -> Self
+    where
+        M: Named,
+    {
+        Self {
+            mutator_name: mutator.name().clone(),
+            prefix: Cow::Borrowed("mutation"),
+            report_interval: Duration::from_secs(15),
+            last_report: Duration::ZERO,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Sets the prefix of the user stats names, to tell several mutators apart
+    #[must_use]
+    pub fn with_prefix<P>(mut self, prefix: P) -> Self
+    where
+        P: Into<Cow<'static, str>>,
+    {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Sets the interval between two reports
+    #[must_use]
+    pub fn with_report_interval(mut self, report_interval: Duration) -> Self {
+        self.report_interval = report_interval;
+        self
+    }
+}
+
+impl<E, EM, I, S, Z> Stage<E, EM, S, Z> for MutationStatsStage<I>
+where
+    EM: EventFirer<I, S>,
+    S: HasNamedMetadata + HasExecutions,
+{
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        let now = current_time();
+        if now.saturating_sub(self.last_report) < self.report_interval {
+            return Ok(());
+        }
+        let Ok(meta) = state.named_metadata::<BanditMutatorMetadata>(&self.mutator_name) else {
+            return Ok(());
+        };
+        let stats: HashMap<_, _> = meta
+            .operators
+            .iter()
+            .map(|op| {
+                (
+                    Cow::Owned(format!("{}_{}", self.prefix, op.name)),
+                    UserStats::new(
+                        UserStatsValue::Ratio(op.successes, op.selections),
+                        AggregatorOps::Avg,
+                    ),
+                )
+            })
+            .collect();
+        if stats.is_empty() {
+            return Ok(());
+        }
+        self.last_report = now;
+        manager.fire(
+            state,
+            EventWithStats::with_current_time(
+                Event::UpdateUserStatsMap {
+                    stats,
+                    phantom: PhantomData,
+                },
+                *state.executions(),
+            ),
+        )
+    }
+}
+
+impl<I, S> Restartable<S> for MutationStatsStage<I> {
+    #[inline]
+    fn should_restart(&mut self, _state: &mut S) -> Result<bool, Error> {
+        // Not executing the target, so restart safety is not needed
+        Ok(true)
+    }
+
+    #[inline]
+    fn clear_progress(&mut self, _state: &mut S) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{borrow::Cow, vec::Vec};
+    use core::time::Duration;
+
+    use hashbrown::HashMap;
+    use libafl_bolts::{Named, tuples::tuple_list};
+
+    use super::MutationStatsStage;
+    use crate::{
+        Error, HasNamedMetadata,
+        events::{Event, EventFirer, EventWithStats},
+        inputs::BytesInput,
+        monitors::stats::{UserStats, UserStatsValue},
+        mutators::{BanditMutatorMetadata, BanditScheduledMutator, BitFlipMutator, ByteIncMutator},
+        stages::Stage,
+        state::NopState,
+    };
+
+    /// Records the fired user stats
+    #[derive(Default)]
+    struct StatsRecorder {
+        reports: Vec<HashMap<Cow<'static, str>, UserStats>>,
+    }
+
+    impl<I, S> EventFirer<I, S> for StatsRecorder {
+        fn fire(&mut self, _state: &mut S, event: EventWithStats<I>) -> Result<(), Error> {
+            if let Event::UpdateUserStatsMap { stats, .. } = event.event() {
+                self.reports.push(stats.clone());
+            }
+            Ok(())
+        }
+
+        fn should_send(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn reports_operator_ratios() {
+        let mutator =
+            BanditScheduledMutator::new(tuple_list!(BitFlipMutator::new(), ByteIncMutator::new()));
+        let mut stage = MutationStatsStage::<BytesInput>::new(&mutator)
+            .with_prefix("havoc")
+            .with_report_interval(Duration::from_secs(3600));
+        let mut state = NopState::<BytesInput>::new();
+        let mut mgr = StatsRecorder::default();
+
+        // Nothing to report before the mutator ran
+        stage
+            .perform(&mut (), &mut (), &mut state, &mut mgr)
+            .unwrap();
+        assert!(mgr.reports.is_empty());
+
+        let mut meta = BanditMutatorMetadata::new(&[
+            Cow::Borrowed("BitFlipMutator"),
+            Cow::Borrowed("ByteIncMutator"),
+        ]);
+        meta.operators[1].selections = 10;
+        meta.operators[1].successes = 3;
+        state.add_named_metadata(mutator.name(), meta);
+        stage
+            .perform(&mut (), &mut (), &mut state, &mut mgr)
+            .unwrap();
+        assert_eq!(mgr.reports.len(), 1);
+        let report = &mgr.reports[0];
+        assert_eq!(report.len(), 2);
+        assert!(matches!(
+            report["havoc_ByteIncMutator"].value(),
+            UserStatsValue::Ratio(3, 10)
+        ));
+        assert!(matches!(
+            report["havoc_BitFlipMutator"].value(),
+            UserStatsValue::Ratio(0, 0)
+        ));
+
+        // Not reported again within the interval
+        stage
+            .perform(&mut (), &mut (), &mut state, &mut mgr)
+            .unwrap();
+        assert_eq!(mgr.reports.len(), 1);
+    }
+}
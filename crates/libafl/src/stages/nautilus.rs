@@ -0,0 +1,382 @@
+//! Grammar-aware minimization of [`NautilusInput`] trees, like the `minimize` queue state of the original `Nautilus`.
+//! See <https://www.ndss-symposium.org/ndss-paper/nautilus-fishing-for-deep-bugs-with-grammars/>
+
+use alloc::{borrow::Cow, vec::Vec};
+use core::{fmt::Debug, marker::PhantomData};
+
+use hashbrown::HashSet;
+use libafl_bolts::{
+    Named,
+    rands::{Rand, RomuDuoJrRand},
+    tuples::{Handle, Handled},
+};
+
+#[cfg(feature = "introspection")]
+use crate::monitors::stats::PerfFeature;
+use crate::{
+    Error, ExecutionProcessor, HasMetadata, HasNamedMetadata,
+    common::nautilus::grammartec::{
+        context::Context,
+        mutator::Mutator as BackingMutator,
+        tree::{TreeLike, TreeMutation},
+    },
+    corpus::{Corpus, HasCurrentCorpusId},
+    executors::{Executor, ExitKind, HasObservers},
+    feedbacks::map::MapNoveltiesMetadata,
+    generators::nautilus::NautilusContext,
+    inputs::nautilus::NautilusInput,
+    mark_feature_time,
+    observers::{MapObserver, ObserversTuple},
+    stages::{Restartable, RetryCountRestartHelper, Stage},
+    start_timer,
+    state::{HasCorpus, HasExecutions, HasRand, MaybeHasClientPerfMonitor},
+};
+
+/// The name for the nautilus minimization stage
+pub static NAUTILUS_MINIMIZATION_STAGE_NAME: &str = "nautilus_minimization";
+
+/// A stage that shrinks the tree of each new [`NautilusInput`] corpus entry.
+///
+/// First, every subtree is replaced by the smallest subtree of the same nonterminal (subtree minimization),
+/// then recursive nonterminals are collapsed into their innermost occurrence (recursive minimization).
+/// A replacement is only kept if the coverage bits of the entry are still hit.
+/// The bits are taken from the [`MapNoveltiesMetadata`] of the testcase, if present,
+/// otherwise all indices set by the original input are kept.
+pub struct NautilusMinimizationStage<'a, C, EM, O, S, Z> {
+    name: Cow<'static, str>,
+    ctx: &'a Context,
+    mutator: BackingMutator,
+    map_observer_handle: Handle<C>,
+    phantom: PhantomData<(EM, O, S, Z)>,
+}
+
+impl<C, EM, O, S, Z> Debug for NautilusMinimizationStage<'_, C, EM, O, S, Z> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "NautilusMinimizationStage {{}}")
+    }
+}
+
+impl<C, EM, O, S, Z> Named for NautilusMinimizationStage<'_, C, EM, O, S, Z> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<C, EM, O, S, Z> Restartable<S> for NautilusMinimizationStage<'_, C, EM, O, S, Z>
+where
+    S: HasMetadata + HasNamedMetadata + HasCurrentCorpusId,
+{
+    #[inline]
+    fn should_restart(&mut self, state: &mut S) -> Result<bool, Error> {
+        // Inputs that keep crashing the minimization are skipped
+        RetryCountRestartHelper::should_restart::<S>(state, &self.name, 3)
+    }
+
+    #[inline]
+    fn clear_progress(&mut self, state: &mut S) -> Result<(), Error> {
+        RetryCountRestartHelper::clear_progress::<S>(state, &self.name)
+    }
+}
+
+impl<C, E, EM, O, S, Z> Stage<E, EM, S, Z> for NautilusMinimizationStage<'_, C, EM, O, S, Z>
+where
+    C: AsRef<O> + Named,
+    E: Executor<EM, NautilusInput, S, Z> + HasObservers,
+    E::Observers: ObserversTuple<NautilusInput, S>,
+    O: MapObserver,
+    S: HasExecutions
+        + HasMetadata
+        + HasRand
+        + HasCorpus<NautilusInput>
+        + HasNamedMetadata
+        + HasCurrentCorpusId
+        + MaybeHasClientPerfMonitor,
+    Z: ExecutionProcessor<EM, NautilusInput, E::Observers, S>,
+{
+    #[inline]
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        let Some(corpus_id) = state.current_corpus_id()? else {
+            return Err(Error::illegal_state(
+                "state is not currently processing a corpus index",
+            ));
+        };
+
+        let (original, novelties) = {
+            start_timer!(state);
+            {
+                let corpus = state.corpus();
+                let mut testcase = corpus.get(corpus_id)?.borrow_mut();
+                if testcase.scheduled_count() > 0 {
+                    return Ok(());
+                }
+
+                corpus.load_input_into(&mut testcase)?;
+            }
+            mark_feature_time!(state, PerfFeature::GetInputFromCorpus);
+            let entry = state.corpus().get(corpus_id)?.borrow();
+            let original = entry.input().as_ref().unwrap().clone();
+            let novelties = entry
+                .metadata_map()
+                .get::<MapNoveltiesMetadata>()
+                .map(|meta| meta.list.clone());
+            (original, novelties)
+        };
+
+        // Establish the bits to keep, and do not minimize unstable or crashing inputs
+        let Some(bits) = self.verify_input(
+            fuzzer,
+            executor,
+            state,
+            manager,
+            novelties.as_deref(),
+            &original,
+        )?
+        else {
+            return Ok(());
+        };
+        if bits.is_empty() {
+            return Ok(());
+        }
+
+        let mut tree = original.tree.clone();
+        let mut rand = RomuDuoJrRand::with_seed(state.rand_mut().next());
+        let map_observer_handle = &self.map_observer_handle;
+        let mut tester = |t: &TreeMutation, bits: &HashSet<usize>, ctx: &Context| {
+            let input = NautilusInput::new(t.to_tree(ctx));
+            let bits: Vec<usize> = bits.iter().copied().collect();
+            if !Self::run_input(fuzzer, executor, state, manager, &input)? {
+                return Ok(false);
+            }
+            let cnt = executor.observers()[map_observer_handle]
+                .as_ref()
+                .how_many_set(&bits);
+            Ok(cnt == bits.len())
+        };
+
+        self.mutator.minimize_tree(
+            &mut rand,
+            &mut tree,
+            &bits,
+            self.ctx,
+            0,
+            usize::MAX,
+            &mut tester,
+        )?;
+        self.mutator
+            .minimize_rec(&mut tree, &bits, self.ctx, 0, usize::MAX, &mut tester)?;
+
+        if tree.size() < original.tree.size() {
+            let mut testcase = state.corpus().get(corpus_id)?.borrow().clone();
+            testcase.set_input(NautilusInput::new(tree));
+            state.corpus_mut().replace(corpus_id, testcase)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a, C, EM, O, S, Z> NautilusMinimizationStage<'a, C, EM, O, S, Z>
+where
+    O: MapObserver,
+    C: AsRef<O> + Named,
+{
+    /// Create a new [`NautilusMinimizationStage`].
+    #[must_use]
+    pub fn new(context: &'a NautilusContext, map_observer: &C) -> Self {
+        Self {
+            name: Cow::Borrowed(NAUTILUS_MINIMIZATION_STAGE_NAME),
+            ctx: &context.ctx,
+            mutator: BackingMutator::new(&context.ctx),
+            map_observer_handle: map_observer.handle(),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Runs the original input and returns the bits that every minimized tree has to keep hitting,
+    /// or `None` if the input does not reproduce them.
+    fn verify_input<E>(
+        &self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+        novelties: Option<&[usize]>,
+        input: &NautilusInput,
+    ) -> Result<Option<HashSet<usize>>, Error>
+    where
+        E: Executor<EM, NautilusInput, S, Z> + HasObservers,
+        E::Observers: ObserversTuple<NautilusInput, S>,
+        S: HasExecutions + MaybeHasClientPerfMonitor,
+        Z: ExecutionProcessor<EM, NautilusInput, E::Observers, S>,
+    {
+        if !Self::run_input(fuzzer, executor, state, manager, input)? {
+            return Ok(None);
+        }
+
+        let observers = executor.observers();
+        let map = observers[&self.map_observer_handle].as_ref();
+
+        if let Some(novelties) = novelties {
+            if map.how_many_set(novelties) != novelties.len() {
+                return Ok(None);
+            }
+            return Ok(Some(novelties.iter().copied().collect()));
+        }
+
+        let initial = map.initial();
+        Ok(Some(
+            (0..map.usable_count())
+                .filter(|&idx| map.get(idx) != initial)
+                .collect(),
+        ))
+    }
+
+    /// Runs the target, returning `false` if the execution did not end with [`ExitKind::Ok`].
+    fn run_input<E>(
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+        input: &NautilusInput,
+    ) -> Result<bool, Error>
+    where
+        E: Executor<EM, NautilusInput, S, Z> + HasObservers,
+        E::Observers: ObserversTuple<NautilusInput, S>,
+        S: HasExecutions + MaybeHasClientPerfMonitor,
+        Z: ExecutionProcessor<EM, NautilusInput, E::Observers, S>,
+    {
+        start_timer!(state);
+        executor.observers_mut().pre_exec_all(state, input)?;
+        mark_feature_time!(state, PerfFeature::PreExecObservers);
+
+        start_timer!(state);
+        let exit_kind = executor.run_target(fuzzer, state, manager, input)?;
+        mark_feature_time!(state, PerfFeature::TargetExecution);
+
+        start_timer!(state);
+        executor
+            .observers_mut()
+            .post_exec_all(state, input, &exit_kind)?;
+        mark_feature_time!(state, PerfFeature::PostExecObservers);
+
+        if exit_kind != ExitKind::Ok {
+            let observers = executor.observers();
+            fuzzer.evaluate_execution(state, manager, input, &*observers, &exit_kind, true)?;
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::ToString, vec, vec::Vec};
+
+    use libafl_bolts::{
+        Error, HasLen,
+        rands::StdRand,
+        tuples::{RefIndexable, tuple_list, tuple_list_type},
+    };
+
+    use super::NautilusMinimizationStage;
+    use crate::{
+        StdFuzzer,
+        corpus::{Corpus, HasCurrentCorpusId, InMemoryCorpus, Testcase},
+        events::NopEventManager,
+        executors::{Executor, ExitKind, HasObservers},
+        generators::NautilusContext,
+        inputs::{FromTargetBytesConverter, NautilusBytesConverter, NautilusInput},
+        observers::{MapObserver, StdMapObserver},
+        schedulers::QueueScheduler,
+        stages::Stage,
+        state::{HasCorpus, StdState},
+    };
+
+    /// Sets map index 0 for every input, and index 1 for inputs containing an `x`
+    struct GrammarExecutor<'a> {
+        context: &'a NautilusContext,
+        observers: tuple_list_type!(StdMapObserver<'static, u8, false>),
+    }
+
+    impl<EM, S, Z> Executor<EM, NautilusInput, S, Z> for GrammarExecutor<'_> {
+        fn run_target(
+            &mut self,
+            _fuzzer: &mut Z,
+            _state: &mut S,
+            _mgr: &mut EM,
+            input: &NautilusInput,
+        ) -> Result<ExitKind, Error> {
+            let mut bytes = Vec::new();
+            input.unparse(self.context, &mut bytes);
+            let map = &mut self.observers.0;
+            map.set(0, 1);
+            if bytes.contains(&b'x') {
+                map.set(1, 1);
+            }
+            Ok(ExitKind::Ok)
+        }
+    }
+
+    impl HasObservers for GrammarExecutor<'_> {
+        type Observers = tuple_list_type!(StdMapObserver<'static, u8, false>);
+
+        fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+            RefIndexable::from(&self.observers)
+        }
+
+        fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+            RefIndexable::from(&mut self.observers)
+        }
+    }
+
+    #[test]
+    fn test_nautilus_minimization() -> Result<(), Error> {
+        let rules = vec![
+            vec!["START".to_string(), "{EXPR}".to_string()],
+            vec!["EXPR".to_string(), "{EXPR}+{EXPR}".to_string()],
+            vec!["EXPR".to_string(), "1".to_string()],
+            vec!["EXPR".to_string(), "x".to_string()],
+        ];
+        let context = NautilusContext::new(10, &rules);
+        let original =
+            NautilusBytesConverter::new(&context).convert_from_target_bytes(&mut (), b"1+1+x+1")?;
+        let original_size = original.len();
+
+        let observer = StdMapObserver::owned("map", vec![0u8; 4]);
+        let mut stage = NautilusMinimizationStage::new(&context, &observer);
+        let mut executor = GrammarExecutor {
+            context: &context,
+            observers: tuple_list!(observer),
+        };
+
+        let mut feedback = ();
+        let mut objective = ();
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )?;
+        let id = state.corpus_mut().add(Testcase::new(original))?;
+        state.set_corpus_id(id)?;
+        let mut fuzzer = StdFuzzer::new(QueueScheduler::new(), feedback, objective);
+        let mut mgr = NopEventManager::new();
+
+        stage.perform(&mut fuzzer, &mut executor, &mut state, &mut mgr)?;
+
+        let minimized = state.corpus().cloned_input_for_id(id)?;
+        let mut bytes = Vec::new();
+        minimized.unparse(&context, &mut bytes);
+        assert!(minimized.len() < original_size);
+        assert_eq!(bytes, b"x");
+        Ok(())
+    }
+}
@@ -0,0 +1,279 @@
+//! The [`RareEdgeMaskStage`] computes which bytes of a testcase can be mutated without losing its rarest edge,
+//! like the mutation masks of `FairFuzz`.
+
+use alloc::{
+    borrow::{Cow, ToOwned},
+    vec::Vec,
+};
+use core::marker::PhantomData;
+
+use libafl_bolts::{
+    Named, impl_serdeany,
+    tuples::{Handle, Handled, MatchName},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Error, ExecutesInput, HasMetadata, HasNamedMetadata,
+    corpus::HasCurrentCorpusId,
+    executors::HasObservers,
+    feedbacks::{RareEdgeMetadata, RareEdgesMetadata},
+    inputs::HasMutatorBytes,
+    mutators::MutationMask,
+    observers::MapObserver,
+    stages::{Restartable, RetryCountRestartHelper, Stage},
+    state::HasCurrentTestcase,
+};
+
+/// The name for the rare edge mask stage
+pub static RARE_EDGE_MASK_STAGE_NAME: &str = "rare_edge_mask";
+
+/// The bytes of a testcase that can be changed without losing its rarest edge
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    expect(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct RareEdgeMaskMetadata {
+    /// Whether the rarest edge is still hit after flipping each byte
+    pub mutable: Vec<bool>,
+}
+
+impl_serdeany!(RareEdgeMaskMetadata);
+
+impl RareEdgeMaskMetadata {
+    /// Creates a new [`RareEdgeMaskMetadata`]
+    #[must_use]
+    pub fn new(mutable: Vec<bool>) -> Self {
+        Self { mutable }
+    }
+}
+
+impl MutationMask for RareEdgeMaskMetadata {
+    fn mutable(&self) -> &[bool] {
+        &self.mutable
+    }
+}
+
+/// Flips every byte of a testcase hitting a rare edge and stores the bytes whose flip keeps the rarest edge
+/// as the [`RareEdgeMaskMetadata`] of the testcase.
+///
+/// Use it with the [`crate::feedbacks::RareEdgeFeedback`], and wrap the mutator of the following mutational
+/// stage in a [`crate::mutators::MaskedMutator`] for the [`RareEdgeMaskMetadata`] to keep the rare edge while mutating.
+/// The mask is computed once per testcase, and costs one execution per byte.
+#[derive(Debug, Clone)]
+pub struct RareEdgeMaskStage<C, E, EM, I, O, S, Z> {
+    map_observer_handle: Handle<C>,
+    name: Cow<'static, str>,
+    max_len: usize,
+    phantom: PhantomData<(E, EM, I, O, S, Z)>,
+}
+
+impl<C, E, EM, I, O, S, Z> RareEdgeMaskStage<C, E, EM, I, O, S, Z>
+where
+    C: Named,
+{
+    /// Creates a new [`RareEdgeMaskStage`] for the map observer used by the [`crate::feedbacks::RareEdgeFeedback`]
+    #[must_use]
+    pub fn new(map_observer: &C) -> Self {
+        Self {
+            map_observer_handle: map_observer.handle(),
+            name: Cow::Owned(
+                RARE_EDGE_MASK_STAGE_NAME.to_owned() + ":" + map_observer.name().as_ref(),
+            ),
+            max_len: 4096,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Skips testcases longer than `max_len` bytes, as the mask costs one execution per byte
+    #[must_use]
+    pub fn with_max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+}
+
+impl<C, E, EM, I, O, S, Z> Named for RareEdgeMaskStage<C, E, EM, I, O, S, Z> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<C, E, EM, I, O, S, Z> Stage<E, EM, S, Z> for RareEdgeMaskStage<C, E, EM, I, O, S, Z>
+where
+    C: AsRef<O>,
+    E: HasObservers,
+    E::Observers: MatchName,
+    I: HasMutatorBytes + Clone,
+    O: MapObserver,
+    S: HasCurrentTestcase<I> + HasCurrentCorpusId + HasMetadata,
+    Z: ExecutesInput<E, EM, I, S>,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        let edge = {
+            let testcase = state.current_testcase()?;
+            if testcase.has_metadata::<RareEdgeMaskMetadata>() {
+                return Ok(());
+            }
+            let Ok(edges) = testcase.metadata::<RareEdgesMetadata>() else {
+                return Ok(());
+            };
+            let Some(edge) = edges.rarest(state.metadata::<RareEdgeMetadata>()?) else {
+                return Ok(());
+            };
+            edge
+        };
+
+        let input = state.current_input_cloned()?;
+        let len = input.mutator_bytes().len();
+        if len > self.max_len {
+            return Ok(());
+        }
+
+        let mut mutable = Vec::with_capacity(len);
+        let mut flipped = input.clone();
+        for idx in 0..len {
+            flipped.mutator_bytes_mut()[idx] ^= 0xff;
+            fuzzer.execute_input(state, executor, manager, &flipped)?;
+            let observers = executor.observers();
+            let observer = observers[&self.map_observer_handle].as_ref();
+            mutable.push(observer.get(edge) != observer.initial());
+            flipped.mutator_bytes_mut()[idx] = input.mutator_bytes()[idx];
+        }
+
+        state
+            .current_testcase_mut()?
+            .add_metadata(RareEdgeMaskMetadata::new(mutable));
+        Ok(())
+    }
+}
+
+impl<C, E, EM, I, O, S, Z> Restartable<S> for RareEdgeMaskStage<C, E, EM, I, O, S, Z>
+where
+    S: HasNamedMetadata + HasCurrentCorpusId,
+{
+    fn should_restart(&mut self, state: &mut S) -> Result<bool, Error> {
+        // This is a deterministic stage
+        // Once it failed, then don't retry,
+        // It will just fail again
+        RetryCountRestartHelper::no_retry(state, &self.name)
+    }
+
+    fn clear_progress(&mut self, state: &mut S) -> Result<(), Error> {
+        RetryCountRestartHelper::clear_progress::<S>(state, &self.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::{
+        Error,
+        rands::StdRand,
+        tuples::{RefIndexable, tuple_list, tuple_list_type},
+    };
+
+    use super::{RareEdgeMaskMetadata, RareEdgeMaskStage};
+    use crate::{
+        HasMetadata, StdFuzzer,
+        corpus::{Corpus, HasCurrentCorpusId, InMemoryCorpus, Testcase},
+        events::NopEventManager,
+        executors::{Executor, ExitKind, HasObservers},
+        feedbacks::{RareEdgeMetadata, RareEdgesMetadata},
+        inputs::{BytesInput, HasMutatorBytes},
+        observers::{MapObserver, StdMapObserver},
+        schedulers::QueueScheduler,
+        stages::Stage,
+        state::{HasCorpus, StdState},
+    };
+
+    /// Sets map index 0 for every input, and the rare index 1 if the second byte is an `r`
+    struct RareExecutor {
+        observers: tuple_list_type!(StdMapObserver<'static, u8, false>),
+    }
+
+    impl<EM, S, Z> Executor<EM, BytesInput, S, Z> for RareExecutor {
+        fn run_target(
+            &mut self,
+            _fuzzer: &mut Z,
+            _state: &mut S,
+            _mgr: &mut EM,
+            input: &BytesInput,
+        ) -> Result<ExitKind, Error> {
+            let map = &mut self.observers.0;
+            map.set(0, 1);
+            if input.mutator_bytes().get(1) == Some(&b'r') {
+                map.set(1, 1);
+            }
+            Ok(ExitKind::Ok)
+        }
+    }
+
+    impl HasObservers for RareExecutor {
+        type Observers = tuple_list_type!(StdMapObserver<'static, u8, false>);
+
+        fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+            RefIndexable::from(&self.observers)
+        }
+
+        fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+            RefIndexable::from(&mut self.observers)
+        }
+    }
+
+    #[test]
+    fn rare_edge_mask() -> Result<(), Error> {
+        let observer = StdMapObserver::owned("map", vec![0u8; 4]);
+        let mut stage = RareEdgeMaskStage::new(&observer);
+        let mut short_stage = RareEdgeMaskStage::new(&observer).with_max_len(3);
+        let mut executor = RareExecutor {
+            observers: tuple_list!(observer),
+        };
+
+        let mut feedback = ();
+        let mut objective = ();
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )?;
+        let mut meta = RareEdgeMetadata::new();
+        meta.hit_counts = vec![100, 3, 0, 0];
+        state.add_metadata(meta);
+        let mut testcase = Testcase::new(BytesInput::new(b"xrxx".to_vec()));
+        testcase.add_metadata(RareEdgesMetadata { edges: vec![1] });
+        let id = state.corpus_mut().add(testcase)?;
+        state.set_corpus_id(id)?;
+        let mut fuzzer = StdFuzzer::new(QueueScheduler::new(), feedback, objective);
+        let mut mgr = NopEventManager::new();
+
+        short_stage.perform(&mut fuzzer, &mut executor, &mut state, &mut mgr)?;
+        assert!(
+            !state
+                .corpus()
+                .get(id)?
+                .borrow()
+                .has_metadata::<RareEdgeMaskMetadata>()
+        );
+
+        stage.perform(&mut fuzzer, &mut executor, &mut state, &mut mgr)?;
+        assert_eq!(
+            state
+                .corpus()
+                .get(id)?
+                .borrow()
+                .metadata::<RareEdgeMaskMetadata>()?
+                .mutable,
+            vec![true, false, true, true]
+        );
+        Ok(())
+    }
+}
@@ -144,6 +144,16 @@ impl<H, I> ReplayStage<H, I> {
             hook,
         }
     }
+
+    /// The hook of this stage
+    pub fn hook(&self) -> &H {
+        &self.hook
+    }
+
+    /// The hook of this stage, mutable
+    pub fn hook_mut(&mut self) -> &mut H {
+        &mut self.hook
+    }
 }
 
 impl<E, EM, H, I, S, Z> Stage<E, EM, S, Z> for ReplayStage<H, I>
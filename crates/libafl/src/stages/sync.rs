@@ -20,7 +20,7 @@ use crate::{
     events::{Event, EventConfig, EventFirer, EventWithStats, llmp::LlmpEventConverter},
     executors::{Executor, ExitKind, HasObservers},
     fuzzer::{Evaluator, EvaluatorObservers, ExecutionProcessor, HasObjective},
-    inputs::{Input, InputConverter},
+    inputs::{BytesInput, Input, InputConverter},
     stages::{Restartable, RetryCountRestartHelper, Stage},
     state::{
         HasCorpus, HasCurrentTestcase, HasExecutions, HasRand, HasSolutions,
@@ -212,6 +212,30 @@ where
             phantom: PhantomData,
         }
     }
+
+    /// Creates a new [`SyncFromDiskStage`] reading plain [`BytesInput`] files and converting them with `converter`,
+    /// e.g., to parse files of other fuzzers into grammar inputs.
+    /// Files the converter rejects with [`Error::invalid_input()`] are skipped.
+    #[must_use]
+    #[expect(clippy::type_complexity)]
+    pub fn with_converter<IC>(
+        sync_dirs: Vec<PathBuf>,
+        interval: Duration,
+        mut converter: IC,
+    ) -> SyncFromDiskStage<impl FnMut(&mut Z, &mut S, &Path) -> Result<I, Error>, E, EM, I, S, Z>
+    where
+        IC: InputConverter<S, From = BytesInput, To = I>,
+    {
+        SyncFromDiskStage {
+            interval,
+            name: Cow::Borrowed(SYNC_FROM_DISK_STAGE_NAME),
+            sync_dirs,
+            load_callback: move |_: &mut Z, state: &mut S, path: &Path| {
+                converter.convert(state, BytesInput::from_file(path)?)
+            },
+            phantom: PhantomData,
+        }
+    }
 }
 
 /// Metadata used to store information about the last sent testcase with `SyncFromBrokerStage`
@@ -3,15 +3,17 @@
 use alloc::{
     borrow::{Cow, ToOwned},
     string::ToString,
+    vec::Vec,
 };
 use core::{borrow::BorrowMut, fmt::Debug, hash::Hash, marker::PhantomData};
 
 use ahash::RandomState;
+use hashbrown::HashSet;
 use libafl_bolts::{
     HasLen, Named, generic_hash_std,
     tuples::{Handle, Handled, MatchName, MatchNameRef},
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 #[cfg(feature = "track_hit_feedbacks")]
 use crate::feedbacks::premature_last_result_err;
@@ -20,14 +22,14 @@ use crate::monitors::stats::PerfFeature;
 use crate::{
     Error, ExecutesInput, ExecutionProcessor, HasFeedback, HasMetadata, HasNamedMetadata,
     HasScheduler,
-    corpus::{Corpus, HasCurrentCorpusId, Testcase},
+    corpus::{Corpus, CorpusId, HasCurrentCorpusId, Testcase},
     events::EventFirer,
     executors::{ExitKind, HasObservers},
     feedbacks::{Feedback, FeedbackFactory, HasObserverHandle, StateInitializer},
-    inputs::Input,
+    inputs::{HasMutatorBytes, Input, ResizableMutator},
     mark_feature_time,
     mutators::{MutationResult, Mutator},
-    observers::ObserversTuple,
+    observers::{ObserverWithHashField, ObserversTuple},
     schedulers::RemovableScheduler,
     stages::{
         ExecutionCountRestartHelper, Restartable, Stage,
@@ -442,3 +444,397 @@ where
         }
     }
 }
+
+/// A feedback which checks that an execution crashed the same way as the original solution:
+/// the [`ExitKind`] and the hash of the backtrace observer both need to be identical.
+#[derive(Debug, Clone)]
+pub struct CrashSignatureFeedback<O> {
+    name: Cow<'static, str>,
+    observer_handle: Handle<O>,
+    orig_hash: Option<u64>,
+    orig_exit_kind: ExitKind,
+    #[cfg(feature = "track_hit_feedbacks")]
+    // The previous run's result of `Self::is_interesting`
+    last_result: Option<bool>,
+}
+
+impl<O> CrashSignatureFeedback<O> {
+    /// Creates a new [`CrashSignatureFeedback`], expecting the given backtrace hash and [`ExitKind`]
+    #[must_use]
+    pub fn new(
+        observer_handle: Handle<O>,
+        orig_hash: Option<u64>,
+        orig_exit_kind: ExitKind,
+    ) -> Self {
+        Self {
+            name: Cow::from("CrashSignature"),
+            observer_handle,
+            orig_hash,
+            orig_exit_kind,
+            #[cfg(feature = "track_hit_feedbacks")]
+            last_result: None,
+        }
+    }
+}
+
+impl<O> Named for CrashSignatureFeedback<O> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<O> HasObserverHandle for CrashSignatureFeedback<O> {
+    type Observer = O;
+
+    fn observer_handle(&self) -> &Handle<Self::Observer> {
+        &self.observer_handle
+    }
+}
+
+impl<O, S> StateInitializer<S> for CrashSignatureFeedback<O> {}
+
+impl<EM, I, O, OT, S> Feedback<EM, I, OT, S> for CrashSignatureFeedback<O>
+where
+    O: ObserverWithHashField,
+    OT: MatchName,
+{
+    fn is_interesting(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        let obs = observers
+            .get(self.observer_handle())
+            .expect("Should have been provided valid observer name.");
+        let res = *exit_kind == self.orig_exit_kind && obs.hash() == self.orig_hash;
+        #[cfg(feature = "track_hit_feedbacks")]
+        {
+            self.last_result = Some(res);
+        }
+        Ok(res)
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        self.last_result.ok_or(premature_last_result_err())
+    }
+}
+
+/// A feedback factory for ensuring that minimized solutions still crash the same way,
+/// i.e., with the same [`ExitKind`] and the same backtrace hash as observed by the given observer.
+#[derive(Debug, Clone)]
+pub struct CrashSignatureFactory<O> {
+    observer_handle: Handle<O>,
+    exit_kind: ExitKind,
+}
+
+impl<O> CrashSignatureFactory<O>
+where
+    O: ObserverWithHashField + Handled,
+{
+    /// Creates a new crash signature feedback factory for the given backtrace observer,
+    /// expecting the minimized inputs to [`ExitKind::Crash`].
+    pub fn new(obs: &O) -> Self {
+        Self::with_exit_kind(obs, ExitKind::Crash)
+    }
+
+    /// Creates a new crash signature feedback factory for the given backtrace observer,
+    /// expecting the minimized inputs to exit with `exit_kind`.
+    pub fn with_exit_kind(obs: &O, exit_kind: ExitKind) -> Self {
+        Self {
+            observer_handle: obs.handle(),
+            exit_kind,
+        }
+    }
+}
+
+impl<O> HasObserverHandle for CrashSignatureFactory<O> {
+    type Observer = O;
+
+    fn observer_handle(&self) -> &Handle<O> {
+        &self.observer_handle
+    }
+}
+
+impl<O, OT> FeedbackFactory<CrashSignatureFeedback<O>, OT> for CrashSignatureFactory<O>
+where
+    O: ObserverWithHashField,
+    OT: MatchName,
+{
+    fn create_feedback(&self, observers: &OT) -> CrashSignatureFeedback<O> {
+        let obs = observers
+            .get(self.observer_handle())
+            .expect("Should have been provided valid observer name.");
+        CrashSignatureFeedback::new(self.observer_handle.clone(), obs.hash(), self.exit_kind)
+    }
+}
+
+/// The original, unminimized, solution, stored on the minimized solution by the [`SolutionTMinStage`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OriginalSolutionMetadata {
+    /// The bytes of the original solution
+    pub bytes: Vec<u8>,
+    /// The [`ExitKind`] of the original solution
+    pub exit_kind: ExitKind,
+    /// The backtrace hash of the original solution
+    pub backtrace_hash: Option<u64>,
+}
+
+libafl_bolts::impl_serdeany!(OriginalSolutionMetadata);
+
+/// The solutions the [`SolutionTMinStage`] already handled
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[expect(clippy::unsafe_derive_deserialize)]
+pub struct SolutionTMinMetadata {
+    handled: HashSet<CorpusId>,
+}
+
+libafl_bolts::impl_serdeany!(SolutionTMinMetadata);
+
+/// A stage that minimizes new solutions while preserving their crash signature.
+///
+/// Each solution that was not handled yet is replayed, then delta-debugged (by removing chunks
+/// of bytes of decreasing size) as long as the [`ExitKind`] and the backtrace hash stay identical,
+/// see [`CrashSignatureFeedback`].
+/// The solution is then replaced by its minimized version, keeping the original bytes as
+/// [`OriginalSolutionMetadata`].
+///
+/// Note: Will NOT work with in-process executors, since replaying a solution will crash the fuzzer.
+/// Solutions that crashed the fuzzer during minimization are not retried.
+#[derive(Debug, Clone)]
+pub struct SolutionTMinStage<I, O, S> {
+    observer_handle: Handle<O>,
+    max_execs: usize,
+    phantom: PhantomData<(I, S)>,
+}
+
+impl<I, O, S> SolutionTMinStage<I, O, S>
+where
+    O: ObserverWithHashField + Handled,
+{
+    /// Creates a new [`SolutionTMinStage`], preserving the hash of the given backtrace observer,
+    /// spending at most `max_execs` executions per solution.
+    pub fn new(obs: &O, max_execs: usize) -> Self {
+        Self {
+            observer_handle: obs.handle(),
+            max_execs,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<E, EM, I, O, S, Z> Stage<E, EM, S, Z> for SolutionTMinStage<I, O, S>
+where
+    E: HasObservers,
+    E::Observers: MatchName,
+    I: Input + HasMutatorBytes + ResizableMutator<u8>,
+    O: ObserverWithHashField,
+    S: HasMetadata + HasSolutions<I>,
+    Z: ExecutesInput<E, EM, I, S>,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        loop {
+            state.metadata_or_insert_with(SolutionTMinMetadata::default);
+            let handled = &state.metadata::<SolutionTMinMetadata>()?.handled;
+            let mut next = None;
+            let mut id = state.solutions().first();
+            while let Some(cur) = id {
+                if !handled.contains(&cur)
+                    && !state
+                        .solutions()
+                        .get(cur)?
+                        .borrow()
+                        .has_metadata::<OriginalSolutionMetadata>()
+                {
+                    next = Some(cur);
+                    break;
+                }
+                id = state.solutions().next(cur);
+            }
+            let Some(id) = next else {
+                return Ok(());
+            };
+
+            // mark as handled first, so that we do not retry solutions that bring down the fuzzer
+            state
+                .metadata_mut::<SolutionTMinMetadata>()?
+                .handled
+                .insert(id);
+
+            let orig = state.solutions().cloned_input_for_id(id)?;
+            let exit_kind = fuzzer.execute_input(state, executor, manager, &orig)?;
+            if exit_kind == ExitKind::Ok {
+                log::info!("Solution {id} did not reproduce, not minimizing it");
+                continue;
+            }
+            let mut feedback = CrashSignatureFactory {
+                observer_handle: self.observer_handle.clone(),
+                exit_kind,
+            }
+            .create_feedback(&*executor.observers());
+
+            let base = delta_debug(&orig, self.max_execs, |candidate| {
+                let exit_kind = fuzzer.execute_input(state, executor, manager, candidate)?;
+                feedback.is_interesting(
+                    state,
+                    manager,
+                    candidate,
+                    &*executor.observers(),
+                    &exit_kind,
+                )
+            })?;
+
+            if base.len() < orig.len() {
+                log::info!(
+                    "Minimized solution {id} from {} to {} bytes",
+                    orig.len(),
+                    base.len()
+                );
+                let mut testcase = state.solutions().get(id)?.borrow().clone();
+                testcase.set_input(base);
+                *testcase.filename_mut() = None;
+                #[cfg(feature = "std")]
+                {
+                    *testcase.file_path_mut() = None;
+                    *testcase.metadata_path_mut() = None;
+                }
+                testcase.add_metadata(OriginalSolutionMetadata {
+                    bytes: orig.mutator_bytes().to_vec(),
+                    exit_kind: feedback.orig_exit_kind,
+                    backtrace_hash: feedback.orig_hash,
+                });
+                state.solutions_mut().replace(id, testcase)?;
+            }
+        }
+    }
+}
+
+/// Minimizes `orig` by removing chunks of bytes of decreasing size, as long as `keeps` returns `true`
+/// for the reduced input, using at most `max_execs` calls to `keeps`.
+fn delta_debug<I, F>(orig: &I, max_execs: usize, mut keeps: F) -> Result<I, Error>
+where
+    I: Clone + HasMutatorBytes + ResizableMutator<u8>,
+    F: FnMut(&I) -> Result<bool, Error>,
+{
+    let mut base = orig.clone();
+    let mut execs = 0;
+    let mut granularity = 2;
+    while base.mutator_bytes().len() >= 2 && execs < max_execs {
+        let chunk = base.mutator_bytes().len().div_ceil(granularity);
+        let mut reduced = false;
+        let mut start = 0;
+        while start < base.mutator_bytes().len() && execs < max_execs {
+            let mut candidate = base.clone();
+            candidate.drain(start..(start + chunk).min(base.mutator_bytes().len()));
+            execs += 1;
+            if keeps(&candidate)? {
+                base = candidate;
+                reduced = true;
+            } else {
+                start += chunk;
+            }
+        }
+        if reduced {
+            granularity = (granularity - 1).max(2);
+        } else if chunk == 1 {
+            break;
+        } else {
+            granularity = (granularity * 2).min(base.mutator_bytes().len());
+        }
+    }
+    Ok(base)
+}
+
+impl<I, O, S> Restartable<S> for SolutionTMinStage<I, O, S> {
+    fn should_restart(&mut self, _state: &mut S) -> Result<bool, Error> {
+        Ok(true)
+    }
+
+    fn clear_progress(&mut self, _state: &mut S) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::{Error, tuples::tuple_list};
+
+    use super::{CrashSignatureFactory, delta_debug};
+    use crate::{
+        executors::ExitKind,
+        feedbacks::{Feedback, FeedbackFactory},
+        inputs::{BytesInput, HasMutatorBytes},
+        observers::{BacktraceObserver, HarnessType},
+    };
+
+    #[test]
+    fn test_delta_debug() -> Result<(), Error> {
+        let orig = BytesInput::new(b"aaaaXbbbbbbbYcccc".to_vec());
+        let min = delta_debug(&orig, 1000, |input: &BytesInput| {
+            let bytes = input.mutator_bytes();
+            Ok(bytes.contains(&b'X') && bytes.contains(&b'Y'))
+        })?;
+        assert_eq!(min.mutator_bytes(), b"XY");
+
+        // The execution budget is respected
+        let mut execs = 0;
+        let min = delta_debug(&orig, 3, |_: &BytesInput| {
+            execs += 1;
+            Ok(false)
+        })?;
+        assert_eq!(execs, 3);
+        assert_eq!(min.mutator_bytes(), orig.mutator_bytes());
+        Ok(())
+    }
+
+    #[test]
+    fn test_crash_signature_feedback() -> Result<(), Error> {
+        let mut observer = BacktraceObserver::owned("backtrace", HarnessType::External);
+        let input = BytesInput::new(b"a".to_vec());
+        let mut state = ();
+
+        observer.fill_external(42, &ExitKind::Crash);
+        let observers = tuple_list!(observer);
+
+        // The original crash signature is taken from the observers when creating the feedback
+        let mut feedback = CrashSignatureFactory::new(&observers.0).create_feedback(&observers);
+        assert!(Feedback::<(), _, _, ()>::is_interesting(
+            &mut feedback,
+            &mut state,
+            &mut (),
+            &input,
+            &observers,
+            &ExitKind::Crash
+        )?);
+        assert!(!Feedback::<(), _, _, ()>::is_interesting(
+            &mut feedback,
+            &mut state,
+            &mut (),
+            &input,
+            &observers,
+            &ExitKind::Timeout
+        )?);
+
+        // A different backtrace is a different crash
+        let (mut observer, ()) = observers;
+        observer.fill_external(7, &ExitKind::Crash);
+        assert!(!Feedback::<(), _, _, ()>::is_interesting(
+            &mut feedback,
+            &mut state,
+            &mut (),
+            &input,
+            &tuple_list!(observer),
+            &ExitKind::Crash
+        )?);
+        Ok(())
+    }
+}
@@ -0,0 +1,776 @@
+//! Crash triage clusters solutions into buckets, by backtrace hash, severity and faulting pc.
+//!
+//! New solutions are triaged by wrapping the objective in a [`CrashTriageFeedback`],
+//! solutions that are already in the solutions corpus can be triaged with the [`CrashTriageStage`].
+//! Each bucket gets its own directory, `<out_dir>/<severity>/<bucket>/`, and a summary of all
+//! buckets is kept in `<out_dir>/triage.json`.
+//! The number of buckets is reported to the monitors as user stats.
+
+use alloc::{
+    borrow::Cow,
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt, marker::PhantomData};
+use std::path::{Path, PathBuf};
+
+use hashbrown::{HashMap, HashSet};
+use libafl_bolts::{
+    Named, impl_serdeany,
+    tuples::{Handle, Handled, MatchName, MatchNameRef},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Error, ExecutesInput, HasMetadata,
+    corpus::{Corpus, CorpusId, Testcase},
+    events::{Event, EventFirer, EventWithStats},
+    executors::{ExitKind, HasObservers},
+    feedbacks::{Feedback, HasObserverHandle, StateInitializer},
+    inputs::Input,
+    monitors::stats::{AggregatorOps, UserStats, UserStatsValue},
+    observers::{CrashReport, ObserverWithCrashReport},
+    stages::{Restartable, Stage},
+    state::{HasExecutions, HasSolutions},
+};
+
+/// The name of the JSON summary file written to the triage output directory
+pub const CRASH_TRIAGE_SUMMARY_FILE: &str = "triage.json";
+
+/// The severity of a crash, following the classes used by CASR
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum CrashSeverity {
+    /// The crash is likely exploitable, e.g., an out-of-bounds write
+    Exploitable,
+    /// The crash may be exploitable, e.g., an out-of-bounds read or a double free
+    ProbablyExploitable,
+    /// The crash is most likely not exploitable, e.g., a null-pointer dereference or a stack exhaustion
+    NotExploitable,
+    /// Not enough information to tell
+    Unknown,
+}
+
+impl CrashSeverity {
+    /// Classifies a crash from its [`ExitKind`] and the sanitizer report, if any.
+    ///
+    /// With the `casr` feature, the severity assigned by CASR to the report is used.
+    /// Otherwise, or if CASR could not classify the report, the severity is guessed from the error type.
+    #[must_use]
+    pub fn classify(exit_kind: &ExitKind, report: Option<&CrashReport>) -> Self {
+        if *exit_kind == ExitKind::Timeout || *exit_kind == ExitKind::Oom {
+            return Self::NotExploitable;
+        }
+        let Some(report) = report else {
+            return Self::Unknown;
+        };
+        if let Some(severity) = report.casr_severity.as_deref().and_then(Self::from_casr) {
+            return severity;
+        }
+        match report.error_type.as_str() {
+            "heap-buffer-overflow"
+            | "stack-buffer-overflow"
+            | "stack-buffer-underflow"
+            | "global-buffer-overflow"
+            | "container-overflow"
+            | "heap-use-after-free"
+            | "stack-use-after-return"
+            | "stack-use-after-scope"
+            | "dynamic-stack-buffer-overflow" => {
+                if report.is_write == Some(false) {
+                    Self::ProbablyExploitable
+                } else {
+                    Self::Exploitable
+                }
+            }
+            "attempting" | "double-free" | "bad-free" | "alloc-dealloc-mismatch" => {
+                Self::ProbablyExploitable
+            }
+            "SEGV" | "BUS" => {
+                if report.pc.is_some() && report.pc == report.address {
+                    // we jumped to an invalid address
+                    Self::Exploitable
+                } else if report.address.is_some_and(|addr| addr < 0x1000) {
+                    // near-null access
+                    Self::NotExploitable
+                } else if report.is_write == Some(true) {
+                    Self::ProbablyExploitable
+                } else {
+                    Self::NotExploitable
+                }
+            }
+            "stack-overflow"
+            | "allocation-size-too-big"
+            | "out-of-memory"
+            | "requested"
+            | "calloc-overflow"
+            | "ABRT"
+            | "FPE"
+            | "ILL" => Self::NotExploitable,
+            _ => Self::Unknown,
+        }
+    }
+
+    /// Parses the severity class of a CASR `ExecutionClass`
+    #[must_use]
+    pub fn from_casr(severity: &str) -> Option<Self> {
+        match severity {
+            "EXPLOITABLE" => Some(Self::Exploitable),
+            "PROBABLY_EXPLOITABLE" => Some(Self::ProbablyExploitable),
+            "NOT_EXPLOITABLE" => Some(Self::NotExploitable),
+            _ => None,
+        }
+    }
+
+    /// The name of this severity, as used for the output directories
+    #[must_use]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Exploitable => "EXPLOITABLE",
+            Self::ProbablyExploitable => "PROBABLY_EXPLOITABLE",
+            Self::NotExploitable => "NOT_EXPLOITABLE",
+            Self::Unknown => "UNKNOWN",
+        }
+    }
+}
+
+impl fmt::Display for CrashSeverity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A bucket of solutions that share the same backtrace hash, severity, and faulting pc
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CrashBucket {
+    /// The backtrace hash of all solutions in this bucket
+    pub backtrace_hash: Option<u64>,
+    /// The severity of all solutions in this bucket
+    pub severity: CrashSeverity,
+    /// The faulting pc of all solutions in this bucket, if known
+    pub pc: Option<u64>,
+    /// The error type reported by the sanitizer, if any
+    pub error_type: Option<String>,
+    /// The file names of the solutions in this bucket
+    pub inputs: Vec<String>,
+}
+
+impl CrashBucket {
+    /// The name of the bucket with the given properties
+    #[must_use]
+    pub fn bucket_name(backtrace_hash: Option<u64>, pc: Option<u64>) -> String {
+        let name = match backtrace_hash {
+            Some(hash) => format!("{hash:016x}"),
+            None => "nohash".to_string(),
+        };
+        match pc {
+            Some(pc) => format!("{name}_pc{pc:x}"),
+            None => name,
+        }
+    }
+
+    /// The path of this bucket, relative to the triage output directory
+    #[must_use]
+    pub fn relative_path(&self) -> PathBuf {
+        Path::new(self.severity.as_str()).join(Self::bucket_name(self.backtrace_hash, self.pc))
+    }
+}
+
+/// The state of the crash triage, keeping all known [`CrashBucket`]s
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[expect(clippy::unsafe_derive_deserialize)]
+pub struct CrashBucketsMetadata {
+    /// All buckets, by their relative path
+    buckets: BTreeMap<String, CrashBucket>,
+    /// The solutions that have already been (or are being) triaged by the [`CrashTriageStage`]
+    handled: HashSet<CorpusId>,
+}
+
+impl_serdeany!(CrashBucketsMetadata);
+
+impl CrashBucketsMetadata {
+    /// All buckets, by their path relative to the triage output directory
+    #[must_use]
+    pub fn buckets(&self) -> &BTreeMap<String, CrashBucket> {
+        &self.buckets
+    }
+
+    /// The number of buckets
+    #[must_use]
+    pub fn count(&self) -> usize {
+        self.buckets.len()
+    }
+
+    /// The number of buckets with the given [`CrashSeverity`]
+    #[must_use]
+    pub fn count_with_severity(&self, severity: CrashSeverity) -> usize {
+        self.buckets
+            .values()
+            .filter(|bucket| bucket.severity == severity)
+            .count()
+    }
+}
+
+/// The bucket a solution was sorted into
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CrashTriageMetadata {
+    /// The path of the bucket, relative to the triage output directory
+    pub bucket: String,
+    /// The severity of this solution
+    pub severity: CrashSeverity,
+    /// The backtrace hash of this solution
+    pub backtrace_hash: Option<u64>,
+    /// The faulting pc of this solution, if known
+    pub pc: Option<u64>,
+}
+
+impl_serdeany!(CrashTriageMetadata);
+
+/// Sorts solutions into buckets, shared by [`CrashTriageFeedback`] and [`CrashTriageStage`]
+#[derive(Debug, Clone)]
+pub struct CrashTriage<O> {
+    out_dir: PathBuf,
+    o_ref: Handle<O>,
+}
+
+impl<O> CrashTriage<O>
+where
+    O: ObserverWithCrashReport,
+{
+    /// Creates a new [`CrashTriage`], writing buckets to `out_dir`, using the backtrace of the given observer
+    pub fn new<P>(out_dir: P, observer: &O) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+        O: Handled,
+    {
+        std::fs::create_dir_all(out_dir.as_ref())?;
+        Ok(Self {
+            out_dir: out_dir.as_ref().into(),
+            o_ref: observer.handle(),
+        })
+    }
+
+    /// The directory all buckets are written to
+    #[must_use]
+    pub fn out_dir(&self) -> &Path {
+        &self.out_dir
+    }
+
+    /// Sorts the `input` of the last execution into its bucket, writes it to the bucket directory,
+    /// updates the summary and fires the bucket counts as user stats.
+    pub fn triage<EM, I, OT, S>(
+        &self,
+        state: &mut S,
+        manager: &mut EM,
+        observers: &OT,
+        exit_kind: &ExitKind,
+        input: &I,
+    ) -> Result<CrashTriageMetadata, Error>
+    where
+        EM: EventFirer<I, S>,
+        I: Input,
+        OT: MatchName,
+        S: HasMetadata + HasExecutions,
+    {
+        let observer = observers.get(&self.o_ref).ok_or_else(|| {
+            Error::illegal_state("CrashTriage could not find its backtrace observer")
+        })?;
+        let report = observer.crash_report();
+        let backtrace_hash = observer.hash();
+        let severity = CrashSeverity::classify(exit_kind, report);
+        let pc = report.and_then(|report| report.pc);
+
+        let mut bucket = CrashBucket {
+            backtrace_hash,
+            severity,
+            pc,
+            error_type: report.map(|report| report.error_type.clone()),
+            inputs: Vec::new(),
+        };
+        let relative_path = bucket.relative_path();
+        let bucket_dir = self.out_dir.join(&relative_path);
+        std::fs::create_dir_all(&bucket_dir)?;
+
+        let file_name = input.generate_name(None);
+        input.to_file(bucket_dir.join(&file_name))?;
+
+        let key = relative_path.to_string_lossy().into_owned();
+        let buckets = state.metadata_or_insert_with(CrashBucketsMetadata::default);
+        let inputs = &mut buckets
+            .buckets
+            .entry(key.clone())
+            .or_insert_with(|| {
+                bucket.inputs.clear();
+                bucket
+            })
+            .inputs;
+        if !inputs.contains(&file_name) {
+            inputs.push(file_name);
+        }
+
+        self.write_summary(buckets)?;
+        Self::fire_stats(state, manager)?;
+
+        Ok(CrashTriageMetadata {
+            bucket: key,
+            severity,
+            backtrace_hash,
+            pc,
+        })
+    }
+
+    /// Writes the JSON summary of all buckets
+    fn write_summary(&self, buckets: &CrashBucketsMetadata) -> Result<(), Error> {
+        let summary = serde_json::to_vec_pretty(&buckets.buckets).map_err(|err| {
+            Error::serialize(format!("Failed to json-ify triage summary: {err:?}"))
+        })?;
+        libafl_bolts::fs::write_file_atomic(self.out_dir.join(CRASH_TRIAGE_SUMMARY_FILE), &summary)
+    }
+
+    /// Fires the bucket counts as user stats
+    fn fire_stats<EM, I, S>(state: &mut S, manager: &mut EM) -> Result<(), Error>
+    where
+        EM: EventFirer<I, S>,
+        S: HasMetadata + HasExecutions,
+    {
+        let buckets = state.metadata::<CrashBucketsMetadata>()?;
+        let mut stats = HashMap::new();
+        stats.insert(
+            Cow::Borrowed("crash_buckets"),
+            UserStats::new(
+                UserStatsValue::Number(buckets.count() as u64),
+                AggregatorOps::Max,
+            ),
+        );
+        for severity in [
+            CrashSeverity::Exploitable,
+            CrashSeverity::ProbablyExploitable,
+            CrashSeverity::NotExploitable,
+            CrashSeverity::Unknown,
+        ] {
+            stats.insert(
+                Cow::Owned(format!(
+                    "crash_buckets_{}",
+                    severity.as_str().to_lowercase()
+                )),
+                UserStats::new(
+                    UserStatsValue::Number(buckets.count_with_severity(severity) as u64),
+                    AggregatorOps::Max,
+                ),
+            );
+        }
+        manager.fire(
+            state,
+            EventWithStats::with_current_time(
+                Event::UpdateUserStatsMap {
+                    stats,
+                    phantom: PhantomData,
+                },
+                *state.executions(),
+            ),
+        )
+    }
+}
+
+/// Wraps an objective [`Feedback`] and sorts each new solution into a crash bucket.
+///
+/// The wrapped feedback decides whether an input is a solution, this feedback only adds
+/// [`CrashTriageMetadata`] to the solution and writes it to its bucket directory.
+#[derive(Debug, Clone)]
+pub struct CrashTriageFeedback<A, O> {
+    inner: A,
+    triage: CrashTriage<O>,
+    name: Cow<'static, str>,
+    last_exit_kind: ExitKind,
+}
+
+impl<A, O> CrashTriageFeedback<A, O>
+where
+    A: Named,
+    O: ObserverWithCrashReport + Handled,
+{
+    /// Creates a new [`CrashTriageFeedback`], wrapping the given objective `inner`
+    pub fn new<P>(inner: A, observer: &O, out_dir: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let name = Cow::Owned(format!("CrashTriage({})", inner.name()));
+        Ok(Self {
+            inner,
+            triage: CrashTriage::new(out_dir, observer)?,
+            name,
+            last_exit_kind: ExitKind::Ok,
+        })
+    }
+}
+
+impl<A, O> Named for CrashTriageFeedback<A, O> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<A, O> HasObserverHandle for CrashTriageFeedback<A, O> {
+    type Observer = O;
+
+    fn observer_handle(&self) -> &Handle<O> {
+        &self.triage.o_ref
+    }
+}
+
+impl<A, O, S> StateInitializer<S> for CrashTriageFeedback<A, O>
+where
+    A: StateInitializer<S>,
+{
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        self.inner.init_state(state)
+    }
+}
+
+impl<A, EM, I, O, OT, S> Feedback<EM, I, OT, S> for CrashTriageFeedback<A, O>
+where
+    A: Feedback<EM, I, OT, S>,
+    EM: EventFirer<I, S>,
+    I: Input,
+    O: ObserverWithCrashReport,
+    OT: MatchName,
+    S: HasMetadata + HasExecutions,
+{
+    fn is_interesting(
+        &mut self,
+        state: &mut S,
+        manager: &mut EM,
+        input: &I,
+        observers: &OT,
+        exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        self.last_exit_kind = *exit_kind;
+        self.inner
+            .is_interesting(state, manager, input, observers, exit_kind)
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        self.inner.last_result()
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn append_hit_feedbacks(&self, list: &mut Vec<Cow<'static, str>>) -> Result<(), Error> {
+        self.inner.append_hit_feedbacks(list)
+    }
+
+    fn append_metadata(
+        &mut self,
+        state: &mut S,
+        manager: &mut EM,
+        observers: &OT,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        self.inner
+            .append_metadata(state, manager, observers, testcase)?;
+        let input = testcase
+            .input()
+            .as_ref()
+            .ok_or_else(|| Error::empty_optional("The solution has no input"))?;
+        let meta = self
+            .triage
+            .triage(state, manager, observers, &self.last_exit_kind, input)?;
+        testcase.add_metadata(meta);
+        Ok(())
+    }
+}
+
+/// A stage that re-runs all solutions that have not been triaged yet, e.g., solutions loaded
+/// from a previous campaign, and sorts them into crash buckets.
+///
+/// Note: Will NOT work with in-process executors for crashing solutions, since re-running them
+/// will crash the fuzzer. Solutions that crashed the fuzzer during triage are not retried.
+#[derive(Debug)]
+pub struct CrashTriageStage<I, O, S> {
+    triage: CrashTriage<O>,
+    phantom: PhantomData<(I, S)>,
+}
+
+impl<I, O, S> CrashTriageStage<I, O, S>
+where
+    O: ObserverWithCrashReport + Handled,
+{
+    /// Creates a new [`CrashTriageStage`], writing buckets to `out_dir`
+    pub fn new<P>(out_dir: P, observer: &O) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        Ok(Self {
+            triage: CrashTriage::new(out_dir, observer)?,
+            phantom: PhantomData,
+        })
+    }
+
+    /// Creates a new [`CrashTriageStage`] sharing the configuration of an existing [`CrashTriage`]
+    #[must_use]
+    pub fn with_triage(triage: CrashTriage<O>) -> Self {
+        Self {
+            triage,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<E, EM, I, O, S, Z> Stage<E, EM, S, Z> for CrashTriageStage<I, O, S>
+where
+    E: HasObservers,
+    E::Observers: MatchName,
+    EM: EventFirer<I, S>,
+    I: Input,
+    O: ObserverWithCrashReport,
+    S: HasMetadata + HasExecutions + HasSolutions<I>,
+    Z: ExecutesInput<E, EM, I, S>,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        loop {
+            state.metadata_or_insert_with(CrashBucketsMetadata::default);
+            let handled = &state.metadata::<CrashBucketsMetadata>()?.handled;
+            let mut next = None;
+            let mut id = state.solutions().first();
+            while let Some(cur) = id {
+                if !handled.contains(&cur)
+                    && !state
+                        .solutions()
+                        .get_from_all(cur)?
+                        .borrow()
+                        .has_metadata::<CrashTriageMetadata>()
+                {
+                    next = Some(cur);
+                    break;
+                }
+                id = state.solutions().next(cur);
+            }
+            let Some(id) = next else {
+                return Ok(());
+            };
+
+            // mark as handled first, so that we do not retry solutions that bring down the fuzzer
+            state
+                .metadata_mut::<CrashBucketsMetadata>()?
+                .handled
+                .insert(id);
+
+            let input = state.solutions().cloned_input_for_id(id)?;
+            let exit_kind = fuzzer.execute_input(state, executor, manager, &input)?;
+            let meta = {
+                let observers = executor.observers();
+                self.triage
+                    .triage(state, manager, &*observers, &exit_kind, &input)?
+            };
+            let solutions = state.solutions_mut();
+            solutions.get_from_all(id)?.borrow_mut().add_metadata(meta);
+        }
+    }
+}
+
+impl<I, O, S> Restartable<S> for CrashTriageStage<I, O, S> {
+    fn should_restart(&mut self, _state: &mut S) -> Result<bool, Error> {
+        Ok(true)
+    }
+
+    fn clear_progress(&mut self, _state: &mut S) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{
+        collections::BTreeMap,
+        string::{String, ToString},
+    };
+    use std::fs;
+
+    use libafl_bolts::{
+        Error,
+        rands::StdRand,
+        tuples::{RefIndexable, tuple_list, tuple_list_type},
+    };
+
+    use super::{
+        CRASH_TRIAGE_SUMMARY_FILE, CrashBucket, CrashSeverity, CrashTriageMetadata,
+        CrashTriageStage,
+    };
+    use crate::{
+        HasMetadata, StdFuzzer,
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        events::NopEventManager,
+        executors::{Executor, ExitKind, HasObservers},
+        inputs::{BytesInput, HasTargetBytes},
+        observers::{AsanBacktraceObserver, CrashReport},
+        schedulers::QueueScheduler,
+        stages::Stage,
+        state::{HasSolutions, StdState},
+    };
+
+    /// Crashes with an out-of-bounds write if the input starts with `w`, and with a null read otherwise
+    struct AsanExecutor {
+        observers: tuple_list_type!(AsanBacktraceObserver),
+    }
+
+    impl<EM, S, Z> Executor<EM, BytesInput, S, Z> for AsanExecutor {
+        fn run_target(
+            &mut self,
+            _fuzzer: &mut Z,
+            _state: &mut S,
+            _mgr: &mut EM,
+            input: &BytesInput,
+        ) -> Result<ExitKind, Error> {
+            let output = if input.target_bytes().first() == Some(&b'w') {
+                "==1==ERROR: AddressSanitizer: heap-buffer-overflow on address 0x602000000011 at pc 0x1111 bp 0x7ffd sp 0x7ffd\n\
+                 WRITE of size 1 at 0x602000000011 thread T0\n\
+                 #0 0x1111 in write_oob\n"
+            } else {
+                "==1==ERROR: AddressSanitizer: SEGV on unknown address 0x000000000000 (pc 0x2222 bp 0x1 sp 0x2 T0)\n\
+                 ==1==The signal is caused by a READ memory access.\n\
+                 #0 0x2222 in read_null\n"
+            };
+            self.observers.0.parse_asan_output(output);
+            Ok(ExitKind::Crash)
+        }
+    }
+
+    impl HasObservers for AsanExecutor {
+        type Observers = tuple_list_type!(AsanBacktraceObserver);
+
+        fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+            RefIndexable::from(&self.observers)
+        }
+
+        fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+            RefIndexable::from(&mut self.observers)
+        }
+    }
+
+    #[test]
+    fn test_triage_stage() -> Result<(), Error> {
+        let out_dir = std::env::temp_dir().join(format!("libafl_triage_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&out_dir);
+
+        let observer = AsanBacktraceObserver::default();
+        let mut stage = CrashTriageStage::new(&out_dir, &observer)?;
+        let mut executor = AsanExecutor {
+            observers: tuple_list!(observer),
+        };
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )?;
+        let write = state
+            .solutions_mut()
+            .add(Testcase::new(BytesInput::new(b"write".to_vec())))?;
+        let read = state
+            .solutions_mut()
+            .add(Testcase::new(BytesInput::new(b"read".to_vec())))?;
+        let mut fuzzer = StdFuzzer::new(QueueScheduler::new(), (), ());
+        let mut mgr = NopEventManager::new();
+
+        stage.perform(&mut fuzzer, &mut executor, &mut state, &mut mgr)?;
+
+        let bucket_of = |id| {
+            state
+                .solutions()
+                .get(id)
+                .unwrap()
+                .borrow()
+                .metadata::<CrashTriageMetadata>()
+                .unwrap()
+                .clone()
+        };
+        let write_meta = bucket_of(write);
+        let read_meta = bucket_of(read);
+        assert_eq!(write_meta.severity, CrashSeverity::Exploitable);
+        assert_eq!(write_meta.pc, Some(0x1111));
+        assert_eq!(read_meta.severity, CrashSeverity::NotExploitable);
+        assert_eq!(read_meta.pc, Some(0x2222));
+        assert!(write_meta.bucket.starts_with("EXPLOITABLE"));
+        assert!(read_meta.bucket.starts_with("NOT_EXPLOITABLE"));
+
+        let summary: BTreeMap<String, CrashBucket> =
+            serde_json::from_slice(&fs::read(out_dir.join(CRASH_TRIAGE_SUMMARY_FILE))?).unwrap();
+        assert_eq!(summary.len(), 2);
+        for meta in [&write_meta, &read_meta] {
+            let bucket = &summary[&meta.bucket];
+            assert_eq!(bucket.severity, meta.severity);
+            assert_eq!(bucket.pc, meta.pc);
+            assert_eq!(bucket.inputs.len(), 1);
+            let dir = out_dir.join(&meta.bucket);
+            assert_eq!(fs::read_dir(&dir)?.count(), 1);
+            assert!(dir.join(&bucket.inputs[0]).is_file());
+        }
+        assert_eq!(
+            summary[&write_meta.bucket].error_type.as_deref(),
+            Some("heap-buffer-overflow")
+        );
+
+        // Triaged solutions are not triaged again
+        stage.perform(&mut fuzzer, &mut executor, &mut state, &mut mgr)?;
+        assert_eq!(
+            fs::read(out_dir.join(CRASH_TRIAGE_SUMMARY_FILE))?,
+            serde_json::to_vec_pretty(&summary).unwrap()
+        );
+
+        fs::remove_dir_all(&out_dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_classify_asan_reports() {
+        let report = CrashReport::parse_asan(
+            "==1==ERROR: AddressSanitizer: heap-buffer-overflow on address 0x602000000011 at pc 0x55d4c2a4d1a2 bp 0x7ffd sp 0x7ffd\n\
+             WRITE of size 1 at 0x602000000011 thread T0\n",
+        )
+        .unwrap();
+        assert_eq!(report.error_type, "heap-buffer-overflow");
+        assert_eq!(report.pc, Some(0x55d4c2a4d1a2));
+        assert_eq!(report.address, Some(0x602000000011));
+        assert_eq!(report.is_write, Some(true));
+        assert_eq!(
+            CrashSeverity::classify(&ExitKind::Crash, Some(&report)),
+            CrashSeverity::Exploitable
+        );
+
+        let report = CrashReport::parse_asan(
+            "==1==ERROR: AddressSanitizer: SEGV on unknown address 0x000000000000 (pc 0x5555 bp 0x1 sp 0x2 T0)\n\
+             ==1==The signal is caused by a READ memory access.\n",
+        )
+        .unwrap();
+        assert_eq!(report.error_type, "SEGV");
+        assert_eq!(report.pc, Some(0x5555));
+        assert_eq!(report.is_write, Some(false));
+        assert_eq!(
+            CrashSeverity::classify(&ExitKind::Crash, Some(&report)),
+            CrashSeverity::NotExploitable
+        );
+
+        let report = CrashReport {
+            error_type: "SEGV".to_string(),
+            casr_severity: Some("EXPLOITABLE".to_string()),
+            ..CrashReport::default()
+        };
+        assert_eq!(
+            CrashSeverity::classify(&ExitKind::Crash, Some(&report)),
+            CrashSeverity::Exploitable
+        );
+
+        assert!(CrashReport::parse_asan("no crash here").is_none());
+        assert_eq!(
+            CrashSeverity::classify(&ExitKind::Crash, None),
+            CrashSeverity::Unknown
+        );
+    }
+}
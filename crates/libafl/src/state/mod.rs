@@ -26,6 +26,8 @@ use serde::{Deserialize, Serialize, de::DeserializeOwned};
 mod stack;
 pub use stack::StageStack;
 
+#[cfg(feature = "std")]
+use crate::inputs::{BytesInput, InputConverter};
 #[cfg(feature = "introspection")]
 use crate::monitors::stats::ClientPerfStats;
 use crate::{
@@ -904,6 +906,37 @@ where
         )
     }
 
+    /// Loads initial inputs from the passed-in `in_dirs`, reading each file as [`BytesInput`]
+    /// and converting it with `converter`, e.g., to parse seed files into grammar inputs.
+    /// Files the converter fails on are skipped.
+    pub fn load_initial_inputs_with_converter<E, EM, IC, Z>(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        manager: &mut EM,
+        in_dirs: &[PathBuf],
+        converter: &mut IC,
+    ) -> Result<(), Error>
+    where
+        EM: EventFirer<I, Self>,
+        IC: InputConverter<Self, From = BytesInput, To = I>,
+        Z: Evaluator<E, EM, I, Self>,
+    {
+        self.canonicalize_input_dirs(in_dirs)?;
+        self.continue_loading_initial_inputs_custom(
+            fuzzer,
+            executor,
+            manager,
+            LoadConfig {
+                loader: &mut |_, state, path| {
+                    converter.convert(state, BytesInput::from_file(path)?)
+                },
+                forced: false,
+                exit_on_solution: false,
+            },
+        )
+    }
+
     /// Loads initial inputs from the passed-in `in_dirs`.
     /// Will return a `CorpusError` if a solution is found
     pub fn load_initial_inputs_disallow_solution<E, EM, Z>(
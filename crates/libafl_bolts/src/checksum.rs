@@ -0,0 +1,376 @@
+//! Checksums and digests commonly found in file formats and protocols: `CRC-32`, `Adler-32` and `MD5`.
+//!
+//! These are small, dependency-free implementations, meant to recognize and recompute checksums
+//! in fuzzing inputs, not to be fast or cryptographically hardened.
+
+/// The reversed polynomial of `CRC-32` (IEEE 802.3)
+const CRC32_POLY: u32 = 0xedb8_8320;
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0_u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 0 {
+                crc >> 1
+            } else {
+                (crc >> 1) ^ CRC32_POLY
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+static CRC32_TABLE: [u32; 256] = crc32_table();
+
+/// An incremental `CRC-32` (IEEE 802.3), as used by `PNG`, `zlib` and `Ethernet`
+#[derive(Debug, Clone, Copy)]
+pub struct Crc32 {
+    crc: u32,
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Crc32 {
+    /// Creates a new [`Crc32`] over no data
+    #[must_use]
+    pub fn new() -> Self {
+        Self { crc: !0 }
+    }
+
+    /// Feeds `data` into the checksum
+    pub fn update(&mut self, data: &[u8]) {
+        self.crc = data.iter().fold(self.crc, |crc, byte| {
+            CRC32_TABLE[((crc ^ u32::from(*byte)) & 0xff) as usize] ^ (crc >> 8)
+        });
+    }
+
+    /// The checksum of all data fed so far
+    #[must_use]
+    pub fn finish(&self) -> u32 {
+        !self.crc
+    }
+
+    /// The `CRC-32` of the concatenation of two buffers, from the `CRC-32` of both buffers and the length of the second one,
+    /// like `crc32_combine` of `zlib`.
+    #[must_use]
+    pub fn combine(crc1: u32, crc2: u32, len2: usize) -> u32 {
+        // x^(8 * len2), by squaring x^8
+        let mut power = 1_u32 << 31;
+        let mut square = 1_u32 << 23;
+        let mut len = len2;
+        while len != 0 {
+            if len & 1 == 1 {
+                power = crc32_multiply(power, square);
+            }
+            square = crc32_multiply(square, square);
+            len >>= 1;
+        }
+        crc32_multiply(power, crc1) ^ crc2
+    }
+}
+
+/// Multiplies two polynomials modulo the `CRC-32` polynomial, in the reflected bit order
+fn crc32_multiply(a: u32, mut b: u32) -> u32 {
+    let mut product = 0;
+    let mut mask = 1_u32 << 31;
+    while mask != 0 {
+        if a & mask != 0 {
+            product ^= b;
+        }
+        mask >>= 1;
+        b = if b & 1 == 0 {
+            b >> 1
+        } else {
+            (b >> 1) ^ CRC32_POLY
+        };
+    }
+    product
+}
+
+/// The `CRC-32` (IEEE 802.3) of `data`
+#[must_use]
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(data);
+    crc.finish()
+}
+
+/// The modulus of `Adler-32`
+const ADLER32_MOD: u32 = 65521;
+
+/// An incremental `Adler-32`, as used by `zlib` streams
+#[derive(Debug, Clone, Copy)]
+pub struct Adler32 {
+    a: u32,
+    b: u32,
+}
+
+impl Default for Adler32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Adler32 {
+    /// Creates a new [`Adler32`] over no data
+    #[must_use]
+    pub fn new() -> Self {
+        Self { a: 1, b: 0 }
+    }
+
+    /// Feeds `data` into the checksum
+    pub fn update(&mut self, data: &[u8]) {
+        for byte in data {
+            self.a = (self.a + u32::from(*byte)) % ADLER32_MOD;
+            self.b = (self.b + self.a) % ADLER32_MOD;
+        }
+    }
+
+    /// The checksum of all data fed so far
+    #[must_use]
+    pub fn finish(&self) -> u32 {
+        (self.b << 16) | self.a
+    }
+
+    /// The `Adler-32` of a buffer of length `len`, from the plain sum of its bytes,
+    /// and the sum of its bytes weighted by their distance to the end of the buffer (the last byte has weight 1).
+    #[must_use]
+    pub fn from_sums(len: usize, sum: u64, weighted_sum: u64) -> u32 {
+        let modulus = u64::from(ADLER32_MOD);
+        let a = (1 + sum) % modulus;
+        let b = (weighted_sum + len as u64) % modulus;
+        ((b << 16) | a) as u32
+    }
+}
+
+/// The `Adler-32` of `data`
+#[must_use]
+pub fn adler32(data: &[u8]) -> u32 {
+    let mut adler = Adler32::new();
+    adler.update(data);
+    adler.finish()
+}
+
+const MD5_SHIFTS: [u32; 16] = [7, 12, 17, 22, 5, 9, 14, 20, 4, 11, 16, 23, 6, 10, 15, 21];
+
+const MD5_CONSTANTS: [u32; 64] = [
+    0xd76a_a478,
+    0xe8c7_b756,
+    0x2420_70db,
+    0xc1bd_ceee,
+    0xf57c_0faf,
+    0x4787_c62a,
+    0xa830_4613,
+    0xfd46_9501,
+    0x6980_98d8,
+    0x8b44_f7af,
+    0xffff_5bb1,
+    0x895c_d7be,
+    0x6b90_1122,
+    0xfd98_7193,
+    0xa679_438e,
+    0x49b4_0821,
+    0xf61e_2562,
+    0xc040_b340,
+    0x265e_5a51,
+    0xe9b6_c7aa,
+    0xd62f_105d,
+    0x0244_1453,
+    0xd8a1_e681,
+    0xe7d3_fbc8,
+    0x21e1_cde6,
+    0xc337_07d6,
+    0xf4d5_0d87,
+    0x455a_14ed,
+    0xa9e3_e905,
+    0xfcef_a3f8,
+    0x676f_02d9,
+    0x8d2a_4c8a,
+    0xfffa_3942,
+    0x8771_f681,
+    0x6d9d_6122,
+    0xfde5_380c,
+    0xa4be_ea44,
+    0x4bde_cfa9,
+    0xf6bb_4b60,
+    0xbebf_bc70,
+    0x289b_7ec6,
+    0xeaa1_27fa,
+    0xd4ef_3085,
+    0x0488_1d05,
+    0xd9d4_d039,
+    0xe6db_99e5,
+    0x1fa2_7cf8,
+    0xc4ac_5665,
+    0xf429_2244,
+    0x432a_ff97,
+    0xab94_23a7,
+    0xfc93_a039,
+    0x655b_59c3,
+    0x8f0c_cc92,
+    0xffef_f47d,
+    0x8584_5dd1,
+    0x6fa8_7e4f,
+    0xfe2c_e6e0,
+    0xa301_4314,
+    0x4e08_11a1,
+    0xf753_7e82,
+    0xbd3a_f235,
+    0x2ad7_d2bb,
+    0xeb86_d391,
+];
+
+/// An incremental `MD5` digest, following RFC 1321
+#[derive(Debug, Clone)]
+pub struct Md5 {
+    state: [u32; 4],
+    block: [u8; 64],
+    block_len: usize,
+    total_len: u64,
+}
+
+impl Default for Md5 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Md5 {
+    /// Creates a new [`Md5`] over no data
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            state: [0x6745_2301, 0xefcd_ab89, 0x98ba_dcfe, 0x1032_5476],
+            block: [0; 64],
+            block_len: 0,
+            total_len: 0,
+        }
+    }
+
+    /// Feeds `data` into the digest
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.total_len = self.total_len.wrapping_add(data.len() as u64);
+        while !data.is_empty() {
+            let take = (64 - self.block_len).min(data.len());
+            self.block[self.block_len..self.block_len + take].copy_from_slice(&data[..take]);
+            self.block_len += take;
+            data = &data[take..];
+            if self.block_len == 64 {
+                self.compress();
+                self.block_len = 0;
+            }
+        }
+    }
+
+    #[expect(clippy::many_single_char_names)] // named like in RFC 1321
+    fn compress(&mut self) {
+        let mut words = [0_u32; 16];
+        for (word, chunk) in words.iter_mut().zip(self.block.chunks_exact(4)) {
+            *word = u32::from_le_bytes(chunk.try_into().unwrap());
+        }
+        let [mut a, mut b, mut c, mut d] = self.state;
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let rotated = a
+                .wrapping_add(f)
+                .wrapping_add(MD5_CONSTANTS[i])
+                .wrapping_add(words[g])
+                .rotate_left(MD5_SHIFTS[(i / 16) * 4 + i % 4]);
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(rotated);
+        }
+        for (state, value) in self.state.iter_mut().zip([a, b, c, d]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+
+    /// The digest of all data fed so far
+    #[must_use]
+    pub fn finish(&self) -> [u8; 16] {
+        let mut md5 = self.clone();
+        let bit_len = md5.total_len.wrapping_mul(8);
+        md5.update(&[0x80]);
+        while md5.block_len != 56 {
+            md5.update(&[0]);
+        }
+        md5.update(&bit_len.to_le_bytes());
+        let mut digest = [0; 16];
+        for (chunk, state) in digest.chunks_exact_mut(4).zip(md5.state) {
+            chunk.copy_from_slice(&state.to_le_bytes());
+        }
+        digest
+    }
+}
+
+/// The `MD5` digest of `data`
+#[must_use]
+pub fn md5(data: &[u8]) -> [u8; 16] {
+    let mut md5 = Md5::new();
+    md5.update(data);
+    md5.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Adler32, Crc32, adler32, crc32, md5};
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+        assert_eq!(
+            md5(b""),
+            *b"\xd4\x1d\x8c\xd9\x8f\x00\xb2\x04\xe9\x80\x09\x98\xec\xf8\x42\x7e"
+        );
+        assert_eq!(
+            md5(
+                b"12345678901234567890123456789012345678901234567890123456789012345678901234567890"
+            ),
+            *b"\x57\xed\xf4\xa2\x2b\xe3\xc9\x55\xac\x49\xda\x2e\x21\x07\xb6\x7a"
+        );
+    }
+
+    #[test]
+    fn test_crc32_combine() {
+        let data = b"The quick brown fox jumps over the lazy dog";
+        for split in [0, 1, 9, data.len()] {
+            let (first, second) = data.split_at(split);
+            assert_eq!(
+                Crc32::combine(crc32(first), crc32(second), second.len()),
+                crc32(data)
+            );
+        }
+    }
+
+    #[test]
+    fn test_adler32_from_sums() {
+        let data = b"Wikipedia";
+        let sum = data.iter().map(|b| u64::from(*b)).sum();
+        let weighted_sum = data
+            .iter()
+            .enumerate()
+            .map(|(i, b)| (data.len() - i) as u64 * u64::from(*b))
+            .sum();
+        assert_eq!(
+            Adler32::from_sums(data.len(), sum, weighted_sum),
+            adler32(data)
+        );
+    }
+}
@@ -58,6 +58,7 @@ pub extern crate alloc;
 pub use build_id2 as build_id;
 #[cfg(feature = "alloc")]
 pub use serde_anymap::anymap;
+pub mod checksum;
 #[cfg(all(
     any(feature = "cli", feature = "frida_cli", feature = "qemu_cli"),
     feature = "std"
@@ -8,6 +8,7 @@ use core::str;
     feature = "cmplog-instructions",
     feature = "ctx",
     feature = "dump-cfg",
+    feature = "distance",
 ))]
 use std::path::PathBuf;
 #[cfg(any(
@@ -18,6 +19,7 @@ use std::path::PathBuf;
     feature = "cmplog-instructions",
     feature = "ctx",
     feature = "dump-cfg",
+    feature = "distance",
 ))]
 use std::process::Command;
 use std::{env, fs::File, io::Write, path::Path};
@@ -30,6 +32,7 @@ use std::{env, fs::File, io::Write, path::Path};
     feature = "cmplog-instructions",
     feature = "ctx",
     feature = "dump-cfg",
+    feature = "distance",
 ))]
 #[expect(clippy::too_many_arguments)]
 fn build_pass(
@@ -390,6 +393,18 @@ pub const LIBAFL_CC_LLVM_VERSION: Option<usize> = None;
         false,
     );
 
+    #[cfg(feature = "distance")]
+    build_pass(
+        bindir_path,
+        out_dir,
+        &cxxflags,
+        &ldflags,
+        src_dir,
+        "distance-pass.cc",
+        None,
+        true,
+    );
+
     cc::Build::new()
         .file(src_dir.join("no-link-rt.c"))
         .compile("no-link-rt");
@@ -4,7 +4,7 @@
 extern crate alloc;
 
 use alloc::collections::BinaryHeap;
-use core::marker::PhantomData;
+use core::{cmp::Reverse, marker::PhantomData};
 use std::collections::{HashMap, HashSet};
 
 use serde::{Deserialize, Serialize};
@@ -116,6 +116,49 @@ where
         self.func_to_entry_bb
             .insert(func_name.to_string(), entry_info);
     }
+
+    /// Inserts a basic block as a node keyed by its unique `loc`, instead of an edge keyed by the AFL edge index.
+    ///
+    /// In a graph built from blocks, `successors` are the `loc`s of the successor blocks, so
+    /// [`ControlFlowGraph::calculate_distances_to_all_edges`] returns the distances between blocks, without collisions.
+    /// This fits dumps that number the blocks, like the one of the `DumpCfg` pass.
+    pub fn insert_block(
+        &mut self,
+        func_name: &str,
+        loc: usize,
+        successors: Vec<usize>,
+        metadata: Option<T>,
+    ) {
+        if loc >= self.edges.len() {
+            self.edges.resize_with(loc + 1, || None);
+        }
+        let block = CfgEdge {
+            xored_loc: loc,
+            top_node_loc: loc,
+            bottom_node_loc: loc,
+            calling_func: func_name.to_string(),
+            successor_edges: successors.clone(),
+            successor_basic_blocks: successors,
+            metadata,
+        };
+        self.insert_edge(loc, block);
+    }
+
+    /// Marks the block at `loc`, inserted with [`ControlFlowGraph::insert_block`], as the entry of `func_name`.
+    pub fn insert_entry_block(&mut self, func_name: &str, loc: usize) {
+        let successor_edges = self
+            .get_edge(loc)
+            .map(|block| block.successor_edges.clone())
+            .unwrap_or_default();
+        self.create_func_entry(
+            func_name,
+            EntryBasicBlockInfo {
+                calling_func: func_name.to_string(),
+                node_loc: loc,
+                successor_edges,
+            },
+        );
+    }
 }
 
 /// Helper for reading CFG dump files.
@@ -271,13 +314,13 @@ where
     /// Get the edge at the index of the coverage map AFL inserts to.
     #[must_use]
     pub fn get_edge(&self, xored_loc: usize) -> Option<&CfgEdge<T>> {
-        self.edges[xored_loc].as_ref()
+        self.edges.get(xored_loc)?.as_ref()
     }
 
     /// Get the mutable edge at the index of the coverage map AFL inserts to.
     #[must_use]
     pub fn get_edge_mut(&mut self, xored_loc: usize) -> Option<&mut CfgEdge<T>> {
-        self.edges[xored_loc].as_mut()
+        self.edges.get_mut(xored_loc)?.as_mut()
     }
 
     /// Get entry basic block information of a function.
@@ -300,15 +343,15 @@ where
     pub fn calculate_distances_to_all_edges(&self, start: usize) -> HashMap<usize, u32> {
         let mut distances: HashMap<usize, u32> = HashMap::new();
         let mut visited = HashSet::new();
-        let mut to_visit = BinaryHeap::new(); // BinaryHeap<(loc, distance)>
+        let mut to_visit = BinaryHeap::new(); // BinaryHeap<Reverse<(distance, loc)>>, closest first
         let initial_weight = self
             .get_edge(start)
             .expect("unknown destination")
             .get_weight();
         distances.insert(start, initial_weight);
-        to_visit.push((start, initial_weight));
+        to_visit.push(Reverse((initial_weight, start)));
 
-        while let Some((edge, distance)) = to_visit.pop() {
+        while let Some(Reverse((distance, edge))) = to_visit.pop() {
             if !visited.insert(edge) {
                 continue;
             }
@@ -323,7 +366,7 @@ where
 
                     if is_shorter {
                         distances.insert(*successor, new_distance);
-                        to_visit.push((*successor, new_distance));
+                        to_visit.push(Reverse((new_distance, *successor)));
                     }
                 }
             }
@@ -403,4 +446,27 @@ mod tests {
         assert_eq!(*distances.get(&((0x691f >> 1) ^ 0xa3c5)).unwrap(), 2);
         assert!(!distances.contains_key(&((41864 >> 1) ^ 0xcde2)));
     }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // Testcase takes too long in miri.
+    fn test_block_graph() {
+        // 0 -> 1 -> 2 -> 3 and the shortcut 0 -> 3, with block 5 being the entry of another function
+        let mut cfg: ControlFlowGraph<TestMetadata> = ControlFlowGraph::new();
+        cfg.insert_block("f", 0, vec![3, 1], None);
+        cfg.insert_block("f", 1, vec![2], None);
+        cfg.insert_block("f", 2, vec![3], None);
+        cfg.insert_block("f", 3, vec![], None);
+        cfg.insert_block("g", 70_000, vec![], None);
+        cfg.insert_entry_block("f", 0);
+        cfg.insert_entry_block("g", 70_000);
+
+        assert_eq!(cfg.get_entry("f").unwrap().successor_edges, vec![3, 1]);
+        assert_eq!(cfg.get_entry("g").unwrap().node_loc, 70_000);
+        let distances = cfg.calculate_distances_to_all_edges(0);
+        assert_eq!(distances[&0], 1);
+        assert_eq!(distances[&1], 2);
+        assert_eq!(distances[&2], 3);
+        assert_eq!(distances[&3], 2);
+        assert!(!distances.contains_key(&70_000));
+    }
 }
@@ -0,0 +1,136 @@
+/*
+   LibAFL - Distance LLVM pass
+   --------------------------------------------------
+
+   Instruments every basic block that has a distance to the fuzzing targets,
+   as computed by `libafl_cc::distance` from the dumps of the DumpCfg pass.
+   Each instrumented block adds its distance to `__libafl_distance_sum` and
+   increments `__libafl_distance_count`, so the runtime can compute the
+   average distance of an execution (AFLGo-style directed fuzzing).
+
+   The distances are read from the file named by the `LIBAFL_DISTANCE_FILE`
+   environment variable, one `function,block,distance` line per block.
+
+   Copyright 2026 AFLplusplus Project. All rights reserved.
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at:
+
+     http://www.apache.org/licenses/LICENSE-2.0
+
+*/
+
+#include <stdio.h>
+#include <stdlib.h>
+
+#include <fstream>
+#include <map>
+#include <string>
+
+#include "common-llvm.h"
+
+using namespace llvm;
+
+namespace {
+
+class DistancePass : public PassInfoMixin<DistancePass> {
+ public:
+  DistancePass() {
+  }
+
+  PreservedAnalyses run(Module &M, ModuleAnalysisManager &MAM);
+
+ protected:
+  // function name -> (block index -> distance)
+  std::map<std::string, std::map<uint32_t, uint64_t>> distances;
+
+  void loadDistances(const char *path);
+};
+
+}  // namespace
+
+extern "C" ::llvm::PassPluginLibraryInfo LLVM_ATTRIBUTE_WEAK
+llvmGetPassPluginInfo() {
+  return {LLVM_PLUGIN_API_VERSION, "DistancePass", "v0.1",
+          /* lambda to insert our pass into the pass pipeline. */
+          [](PassBuilder &PB) {
+            PB.registerOptimizerLastEPCallback(
+                [](ModulePassManager &MPM, OptimizationLevel OL
+#if LLVM_VERSION_MAJOR >= 20
+                   ,
+                   ThinOrFullLTOPhase Phase
+#endif
+
+                ) { MPM.addPass(DistancePass()); });
+          }};
+}
+
+void DistancePass::loadDistances(const char *path) {
+  std::ifstream in(path);
+  if (!in.is_open()) { FATAL("Could not open the distance file %s\n", path); }
+
+  std::string line;
+  while (std::getline(in, line)) {
+    // Function names may contain commas, so split from the right
+    size_t second = line.rfind(',');
+    if (second == std::string::npos || second == 0) { continue; }
+    size_t first = line.rfind(',', second - 1);
+    if (first == std::string::npos) { continue; }
+
+    std::string func_name = line.substr(0, first);
+    uint32_t    bb = std::stoul(line.substr(first + 1, second - first - 1));
+    uint64_t    distance = std::stoull(line.substr(second + 1));
+    distances[func_name][bb] = distance;
+  }
+}
+
+PreservedAnalyses DistancePass::run(Module &M, ModuleAnalysisManager &MAM) {
+  const char *distance_file = getenv("LIBAFL_DISTANCE_FILE");
+  if (!distance_file) {
+    // First compilation, only the CFG is dumped
+    return PreservedAnalyses::all();
+  }
+  loadDistances(distance_file);
+
+  LLVMContext  &C = M.getContext();
+  IntegerType  *Int64Ty = IntegerType::getInt64Ty(C);
+  GlobalVariable *DistanceSum = new GlobalVariable(
+      M, Int64Ty, false, GlobalValue::ExternalLinkage, 0,
+      "__libafl_distance_sum");
+  GlobalVariable *DistanceCount = new GlobalVariable(
+      M, Int64Ty, false, GlobalValue::ExternalLinkage, 0,
+      "__libafl_distance_count");
+
+  for (auto &F : M) {
+    if (isIgnoreFunction(&F)) { continue; }
+    auto func_distances = distances.find(std::string(F.getName()));
+    if (func_distances == distances.end()) { continue; }
+
+    // Same numbering as the DumpCfg pass
+    uint32_t bb_cnt = 0;
+    for (auto &BB : F) {
+      auto distance = func_distances->second.find(bb_cnt);
+      bb_cnt++;
+      if (distance == func_distances->second.end()) { continue; }
+
+      BasicBlock::iterator IP = BB.getFirstInsertionPt();
+      IRBuilder<>          IRB(&(*IP));
+
+      LoadInst *Sum = IRB.CreateLoad(Int64Ty, DistanceSum);
+      Sum->setMetadata(M.getMDKindID("nosanitize"), MDNode::get(C, None));
+      IRB.CreateStore(
+             IRB.CreateAdd(Sum, ConstantInt::get(Int64Ty, distance->second)),
+             DistanceSum)
+          ->setMetadata(M.getMDKindID("nosanitize"), MDNode::get(C, None));
+
+      LoadInst *Count = IRB.CreateLoad(Int64Ty, DistanceCount);
+      Count->setMetadata(M.getMDKindID("nosanitize"), MDNode::get(C, None));
+      IRB.CreateStore(IRB.CreateAdd(Count, ConstantInt::get(Int64Ty, 1)),
+                      DistanceCount)
+          ->setMetadata(M.getMDKindID("nosanitize"), MDNode::get(C, None));
+    }
+  }
+
+  return PreservedAnalyses::none();
+}
@@ -0,0 +1,419 @@
+//! Distances of basic blocks to target sites, for AFLGo-style directed fuzzing.
+//! Needs the `distance` feature, which also builds the distance pass.
+//!
+//! Directed fuzzing needs two compilations of the target:
+//! 1. Compile with [`crate::LLVMPasses::DumpCfg`] and `CFG_OUTPUT_PATH` set, which dumps the
+//!    control flow graph, the calls and the source lines of every module as a `.cfg` file.
+//! 2. Load the dumps into a [`DistanceCalculator`] and write the distances to the targets with
+//!    [`DistanceCalculator::write_distance_file`].
+//! 3. Compile again with [`crate::LLVMPasses::Distance`] and `LIBAFL_DISTANCE_FILE` pointing to
+//!    the distance file. Every block with a distance then adds it to `__libafl_distance_sum`
+//!    and increments `__libafl_distance_count`, which `libafl_targets` exposes as an observer.
+//!
+//! The distances follow `AFLGo`: the function-level distance is the harmonic mean of the call graph
+//! distances to the target functions, and the block-level distance is the harmonic mean of the
+//! distances to the blocks of the same function that are targets or call towards a target.
+//! The blocks of all functions form a [`ControlFlowGraph`], whose shortest paths give the block distances.
+
+extern crate alloc;
+
+use alloc::collections::{BTreeMap, VecDeque};
+use core::str::FromStr;
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use serde::Deserialize;
+
+use crate::{
+    Error,
+    cfg::{ControlFlowGraph, HasWeight},
+};
+
+/// The factor `AFLGo` applies to the function-level distance of a call site
+const CALL_DISTANCE_FACTOR: f64 = 10.0;
+/// The factor to turn distances into the integers written to the distance file
+const DISTANCE_SCALE: f64 = 100.0;
+
+/// A target site for directed fuzzing
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DistanceTarget {
+    /// The entry of the function with the given (mangled) name
+    Function(String),
+    /// All blocks with instructions from the given source line
+    Line {
+        /// The file name, directories are ignored
+        file: String,
+        /// The line in the file
+        line: u32,
+    },
+}
+
+impl FromStr for DistanceTarget {
+    type Err = Error;
+
+    /// Parses `file:line` as a [`DistanceTarget::Line`] and anything else as a [`DistanceTarget::Function`]
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(Error::InvalidArguments("Empty distance target".to_string()));
+        }
+        if let Some((file, line)) = s.rsplit_once(':')
+            && let Ok(line) = line.parse()
+        {
+            let file = file.rsplit(['/', '\\']).next().unwrap_or(file);
+            return Ok(Self::Line {
+                file: file.to_string(),
+                line,
+            });
+        }
+        Ok(Self::Function(s.to_string()))
+    }
+}
+
+/// The JSON dump of a module, as written by the `DumpCfg` pass
+#[derive(Debug, Default, Deserialize)]
+struct CfgDump {
+    #[serde(default)]
+    edges: HashMap<String, Vec<Option<Vec<u32>>>>,
+    #[serde(default)]
+    calls: HashMap<String, HashMap<String, Vec<String>>>,
+    #[serde(default)]
+    lines: HashMap<String, HashMap<String, Vec<String>>>,
+    #[serde(default)]
+    entries: HashMap<String, u32>,
+}
+
+/// Every control flow edge between two blocks counts as one step
+#[derive(Debug)]
+struct BlockStep;
+
+impl HasWeight<BlockStep> for BlockStep {
+    fn compute(_metadata: Option<&BlockStep>) -> u32 {
+        1
+    }
+}
+
+/// A function of the dumps, whose blocks are the nodes `first_loc..first_loc + blocks` of the [`ControlFlowGraph`]
+#[derive(Debug, Default)]
+struct FunctionInfo {
+    first_loc: usize,
+    blocks: usize,
+    calls: HashMap<u32, Vec<String>>,
+    lines: HashMap<u32, Vec<String>>,
+}
+
+/// Computes the distance of every basic block to a set of [`DistanceTarget`]s
+#[derive(Debug)]
+pub struct DistanceCalculator {
+    cfg: ControlFlowGraph<BlockStep>,
+    functions: HashMap<String, FunctionInfo>,
+    next_loc: usize,
+}
+
+impl Default for DistanceCalculator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DistanceCalculator {
+    /// Creates an empty [`DistanceCalculator`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            cfg: ControlFlowGraph::new(),
+            functions: HashMap::new(),
+            next_loc: 0,
+        }
+    }
+
+    /// Loads all `.cfg` dumps in `dir`, the `CFG_OUTPUT_PATH` of the first compilation
+    pub fn from_dir<P>(dir: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let mut calculator = Self::new();
+        for entry in fs::read_dir(dir).map_err(Error::Io)? {
+            let path = entry.map_err(Error::Io)?.path();
+            if path.extension().is_some_and(|ext| ext == "cfg") {
+                calculator.add_dump(&fs::read_to_string(&path).map_err(Error::Io)?)?;
+            }
+        }
+        Ok(calculator)
+    }
+
+    /// Adds the dump of a module
+    pub fn add_dump(&mut self, content: &str) -> Result<(), Error> {
+        let dump: CfgDump = serde_json::from_str(content)
+            .map_err(|err| Error::Unknown(format!("Could not parse CFG dump: {err}")))?;
+
+        let parse_bb = |bb: &str| {
+            bb.parse::<u32>()
+                .map_err(|err| Error::Unknown(format!("Invalid block {bb} in CFG dump: {err}")))
+        };
+        // Functions defined in several modules keep their first definition
+        let mut defined = HashSet::new();
+        for (name, edges) in dump.edges {
+            if self.functions.contains_key(&name) {
+                continue;
+            }
+            let first_loc = self.next_loc;
+            let blocks = edges.len();
+            for (loc, successors) in (first_loc..).zip(edges) {
+                let successors = successors
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|bb| {
+                        let bb = bb as usize;
+                        if bb < blocks {
+                            Ok(first_loc + bb)
+                        } else {
+                            Err(Error::Unknown(format!(
+                                "Invalid successor {bb} in CFG dump of {name}"
+                            )))
+                        }
+                    })
+                    .collect::<Result<_, _>>()?;
+                self.cfg.insert_block(&name, loc, successors, None);
+            }
+            self.next_loc += blocks;
+            self.functions.insert(
+                name.clone(),
+                FunctionInfo {
+                    first_loc,
+                    blocks,
+                    ..FunctionInfo::default()
+                },
+            );
+            defined.insert(name);
+        }
+        for (name, calls) in dump.calls {
+            if let Some(function) = self.functions.get_mut(&name)
+                && defined.contains(&name)
+            {
+                for (bb, callees) in calls {
+                    function.calls.insert(parse_bb(&bb)?, callees);
+                }
+            }
+        }
+        for (name, lines) in dump.lines {
+            if let Some(function) = self.functions.get_mut(&name)
+                && defined.contains(&name)
+            {
+                for (bb, lines) in lines {
+                    function.lines.insert(parse_bb(&bb)?, lines);
+                }
+            }
+        }
+        for (name, entry) in dump.entries {
+            if let Some(function) = self.functions.get(&name)
+                && defined.contains(&name)
+                && (entry as usize) < function.blocks
+            {
+                self.cfg
+                    .insert_entry_block(&name, function.first_loc + entry as usize);
+            }
+        }
+        Ok(())
+    }
+
+    /// The blocks matching the targets, by function
+    fn target_blocks(&self, targets: &[DistanceTarget]) -> HashMap<&str, HashSet<u32>> {
+        let mut blocks: HashMap<&str, HashSet<u32>> = HashMap::new();
+        for target in targets {
+            match target {
+                DistanceTarget::Function(name) => {
+                    if let Some((name, function)) = self.functions.get_key_value(name)
+                        && let Some(entry) = self.cfg.get_entry(name)
+                        && let Ok(bb) = u32::try_from(entry.node_loc - function.first_loc)
+                    {
+                        blocks.entry(name).or_default().insert(bb);
+                    }
+                }
+                DistanceTarget::Line { file, line } => {
+                    let site = format!("{file}:{line}");
+                    for (name, function) in &self.functions {
+                        for (bb, lines) in &function.lines {
+                            if lines.contains(&site) {
+                                blocks.entry(name).or_default().insert(*bb);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        blocks
+    }
+
+    /// The function-level distances to the functions containing targets
+    fn function_distances<'a>(
+        &'a self,
+        target_functions: &HashSet<&'a str>,
+    ) -> HashMap<&'a str, f64> {
+        // callee -> callers
+        let mut callers: HashMap<&str, HashSet<&str>> = HashMap::new();
+        for (name, function) in &self.functions {
+            for callee in function.calls.values().flatten() {
+                callers
+                    .entry(callee.as_str())
+                    .or_default()
+                    .insert(name.as_str());
+            }
+        }
+
+        // (sum of 1 / (1 + d), number of reachable targets)
+        let mut harmonic: HashMap<&str, (f64, u32)> = HashMap::new();
+        for target in target_functions {
+            let mut visited = HashSet::from([*target]);
+            let mut queue = VecDeque::from([(*target, 0_u32)]);
+            while let Some((function, distance)) = queue.pop_front() {
+                let entry = harmonic.entry(function).or_default();
+                entry.0 += 1.0 / (1.0 + f64::from(distance));
+                entry.1 += 1;
+                for caller in callers.get(function).into_iter().flatten() {
+                    if visited.insert(caller) {
+                        queue.push_back((caller, distance + 1));
+                    }
+                }
+            }
+        }
+        harmonic
+            .into_iter()
+            .map(|(function, (sum, count))| (function, f64::from(count) / sum))
+            .collect()
+    }
+
+    /// Computes the distance of every block that can reach a target.
+    ///
+    /// Blocks containing a target have distance `0`, unreachable blocks are left out.
+    #[must_use]
+    pub fn block_distances(&self, targets: &[DistanceTarget]) -> BTreeMap<(String, u32), f64> {
+        let target_blocks = self.target_blocks(targets);
+        let target_functions = target_blocks.keys().copied().collect();
+        let function_distances = self.function_distances(&target_functions);
+
+        let mut distances = BTreeMap::new();
+        for (name, function) in &self.functions {
+            // The blocks of this function leading towards a target, with their own distance
+            let mut sites: HashMap<u32, f64> = HashMap::new();
+            for bb in target_blocks.get(name.as_str()).into_iter().flatten() {
+                sites.insert(*bb, 0.0);
+            }
+            for (bb, callees) in &function.calls {
+                let call_distance = callees
+                    .iter()
+                    .filter_map(|callee| function_distances.get(callee.as_str()))
+                    .copied()
+                    .reduce(f64::min);
+                if let Some(call_distance) = call_distance {
+                    let site = sites.entry(*bb).or_insert(f64::INFINITY);
+                    *site = site.min(CALL_DISTANCE_FACTOR * call_distance);
+                }
+            }
+            if sites.is_empty() {
+                continue;
+            }
+
+            // The shortest paths from each block of this function to its sites
+            let targets_here = target_blocks.get(name.as_str());
+            for (bb, loc) in (0_u32..).zip(function.first_loc..function.first_loc + function.blocks)
+            {
+                let reachable = self.cfg.calculate_distances_to_all_edges(loc);
+                let start = reachable[&loc];
+                // (sum of 1 / (1 + site distance + cfg distance), number of reachable sites)
+                let (sum, count) = sites
+                    .iter()
+                    .filter_map(|(site, site_distance)| {
+                        let cfg_distance = reachable.get(&(function.first_loc + *site as usize))?;
+                        Some(1.0 / (1.0 + site_distance + f64::from(cfg_distance - start)))
+                    })
+                    .fold((0.0, 0_u32), |(sum, count), term| (sum + term, count + 1));
+                if count == 0 {
+                    continue;
+                }
+                let distance = if targets_here.is_some_and(|blocks| blocks.contains(&bb)) {
+                    0.0
+                } else {
+                    f64::from(count) / sum
+                };
+                distances.insert((name.clone(), bb), distance);
+            }
+        }
+        distances
+    }
+
+    /// Writes the distances to the targets as `function,block,distance` lines, for the `Distance` pass.
+    ///
+    /// The distances are scaled by 100 and rounded. Returns the number of blocks with a distance.
+    #[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn write_distance_file<P>(
+        &self,
+        targets: &[DistanceTarget],
+        path: P,
+    ) -> Result<usize, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let distances = self.block_distances(targets);
+        if distances.is_empty() {
+            return Err(Error::InvalidArguments(
+                "None of the distance targets can be reached".to_string(),
+            ));
+        }
+        let mut file = BufWriter::new(File::create(path).map_err(Error::Io)?);
+        for ((function, bb), distance) in &distances {
+            let distance = (distance * DISTANCE_SCALE).round() as u64;
+            writeln!(file, "{function},{bb},{distance}").map_err(Error::Io)?;
+        }
+        file.flush().map_err(Error::Io)?;
+        Ok(distances.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DistanceCalculator, DistanceTarget};
+
+    // main: 0 -> 1 -> 2, block 1 calls parse
+    // parse: 0 -> 1, 0 -> 2, block 2 is the target line
+    const DUMP: &str = r#"{
+        "edges": {"main": [[1], [2], []], "parse": [[1, 2], [], []]},
+        "calls": {"main": {"1": ["parse"]}},
+        "lines": {"parse": {"2": ["parse.c:42"]}, "main": {"0": ["main.c:3"]}},
+        "entries": {"main": 0, "parse": 0}
+    }"#;
+
+    #[test]
+    fn target_parsing() {
+        assert_eq!(
+            "src/parse.c:42".parse::<DistanceTarget>().unwrap(),
+            DistanceTarget::Line {
+                file: "parse.c".to_string(),
+                line: 42
+            }
+        );
+        assert_eq!(
+            "_ZN3foo3barEv".parse::<DistanceTarget>().unwrap(),
+            DistanceTarget::Function("_ZN3foo3barEv".to_string())
+        );
+    }
+
+    #[test]
+    fn block_distances() {
+        let mut calculator = DistanceCalculator::new();
+        calculator.add_dump(DUMP).unwrap();
+        let distances = calculator.block_distances(&["parse.c:42".parse().unwrap()]);
+
+        let distance = |function: &str, bb: u32| distances.get(&(function.to_string(), bb));
+        assert_eq!(distance("parse", 2), Some(&0.0));
+        assert_eq!(distance("parse", 0), Some(&2.0));
+        assert!(distance("parse", 1).is_none());
+        // The call site is closer than the block before it
+        assert!(distance("main", 1).unwrap() < distance("main", 0).unwrap());
+        assert!(distance("main", 2).is_none());
+    }
+}
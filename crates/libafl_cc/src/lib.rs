@@ -51,6 +51,10 @@ pub mod cfg;
 pub use cfg::{CfgEdge, ControlFlowGraph, EntryBasicBlockInfo, HasWeight};
 pub mod clang;
 pub use clang::{ClangWrapper, LLVMPasses};
+#[cfg(feature = "distance")]
+pub mod distance;
+#[cfg(feature = "distance")]
+pub use distance::{DistanceCalculator, DistanceTarget};
 pub mod libtool;
 pub use libtool::LibtoolWrapper;
 
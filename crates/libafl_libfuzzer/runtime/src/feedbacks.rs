@@ -1,11 +1,11 @@
 use alloc::rc::Rc;
 use core::{cell::RefCell, fmt::Debug};
-use std::borrow::Cow;
+use std::{borrow::Cow, path::PathBuf};
 
 use libafl::{
     Error, HasMetadata, alloc,
     corpus::Testcase,
-    executors::ExitKind,
+    executors::{ExitKind, hooks::leak::LeakCheckHook},
     feedbacks::{Feedback, MinMapFeedback, StateInitializer},
     inputs::{BytesInput, Input},
     state::HasCorpus,
@@ -63,29 +63,55 @@ where
     }
 }
 
+/// Why an input is a solution, like the artifact names of libfuzzer
+#[derive(Deserialize, Serialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LibfuzzerCrashCause {
+    Crash,
+    Oom,
+    Timeout,
+    /// `LeakSanitizer` found a leak after the target returned normally
+    Leak,
+    Uncategorized(ExitKind),
+}
+
+impl LibfuzzerCrashCause {
+    /// The prefix of the artifact name
+    fn name(self) -> &'static str {
+        match self {
+            LibfuzzerCrashCause::Crash => "crash",
+            LibfuzzerCrashCause::Oom => "oom",
+            LibfuzzerCrashCause::Timeout => "timeout",
+            LibfuzzerCrashCause::Leak => "leak",
+            LibfuzzerCrashCause::Uncategorized(_) => "uncategorized",
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug)]
 pub struct LibfuzzerCrashCauseMetadata {
-    kind: ExitKind,
+    cause: LibfuzzerCrashCause,
 }
 
 impl_serdeany!(LibfuzzerCrashCauseMetadata);
 
 impl LibfuzzerCrashCauseMetadata {
-    pub fn kind(&self) -> ExitKind {
-        self.kind
+    pub fn cause(&self) -> LibfuzzerCrashCause {
+        self.cause
     }
 }
 
 #[derive(Debug)]
 pub struct LibfuzzerCrashCauseFeedback {
     artifact_prefix: ArtifactPrefix,
+    exact_artifact_path: Option<PathBuf>,
     exit_kind: ExitKind,
 }
 
 impl LibfuzzerCrashCauseFeedback {
-    pub fn new(artifact_prefix: ArtifactPrefix) -> Self {
+    pub fn new(artifact_prefix: ArtifactPrefix, exact_artifact_path: Option<PathBuf>) -> Self {
         Self {
             artifact_prefix,
+            exact_artifact_path,
             exit_kind: ExitKind::Ok,
         }
     }
@@ -100,6 +126,10 @@ impl Named for LibfuzzerCrashCauseFeedback {
 
 impl LibfuzzerCrashCauseFeedback {
     fn set_filename<I: Input>(&self, prefix: &str, testcase: &mut Testcase<I>) {
+        if let Some(path) = &self.exact_artifact_path {
+            *testcase.file_path_mut() = Some(path.clone());
+            return;
+        }
         let base = if let Some(filename) = testcase.filename() {
             filename.clone()
         } else {
@@ -142,32 +172,15 @@ where
         _observers: &OT,
         testcase: &mut Testcase<BytesInput>,
     ) -> Result<(), Error> {
-        match self.exit_kind {
-            ExitKind::Crash | ExitKind::Oom if OomFeedback::oomed() => {
-                self.set_filename("oom", testcase);
-                testcase.add_metadata(LibfuzzerCrashCauseMetadata {
-                    kind: ExitKind::Oom,
-                });
-            }
-            ExitKind::Crash => {
-                self.set_filename("crash", testcase);
-                testcase.add_metadata(LibfuzzerCrashCauseMetadata {
-                    kind: ExitKind::Crash,
-                });
-            }
-            ExitKind::Timeout => {
-                self.set_filename("timeout", testcase);
-                testcase.add_metadata(LibfuzzerCrashCauseMetadata {
-                    kind: ExitKind::Timeout,
-                });
-            }
-            _ => {
-                self.set_filename("uncategorized", testcase);
-                testcase.add_metadata(LibfuzzerCrashCauseMetadata {
-                    kind: self.exit_kind,
-                });
-            }
-        }
+        let cause = match self.exit_kind {
+            ExitKind::Crash | ExitKind::Oom if OomFeedback::oomed() => LibfuzzerCrashCause::Oom,
+            ExitKind::Crash => LibfuzzerCrashCause::Crash,
+            ExitKind::Ok if LeakCheckHook::leaked() => LibfuzzerCrashCause::Leak,
+            ExitKind::Timeout => LibfuzzerCrashCause::Timeout,
+            kind => LibfuzzerCrashCause::Uncategorized(kind),
+        };
+        self.set_filename(cause.name(), testcase);
+        testcase.add_metadata(LibfuzzerCrashCauseMetadata { cause });
         Ok(())
     }
 }
@@ -1,4 +1,4 @@
-use core::ffi::c_int;
+use core::{ffi::c_int, time::Duration};
 #[cfg(unix)]
 use std::{
     fmt::Debug,
@@ -14,11 +14,15 @@ use libafl::monitors::tui::TuiMonitor;
 use libafl::{
     Error, Fuzzer, HasMetadata,
     corpus::Corpus,
-    events::{EventReceiver, ProgressReporter, SimpleEventManager},
-    executors::ExitKind,
+    events::{
+        Event, EventFirer, EventReceiver, EventWithStats, ProgressReporter, SimpleEventManager,
+    },
     monitors::MultiMonitor,
     stages::StagesTuple,
-    state::{HasCurrentStageId, HasExecutions, HasLastReportTime, HasSolutions, Stoppable},
+    state::{
+        HasCorpus, HasCurrentStageId, HasExecutions, HasLastReportTime, HasMaxSize, HasSolutions,
+        HasStartTime, Stoppable,
+    },
 };
 #[cfg(unix)]
 use libafl::{
@@ -30,8 +34,178 @@ use libafl_bolts::{
     core_affinity::Cores,
     shmem::{ShMemProvider, StdShMemProvider},
 };
+use libafl_bolts::{current_time, impl_serdeany};
+use serde::{Deserialize, Serialize};
 
-use crate::{feedbacks::LibfuzzerCrashCauseMetadata, fuzz_with, options::LibfuzzerOptions};
+use crate::{
+    feedbacks::{LibfuzzerCrashCause, LibfuzzerCrashCauseMetadata},
+    fuzz_with,
+    options::LibfuzzerOptions,
+};
+
+/// How often the fuzzer reports progress, same as [`Fuzzer::fuzz_loop`]
+const STATS_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// The smallest maximum input length libfuzzer guesses when `-max_len` is not set
+const MIN_DEFAULT_MAX_LEN: usize = 4096;
+
+/// The progress of the fuzzing campaign, for `-len_control` and `-print_final_stats`
+#[derive(Deserialize, Serialize, Debug)]
+struct LibfuzzerProgressMetadata {
+    /// The limit the mutation length grows up to
+    max_len: usize,
+    /// The corpus size before fuzzing started
+    initial_corpus: usize,
+    /// The corpus size when we last checked
+    last_corpus: usize,
+    /// The executions when the corpus last grew, or the mutation length limit was last raised
+    last_update: u64,
+}
+
+impl_serdeany!(LibfuzzerProgressMetadata);
+
+/// The size of the largest input in the corpus
+fn max_input_len<I, S>(state: &S) -> Result<usize, Error>
+where
+    S: HasCorpus<I>,
+{
+    let mut max_len = 0;
+    for id in state.corpus().ids() {
+        let testcase = state.corpus().get(id)?.borrow();
+        if let Some(metadata) = testcase
+            .file_path()
+            .as_ref()
+            .and_then(|path| std::fs::metadata(path).ok())
+        {
+            max_len = max_len.max(usize::try_from(metadata.len()).unwrap_or(usize::MAX));
+        }
+    }
+    Ok(max_len)
+}
+
+/// Sets up the mutation length limit on the first run; mimics libfuzzer, which starts with the
+/// largest input in the corpus and raises the limit if no new input was found for a while
+fn init_progress<I, S>(options: &LibfuzzerOptions, state: &mut S) -> Result<(), Error>
+where
+    S: HasMetadata + HasCorpus<I> + HasExecutions + HasMaxSize,
+{
+    if state.has_metadata::<LibfuzzerProgressMetadata>() {
+        return Ok(());
+    }
+    let corpus_len = max_input_len(state)?;
+    let max_len = options
+        .max_len()
+        .unwrap_or_else(|| corpus_len.max(MIN_DEFAULT_MAX_LEN));
+    if options.len_control() == 0 {
+        state.set_max_size(max_len);
+    } else {
+        state.set_max_size(corpus_len.max(4).min(max_len));
+    }
+    log::info!(
+        "Maximum input length is {max_len}, mutating inputs up to {} bytes",
+        state.max_size()
+    );
+
+    let corpus = state.corpus().count();
+    let executions = *state.executions();
+    state.add_metadata(LibfuzzerProgressMetadata {
+        max_len,
+        initial_corpus: corpus,
+        last_corpus: corpus,
+        last_update: executions,
+    });
+    Ok(())
+}
+
+/// Raises the mutation length limit if no new input was found in the last
+/// `len_control * log2(limit)` executions
+fn update_len_control<I, S>(options: &LibfuzzerOptions, state: &mut S) -> Result<(), Error>
+where
+    S: HasMetadata + HasCorpus<I> + HasExecutions + HasMaxSize,
+{
+    if options.len_control() == 0 {
+        return Ok(());
+    }
+    let corpus = state.corpus().count();
+    let executions = *state.executions();
+    let max_size = state.max_size();
+    let log = max_size.max(2).ilog2() as usize;
+
+    let progress = state.metadata_mut::<LibfuzzerProgressMetadata>()?;
+    if corpus != progress.last_corpus {
+        progress.last_corpus = corpus;
+        progress.last_update = executions;
+    } else if max_size < progress.max_len
+        && executions - progress.last_update > (options.len_control() * log) as u64
+    {
+        progress.last_update = executions;
+        let max_size = (max_size + log).min(progress.max_len);
+        state.set_max_size(max_size);
+        log::debug!("Raised the mutation length limit to {max_size}");
+    }
+    Ok(())
+}
+
+/// Whether `-max_total_time` passed since the fuzzer started
+fn max_total_time_reached<S>(options: &LibfuzzerOptions, state: &S) -> bool
+where
+    S: HasStartTime,
+{
+    options.max_total_time().is_some_and(|max_total_time| {
+        current_time().saturating_sub(*state.start_time()) >= max_total_time
+    })
+}
+
+/// Prints the statistics libfuzzer prints on exit with `-print_final_stats=1`
+fn print_final_stats<I, S>(state: &S) -> Result<(), Error>
+where
+    S: HasMetadata + HasCorpus<I> + HasExecutions + HasStartTime,
+{
+    let elapsed = current_time().saturating_sub(*state.start_time());
+    let executions = *state.executions();
+    let initial_corpus = state
+        .metadata::<LibfuzzerProgressMetadata>()
+        .map_or(0, |progress| progress.initial_corpus);
+    let mut slowest = 0;
+    for id in state.corpus().ids() {
+        if let Some(exec_time) = state.corpus().get(id)?.borrow().exec_time() {
+            slowest = slowest.max(exec_time.as_secs());
+        }
+    }
+
+    let print = create_monitor_closure();
+    print(&format!("stat::number_of_executed_units: {executions}"));
+    print(&format!(
+        "stat::average_exec_per_sec:     {}",
+        executions / elapsed.as_secs().max(1)
+    ));
+    print(&format!(
+        "stat::new_units_added:          {}",
+        state.corpus().count().saturating_sub(initial_corpus)
+    ));
+    print(&format!("stat::slowest_unit_time_sec:    {slowest}"));
+    #[cfg(unix)]
+    print(&format!(
+        "stat::peak_rss_mb:              {}",
+        peak_rss_mb()
+    ));
+    Ok(())
+}
+
+/// The peak resident set size of this process, in megabytes
+#[cfg(unix)]
+fn peak_rss_mb() -> i64 {
+    let mut usage = unsafe { core::mem::zeroed::<libc::rusage>() };
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, &raw mut usage) } != 0 {
+        return 0;
+    }
+    // `ru_maxrss` is in bytes on apple platforms, and in kilobytes elsewhere
+    if cfg!(target_vendor = "apple") {
+        usage.ru_maxrss >> 20
+    } else {
+        usage.ru_maxrss >> 10
+    }
+}
 
 #[cfg(unix)]
 fn destroy_output_fds(options: &LibfuzzerOptions) {
@@ -83,43 +257,85 @@ fn do_fuzz<F, ST, E, I, S, EM>(
 where
     F: Fuzzer<E, EM, I, S, ST>,
     S: HasMetadata
+        + HasCorpus<I>
         + HasExecutions
         + HasSolutions<I>
         + HasLastReportTime
         + HasCurrentStageId
+        + HasMaxSize
+        + HasStartTime
         + Stoppable,
-    EM: ProgressReporter<S> + EventReceiver<I, S>,
+    EM: ProgressReporter<S> + EventReceiver<I, S> + EventFirer<I, S>,
     ST: StagesTuple<E, EM, S, F>,
 {
     if let Some(solution) = state.solutions().last() {
-        let kind = state
+        let cause = state
             .solutions()
             .get(solution)
             .expect("Last solution was not available")
             .borrow()
             .metadata::<LibfuzzerCrashCauseMetadata>()
             .expect("Crash cause not attached to solution")
-            .kind();
+            .cause();
         let mut halt = false;
-        match kind {
-            ExitKind::Oom if !options.ignore_ooms() => halt = true,
-            ExitKind::Crash if !options.ignore_crashes() => halt = true,
-            ExitKind::Timeout if !options.ignore_timeouts() => halt = true,
+        match cause {
+            LibfuzzerCrashCause::Oom if !options.ignore_ooms() => halt = true,
+            LibfuzzerCrashCause::Crash if !options.ignore_crashes() => halt = true,
+            LibfuzzerCrashCause::Timeout if !options.ignore_timeouts() => halt = true,
+            // like libfuzzer, leaks always stop the fuzzer, -detect_leaks=0 disables the leak checks instead
+            LibfuzzerCrashCause::Leak => halt = true,
             _ => {
-                log::info!("Ignoring {kind:?} according to requested ignore rules.");
+                log::info!("Ignoring {cause:?} according to requested ignore rules.");
             }
         }
         if halt {
+            if options.print_final_stats() {
+                print_final_stats(state)?;
+            }
             log::info!("Halting; the error on the next line is actually okay. :)");
             return Err(Error::shutting_down());
         }
     }
-    if options.runs() == 0 {
-        fuzzer.fuzz_loop(stages, executor, state, mgr)?;
-    } else {
-        for _ in 0..options.runs() {
-            fuzzer.fuzz_one(stages, executor, state, mgr)?;
+    init_progress(options, state)?;
+
+    let res = fuzz_until_done(options, fuzzer, stages, executor, state, mgr);
+    if options.print_final_stats() {
+        print_final_stats(state)?;
+    }
+    res
+}
+
+/// Fuzzes for `-runs` iterations, or until `-max_total_time` passed or we were stopped
+fn fuzz_until_done<F, ST, E, I, S, EM>(
+    options: &LibfuzzerOptions,
+    fuzzer: &mut F,
+    stages: &mut ST,
+    executor: &mut E,
+    state: &mut S,
+    mgr: &mut EM,
+) -> Result<(), Error>
+where
+    F: Fuzzer<E, EM, I, S, ST>,
+    S: HasMetadata + HasCorpus<I> + HasExecutions + HasMaxSize + HasStartTime + Stoppable,
+    EM: ProgressReporter<S> + EventFirer<I, S>,
+{
+    let mut runs = 0;
+    while options.runs() == 0 || runs < options.runs() {
+        if !state.stop_requested() && max_total_time_reached(options, state) {
+            log::info!("Reached -max_total_time, stopping all clients");
+            let executions = *state.executions();
+            mgr.fire(
+                state,
+                EventWithStats::with_current_time(Event::Stop, executions),
+            )?;
+            // the next fuzzing iteration shuts down gracefully
+            state.request_stop();
         }
+
+        mgr.maybe_report_progress(state, STATS_TIMEOUT)?;
+        fuzzer.fuzz_one(stages, executor, state, mgr)?;
+        update_len_control(options, state)?;
+        runs += 1;
     }
     Ok(())
 }
@@ -277,3 +493,99 @@ pub fn fuzz(
         crate::start_fuzzing_single(fuzz_single, None, mgr)
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use libafl::{
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        inputs::BytesInput,
+        state::{HasCorpus, HasExecutions, HasMaxSize, HasStartTime, StdState},
+    };
+    use libafl_bolts::{current_time, rands::StdRand};
+
+    use super::{init_progress, max_total_time_reached, update_len_control};
+    use crate::options::LibfuzzerOptions;
+
+    type TestState =
+        StdState<InMemoryCorpus<BytesInput>, BytesInput, StdRand, InMemoryCorpus<BytesInput>>;
+
+    fn options(args: &[&'static str]) -> LibfuzzerOptions {
+        LibfuzzerOptions::new(core::iter::once("fuzzer").chain(args.iter().copied())).unwrap()
+    }
+
+    fn state() -> TestState {
+        StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_max_total_time() {
+        let mut state = state();
+        *state.start_time_mut() = current_time().saturating_sub(Duration::from_secs(10));
+
+        assert!(!max_total_time_reached(&options(&[]), &state));
+        assert!(!max_total_time_reached(
+            &options(&["-max_total_time=60"]),
+            &state
+        ));
+        assert!(max_total_time_reached(
+            &options(&["-max_total_time=5"]),
+            &state
+        ));
+    }
+
+    #[test]
+    fn test_len_control() {
+        let options = options(&["-len_control=10"]);
+        let mut state = state();
+        init_progress(&options, &mut state).unwrap();
+        // the in-memory corpus has no inputs on disk, so mutations start at the minimum length
+        assert_eq!(state.max_size(), 4);
+
+        // raised by log2(4) once no new input was found in 10 * log2(4) executions
+        *state.executions_mut() = 20;
+        update_len_control(&options, &mut state).unwrap();
+        assert_eq!(state.max_size(), 4);
+        *state.executions_mut() = 21;
+        update_len_control(&options, &mut state).unwrap();
+        assert_eq!(state.max_size(), 6);
+
+        // a new input postpones raising the limit
+        state
+            .corpus_mut()
+            .add(Testcase::new(BytesInput::new(vec![0; 6])))
+            .unwrap();
+        *state.executions_mut() = 100;
+        update_len_control(&options, &mut state).unwrap();
+        assert_eq!(state.max_size(), 6);
+        *state.executions_mut() = 121;
+        update_len_control(&options, &mut state).unwrap();
+        assert_eq!(state.max_size(), 8);
+    }
+
+    #[test]
+    fn test_len_control_limit() {
+        let mut unlimited = state();
+        init_progress(&options(&["-len_control=0", "-max_len=64"]), &mut unlimited).unwrap();
+        assert_eq!(unlimited.max_size(), 64);
+
+        let options = options(&["-len_control=1", "-max_len=5"]);
+        let mut state = state();
+        init_progress(&options, &mut state).unwrap();
+        assert_eq!(state.max_size(), 4);
+        for executions in (0..100).step_by(10) {
+            *state.executions_mut() = executions;
+            update_len_control(&options, &mut state).unwrap();
+        }
+        // never raised above -max_len
+        assert_eq!(state.max_size(), 5);
+    }
+}
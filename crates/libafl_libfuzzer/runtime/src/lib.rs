@@ -91,6 +91,7 @@ mod corpus;
 mod feedbacks;
 mod fuzz;
 mod merge;
+mod merge_control;
 mod misc;
 mod observers;
 mod options;
@@ -153,10 +154,13 @@ macro_rules! fuzz_with {
         };
         use libafl::{
             corpus::Corpus,
-            executors::{ExitKind, InProcessExecutor, ShadowExecutor},
+            executors::{
+                hooks::leak::{LeakCheckHook, LeakFeedback},
+                inprocess::HookableInProcessExecutor, ExitKind, ShadowExecutor,
+            },
             feedback_and_fast, feedback_not, feedback_or, feedback_or_fast,
             feedbacks::{ConstFeedback, CrashFeedback, MaxMapFeedback, NewHashFeedback, TimeFeedback, TimeoutFeedback},
-            generators::RandBytesGenerator,
+            generators::{RandBytesGenerator, RandPrintablesGenerator},
             inputs::{BytesInput, HasTargetBytes, GeneralizedInputMetadata},
             mutators::{
                 GrimoireExtensionMutator, GrimoireRecursiveReplacementMutator, GrimoireRandomDeleteMutator,
@@ -167,7 +171,7 @@ macro_rules! fuzz_with {
             },
             observers::{stacktrace::BacktraceObserver, TimeObserver, CanTrack, ConstMapObserver},
             schedulers::{
-                IndexesLenTimeMinimizerScheduler, powersched::PowerSchedule, PowerQueueScheduler,
+                EntropicScheduler, IndexesLenTimeMinimizerScheduler, powersched::PowerSchedule, PowerQueueScheduler,
             },
             stages::{
                 CalibrationStage, GeneralizationStage, IfStage, StdMutationalStage,
@@ -176,16 +180,16 @@ macro_rules! fuzz_with {
             state::{HasCorpus, StdState},
             StdFuzzer,
         };
-        use libafl_targets::{CmpLogObserver, LLVMCustomMutator, OomFeedback, OomObserver, CMP_MAP};
-        use libafl_bolts::nonzero;
+        use libafl_targets::{has_lsan, lsan_leak_check, CmpLogObserver, LLVMCustomMutator, OomFeedback, OomObserver, CMP_MAP};
         use rand::{thread_rng, RngCore};
-        use std::{env::temp_dir, fs::create_dir, path::PathBuf};
+        use std::{env::temp_dir, fs::create_dir, num::NonZeroUsize, path::PathBuf};
         use crate::{
             CustomMutationStatus,
             corpus::{ArtifactCorpus, LibfuzzerCorpus},
             feedbacks::{LibfuzzerCrashCauseFeedback, LibfuzzerKeepFeedback, ShrinkMapFeedback},
-            misc::should_use_grimoire,
+            misc::{should_use_grimoire, AsciiMutator},
             observers::{MappedEdgeMapObserver, SizeValueObserver},
+            schedulers::{focus_function_indexes, FocusScheduler},
         };
 
         let edge_maker = &$edge_maker;
@@ -258,8 +262,9 @@ macro_rules! fuzz_with {
 
             // A feedback to choose if an input is a solution or not
             let mut objective = feedback_or_fast!(
-                LibfuzzerCrashCauseFeedback::new($options.artifact_prefix().clone()),
+                LibfuzzerCrashCauseFeedback::new($options.artifact_prefix().clone(), $options.exact_artifact_path().cloned()),
                 OomFeedback,
+                LeakFeedback::new(),
                 feedback_and_fast!(
                     CrashFeedback::new(),
                     feedback_or_fast!(ConstFeedback::new(!$options.dedup()), NewHashFeedback::new(&backtrace_observer))
@@ -288,9 +293,12 @@ macro_rules! fuzz_with {
 
             // If not restarting, create a State from scratch
             let mut state = state.unwrap_or_else(|| {
+                // like libfuzzer, report the seed so the run can be reproduced with -seed
+                let seed = $options.seed().unwrap_or_else(|| thread_rng().next_u64());
+                eprintln!("INFO: Seed: {seed}");
                 StdState::new(
                     // RNG
-                    StdRand::new(),
+                    StdRand::with_seed(seed),
                     // Corpus that will be evolved, we keep it in memory for performance
                     LibfuzzerCorpus::new(corpus_dir.clone(), 4096),
                     // Corpus in which we store solutions (crashes in this example),
@@ -357,19 +365,22 @@ macro_rules! fuzz_with {
                 }
             }
 
+            // For -only_ascii, all the byte-level mutators convert their mutants to ASCII like libfuzzer
+            let only_ascii = $options.only_ascii();
+
             // Setup a randomic Input2State stage, conditionally within a custom mutator
             let i2s =
-                StdMutationalStage::new(HavocScheduledMutator::new(tuple_list!(I2SRandReplace::new())));
+                StdMutationalStage::new(AsciiMutator::new(HavocScheduledMutator::new(tuple_list!(I2SRandReplace::new())), only_ascii));
             let i2s = IfStage::new(|_, _, _, _| Ok((!mutator_status.custom_mutation).into()), (i2s, ()));
-            let cm_i2s = StdMutationalStage::new(unsafe {
+            let cm_i2s = StdMutationalStage::new(AsciiMutator::new(unsafe {
                 LLVMCustomMutator::mutate_unchecked(HavocScheduledMutator::new(tuple_list!(
                     I2SRandReplace::new()
                 )))
-            });
+            }, only_ascii));
             let cm_i2s = IfStage::new(|_, _, _, _| Ok(mutator_status.custom_mutation.into()), (cm_i2s, ()));
 
             // TODO configure with mutation stacking options from libfuzzer
-            let std_mutator = HavocScheduledMutator::new(havoc_mutations().merge(tokens_mutations()));
+            let std_mutator = AsciiMutator::new(HavocScheduledMutator::new(havoc_mutations().merge(tokens_mutations())), only_ascii);
 
             let std_power: StdPowerMutationalStage<_, _, BytesInput, _, _, _> = StdPowerMutationalStage::new(std_mutator);
             let std_power = IfStage::new(|_, _, _, _| Ok(mutator_status.std_mutational.into()), (std_power, ()));
@@ -387,11 +398,11 @@ macro_rules! fuzz_with {
             // without performing the custom mutator's preprocessing beforehand
             // we opt not to use crossover in the LLVMFuzzerMutate and instead have a second crossover pass,
             // though it is likely an error for fuzzers to provide custom mutators but not custom crossovers
-            let custom_mutator = unsafe {
+            let custom_mutator = AsciiMutator::new(unsafe {
                 LLVMCustomMutator::mutate_unchecked(HavocScheduledMutator::new(havoc_mutations_no_crossover().merge(tokens_mutations())))
-            };
+            }, only_ascii);
             // Safe to unwrap: stack pow is not 0.
-            let std_mutator_no_mutate = HavocScheduledMutator::with_max_stack_pow(havoc_crossover(),3);
+            let std_mutator_no_mutate = AsciiMutator::new(HavocScheduledMutator::with_max_stack_pow(havoc_crossover(),3), only_ascii);
 
             let cm_power: StdPowerMutationalStage<_, _, BytesInput, _, _, _> = StdPowerMutationalStage::new(custom_mutator);
             let cm_power = IfStage::new(|_, _, _, _| Ok(mutator_status.custom_mutation.into()), (cm_power, ()));
@@ -403,13 +414,13 @@ macro_rules! fuzz_with {
             // while the scenario that a custom crossover is defined without a custom mutator is unlikely
             // we handle it here explicitly anyways
             // Safe to unwrap: stack pow is not 0.
-            let custom_crossover = unsafe {
+            let custom_crossover = AsciiMutator::new(unsafe {
                 LLVMCustomMutator::crossover_unchecked(HavocScheduledMutator::with_max_stack_pow(
                     havoc_mutations_no_crossover().merge(tokens_mutations()),
                     3,
                 ))
-            };
-            let std_mutator_no_crossover = HavocScheduledMutator::new(havoc_mutations_no_crossover().merge(tokens_mutations()));
+            }, only_ascii);
+            let std_mutator_no_crossover = AsciiMutator::new(HavocScheduledMutator::new(havoc_mutations_no_crossover().merge(tokens_mutations())), only_ascii);
 
             let cc_power = StdMutationalStage::new(custom_crossover);
             let cc_power = IfStage::new(|_, _, _, _| Ok(mutator_status.custom_crossover.into()), (cc_power, ()));
@@ -431,8 +442,26 @@ macro_rules! fuzz_with {
             );
             let grimoire = IfStage::new(|_, _, _, _| Ok(grimoire.into()), (StdMutationalStage::<_, _, GeneralizedInputMetadata, BytesInput, _, _, _>::transforming(grimoire_mutator), ()));
 
-            // A minimization+queue policy to get testcasess from the corpus
-            let scheduler = IndexesLenTimeMinimizerScheduler::new(&edges_observer, PowerQueueScheduler::new(&mut state, &edges_observer, PowerSchedule::fast()));
+            // Like libfuzzer, only fuzz the inputs reaching the -focus_function once there are any
+            let focus = if let Some(name) = $options.focus_function() {
+                let focus = focus_function_indexes(name);
+                if focus.is_empty() {
+                    return Err(libafl::Error::illegal_argument(format!(
+                        "Failed to set the focus function {name}, make sure the name is correct and the function is instrumented"
+                    )));
+                }
+                eprintln!("INFO: Focus function is set to '{name}'");
+                focus
+            } else {
+                hashbrown::HashSet::new()
+            };
+
+            // A minimization+queue policy to get testcasess from the corpus, optionally picking by entropic energy
+            let entropic_rare_features = if $options.entropic() { $options.entropic_number_of_rarest_features() } else { 0 };
+            let power = PowerQueueScheduler::new(&mut state, &edges_observer, PowerSchedule::fast());
+            let entropic = EntropicScheduler::with_parameters(&mut state, &edges_observer, power, entropic_rare_features, $options.entropic_feature_frequency_threshold());
+            let focus = FocusScheduler::new(&mut state, focus, entropic);
+            let scheduler = IndexesLenTimeMinimizerScheduler::new(&edges_observer, focus);
 
             // A fuzzer with feedbacks and a corpus scheduler
             let mut fuzzer = StdFuzzer::new(scheduler, feedback, objective);
@@ -458,8 +487,15 @@ macro_rules! fuzz_with {
                 value_profile_observer
             );
 
+            // Like libfuzzer's -detect_leaks, check for leaks with LSan after executions which allocated more than they freed
+            let leak_check_hook = LeakCheckHook::with_max_checks(
+                lsan_leak_check,
+                if $options.detect_leaks() && has_lsan() { libafl::executors::hooks::leak::DEFAULT_MAX_LEAK_CHECKS } else { 0 },
+            );
+
             // Create the executor for an in-process function with one observer for edge coverage and one for the execution time
-            let mut executor = InProcessExecutor::with_timeout(
+            let mut executor = HookableInProcessExecutor::with_timeout_generic(
+                    tuple_list!(leak_check_hook),
                     &mut harness,
                     observers,
                     &mut fuzzer,
@@ -480,19 +516,28 @@ macro_rules! fuzz_with {
                     println!("We imported {} inputs from disk.", state.corpus().count());
                 }
                 if state.corpus().count() < 1 {
-                    // Generator of bytearrays of max size 64
-                    let mut generator = RandBytesGenerator::from(RandBytesGenerator::new(nonzero!(64)));
+                    // Generate inputs of max size 64, or max_len if smaller
+                    let max_size = NonZeroUsize::new($options.max_len().map_or(64, |max_len| max_len.min(64))).unwrap();
 
                     // Generate 1024 initial inputs
-                    state
-                        .generate_initial_inputs(
+                    if $options.only_ascii() {
+                        state.generate_initial_inputs(
                             &mut fuzzer,
                             &mut executor,
-                            &mut generator,
+                            &mut RandPrintablesGenerator::new(max_size),
                             &mut mgr,
                             1 << 10,
                         )
-                        .expect("Failed to generate the initial corpus");
+                    } else {
+                        state.generate_initial_inputs(
+                            &mut fuzzer,
+                            &mut executor,
+                            &mut RandBytesGenerator::new(max_size),
+                            &mut mgr,
+                            1 << 10,
+                        )
+                    }
+                    .expect("Failed to generate the initial corpus");
                     println!(
                         "We imported {} inputs from the generator.",
                         state.corpus().count()
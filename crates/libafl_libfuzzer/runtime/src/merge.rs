@@ -1,40 +1,218 @@
-use std::{env::temp_dir, ffi::c_int, fs::rename};
+use core::{cell::RefCell, fmt::Write as _};
 #[cfg(unix)]
+use std::os::fd::{AsRawFd, FromRawFd};
 use std::{
-    fs::File,
+    collections::VecDeque,
+    env::temp_dir,
+    ffi::c_int,
+    fs::{File, OpenOptions, read, read_dir, remove_file, rename},
     io::Write,
-    os::fd::{AsRawFd, FromRawFd},
+    path::{Path, PathBuf},
 };
 
 use libafl::{
-    Error, HasScheduler, StdFuzzer,
+    Error, ExecutesInput, HasScheduler, StdFuzzer,
     corpus::Corpus,
     events::{SendExiting, SimpleRestartingEventManager},
-    executors::{ExitKind, InProcessExecutor},
+    executors::{ExitKind, HasObservers, InProcessExecutor},
     feedback_and_fast, feedback_or_fast,
     feedbacks::{CrashFeedback, MinMapFeedback, TimeoutFeedback},
-    inputs::{BytesInput, HasTargetBytes},
+    inputs::{BytesInput, HasTargetBytes, Input},
     monitors::MultiMonitor,
     observers::{MultiMapObserver, TimeObserver},
     schedulers::RemovableScheduler,
     state::{HasCorpus, HasRand, StdState},
 };
 use libafl_bolts::{
-    AsSlice,
+    AsIter, AsSlice,
     rands::{Rand, StdRand},
     shmem::{ShMemProvider, StdShMemProvider},
-    tuples::tuple_list,
+    tuples::{Handle, Handled, MatchName, tuple_list},
 };
 use libafl_targets::{OomFeedback, OomObserver, counters_maps_ptr_mut};
 
 use crate::{
     corpus::{ArtifactCorpus, LibfuzzerCorpus},
     feedbacks::{LibfuzzerCrashCauseFeedback, LibfuzzerKeepFeedback},
+    merge_control::MergeControl,
     observers::{MappedEdgeMapObserver, SizeTimeValueObserver},
     options::LibfuzzerOptions,
     schedulers::MergeScheduler,
 };
 
+/// The files in `dir` and its subdirectories, sorted
+fn list_files(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::new();
+    let mut queue = VecDeque::from([dir.to_path_buf()]);
+    while let Some(entry) = queue.pop_front() {
+        if entry.is_dir() {
+            for child in read_dir(&entry)? {
+                queue.push_back(child?.path());
+            }
+        } else if entry.is_file()
+            && !entry
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+        {
+            files.push(entry);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// The hit count bucket of an edge, as in libfuzzer's features
+fn counter_bucket(count: u8) -> u64 {
+    match count {
+        1 => 0,
+        2 => 1,
+        3 => 2,
+        4..=7 => 3,
+        8..=15 => 4,
+        16..=31 => 5,
+        32..=127 => 6,
+        _ => 7,
+    }
+}
+
+/// The features (hit edges combined with the bucket of their hit count) and the hit edges of an execution
+fn features<M>(edges: &M) -> (Vec<u64>, Vec<u64>)
+where
+    M: for<'it> AsIter<'it, Item = u8>,
+{
+    let mut features = Vec::new();
+    let mut cov = Vec::new();
+    for (idx, count) in edges.as_iter().enumerate() {
+        if *count != 0 {
+            let idx = idx as u64;
+            features.push(idx * 8 + counter_bucket(*count));
+            cov.push(idx);
+        }
+    }
+    (features, cov)
+}
+
+/// Appends a `FT` or `COV` record to the control file, in a single write
+fn write_record(file: &mut File, marker: &str, id: usize, values: &[u64]) -> Result<(), Error> {
+    let mut record = format!("{marker} {id}");
+    for value in values {
+        write!(record, " {value}").unwrap();
+    }
+    record.push('\n');
+    file.write_all(record.as_bytes())?;
+    Ok(())
+}
+
+/// Merges like libfuzzer with a control file: the features of each input are recorded in the
+/// control file, so the merge resumes after the last input if the process crashed or was killed.
+///
+/// Inputs of the other corpora adding features are copied to the first corpus.
+#[expect(clippy::too_many_arguments)]
+fn merge_with_control_file<E, EM, M, O, S, Z>(
+    options: &LibfuzzerOptions,
+    control_path: &Path,
+    edges: &Handle<MappedEdgeMapObserver<M, O>>,
+    keep: &RefCell<bool>,
+    fuzzer: &mut Z,
+    executor: &mut E,
+    state: &mut S,
+    mgr: &mut EM,
+) -> Result<(), Error>
+where
+    Z: ExecutesInput<E, EM, BytesInput, S>,
+    E: HasObservers,
+    E::Observers: MatchName,
+    M: for<'it> AsIter<'it, Item = u8>,
+{
+    let control = if let Some(control) = MergeControl::read(control_path) {
+        eprintln!(
+            "MERGE-OUTER: control file ok, {} files total, first not processed file {}",
+            control.inputs().len(),
+            control.first_unprocessed()
+        );
+        if let Some(failure) = control.last_failure() {
+            eprintln!(
+                "MERGE-OUTER: '{}' will be skipped as unlucky",
+                failure.path().display()
+            );
+        }
+        control
+    } else {
+        let mut inputs = list_files(&options.dirs()[0])?;
+        let first_corpus = inputs.len();
+        for dir in &options.dirs()[1..] {
+            inputs.extend(list_files(dir)?);
+        }
+        eprintln!(
+            "MERGE-OUTER: {} files, {first_corpus} in the initial corpus",
+            inputs.len()
+        );
+        let mut control = MergeControl::new(inputs, first_corpus);
+        control.create(control_path)?;
+        control
+    };
+
+    let mut control_file = OpenOptions::new().append(true).open(control_path)?;
+    // drop a record cut off by a crash while writing it
+    control_file.set_len(control.valid_len() as u64)?;
+    for (id, input) in control
+        .inputs()
+        .iter()
+        .enumerate()
+        .skip(control.first_unprocessed())
+    {
+        let mut bytes = read(input.path()).unwrap_or_else(|err| {
+            log::warn!("Could not read {}: {err}", input.path().display());
+            Vec::new()
+        });
+        if let Some(max_len) = options.max_len() {
+            bytes.truncate(max_len);
+        }
+        // written before running the input, so a resumed merge knows which input crashed
+        writeln!(control_file, "STARTED {id} {}", bytes.len())?;
+
+        let exit_kind = fuzzer.execute_input(state, executor, mgr, &BytesInput::new(bytes))?;
+        // inputs rejected by the harness, or which did not exit normally, do not add any feature
+        let (features, cov) = if exit_kind == ExitKind::Ok && *keep.borrow() {
+            features(executor.observers()[edges].inner())
+        } else {
+            (Vec::new(), Vec::new())
+        };
+        write_record(&mut control_file, "FT", id, &features)?;
+        write_record(&mut control_file, "COV", id, &cov)?;
+    }
+    drop(control_file);
+
+    let control = MergeControl::read(control_path)
+        .ok_or_else(|| Error::illegal_state("The merge control file is malformed"))?;
+    let (selected, new_features) = if options.set_cover_merge() {
+        control.set_cover_merge()
+    } else {
+        control.merge()
+    };
+    for input in &selected {
+        let mut bytes = read(input.path())?;
+        if let Some(max_len) = options.max_len() {
+            bytes.truncate(max_len);
+        }
+        let input = BytesInput::new(bytes);
+        let dest = options.dirs()[0].join(input.generate_name(None));
+        if !dest.exists() {
+            input.to_file(dest)?;
+        }
+    }
+    println!(
+        "MERGE-OUTER: {} new files with {new_features} new features added to {}",
+        selected.len(),
+        options.dirs()[0].display()
+    );
+
+    if options.temporary_merge_control_file() {
+        remove_file(control_path)?;
+    }
+    Ok(())
+}
+
 #[expect(clippy::too_many_lines)]
 pub fn merge(
     options: &LibfuzzerOptions,
@@ -53,6 +231,14 @@ pub fn merge(
 
     let mut shmem_provider = StdShMemProvider::new().unwrap();
 
+    // removed before launching, so only restarted clients resume from a temporary control file
+    let control_path = options.merge_control_file().cloned();
+    if let Some(control_path) = &control_path
+        && options.temporary_merge_control_file()
+    {
+        let _ = remove_file(control_path);
+    }
+
     #[cfg(unix)]
     let mut stderr = unsafe {
         let new_fd = libc::dup(std::io::stderr().as_raw_fd());
@@ -101,6 +287,7 @@ pub fn merge(
     let time = TimeObserver::new("time");
     let edges_observer =
         MappedEdgeMapObserver::new(edges_observer, SizeTimeValueObserver::new(time));
+    let edges_handle = edges_observer.handle();
 
     let map_feedback = MinMapFeedback::new(&edges_observer);
 
@@ -113,7 +300,10 @@ pub fn merge(
 
     // A feedback to choose if an input is a solution or not
     let mut objective = feedback_or_fast!(
-        LibfuzzerCrashCauseFeedback::new(options.artifact_prefix().clone()),
+        LibfuzzerCrashCauseFeedback::new(
+            options.artifact_prefix().clone(),
+            options.exact_artifact_path().cloned(),
+        ),
         OomFeedback,
         CrashFeedback::new(),
         TimeoutFeedback::new()
@@ -127,7 +317,8 @@ pub fn merge(
     let mut state = state.map_or_else(|| {
         let mut rand = StdRand::new();
 
-        let corpus_dir = if options.dirs().first().unwrap().exists()
+        // with a control file, inputs are copied to the first corpus directly
+        let corpus_dir = if control_path.is_none() && options.dirs().first().unwrap().exists()
             && options
             .dirs()
             .first()
@@ -183,6 +374,20 @@ pub fn merge(
         options.timeout(),
     )?;
 
+    if let Some(control_path) = control_path {
+        merge_with_control_file(
+            options,
+            &control_path,
+            &edges_handle,
+            &keep,
+            &mut fuzzer,
+            &mut executor,
+            &mut state,
+            &mut mgr,
+        )?;
+        return mgr.send_exiting();
+    }
+
     // In case the corpus is empty (on first run) or crashed while loading, reset
     if state.must_load_initial_inputs() && !options.dirs().is_empty() {
         let loaded_dirs = options
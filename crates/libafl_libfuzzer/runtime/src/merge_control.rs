@@ -0,0 +1,396 @@
+//! The control file of libfuzzer's crash-resistant merge (`-merge_control_file`).
+//!
+//! The control file lists all inputs of the merge, followed by one record per processed input:
+//! ```text
+//! <number of inputs>
+//! <number of inputs in the first corpus>
+//! <input path>*
+//! STARTED <input id> <input size>
+//! FT <input id> <feature>*
+//! COV <input id> <edge>*
+//! ```
+//! `STARTED` is written before the input is executed, `FT` and `COV` after it, so an input which
+//! crashed the merge is the last one without features; a resumed merge skips it.
+//! Inputs which did not exit normally are recorded without features.
+
+use core::fmt::Write as _;
+use std::{
+    collections::{BTreeSet, HashSet},
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use libafl::Error;
+
+/// An input of the merge and what we learned about it
+#[derive(Debug, Clone, Default)]
+pub struct MergeInput {
+    path: PathBuf,
+    size: usize,
+    features: Option<Vec<u64>>,
+    cov: Vec<u64>,
+}
+
+impl MergeInput {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The features of the input, or `None` if it was not (successfully) executed
+    pub fn features(&self) -> Option<&[u64]> {
+        self.features.as_deref()
+    }
+}
+
+/// The parsed contents of a merge control file
+#[derive(Debug, Clone)]
+pub struct MergeControl {
+    inputs: Vec<MergeInput>,
+    first_corpus: usize,
+    first_unprocessed: usize,
+    last_failure: Option<usize>,
+    valid_len: usize,
+}
+
+impl MergeControl {
+    /// A new merge of `inputs`, the first `first_corpus` of which are in the corpus we merge into
+    pub fn new(inputs: Vec<PathBuf>, first_corpus: usize) -> Self {
+        Self {
+            inputs: inputs
+                .into_iter()
+                .map(|path| MergeInput {
+                    path,
+                    ..MergeInput::default()
+                })
+                .collect(),
+            first_corpus,
+            first_unprocessed: 0,
+            last_failure: None,
+            valid_len: 0,
+        }
+    }
+
+    /// Parses a control file, returns `None` if the list of inputs is malformed.
+    ///
+    /// The records are parsed up to the first malformed or cut off one, so a merge which was
+    /// killed while writing a record resumes after the last complete one, see [`Self::valid_len`].
+    pub fn parse(contents: &str) -> Option<Self> {
+        // the complete lines, with the offset after each of them
+        let mut lines = contents.split_inclusive('\n').scan(0, |offset, line| {
+            *offset += line.len();
+            Some((line.strip_suffix('\n')?, *offset))
+        });
+        let count = lines.next()?.0.trim().parse::<usize>().ok()?;
+        let (first_corpus, mut valid_len) = lines.next()?;
+        let first_corpus = first_corpus.trim().parse::<usize>().ok()?;
+        if first_corpus > count {
+            return None;
+        }
+        let mut inputs = Vec::with_capacity(count);
+        for (path, offset) in lines.by_ref().take(count) {
+            inputs.push(PathBuf::from(path));
+            valid_len = offset;
+        }
+        if inputs.len() != count {
+            return None;
+        }
+        let mut control = Self::new(inputs, first_corpus);
+
+        let mut last_started = None;
+        for (line, offset) in lines {
+            if control.parse_record(line, &mut last_started).is_none() {
+                break;
+            }
+            valid_len = offset;
+        }
+        control.last_failure = last_started;
+        control.valid_len = valid_len;
+        Some(control)
+    }
+
+    /// Parses a `STARTED`, `FT` or `COV` record, returns `None` if it is malformed
+    fn parse_record(&mut self, line: &str, last_started: &mut Option<usize>) -> Option<()> {
+        let mut fields = line.split_ascii_whitespace();
+        let marker = fields.next()?;
+        let id = fields.next()?.parse::<usize>().ok()?;
+        match marker {
+            "STARTED" => {
+                // inputs are processed in order
+                if id != self.first_unprocessed || id >= self.inputs.len() {
+                    return None;
+                }
+                self.inputs[id].size = fields.next()?.parse().ok()?;
+                self.first_unprocessed += 1;
+                *last_started = Some(id);
+            }
+            "FT" | "COV" => {
+                if Some(id) != *last_started {
+                    return None;
+                }
+                let values = fields
+                    .map(str::parse::<u64>)
+                    .collect::<Result<Vec<_>, _>>()
+                    .ok()?;
+                if marker == "FT" {
+                    self.inputs[id].features = Some(values);
+                } else {
+                    self.inputs[id].cov = values;
+                    *last_started = None;
+                }
+            }
+            _ => return None,
+        }
+        Some(())
+    }
+
+    /// Reads a control file, returns `None` if it does not exist or is malformed
+    pub fn read(path: &Path) -> Option<Self> {
+        Self::parse(&std::fs::read_to_string(path).ok()?)
+    }
+
+    /// Writes the list of inputs to a new control file
+    pub fn create(&mut self, path: &Path) -> Result<(), Error> {
+        let mut header = format!("{}\n{}\n", self.inputs.len(), self.first_corpus);
+        for input in &self.inputs {
+            writeln!(header, "{}", input.path.display()).unwrap();
+        }
+        File::create(path)?.write_all(header.as_bytes())?;
+        self.valid_len = header.len();
+        Ok(())
+    }
+
+    pub fn inputs(&self) -> &[MergeInput] {
+        &self.inputs
+    }
+
+    /// The length of the well-formed part of the control file, new records are appended after it
+    pub fn valid_len(&self) -> usize {
+        self.valid_len
+    }
+
+    /// The first input which was not started yet
+    pub fn first_unprocessed(&self) -> usize {
+        self.first_unprocessed
+    }
+
+    /// The input which was started last but never finished, most likely because it crashed
+    pub fn last_failure(&self) -> Option<&MergeInput> {
+        self.last_failure.map(|id| &self.inputs[id])
+    }
+
+    /// The features already covered by the corpus we merge into
+    fn initial_features(&self) -> HashSet<u64> {
+        self.inputs[..self.first_corpus]
+            .iter()
+            .filter_map(MergeInput::features)
+            .flatten()
+            .copied()
+            .collect()
+    }
+
+    /// The inputs from the other corpora which executed successfully
+    fn candidates(&self) -> impl Iterator<Item = (&MergeInput, &[u64])> {
+        self.inputs[self.first_corpus..]
+            .iter()
+            .filter_map(|input| Some((input, input.features()?)))
+    }
+
+    /// Selects the inputs adding features to the first corpus, like libfuzzer's `-merge=1`:
+    /// smaller inputs, and then inputs with more features, are considered first.
+    ///
+    /// Returns the selected inputs and the number of new features.
+    pub fn merge(&self) -> (Vec<&MergeInput>, usize) {
+        let mut covered = self.initial_features();
+        let mut candidates = self.candidates().collect::<Vec<_>>();
+        candidates.sort_by(|(a, a_features), (b, b_features)| {
+            a.size
+                .cmp(&b.size)
+                .then(b_features.len().cmp(&a_features.len()))
+        });
+
+        let initial = covered.len();
+        let mut selected = Vec::new();
+        for (input, features) in candidates {
+            let before = covered.len();
+            covered.extend(features.iter().copied());
+            if covered.len() > before {
+                selected.push(input);
+            }
+        }
+        (selected, covered.len() - initial)
+    }
+
+    /// Selects the inputs adding features to the first corpus with a greedy set cover,
+    /// like libfuzzer's `-set_cover_merge=1`: the input adding the most new features is picked
+    /// until no input adds any, preferring smaller inputs on ties.
+    ///
+    /// Returns the selected inputs and the number of new features.
+    pub fn set_cover_merge(&self) -> (Vec<&MergeInput>, usize) {
+        let covered = self.initial_features();
+        let mut candidates = self
+            .candidates()
+            .map(|(input, features)| {
+                let uncovered = features
+                    .iter()
+                    .copied()
+                    .filter(|feature| !covered.contains(feature))
+                    .collect::<BTreeSet<_>>();
+                (input, uncovered)
+            })
+            .filter(|(_, uncovered)| !uncovered.is_empty())
+            .collect::<Vec<_>>();
+
+        let mut selected = Vec::new();
+        let mut new_features = 0;
+        while let Some(best) = candidates
+            .iter()
+            .enumerate()
+            .max_by(|(_, (a, a_uncovered)), (_, (b, b_uncovered))| {
+                a_uncovered
+                    .len()
+                    .cmp(&b_uncovered.len())
+                    .then(b.size.cmp(&a.size))
+            })
+            .map(|(idx, _)| idx)
+        {
+            let (input, picked) = candidates.swap_remove(best);
+            new_features += picked.len();
+            selected.push(input);
+            for (_, uncovered) in &mut candidates {
+                uncovered.retain(|feature| !picked.contains(feature));
+            }
+            candidates.retain(|(_, uncovered)| !uncovered.is_empty());
+        }
+        (selected, new_features)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env::temp_dir, fs::OpenOptions, io::Write, path::PathBuf};
+
+    use super::MergeControl;
+
+    const HEADER: &str = "4\n1\nfirst/a\nsecond/b\nsecond/c\nsecond/d\n";
+
+    fn paths<'a>(inputs: impl IntoIterator<Item = &'a super::MergeInput>) -> Vec<&'a str> {
+        inputs
+            .into_iter()
+            .map(|input| input.path().to_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let contents = format!(
+            "{HEADER}STARTED 0 3\nFT 0 1\nCOV 0 1\nSTARTED 1 5\nFT 1 1 2\nCOV 1 1 2\nSTARTED 2 2\nFT 2\nCOV 2\nSTARTED 3 2\n"
+        );
+        let control = MergeControl::parse(&contents).unwrap();
+        assert_eq!(control.inputs().len(), 4);
+        assert_eq!(control.first_unprocessed(), 4);
+        assert_eq!(control.valid_len(), contents.len());
+        assert_eq!(control.inputs()[1].features(), Some(&[1, 2][..]));
+        // did not exit normally
+        assert_eq!(control.inputs()[2].features(), Some(&[][..]));
+        // crashed
+        assert_eq!(control.inputs()[3].features(), None);
+        assert_eq!(
+            control.last_failure().unwrap().path().to_str(),
+            Some("second/d")
+        );
+
+        let control = MergeControl::parse(HEADER).unwrap();
+        assert_eq!(control.first_unprocessed(), 0);
+        assert!(control.last_failure().is_none());
+
+        // the list of inputs is incomplete
+        assert!(MergeControl::parse("4\n1\nfirst/a\nsecond/b\n").is_none());
+        assert!(MergeControl::parse("4\n1\nfirst/a\nsecond/b\nsecond/c\nsecond/d").is_none());
+        assert!(MergeControl::parse("1\n2\nfirst/a\n").is_none());
+        assert!(MergeControl::parse("").is_none());
+    }
+
+    #[test]
+    fn test_parse_truncated() {
+        let complete = format!("{HEADER}STARTED 0 3\nFT 0 1\nCOV 0 1\nSTARTED 1 5\n");
+
+        // cut off while writing the features of the second input, which counts as failed
+        let contents = format!("{complete}FT 1 1");
+        let control = MergeControl::parse(&contents).unwrap();
+        assert_eq!(control.valid_len(), complete.len());
+        assert_eq!(control.first_unprocessed(), 2);
+        assert_eq!(control.inputs()[0].features(), Some(&[1][..]));
+        assert_eq!(control.inputs()[1].features(), None);
+        assert_eq!(
+            control.last_failure().unwrap().path().to_str(),
+            Some("second/b")
+        );
+
+        // nothing after a malformed record is used
+        let contents = format!("{complete}FT 1 x\nCOV 1 1\nSTARTED 2 2\n");
+        let control = MergeControl::parse(&contents).unwrap();
+        assert_eq!(control.valid_len(), complete.len());
+        assert_eq!(control.first_unprocessed(), 2);
+
+        let contents = format!("{complete}STARTED 3 2\n");
+        let control = MergeControl::parse(&contents).unwrap();
+        assert_eq!(control.valid_len(), complete.len());
+    }
+
+    #[test]
+    fn test_create_and_resume() {
+        let path = temp_dir().join(format!("libafl-merge-control-test-{}", std::process::id()));
+        let mut control = MergeControl::new(vec![PathBuf::from("a"), PathBuf::from("b")], 1);
+        control.create(&path).unwrap();
+        assert_eq!(control.valid_len(), "2\n1\na\nb\n".len());
+
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"STARTED 0 1\nFT 0 7").unwrap();
+        let control = MergeControl::read(&path).unwrap();
+        file.set_len(control.valid_len() as u64).unwrap();
+        file.write_all(b"STARTED 1 1\nFT 1 8\nCOV 1 1\n").unwrap();
+        drop(file);
+
+        let control = MergeControl::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(control.first_unprocessed(), 2);
+        assert_eq!(control.inputs()[0].features(), None);
+        assert_eq!(control.inputs()[1].features(), Some(&[8][..]));
+        assert!(control.last_failure().is_none());
+    }
+
+    #[test]
+    fn test_merge() {
+        // the first corpus covers feature 1
+        let contents = format!(
+            "{HEADER}STARTED 0 3\nFT 0 1\nCOV 0 1\nSTARTED 1 5\nFT 1 1 2\nCOV 1 1\nSTARTED 2 2\nFT 2 2 3\nCOV 2 2\nSTARTED 3 2\nFT 3 3\nCOV 3 3\n"
+        );
+        let control = MergeControl::parse(&contents).unwrap();
+
+        // the smallest inputs first, more features first among the same size
+        let (selected, new_features) = control.merge();
+        assert_eq!(paths(selected), ["second/c"]);
+        assert_eq!(new_features, 2);
+
+        let (selected, new_features) = control.set_cover_merge();
+        assert_eq!(paths(selected), ["second/c"]);
+        assert_eq!(new_features, 2);
+    }
+
+    #[test]
+    fn test_set_cover_merge() {
+        let contents = "3\n0\na\nb\nc\nSTARTED 0 1\nFT 0 1\nCOV 0 1\nSTARTED 1 10\nFT 1 1 2 3\nCOV 1 1\nSTARTED 2 1\nFT 2 4\nCOV 2 4\n";
+        let control = MergeControl::parse(contents).unwrap();
+
+        // the small inputs are picked first, although the large one covers their features
+        let (selected, new_features) = control.merge();
+        assert_eq!(paths(selected), ["a", "c", "b"]);
+        assert_eq!(new_features, 4);
+
+        // the input adding the most features first
+        let (selected, new_features) = control.set_cover_merge();
+        assert_eq!(paths(selected), ["b", "c"]);
+        assert_eq!(new_features, 4);
+    }
+}
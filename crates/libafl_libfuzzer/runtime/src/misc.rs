@@ -1,8 +1,13 @@
-use std::{collections::VecDeque, path::PathBuf};
+use std::{borrow::Cow, collections::VecDeque, path::PathBuf};
 
 use hashbrown::HashSet;
-use libafl::{Error, HasMetadata};
-use libafl_bolts::impl_serdeany;
+use libafl::{
+    Error, HasMetadata,
+    corpus::CorpusId,
+    inputs::HasMutatorBytes,
+    mutators::{MutationResult, Mutator},
+};
+use libafl_bolts::{Named, impl_serdeany};
 use serde::{Deserialize, Serialize};
 use utf8_chars::BufReadCharsExt;
 
@@ -92,3 +97,123 @@ where
 
     Ok(grimoire)
 }
+
+/// Wraps a [`Mutator`] and converts its mutants to printable ASCII (`isprint` or `isspace`), as requested by `-only_ascii`
+#[derive(Debug)]
+pub(crate) struct AsciiMutator<M> {
+    name: Cow<'static, str>,
+    inner: M,
+    only_ascii: bool,
+}
+
+impl<M> AsciiMutator<M>
+where
+    M: Named,
+{
+    pub fn new(inner: M, only_ascii: bool) -> Self {
+        Self {
+            name: Cow::Owned(format!("AsciiMutator[{}]", inner.name())),
+            inner,
+            only_ascii,
+        }
+    }
+}
+
+impl<M> Named for AsciiMutator<M> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<I, M, S> Mutator<I, S> for AsciiMutator<M>
+where
+    I: HasMutatorBytes,
+    M: Mutator<I, S>,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+        let result = self.inner.mutate(state, input)?;
+        if self.only_ascii && result == MutationResult::Mutated {
+            to_ascii(input.mutator_bytes_mut());
+        }
+        Ok(result)
+    }
+
+    fn post_exec(&mut self, state: &mut S, new_corpus_id: Option<CorpusId>) -> Result<(), Error> {
+        self.inner.post_exec(state, new_corpus_id)
+    }
+}
+
+/// Like libfuzzer's `ToASCII`, clears the high bit of each byte and replaces the remaining unprintable bytes with spaces
+pub(crate) fn to_ascii(bytes: &mut [u8]) {
+    for b in bytes {
+        let ascii = *b & 0x7f;
+        *b = if ascii.is_ascii_graphic() || ascii.is_ascii_whitespace() || ascii == b'\x0b' {
+            ascii
+        } else {
+            b' '
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use libafl::{
+        inputs::{BytesInput, HasMutatorBytes},
+        mutators::{MutationResult, Mutator},
+    };
+    use libafl_bolts::Named;
+
+    use super::{AsciiMutator, to_ascii};
+
+    struct SetBytesMutator(&'static [u8]);
+
+    impl Named for SetBytesMutator {
+        fn name(&self) -> &Cow<'static, str> {
+            static NAME: Cow<'static, str> = Cow::Borrowed("SetBytesMutator");
+            &NAME
+        }
+    }
+
+    impl Mutator<BytesInput, ()> for SetBytesMutator {
+        fn mutate(
+            &mut self,
+            _state: &mut (),
+            input: &mut BytesInput,
+        ) -> Result<MutationResult, libafl::Error> {
+            *input = BytesInput::new(self.0.to_vec());
+            Ok(MutationResult::Mutated)
+        }
+
+        fn post_exec(
+            &mut self,
+            _state: &mut (),
+            _new_corpus_id: Option<libafl::corpus::CorpusId>,
+        ) -> Result<(), libafl::Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_to_ascii() {
+        let mut bytes = *b"a\tb\x0bc\x00\x7f\xe1\xff~";
+        to_ascii(&mut bytes);
+        assert_eq!(&bytes, b"a\tb\x0bc  a ~");
+    }
+
+    #[test]
+    fn test_ascii_mutator() {
+        let mut input = BytesInput::new(vec![]);
+        let mut mutator = AsciiMutator::new(SetBytesMutator(b"\x80\xc1x\x01"), true);
+        assert_eq!(
+            mutator.mutate(&mut (), &mut input).unwrap(),
+            MutationResult::Mutated
+        );
+        assert_eq!(input.mutator_bytes(), b" Ax ");
+
+        let mut mutator = AsciiMutator::new(SetBytesMutator(b"\x80\xc1x\x01"), false);
+        mutator.mutate(&mut (), &mut input).unwrap();
+        assert_eq!(input.mutator_bytes(), b"\x80\xc1x\x01");
+    }
+}
@@ -55,6 +55,13 @@ where
     }
 }
 
+impl<M, O> MappedEdgeMapObserver<M, O> {
+    /// The wrapped edge map observer
+    pub fn inner(&self) -> &M {
+        &self.inner
+    }
+}
+
 impl<M, O> AsRef<Self> for MappedEdgeMapObserver<M, O> {
     fn as_ref(&self) -> &Self {
         self
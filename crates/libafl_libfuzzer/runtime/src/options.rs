@@ -1,5 +1,5 @@
 use core::fmt::{Display, Formatter};
-use std::{path::PathBuf, time::Duration};
+use std::{env::temp_dir, path::PathBuf, time::Duration};
 
 use libafl::mutators::Tokens;
 use serde::{Deserialize, Serialize};
@@ -105,8 +105,22 @@ pub struct LibfuzzerOptions {
     #[allow(unused)]
     fuzzer_name: String,
     mode: LibfuzzerMode,
+    merge_control_file: Option<PathBuf>,
+    temporary_merge_control_file: bool,
+    set_cover_merge: bool,
     artifact_prefix: ArtifactPrefix,
+    exact_artifact_path: Option<PathBuf>,
     timeout: Duration,
+    max_len: Option<usize>,
+    len_control: usize,
+    max_total_time: Option<Duration>,
+    seed: Option<u64>,
+    only_ascii: bool,
+    detect_leaks: bool,
+    focus_function: Option<String>,
+    entropic: bool,
+    entropic_feature_frequency_threshold: u16,
+    entropic_number_of_rarest_features: usize,
     grimoire: Option<bool>,
     use_value_profile: bool,
     unicode: bool,
@@ -123,6 +137,7 @@ pub struct LibfuzzerOptions {
     skip_tracing: bool,
     tui: bool,
     runs: usize,
+    print_final_stats: bool,
     #[allow(unused)]
     close_fd_mask: u8,
     unknown: Vec<String>,
@@ -155,14 +170,71 @@ impl LibfuzzerOptions {
         &self.mode
     }
 
+    pub fn merge_control_file(&self) -> Option<&PathBuf> {
+        self.merge_control_file.as_ref()
+    }
+
+    /// Whether the merge control file was not requested with `-merge_control_file`, and is removed after the merge
+    pub fn temporary_merge_control_file(&self) -> bool {
+        self.temporary_merge_control_file
+    }
+
+    pub fn set_cover_merge(&self) -> bool {
+        self.set_cover_merge
+    }
+
     pub fn artifact_prefix(&self) -> &ArtifactPrefix {
         &self.artifact_prefix
     }
 
+    pub fn exact_artifact_path(&self) -> Option<&PathBuf> {
+        self.exact_artifact_path.as_ref()
+    }
+
     pub fn timeout(&self) -> Duration {
         self.timeout
     }
 
+    pub fn max_len(&self) -> Option<usize> {
+        self.max_len
+    }
+
+    pub fn len_control(&self) -> usize {
+        self.len_control
+    }
+
+    pub fn max_total_time(&self) -> Option<Duration> {
+        self.max_total_time
+    }
+
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn only_ascii(&self) -> bool {
+        self.only_ascii
+    }
+
+    pub fn detect_leaks(&self) -> bool {
+        self.detect_leaks
+    }
+
+    pub fn focus_function(&self) -> Option<&str> {
+        self.focus_function.as_deref()
+    }
+
+    pub fn entropic(&self) -> bool {
+        self.entropic
+    }
+
+    pub fn entropic_feature_frequency_threshold(&self) -> u16 {
+        self.entropic_feature_frequency_threshold
+    }
+
+    pub fn entropic_number_of_rarest_features(&self) -> usize {
+        self.entropic_number_of_rarest_features
+    }
+
     pub fn grimoire(&self) -> Option<bool> {
         self.grimoire
     }
@@ -227,6 +299,10 @@ impl LibfuzzerOptions {
         self.runs
     }
 
+    pub fn print_final_stats(&self) -> bool {
+        self.print_final_stats
+    }
+
     #[cfg(unix)]
     pub fn close_fd_mask(&self) -> u8 {
         self.close_fd_mask
@@ -241,8 +317,21 @@ impl LibfuzzerOptions {
 #[expect(clippy::struct_excessive_bools)]
 struct LibfuzzerOptionsBuilder<'a> {
     mode: Option<LibfuzzerMode>,
+    merge_control_file: Option<&'a str>,
+    set_cover_merge: bool,
     artifact_prefix: Option<&'a str>,
+    exact_artifact_path: Option<&'a str>,
     timeout: Option<Duration>,
+    max_len: usize,
+    len_control: Option<usize>,
+    max_total_time: u64,
+    seed: u64,
+    only_ascii: bool,
+    detect_leaks: Option<bool>,
+    focus_function: Option<&'a str>,
+    entropic: bool,
+    entropic_feature_frequency_threshold: Option<u16>,
+    entropic_number_of_rarest_features: Option<usize>,
     grimoire: Option<bool>,
     use_value_profile: Option<bool>,
     unicode: Option<bool>,
@@ -258,8 +347,10 @@ struct LibfuzzerOptionsBuilder<'a> {
     dedup: bool,
     shrink: bool,
     skip_tracing: bool,
+    use_cmp: Option<bool>,
     tui: bool,
     runs: usize,
+    print_final_stats: bool,
     close_fd_mask: u8,
     unknown: Vec<&'a str>,
 }
@@ -291,6 +382,16 @@ impl<'a> LibfuzzerOptionsBuilder<'a> {
                                 return Err(OptionsParseError::MultipleModesSelected);
                             }
                         }
+                        "set_cover_merge" => {
+                            self.set_cover_merge = parse_or_bail!(name, value, u64) > 0;
+                            if self.set_cover_merge
+                                && *self.mode.get_or_insert(LibfuzzerMode::Merge)
+                                    != LibfuzzerMode::Merge
+                            {
+                                return Err(OptionsParseError::MultipleModesSelected);
+                            }
+                        }
+                        "merge_control_file" => self.merge_control_file = Some(value),
                         "minimize_crash" => {
                             if parse_or_bail!(name, value, u64) > 0
                                 && *self.mode.get_or_insert(LibfuzzerMode::Tmin)
@@ -315,12 +416,37 @@ impl<'a> LibfuzzerOptionsBuilder<'a> {
                         "artifact_prefix" => {
                             self.artifact_prefix = Some(value);
                         }
+                        "exact_artifact_path" => {
+                            self.exact_artifact_path = Some(value);
+                        }
                         "timeout" => {
                             self.timeout =
                                 Some(value.parse().map(Duration::from_secs_f64).map_err(|_| {
                                     OptionsParseError::OptionValueParseFailed(name, value)
                                 })?);
                         }
+                        "max_len" => self.max_len = parse_or_bail!(name, value, usize),
+                        "len_control" => {
+                            self.len_control = Some(parse_or_bail!(name, value, usize));
+                        }
+                        "max_total_time" => {
+                            self.max_total_time = parse_or_bail!(name, value, u64);
+                        }
+                        "seed" => self.seed = parse_or_bail!(name, value, u64),
+                        "only_ascii" => self.only_ascii = parse_or_bail!(name, value, u64) > 0,
+                        "detect_leaks" => {
+                            self.detect_leaks = Some(parse_or_bail!(name, value, u64) > 0);
+                        }
+                        "focus_function" => self.focus_function = Some(value),
+                        "entropic" => self.entropic = parse_or_bail!(name, value, u64) > 0,
+                        "entropic_feature_frequency_threshold" => {
+                            self.entropic_feature_frequency_threshold =
+                                Some(parse_or_bail!(name, value, u16));
+                        }
+                        "entropic_number_of_rarest_features" => {
+                            self.entropic_number_of_rarest_features =
+                                Some(parse_or_bail!(name, value, usize));
+                        }
                         "dict" => self.dict = Some(value),
                         #[cfg(not(windows))]
                         "fork" | "jobs" => {
@@ -347,6 +473,7 @@ impl<'a> LibfuzzerOptionsBuilder<'a> {
                         "dedup" => self.dedup = parse_or_bail!(name, value, u64) > 0,
                         "shrink" => self.shrink = parse_or_bail!(name, value, u64) > 0,
                         "skip_tracing" => self.skip_tracing = parse_or_bail!(name, value, u64) > 0,
+                        "use_cmp" => self.use_cmp = Some(parse_or_bail!(name, value, u64) > 0),
                         "tui" => {
                             self.tui = parse_or_bail!(name, value, u64) > 0;
                             if self.tui {
@@ -362,6 +489,9 @@ impl<'a> LibfuzzerOptionsBuilder<'a> {
                             }
                         }
                         "runs" => self.runs = parse_or_bail!(name, value, usize),
+                        "print_final_stats" => {
+                            self.print_final_stats = parse_or_bail!(name, value, u64) > 0;
+                        }
                         "close_fd_mask" => self.close_fd_mask = parse_or_bail!(name, value, u8),
                         "help" => {
                             println!(
@@ -375,7 +505,18 @@ impl<'a> LibfuzzerOptionsBuilder<'a> {
                                 \n\
                                 Flags: (strictly in form -flag=value)\n\
                                 artifact_prefix                        0       Write fuzzing artifacts (crash, timeout, or slow inputs) as $(artifact_prefix)file\n\
+                                exact_artifact_path                    0       Write the single artifact on failure (crash, timeout) as $(exact_artifact_path). This overrides -artifact_prefix.\n\
                                 timeout                                1200    Timeout in seconds. If one unit runs more than this number of seconds the process will abort.\n\
+                                max_len                                0       Maximum length of the test input. If 0, the maximum length is guessed from the corpus (at least 4096).\n\
+                                len_control                            100     Try generating small inputs first, then try larger inputs over time. Specifies the rate at which the length limit is increased (smaller == faster). If 0, immediately try inputs with size up to max_len.\n\
+                                max_total_time                         0       If positive, indicates the maximal total time in seconds to run the fuzzer.\n\
+                                seed                                   0       Random seed. If 0, seed is generated.\n\
+                                only_ascii                             0       If 1, generate only ASCII (isprint+isspace) inputs.\n\
+                                detect_leaks                           1       If 1, and if LeakSanitizer is enabled, try to detect memory leaks during fuzzing (i.e. not only at shut down).\n\
+                                focus_function                         0       Fuzzing will focus on inputs that trigger calls to this function.\n\
+                                entropic                               0       If 1, use the entropic power schedule, prioritizing inputs which hit rare features.\n\
+                                entropic_feature_frequency_threshold   255     Features hit more often than this are no longer rare, once there are more than entropic_number_of_rarest_features rare features.\n\
+                                entropic_number_of_rarest_features     100     The number of rare features the entropic power schedule keeps track of.\n\
                                 grimoire                               0       If 1, enable the Grimoire mutator that is structure-aware.\n\
                                 use_value_profile                      0       Use value profile to guide fuzzing.\n\
                                 unicode                                1       If 1, generate Unicode inputs.\n\
@@ -391,10 +532,14 @@ impl<'a> LibfuzzerOptionsBuilder<'a> {
                                 dedup                                  0       If 1, deduplicate corpus elements.\n\
                                 shrink                                 0       If 1, try to shrink corpus elements.\n\
                                 skip_tracing                           0       If 1, skip coverage tracing for faster execution.\n\
+                                use_cmp                                1       Use CMP traces to guide mutations. Same as skip_tracing=0.\n\
                                 tui                                    0       If 1, use the terminal UI interface.\n\
                                 runs                                   0       Number of individual test runs (0 for infinite runs).\n\
+                                print_final_stats                      0       If 1, print statistics at exit.\n\
                                 close_fd_mask                          0       If 1, close stdout; if 2, close stderr; if 3, close both.\n\
                                 merge                                  0       If 1, merge multiple corpora into a single one.\n\
+                                set_cover_merge                        0       If 1, merge like -merge=1, but pick the smallest set of inputs covering all features.\n\
+                                merge_control_file                     0       Specify a control file used for the merge process. If a merge process gets killed it tries to leave this file in a state suitable for resuming the merge.\n\
                                 minimize_crash                         0       If 1, minimize crashes to their smallest reproducing input.\n\
                                 report                                 0       If 1, report statistics without actually fuzzing.\n\
                                 help                                   0       Print this help message.\n\
@@ -420,14 +565,39 @@ impl<'a> LibfuzzerOptionsBuilder<'a> {
         LibfuzzerOptions {
             fuzzer_name,
             mode: self.mode.unwrap_or(LibfuzzerMode::Fuzz),
+            // a -set_cover_merge is crash-resistant like libfuzzer's, with a temporary control file if none was requested
+            merge_control_file: self.merge_control_file.map(PathBuf::from).or_else(|| {
+                self.set_cover_merge.then(|| {
+                    temp_dir().join(format!("libafl-merge-control-{}", std::process::id()))
+                })
+            }),
+            temporary_merge_control_file: self.merge_control_file.is_none() && self.set_cover_merge,
+            set_cover_merge: self.set_cover_merge,
             artifact_prefix: self
                 .artifact_prefix
                 .map(ArtifactPrefix::new)
                 .unwrap_or_default(),
+            exact_artifact_path: self.exact_artifact_path.map(PathBuf::from),
             timeout: self.timeout.unwrap_or(Duration::from_mins(20)),
+            max_len: (self.max_len != 0).then_some(self.max_len),
+            len_control: self.len_control.unwrap_or(100),
+            max_total_time: (self.max_total_time != 0)
+                .then(|| Duration::from_secs(self.max_total_time)),
+            seed: (self.seed != 0).then_some(self.seed),
+            only_ascii: self.only_ascii,
+            detect_leaks: self.detect_leaks.unwrap_or(true),
+            focus_function: self.focus_function.map(ToString::to_string),
+            entropic: self.entropic,
+            entropic_feature_frequency_threshold: self
+                .entropic_feature_frequency_threshold
+                .unwrap_or(0xFF),
+            entropic_number_of_rarest_features: self
+                .entropic_number_of_rarest_features
+                .unwrap_or(100),
             grimoire: self.grimoire,
             use_value_profile: self.use_value_profile.unwrap_or(false),
-            unicode: self.unicode.unwrap_or(true),
+            // unicode mutations would only produce inputs rejected by -only_ascii
+            unicode: self.unicode.unwrap_or(!self.only_ascii),
             forks: self.forks,
             dict: self.dict.map(|path| {
                 Tokens::from_file(path).expect("Couldn't load tokens from specified tokens file")
@@ -446,11 +616,115 @@ impl<'a> LibfuzzerOptionsBuilder<'a> {
             },
             dedup: self.dedup,
             shrink: self.shrink,
-            skip_tracing: self.skip_tracing,
+            skip_tracing: self.skip_tracing || !self.use_cmp.unwrap_or(true),
             tui: self.tui,
             runs: self.runs,
+            print_final_stats: self.print_final_stats,
             close_fd_mask: self.close_fd_mask,
             unknown: self.unknown.into_iter().map(ToString::to_string).collect(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, time::Duration};
+
+    use super::{LibfuzzerMode, LibfuzzerOptions, OptionsParseError};
+
+    fn parse(args: &[&'static str]) -> Result<LibfuzzerOptions, OptionsParseError<'static>> {
+        LibfuzzerOptions::new(core::iter::once("fuzzer").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn test_defaults() {
+        let options = parse(&[]).unwrap();
+        assert_eq!(*options.mode(), LibfuzzerMode::Fuzz);
+        assert!(!options.only_ascii());
+        assert!(options.unicode());
+        assert_eq!(options.max_len(), None);
+        assert_eq!(options.len_control(), 100);
+        assert_eq!(options.max_total_time(), None);
+        assert_eq!(options.seed(), None);
+        assert!(options.detect_leaks());
+    }
+
+    #[test]
+    fn test_flags() {
+        let options = parse(&[
+            "-only_ascii=1",
+            "-max_len=128",
+            "-len_control=0",
+            "-max_total_time=30",
+            "-seed=1337",
+            "-detect_leaks=0",
+            "-runs=10",
+            "corpus",
+            "-not_a_flag=1",
+            "-no_value",
+            "--passed_through",
+        ])
+        .unwrap();
+        assert!(options.only_ascii());
+        // unicode mutations are off for ASCII inputs unless requested
+        assert!(!options.unicode());
+        assert_eq!(options.max_len(), Some(128));
+        assert_eq!(options.len_control(), 0);
+        assert_eq!(options.max_total_time(), Some(Duration::from_secs(30)));
+        assert_eq!(options.seed(), Some(1337));
+        assert!(!options.detect_leaks());
+        assert_eq!(options.runs(), 10);
+        assert_eq!(options.dirs(), [PathBuf::from("corpus")]);
+        assert_eq!(
+            options.unknown(),
+            ["-not_a_flag=1", "-no_value", "--passed_through"]
+        );
+
+        let options = parse(&["-only_ascii=1", "-unicode=1", "-max_total_time=0"]).unwrap();
+        assert!(options.unicode());
+        assert_eq!(options.max_total_time(), None);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(matches!(
+            parse(&["-max_total_time=soon"]),
+            Err(OptionsParseError::OptionValueParseFailed(
+                "max_total_time",
+                "soon"
+            ))
+        ));
+        assert!(matches!(
+            parse(&["-merge=1", "-minimize_crash=1"]),
+            Err(OptionsParseError::MultipleModesSelected)
+        ));
+        assert_eq!(
+            *parse(&["-merge=1", "-set_cover_merge=1"]).unwrap().mode(),
+            LibfuzzerMode::Merge
+        );
+    }
+
+    #[test]
+    fn test_merge_control_file() {
+        let options = parse(&["-merge=1"]).unwrap();
+        assert_eq!(options.merge_control_file(), None);
+
+        let options = parse(&["-merge=1", "-merge_control_file=control"]).unwrap();
+        assert_eq!(
+            options.merge_control_file(),
+            Some(&PathBuf::from("control"))
+        );
+        assert!(!options.temporary_merge_control_file());
+
+        let options = parse(&["-set_cover_merge=1", "-merge_control_file=control"]).unwrap();
+        assert_eq!(
+            options.merge_control_file(),
+            Some(&PathBuf::from("control"))
+        );
+        assert!(!options.temporary_merge_control_file());
+
+        let options = parse(&["-set_cover_merge=1"]).unwrap();
+        assert!(options.merge_control_file().is_some());
+        assert!(options.temporary_merge_control_file());
+    }
+}
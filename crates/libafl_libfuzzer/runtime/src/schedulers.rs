@@ -1,14 +1,18 @@
+use core::ffi::c_void;
 use std::{collections::BTreeSet, marker::PhantomData};
 
-use hashbrown::HashMap;
+use hashbrown::{HashMap, HashSet};
 use libafl::{
     Error, HasMetadata,
     corpus::{Corpus, CorpusId, Testcase},
-    feedbacks::MapNoveltiesMetadata,
+    feedbacks::{MapIndexesMetadata, MapNoveltiesMetadata},
     inputs::Input,
     schedulers::{RemovableScheduler, Scheduler},
-    state::HasCorpus,
+    state::{HasCorpus, HasRand},
 };
+use libafl_bolts::{rands::Rand, tuples::MatchName};
+use libafl_targets::sanitizer_cov_function_pcs;
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone)]
 pub struct MergeScheduler<I, S> {
@@ -81,3 +85,231 @@ impl<I, S> MergeScheduler<I, S> {
         &self.all
     }
 }
+
+/// The corpus entries reaching the focus function
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct FocusMetadata {
+    reaching: BTreeSet<CorpusId>,
+}
+
+libafl_bolts::impl_serdeany!(FocusMetadata);
+
+/// The number of times the base scheduler is asked for an entry reaching the focus function,
+/// before picking one at random
+const FOCUS_TRIES: usize = 16;
+
+/// Only schedules the corpus entries reaching the focus function, like libfuzzer's `-focus_function`,
+/// once there are any.
+///
+/// An entry reaches the focus function if its `MapIndexesMetadata` contains one of the `focus` indexes.
+#[derive(Debug, Clone)]
+pub struct FocusScheduler<CS> {
+    base: CS,
+    focus: HashSet<usize>,
+}
+
+impl<CS> FocusScheduler<CS> {
+    /// Wraps `base`, without a focus function if `focus` is empty
+    pub fn new<S>(state: &mut S, focus: HashSet<usize>, base: CS) -> Self
+    where
+        S: HasMetadata,
+    {
+        let _ = state.metadata_or_insert_with(FocusMetadata::default);
+        Self { base, focus }
+    }
+}
+
+impl<CS, I, S> RemovableScheduler<I, S> for FocusScheduler<CS>
+where
+    CS: RemovableScheduler<I, S>,
+    S: HasMetadata,
+{
+    fn on_remove(
+        &mut self,
+        state: &mut S,
+        id: CorpusId,
+        testcase: &Option<Testcase<I>>,
+    ) -> Result<(), Error> {
+        self.base.on_remove(state, id, testcase)?;
+        state.metadata_mut::<FocusMetadata>()?.reaching.remove(&id);
+        Ok(())
+    }
+
+    fn on_replace(&mut self, state: &mut S, id: CorpusId, prev: &Testcase<I>) -> Result<(), Error> {
+        self.base.on_replace(state, id, prev)
+    }
+}
+
+impl<CS, I, S> Scheduler<I, S> for FocusScheduler<CS>
+where
+    CS: Scheduler<I, S>,
+    S: HasCorpus<I> + HasMetadata + HasRand,
+{
+    fn on_add(&mut self, state: &mut S, id: CorpusId) -> Result<(), Error> {
+        self.base.on_add(state, id)?;
+        if self.focus.is_empty() {
+            return Ok(());
+        }
+
+        let reaches = state
+            .corpus()
+            .get(id)?
+            .borrow()
+            .metadata_map()
+            .get::<MapIndexesMetadata>()
+            .is_some_and(|meta| meta.list.iter().any(|idx| self.focus.contains(idx)));
+        if reaches {
+            state.metadata_mut::<FocusMetadata>()?.reaching.insert(id);
+        }
+        Ok(())
+    }
+
+    fn on_evaluation<OT>(&mut self, state: &mut S, input: &I, observers: &OT) -> Result<(), Error>
+    where
+        OT: MatchName,
+    {
+        self.base.on_evaluation(state, input, observers)
+    }
+
+    fn next(&mut self, state: &mut S) -> Result<CorpusId, Error> {
+        let mut id = self.base.next(state)?;
+        if state.metadata::<FocusMetadata>()?.reaching.is_empty() {
+            return Ok(id);
+        }
+
+        for _ in 0..FOCUS_TRIES {
+            if state.metadata::<FocusMetadata>()?.reaching.contains(&id) {
+                return Ok(id);
+            }
+            id = self.base.next(state)?;
+        }
+        let reaching = state
+            .metadata::<FocusMetadata>()?
+            .reaching
+            .iter()
+            .copied()
+            .collect::<Vec<_>>();
+        id = state.rand_mut().choose(reaching).unwrap();
+        self.base.set_current_scheduled(state, Some(id))?;
+        Ok(id)
+    }
+
+    fn set_current_scheduled(
+        &mut self,
+        state: &mut S,
+        next_id: Option<CorpusId>,
+    ) -> Result<(), Error> {
+        self.base.set_current_scheduled(state, next_id)
+    }
+}
+
+/// Returns the indexes of the edges map belonging to the functions named `name`,
+/// either by their symbol name, or by their demangled name, with or without parameters.
+pub fn focus_function_indexes(name: &str) -> HashSet<usize> {
+    sanitizer_cov_function_pcs(|pc| {
+        let mut found = false;
+        backtrace::resolve(pc as *mut c_void, |symbol| {
+            if let Some(symbol_name) = symbol.name() {
+                let demangled = format!("{symbol_name:#}");
+                found |= symbol_name.as_str() == Some(name)
+                    || demangled == name
+                    || demangled
+                        .split_once('(')
+                        .is_some_and(|(prefix, _)| prefix == name);
+            }
+        });
+        found
+    })
+    .into_iter()
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use hashbrown::HashSet;
+    use libafl::{
+        HasMetadata,
+        corpus::{Corpus, CorpusId, InMemoryCorpus, Testcase},
+        feedbacks::MapIndexesMetadata,
+        inputs::BytesInput,
+        schedulers::{QueueScheduler, RemovableScheduler, Scheduler},
+        state::{HasCorpus, StdState},
+    };
+    use libafl_bolts::rands::StdRand;
+
+    use super::FocusScheduler;
+
+    type TestState =
+        StdState<InMemoryCorpus<BytesInput>, BytesInput, StdRand, InMemoryCorpus<BytesInput>>;
+
+    /// A state with a corpus entry for each list of indexes
+    fn state(indexes: &[&[usize]]) -> TestState {
+        let mut corpus = InMemoryCorpus::new();
+        for list in indexes {
+            let mut testcase = Testcase::new(BytesInput::new(vec![0]));
+            testcase.add_metadata(MapIndexesMetadata::new(list.to_vec()));
+            corpus.add(testcase).unwrap();
+        }
+        StdState::new(
+            StdRand::with_seed(0),
+            corpus,
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )
+        .unwrap()
+    }
+
+    fn scheduler(state: &mut TestState, focus: &[usize]) -> FocusScheduler<QueueScheduler> {
+        let mut scheduler = FocusScheduler::new(
+            state,
+            focus.iter().copied().collect::<HashSet<_>>(),
+            QueueScheduler::new(),
+        );
+        for id in state.corpus().ids().collect::<Vec<_>>() {
+            scheduler.on_add(state, id).unwrap();
+        }
+        scheduler
+    }
+
+    fn schedule(
+        scheduler: &mut FocusScheduler<QueueScheduler>,
+        state: &mut TestState,
+    ) -> Vec<CorpusId> {
+        (0..6).map(|_| scheduler.next(state).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_no_focus() {
+        let mut state = state(&[&[1], &[2], &[3]]);
+        let mut scheduler = scheduler(&mut state, &[]);
+        let ids = schedule(&mut scheduler, &mut state);
+        assert_eq!(ids.iter().collect::<HashSet<_>>().len(), 3);
+    }
+
+    #[test]
+    fn test_focus() {
+        let mut state = state(&[&[1], &[2, 5], &[3]]);
+        let mut scheduler = scheduler(&mut state, &[4, 5]);
+        assert_eq!(schedule(&mut scheduler, &mut state), [CorpusId(1); 6]);
+
+        // without entries reaching the focus function, all entries are scheduled
+        let testcase = state.corpus_mut().remove(CorpusId(1)).unwrap();
+        scheduler
+            .on_remove(&mut state, CorpusId(1), &Some(testcase))
+            .unwrap();
+        let ids = schedule(&mut scheduler, &mut state);
+        assert_eq!(
+            ids.into_iter().collect::<HashSet<_>>(),
+            [CorpusId(0), CorpusId(2)].into_iter().collect()
+        );
+    }
+
+    #[test]
+    fn test_focus_unreached() {
+        let mut state = state(&[&[1], &[2], &[3]]);
+        let mut scheduler = scheduler(&mut state, &[4]);
+        let ids = schedule(&mut scheduler, &mut state);
+        assert_eq!(ids.iter().collect::<HashSet<_>>().len(), 3);
+    }
+}
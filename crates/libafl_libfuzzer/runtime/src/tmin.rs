@@ -138,12 +138,13 @@ fn minimize_crash_with_mutator<M: Mutator<BytesInput, TMinState>>(
             options.dirs()[0].as_path().as_os_str().to_str().unwrap()
         );
     } else {
-        let mut dest = options.artifact_prefix().dir().clone();
-        dest.push(format!(
-            "{}minimized-from-{}",
-            options.artifact_prefix().filename_prefix(),
-            options.dirs()[0].file_name().unwrap().to_str().unwrap()
-        ));
+        let dest = options.exact_artifact_path().cloned().unwrap_or_else(|| {
+            options.artifact_prefix().dir().join(format!(
+                "{}minimized-from-{}",
+                options.artifact_prefix().filename_prefix(),
+                options.dirs()[0].file_name().unwrap().to_str().unwrap()
+            ))
+        });
         write(&dest, input)?;
         println!(
             "Wrote minimised input to {}",
@@ -0,0 +1,34 @@
+//! Runtime for the distance instrumentation of directed fuzzing.
+//!
+//! Targets compiled with the `Distance` pass of `libafl_cc` add the distance of every executed basic block
+//! to [`__libafl_distance_sum`] and count the blocks in [`__libafl_distance_count`].
+
+use alloc::borrow::Cow;
+
+use libafl::observers::DistanceObserver;
+
+/// The sum of the distances of the executed basic blocks, written by the instrumentation
+#[unsafe(no_mangle)]
+pub static mut __libafl_distance_sum: u64 = 0;
+
+/// The number of executed basic blocks with a distance, written by the instrumentation
+#[unsafe(no_mangle)]
+pub static mut __libafl_distance_count: u64 = 0;
+
+/// Creates a [`DistanceObserver`] for the distance instrumentation of this target.
+///
+/// # Safety
+/// The observer accesses the global distance counters, only one observer should use them at a time.
+#[must_use]
+pub unsafe fn distance_observer<S>(name: S) -> DistanceObserver
+where
+    S: Into<Cow<'static, str>>,
+{
+    unsafe {
+        DistanceObserver::from_mut_ptr(
+            name,
+            &raw mut __libafl_distance_sum,
+            &raw mut __libafl_distance_count,
+        )
+    }
+}
@@ -0,0 +1,287 @@
+//! Line coverage reports in the [`lcov`](https://github.com/linux-test-project/lcov) `.info` format,
+//! and as static HTML pages.
+//!
+//! Fill a [`LcovReport`] with the source lines hit by a corpus, for example using the
+//! `CoverageDumpHook` of `sancov_pcguard_dump_cov`, or by symbolizing `DrCov` traces of `QEMU` or `FRIDA` targets.
+
+use alloc::{
+    borrow::ToOwned,
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::Write as _;
+use std::{fs, path::Path};
+
+use libafl_bolts::Error;
+
+/// The lines and functions of one source file in a [`LcovReport`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LcovFile {
+    /// The hit count of each line, lines with a count of `0` are instrumented but never hit
+    pub lines: BTreeMap<u32, u64>,
+    /// The first line and the hit count of each function
+    pub functions: BTreeMap<String, (u32, u64)>,
+}
+
+impl LcovFile {
+    /// The number of instrumented lines
+    #[must_use]
+    pub fn lines_found(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// The number of lines hit at least once
+    #[must_use]
+    pub fn lines_hit(&self) -> usize {
+        self.lines.values().filter(|hits| **hits > 0).count()
+    }
+}
+
+/// Line coverage, by source file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LcovReport {
+    files: BTreeMap<String, LcovFile>,
+}
+
+impl LcovReport {
+    /// Creates an empty report
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The source files of this report
+    #[must_use]
+    pub fn files(&self) -> &BTreeMap<String, LcovFile> {
+        &self.files
+    }
+
+    /// Whether the report does not contain any line
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Adds `hits` to the hit count of a line.
+    ///
+    /// Add lines with `0` hits to mark them as instrumented, so they show up as not covered.
+    pub fn add_line(&mut self, file: &str, line: u32, hits: u64) {
+        *self.file_mut(file).lines.entry(line).or_default() += hits;
+    }
+
+    /// Adds a function starting at, or containing, `line`.
+    ///
+    /// The function starts at the smallest line it was added with,
+    /// and its hit count is the highest hit count it was added with.
+    pub fn add_function(&mut self, file: &str, name: &str, line: u32, hits: u64) {
+        let (first_line, max_hits) = self
+            .file_mut(file)
+            .functions
+            .entry(name.to_owned())
+            .or_insert((line, hits));
+        *first_line = (*first_line).min(line);
+        *max_hits = (*max_hits).max(hits);
+    }
+
+    /// Adds the hit counts of another report to this one
+    pub fn merge(&mut self, other: &Self) {
+        for (file, other_file) in &other.files {
+            for (line, hits) in &other_file.lines {
+                self.add_line(file, *line, *hits);
+            }
+            for (name, (line, hits)) in &other_file.functions {
+                self.add_function(file, name, *line, *hits);
+            }
+        }
+    }
+
+    fn file_mut(&mut self, file: &str) -> &mut LcovFile {
+        self.files.entry(file.to_owned()).or_default()
+    }
+
+    /// The report in the `lcov` `.info` format
+    #[must_use]
+    pub fn to_info(&self) -> String {
+        let mut info = String::new();
+        for (file, coverage) in &self.files {
+            info.push_str("TN:\nSF:");
+            info.push_str(file);
+            info.push('\n');
+            for (name, (line, _)) in &coverage.functions {
+                writeln!(info, "FN:{line},{name}").unwrap();
+            }
+            for (name, (_, hits)) in &coverage.functions {
+                writeln!(info, "FNDA:{hits},{name}").unwrap();
+            }
+            writeln!(info, "FNF:{}", coverage.functions.len()).unwrap();
+            writeln!(
+                info,
+                "FNH:{}",
+                coverage
+                    .functions
+                    .values()
+                    .filter(|(_, hits)| *hits > 0)
+                    .count()
+            )
+            .unwrap();
+            for (line, hits) in &coverage.lines {
+                writeln!(info, "DA:{line},{hits}").unwrap();
+            }
+            writeln!(info, "LF:{}", coverage.lines_found()).unwrap();
+            writeln!(info, "LH:{}", coverage.lines_hit()).unwrap();
+            info.push_str("end_of_record\n");
+        }
+        info
+    }
+
+    /// Writes the report to an `lcov` `.info` file
+    pub fn write_info<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        fs::write(path, self.to_info())?;
+        Ok(())
+    }
+
+    /// Writes a static HTML report to `dir`, with an `index.html` listing all source files.
+    ///
+    /// Source files that can be read from disk are shown in full, with hit lines in green and
+    /// instrumented, but never hit, lines in red.
+    pub fn write_html<P: AsRef<Path>>(&self, dir: P) -> Result<(), Error> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+
+        let mut rows = String::new();
+        let (mut total_found, mut total_hit) = (0, 0);
+        for (idx, (file, coverage)) in self.files.iter().enumerate() {
+            let page = format!("file_{idx}.html");
+            fs::write(dir.join(&page), file_page(file, coverage))?;
+
+            total_found += coverage.lines_found();
+            total_hit += coverage.lines_hit();
+            writeln!(
+                rows,
+                "<tr><td><a href=\"{page}\">{}</a></td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape_html(file),
+                coverage.lines_hit(),
+                coverage.lines_found(),
+                percent(coverage.lines_hit(), coverage.lines_found())
+            )
+            .unwrap();
+        }
+
+        let index = format!(
+            "{HTML_HEADER}<h1>Coverage report</h1>\n<p>{total_hit} of {total_found} lines hit ({})</p>\n\
+             <table>\n<tr><th>File</th><th>Hit</th><th>Found</th><th>Coverage</th></tr>\n{rows}</table>\n{HTML_FOOTER}",
+            percent(total_hit, total_found)
+        );
+        fs::write(dir.join("index.html"), index)?;
+        Ok(())
+    }
+}
+
+const HTML_HEADER: &str = "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Coverage report</title>\n\
+    <style>body{font-family:sans-serif}table{border-collapse:collapse}td,th{padding:2px 8px;text-align:left}\
+    pre{margin:0}.hit{background:#c8f0c8}.miss{background:#f0c8c8}.count{text-align:right;color:#666}</style>\n\
+    </head>\n<body>\n";
+const HTML_FOOTER: &str = "</body>\n</html>\n";
+
+/// The page of one source file, with the source if it can be read
+fn file_page(file: &str, coverage: &LcovFile) -> String {
+    let source = fs::read_to_string(file).ok();
+    let line_count = source.as_ref().map_or_else(
+        || coverage.lines.keys().last().copied().unwrap_or(0) as usize,
+        |source| source.lines().count(),
+    );
+    let mut source_lines = source.as_deref().map(str::lines);
+
+    let mut rows = String::new();
+    for line in 1..=line_count {
+        let text = source_lines
+            .as_mut()
+            .and_then(Iterator::next)
+            .unwrap_or_default();
+        #[expect(clippy::cast_possible_truncation)]
+        let hits = coverage.lines.get(&(line as u32));
+        if source.is_none() && hits.is_none() {
+            continue;
+        }
+        let (class, count) = match hits {
+            Some(0) => ("miss", "0".to_string()),
+            Some(hits) => ("hit", hits.to_string()),
+            None => ("", String::new()),
+        };
+        writeln!(
+            rows,
+            "<tr class=\"{class}\"><td class=\"count\">{line}</td><td class=\"count\">{count}</td><td><pre>{}</pre></td></tr>",
+            escape_html(text)
+        )
+        .unwrap();
+    }
+
+    let mut functions = coverage.functions.iter().collect::<Vec<_>>();
+    functions.sort_by_key(|(_, (line, _))| *line);
+    let mut function_rows = String::new();
+    for (name, (line, hits)) in functions {
+        writeln!(
+            function_rows,
+            "<tr><td>{}</td><td>{line}</td><td>{hits}</td></tr>",
+            escape_html(name)
+        )
+        .unwrap();
+    }
+
+    format!(
+        "{HTML_HEADER}<p><a href=\"index.html\">Index</a></p>\n<h1>{}</h1>\n<p>{} of {} lines hit ({})</p>\n\
+         <table>\n<tr><th>Function</th><th>Line</th><th>Hits</th></tr>\n{function_rows}</table>\n\
+         <h2>Source</h2>\n<table>\n{rows}</table>\n{HTML_FOOTER}",
+        escape_html(file),
+        coverage.lines_hit(),
+        coverage.lines_found(),
+        percent(coverage.lines_hit(), coverage.lines_found())
+    )
+}
+
+#[expect(clippy::cast_precision_loss)]
+fn percent(hit: usize, found: usize) -> String {
+    if found == 0 {
+        "-".to_string()
+    } else {
+        format!("{:.1}%", hit as f64 * 100.0 / found as f64)
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LcovReport;
+
+    #[test]
+    fn test_lcov_info() {
+        let mut report = LcovReport::new();
+        report.add_line("a.c", 3, 2);
+        report.add_line("a.c", 4, 0);
+        report.add_function("a.c", "main", 3, 2);
+
+        let mut other = LcovReport::new();
+        other.add_line("a.c", 3, 1);
+        other.add_function("a.c", "main", 2, 1);
+        report.merge(&other);
+
+        assert_eq!(
+            report.to_info(),
+            "TN:\nSF:a.c\nFN:2,main\nFNDA:2,main\nFNF:1\nFNH:1\nDA:3,3\nDA:4,0\nLF:2\nLH:1\nend_of_record\n"
+        );
+    }
+}
@@ -119,6 +119,11 @@ pub use coverage::*;
 pub mod value_profile;
 pub use value_profile::*;
 
+#[cfg(feature = "distance")]
+pub mod distance;
+#[cfg(feature = "distance")]
+pub use distance::*;
+
 /// The module to hook call instructions
 #[cfg(feature = "function-logging")]
 pub mod call;
@@ -132,6 +137,9 @@ pub use cmps::*;
 #[cfg(feature = "std")]
 pub mod drcov;
 
+#[cfg(feature = "std")]
+pub mod lcov;
+
 #[cfg(all(windows, feature = "std", feature = "windows_asan"))]
 pub mod windows_asan;
 #[cfg(all(windows, feature = "std", feature = "windows_asan"))]
@@ -141,3 +149,6 @@ pub use windows_asan::*;
 pub mod forkserver;
 #[cfg(all(unix, feature = "std", feature = "forkserver"))]
 pub use forkserver::*;
+
+#[cfg(all(unix, feature = "std", feature = "persistent_command"))]
+pub mod persistent_command;
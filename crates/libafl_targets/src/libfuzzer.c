@@ -29,6 +29,7 @@ EXT_FUNC_IMPL(LLVMFuzzerTestOneInput, int, (const uint8_t *Data, size_t Size),
 }
 
 EXT_FUNC(libafl_main, void, (void), false);
+EXT_FUNC(__lsan_do_recoverable_leak_check, int, (void), false);
 #ifdef FUZZER_DEFINE_RUN_DRIVER
 extern int LLVMFuzzerRunDriver(int *argc, char ***argv,
                                int (*UserCb)(const uint8_t *Data, size_t Size));
@@ -107,6 +108,15 @@ EXPORT_FN size_t libafl_targets_libfuzzer_custom_crossover(
                                    Seed);
 }
 
+EXPORT_FN int libafl_targets_has_lsan() {
+  return CHECK_WEAK_FN(__lsan_do_recoverable_leak_check);
+}
+
+// trust the user to check this appropriately :)
+EXPORT_FN int libafl_targets_lsan_leak_check() {
+  return __lsan_do_recoverable_leak_check();
+}
+
 EXPORT_FN size_t libafl_check_malloc_size(void *ptr) {
 #if defined(__APPLE__)
   return malloc_size(ptr);
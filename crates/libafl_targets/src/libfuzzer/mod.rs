@@ -19,6 +19,10 @@ unsafe extern "C" {
 
     // libafl_targets_libfuzzer_init calls LLVMFuzzerInitialize()
     fn libafl_targets_libfuzzer_init(argc: *const i32, argv: *const *const *const u8) -> i32;
+
+    fn libafl_targets_has_lsan() -> i32;
+    #[cfg(feature = "libfuzzer_oom")]
+    fn libafl_targets_lsan_leak_check() -> i32;
 }
 
 /// Calls the (native) libfuzzer initialize function.
@@ -47,3 +51,20 @@ pub unsafe fn libfuzzer_initialize(args: &[String]) -> i32 {
 pub unsafe fn libfuzzer_test_one_input(buf: &[u8]) -> i32 {
     unsafe { LLVMFuzzerTestOneInput(buf.as_ptr(), buf.len()) }
 }
+
+/// Whether the target is linked with `LeakSanitizer`, standalone or as part of `AddressSanitizer`
+#[must_use]
+pub fn has_lsan() -> bool {
+    unsafe { libafl_targets_has_lsan() != 0 }
+}
+
+/// Runs a `LeakSanitizer` leak check, which reports the leaks it finds without exiting,
+/// and returns whether it found any. Use with a `LeakCheckHook`.
+///
+/// The `LeakCheckHook` only checks after executions which allocated more than they freed, as counted by
+/// the sanitizer malloc hooks of the `libfuzzer_oom` feature.
+#[cfg(feature = "libfuzzer_oom")]
+#[must_use]
+pub fn lsan_leak_check() -> bool {
+    has_lsan() && unsafe { libafl_targets_lsan_leak_check() != 0 }
+}
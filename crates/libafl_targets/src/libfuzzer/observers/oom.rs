@@ -6,7 +6,10 @@ use core::{
 };
 
 use libafl::{
-    executors::ExitKind,
+    executors::{
+        ExitKind,
+        hooks::leak::{count_free, count_malloc},
+    },
     feedbacks::{Feedback, StateInitializer},
     observers::Observer,
 };
@@ -27,12 +30,13 @@ static MALLOC_MAX: AtomicUsize = AtomicUsize::new(2 << 30);
 static MALLOC_SIZE: AtomicUsize = AtomicUsize::new(0);
 
 /// malloc hook which will be invoked if address sanitizer is present. Used to detect if the target makes a malloc call
-/// that will exceed the permissible size
+/// that will exceed the permissible size, and to count the mallocs for the `LeakCheckHook`
 ///
 /// # Safety
 /// Is only safe to call with valid freshly allocated pointers backed by allocations of `size`.
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn __sanitizer_malloc_hook(ptr: *const c_void, size: usize) {
+    count_malloc();
     if RUNNING.load(Ordering::Relaxed) {
         let size = match unsafe { libafl_check_malloc_size(ptr) } {
             0 => size, // either the malloc size function didn't work or it's really zero-sized
@@ -52,12 +56,13 @@ pub unsafe extern "C" fn __sanitizer_malloc_hook(ptr: *const c_void, size: usize
 }
 
 /// free hook which will be invoked if ASAN is present. Used to detect if the target makes a malloc call that will
-/// exceed the permissible size
+/// exceed the permissible size, and to count the frees for the `LeakCheckHook`
 ///
 /// # Safety
 /// Is only safe to call with valid allocated pointers, about to be freed.
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn __sanitizer_free_hook(ptr: *const c_void) {
+    count_free();
     if RUNNING.load(Ordering::Relaxed) {
         let size = unsafe { libafl_check_malloc_size(ptr) };
         MALLOC_SIZE
@@ -0,0 +1,19 @@
+#ifndef __LIBAFL_TARGETS_PERSISTENT_COMMAND__
+#define __LIBAFL_TARGETS_PERSISTENT_COMMAND__
+
+#include <stddef.h>
+#include <stdint.h>
+
+// Client of the `PersistentCommandExecutor`, see `persistent_command.rs`.
+
+// Attaches to the fuzzer, returns 0 on success.
+int32_t libafl_persistent_init(void);
+
+// Waits for the next input. Returns 1 for a new input, 0 once the fuzzer
+// ended the session, and -1 on errors. The input stays valid until the next call.
+int32_t libafl_persistent_next(const uint8_t **data, size_t *len);
+
+// Like `libafl_persistent_next`, but rewinds stdin to the new input instead.
+int32_t libafl_persistent_next_stdin(void);
+
+#endif
@@ -0,0 +1,244 @@
+//! The client side of the `PersistentCommandExecutor`, to be linked into external targets.
+//!
+//! The client maps the input shared memory, announces itself to the fuzzer and then hands out one input per
+//! iteration. Targets reading from stdin can use [`PersistentClient::next_input_on_stdin`], which resets stdin
+//! to the current input before every iteration.
+//!
+//! From C, include `persistent_command.h` and use the exported functions:
+//! ```c
+//! if (libafl_persistent_init() != 0) { return 1; }
+//! const uint8_t *data;
+//! size_t len;
+//! while (libafl_persistent_next(&data, &len) > 0) {
+//!   harness(data, len);
+//! }
+//! ```
+
+use alloc::vec::Vec;
+use core::ptr;
+use std::{
+    env,
+    fs::{self, File},
+    io::{Seek, SeekFrom, Write},
+    os::fd::{AsRawFd, BorrowedFd},
+    process,
+    sync::Mutex,
+};
+
+use libafl::executors::{
+    forkserver::FORKSRV_FD,
+    persistent_command::{
+        PERSISTENT_DONE, PERSISTENT_HDR_SIZE, PERSISTENT_HELLO, PERSISTENT_RUN,
+        PERSISTENT_SHM_ENV_VAR,
+    },
+};
+use libafl_bolts::{Error, os::dup2};
+use shmem_providers::{ShMem, ShMemProvider, UnixShMem, UnixShMemProvider};
+
+/// SAFETY:
+///
+/// The fd is set up by the `PersistentCommandExecutor` and stays open for the lifetime of the target.
+const CTL_FD: BorrowedFd<'static> = unsafe { BorrowedFd::borrow_raw(FORKSRV_FD) };
+/// SAFETY:
+///
+/// The fd is set up by the `PersistentCommandExecutor` and stays open for the lifetime of the target.
+const ST_FD: BorrowedFd<'static> = unsafe { BorrowedFd::borrow_raw(FORKSRV_FD + 1) };
+
+unsafe extern "C" {
+    /// The C `stdin` stream, reset together with fd 0
+    #[cfg_attr(target_vendor = "apple", link_name = "__stdinp")]
+    static mut stdin: *mut libc::FILE;
+}
+
+fn write_status(status: u32) -> Result<(), Error> {
+    let written = nix::unistd::write(ST_FD, &status.to_ne_bytes())?;
+    if written != size_of::<u32>() {
+        return Err(Error::illegal_state(format!(
+            "Could not write to the status pipe. Expected {} bytes, wrote {written} bytes",
+            size_of::<u32>()
+        )));
+    }
+    Ok(())
+}
+
+/// The client of a `PersistentCommandExecutor`, handing out the inputs sent by the fuzzer
+#[derive(Debug)]
+pub struct PersistentClient<SHM> {
+    shmem: SHM,
+    running: bool,
+    stdin_file: Option<File>,
+}
+
+impl<SHM> PersistentClient<SHM>
+where
+    SHM: ShMem,
+{
+    /// Maps the input shared memory and tells the fuzzer that the target is ready
+    pub fn attach<SP>(shmem_provider: &mut SP) -> Result<Self, Error>
+    where
+        SP: ShMemProvider<ShMem = SHM>,
+    {
+        let shmem = shmem_provider.existing_from_env(PERSISTENT_SHM_ENV_VAR)?;
+        if shmem.len() < PERSISTENT_HDR_SIZE {
+            return Err(Error::illegal_state("The input shared memory is too small"));
+        }
+        write_status(PERSISTENT_HELLO)?;
+        Ok(Self {
+            shmem,
+            running: false,
+            stdin_file: None,
+        })
+    }
+
+    /// Waits for the next input, reporting the previous iteration as done.
+    ///
+    /// Returns `None` once the fuzzer closed the session, the target should exit then.
+    pub fn next_input(&mut self) -> Result<Option<&[u8]>, Error> {
+        if self.running {
+            write_status(PERSISTENT_DONE)?;
+            self.running = false;
+        }
+
+        let mut buf = [0_u8; 4];
+        match nix::unistd::read(CTL_FD, &mut buf)? {
+            0 => return Ok(None),
+            4 if u32::from_ne_bytes(buf) == PERSISTENT_RUN => (),
+            _ => {
+                return Err(Error::illegal_state(
+                    "Unexpected message on the control pipe",
+                ));
+            }
+        }
+        self.running = true;
+
+        let mut len = [0_u8; PERSISTENT_HDR_SIZE];
+        len.copy_from_slice(&self.shmem[..PERSISTENT_HDR_SIZE]);
+        let len = (u32::from_ne_bytes(len) as usize).min(self.shmem.len() - PERSISTENT_HDR_SIZE);
+        Ok(Some(
+            &self.shmem[PERSISTENT_HDR_SIZE..PERSISTENT_HDR_SIZE + len],
+        ))
+    }
+
+    /// Waits for the next input like [`Self::next_input`], and makes it the content of stdin.
+    ///
+    /// Both fd 0 and the C `stdin` stream are rewound, so targets can read stdin from the start every iteration.
+    /// Returns `false` once the fuzzer closed the session.
+    pub fn next_input_on_stdin(&mut self) -> Result<bool, Error> {
+        let Some(input) = self.next_input()? else {
+            return Ok(false);
+        };
+        let input: Vec<u8> = input.to_vec();
+
+        if self.stdin_file.is_none() {
+            let path = env::temp_dir().join(format!(".libafl_persistent_stdin_{}", process::id()));
+            let file = File::options()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&path)?;
+            fs::remove_file(&path)?;
+            // # Safety
+            // Both fds are valid, fd 0 is replaced by the file on purpose.
+            unsafe {
+                dup2(file.as_raw_fd(), libc::STDIN_FILENO)?;
+            }
+            self.stdin_file = Some(file);
+        }
+        let file = self.stdin_file.as_mut().unwrap();
+        // fd 0 shares the file offset with our fd
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&input)?;
+        file.seek(SeekFrom::Start(0))?;
+
+        // # Safety
+        // Seeking the C stdin stream drops its buffer and clears the EOF indicator.
+        unsafe {
+            let stream = ptr::read(&raw const stdin);
+            if !stream.is_null() {
+                libc::fseek(stream, 0, libc::SEEK_SET);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Runs the harness on every input until the fuzzer closes the session
+    pub fn run<F>(&mut self, mut harness: F) -> Result<(), Error>
+    where
+        F: FnMut(&[u8]),
+    {
+        while let Some(input) = self.next_input()? {
+            harness(input);
+        }
+        Ok(())
+    }
+}
+
+/// The client used by the C interface
+static CLIENT: Mutex<Option<PersistentClient<UnixShMem>>> = Mutex::new(None);
+
+/// Attaches to the `PersistentCommandExecutor`, returns 0 on success.
+#[unsafe(no_mangle)]
+pub extern "C" fn libafl_persistent_init() -> i32 {
+    let client = UnixShMemProvider::new()
+        .and_then(|mut shmem_provider| PersistentClient::attach(&mut shmem_provider));
+    match client {
+        Ok(client) => {
+            *CLIENT.lock().unwrap() = Some(client);
+            0
+        }
+        Err(err) => {
+            log::error!("Could not attach to the persistent command executor: {err}");
+            -1
+        }
+    }
+}
+
+/// Waits for the next input and stores it in `data` and `len`.
+///
+/// Returns 1 for a new input, 0 once the session is over, and -1 on errors.
+/// The input stays valid until the next call.
+///
+/// # Safety
+/// `data` and `len` must be valid pointers.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn libafl_persistent_next(data: *mut *const u8, len: *mut usize) -> i32 {
+    let mut client = CLIENT.lock().unwrap();
+    let Some(client) = client.as_mut() else {
+        return -1;
+    };
+    match client.next_input() {
+        Ok(Some(input)) => {
+            unsafe {
+                *data = input.as_ptr();
+                *len = input.len();
+            }
+            1
+        }
+        Ok(None) => 0,
+        Err(err) => {
+            log::error!("Could not receive the next input: {err}");
+            -1
+        }
+    }
+}
+
+/// Waits for the next input and makes it the content of stdin.
+///
+/// Returns 1 for a new input, 0 once the session is over, and -1 on errors.
+#[unsafe(no_mangle)]
+pub extern "C" fn libafl_persistent_next_stdin() -> i32 {
+    let mut client = CLIENT.lock().unwrap();
+    let Some(client) = client.as_mut() else {
+        return -1;
+    };
+    match client.next_input_on_stdin() {
+        Ok(true) => 1,
+        Ok(false) => 0,
+        Err(err) => {
+            log::error!("Could not receive the next input: {err}");
+            -1
+        }
+    }
+}
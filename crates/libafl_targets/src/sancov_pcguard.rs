@@ -377,7 +377,11 @@ unsafe extern "C" fn __sanitizer_cov_pcs_init(pcs_beg: *const usize, pcs_end: *c
 
         let pc_tables_ptr = &raw mut PC_TABLES;
         let pc_tables = &mut *pc_tables_ptr;
-        pc_tables.push(slice::from_raw_parts(pcs_beg as *const PcTableEntry, len));
+        // Each entry is a PC and its flags
+        pc_tables.push(slice::from_raw_parts(
+            pcs_beg as *const PcTableEntry,
+            len / 2,
+        ));
     }
 }
 
@@ -413,3 +417,30 @@ pub fn sanitizer_cov_pc_table<'a>() -> impl Iterator<Item = &'a [PcTableEntry]>
         pc_tables.iter().copied()
     }
 }
+
+/// Returns the indexes of the PCs of the functions whose entry PC satisfies `is_function`,
+/// counting the entries of all PC tables one after the other.
+///
+/// The PCs of a function are the ones from its entry to the next function entry in the table.
+/// With `inline-8bit-counters`, these are the indexes of the counters of the functions in the
+/// concatenated counters maps, as used by the libfuzzer `-focus_function`.
+pub fn sanitizer_cov_function_pcs<F>(mut is_function: F) -> Vec<usize>
+where
+    F: FnMut(usize) -> bool,
+{
+    let mut indexes = Vec::new();
+    let mut offset = 0;
+    for table in sanitizer_cov_pc_table() {
+        let mut in_function = false;
+        for (idx, entry) in table.iter().enumerate() {
+            if entry.is_function_entry() {
+                in_function = is_function(entry.addr());
+            }
+            if in_function {
+                indexes.push(offset + idx);
+            }
+        }
+        offset += table.len();
+    }
+    indexes
+}
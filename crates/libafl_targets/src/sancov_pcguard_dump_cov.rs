@@ -1,12 +1,21 @@
 //! Dump coverage to `gcov`/[`lcov`](https://github.com/linux-test-project/lcov)
 //! `.info` files.
-//! Use them with `genhtml` to generate HTML coverage reports.
+//! Use them with `genhtml` to generate HTML coverage reports,
+//! or write the coverage of the whole corpus with [`CoverageDumpHook::write_report`].
+//! Compile the target with `-fsanitize-coverage=trace-pc-guard,pc-table` to also list
+//! the instrumented lines no testcase hits.
 use alloc::{
     string::{String, ToString},
     vec::Vec,
 };
 use core::sync::atomic::Ordering;
-use std::{collections::HashMap, fs::File, io::Write as IoWrite, path::PathBuf, sync::Mutex};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::Write as IoWrite,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
 
 use libafl::{
     Error,
@@ -15,7 +24,12 @@ use libafl::{
     state::HasCorpus,
 };
 
-use crate::sancov_pcguard::{LIBAFL_TARGETS_TRACE_PC_GUARD_HOOK, nop_target_pc_guard};
+use crate::{
+    lcov::LcovReport,
+    sancov_pcguard::{
+        LIBAFL_TARGETS_TRACE_PC_GUARD_HOOK, nop_target_pc_guard, sanitizer_cov_pc_table,
+    },
+};
 
 static COVERED_PCS: Mutex<Option<HashMap<usize, usize>>> = Mutex::new(None);
 
@@ -29,6 +43,32 @@ pub struct SrcLoc {
     hits: usize,
 }
 
+impl SrcLoc {
+    /// Resolves the function and source line of `pc`
+    fn resolve(pc: usize, hits: usize) -> Self {
+        let mut loc = SrcLoc {
+            pc,
+            function: None,
+            filename: None,
+            line: None,
+            hits,
+        };
+
+        backtrace::resolve(pc as *mut _, |symbol| {
+            if let Some(name) = symbol.name() {
+                loc.function = Some(name.to_string());
+            }
+            if let Some(filename) = symbol.filename() {
+                loc.filename = Some(filename.display().to_string());
+            }
+            if let Some(lineno) = symbol.lineno() {
+                loc.line = Some(lineno);
+            }
+        });
+        loc
+    }
+}
+
 /// Dump the covered lines
 ///
 /// # Arguments
@@ -44,26 +84,7 @@ pub fn dump_covered_lines(clear: bool) -> Vec<SrcLoc> {
         && let Some(map) = guard.as_mut()
     {
         for (&pc, &hits) in map.iter() {
-            let mut loc = SrcLoc {
-                pc,
-                function: None,
-                filename: None,
-                line: None,
-                hits,
-            };
-
-            backtrace::resolve(pc as *mut _, |symbol| {
-                if let Some(name) = symbol.name() {
-                    loc.function = Some(name.to_string());
-                }
-                if let Some(filename) = symbol.filename() {
-                    loc.filename = Some(filename.display().to_string());
-                }
-                if let Some(lineno) = symbol.lineno() {
-                    loc.line = Some(lineno);
-                }
-            });
-            res.push(loc);
+            res.push(SrcLoc::resolve(pc, hits));
         }
         if clear {
             map.clear();
@@ -72,6 +93,30 @@ pub fn dump_covered_lines(clear: bool) -> Vec<SrcLoc> {
     res
 }
 
+/// The source lines and functions of all instrumented PCs, with 0 hits.
+///
+/// The PCs are taken from the `sanitizer_cov` PC tables, so this is empty unless the target
+/// is compiled with `-fsanitize-coverage=pc-table`.
+#[must_use]
+pub fn instrumented_lines() -> LcovReport {
+    let mut report = LcovReport::new();
+    for table in sanitizer_cov_pc_table() {
+        for entry in table {
+            // The table holds the start of each block, but `resolve` expects a return address and looks up the byte before
+            let loc = SrcLoc::resolve(entry.addr() + 1, 0);
+            if let (Some(filename), Some(line)) = (&loc.filename, loc.line) {
+                report.add_line(filename, line, 0);
+                if entry.is_function_entry()
+                    && let Some(func) = &loc.function
+                {
+                    report.add_function(filename, func, line, 0);
+                }
+            }
+        }
+    }
+    report
+}
+
 /// Clears the covered pcguard lines.
 pub fn clear_covered_lines() {
     if let Ok(mut guard) = COVERED_PCS.lock()
@@ -96,18 +141,44 @@ pub fn pcguard_disable_coverage_collection() {
 }
 
 /// A hook that dumps coverage to files
+///
+/// It also sums up the coverage of all replayed testcases, write it with [`CoverageDumpHook::write_report`].
+/// The summed up coverage starts with the [`instrumented_lines`], so lines no testcase hits are listed as well.
 #[derive(Debug, Clone)]
 pub struct CoverageDumpHook {
     output_dir: Option<PathBuf>,
+    coverage: LcovReport,
+    seeded: bool,
 }
 
 impl CoverageDumpHook {
     /// Create a new [`CoverageDumpHook`]
     ///
     /// Coverage will be dumped to lcov .info files in `output_dir` if provided.
+    /// Without `output_dir`, the hook does nothing.
     #[must_use]
     pub fn new(output_dir: Option<PathBuf>) -> Self {
-        Self { output_dir }
+        Self {
+            output_dir,
+            coverage: LcovReport::new(),
+            seeded: false,
+        }
+    }
+
+    /// The coverage of all testcases replayed so far.
+    /// Lines are counted once per testcase hitting them.
+    #[must_use]
+    pub fn coverage(&self) -> &LcovReport {
+        &self.coverage
+    }
+
+    /// Writes the coverage of all testcases replayed so far to `coverage.info`,
+    /// and as HTML report to the `html` directory, in `dir`
+    pub fn write_report<P: AsRef<Path>>(&self, dir: P) -> Result<(), Error> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        self.coverage.write_info(dir.join("coverage.info"))?;
+        self.coverage.write_html(dir.join("html"))
     }
 }
 
@@ -123,48 +194,57 @@ where
     }
 
     fn post_exec(&mut self, state: &mut S, _input: &I, id: CorpusId) -> Result<(), Error> {
-        if let Some(output_dir) = &self.output_dir {
-            let map = dump_covered_lines(true);
-            pcguard_disable_coverage_collection();
-
-            let corpus = state.corpus();
-            let testcase = corpus.get(id)?.borrow();
-            let filename_owned = testcase
-                .filename()
-                .clone()
-                .unwrap_or_else(|| format!("id_{id}"));
-            let filename_path = std::path::Path::new(&filename_owned);
-            let filename = filename_path
-                .file_name()
-                .unwrap_or(filename_path.as_os_str());
-
-            let output_path = output_dir.join(format!("{}.info", filename.to_string_lossy()));
-            let mut file = File::create(output_path)?;
-
-            let mut lcov_map: HashMap<String, Vec<SrcLoc>> = HashMap::new();
-            for loc in map {
-                if let Some(filename) = &loc.filename {
-                    lcov_map.entry(filename.clone()).or_default().push(loc);
-                } else {
-                    writeln!(file, "PC: {:x}", loc.pc)?;
-                }
-            }
+        let Some(output_dir) = &self.output_dir else {
+            return Ok(());
+        };
+        let map = dump_covered_lines(true);
+        pcguard_disable_coverage_collection();
 
-            for (filename, locs) in lcov_map {
-                writeln!(file, "TN:")?;
-                writeln!(file, "SF:{filename}")?;
-                for loc in &locs {
-                    if let Some(line) = loc.line {
-                        if let Some(func) = &loc.function {
-                            writeln!(file, "FN:{line},{func}")?;
-                            writeln!(file, "FNDA:{},{func}", loc.hits)?;
-                        }
-                        writeln!(file, "DA:{line},{}", loc.hits)?;
+        if !self.seeded {
+            self.coverage.merge(&instrumented_lines());
+            self.seeded = true;
+        }
+
+        let mut report = LcovReport::new();
+        let mut unresolved = Vec::new();
+        for loc in map {
+            match (&loc.filename, loc.line) {
+                (Some(filename), Some(line)) => {
+                    report.add_line(filename, line, loc.hits as u64);
+                    if let Some(func) = &loc.function {
+                        report.add_function(filename, func, line, loc.hits as u64);
                     }
                 }
-                writeln!(file, "end_of_record")?;
+                _ => unresolved.push(loc.pc),
             }
         }
+
+        for (filename, coverage) in report.files() {
+            for line in coverage.lines.keys() {
+                self.coverage.add_line(filename, *line, 1);
+            }
+            for (func, (line, _)) in &coverage.functions {
+                self.coverage.add_function(filename, func, *line, 1);
+            }
+        }
+
+        let corpus = state.corpus();
+        let testcase = corpus.get(id)?.borrow();
+        let filename_owned = testcase
+            .filename()
+            .clone()
+            .unwrap_or_else(|| format!("id_{id}"));
+        let filename_path = Path::new(&filename_owned);
+        let filename = filename_path
+            .file_name()
+            .unwrap_or(filename_path.as_os_str());
+
+        let output_path = output_dir.join(format!("{}.info", filename.to_string_lossy()));
+        let mut file = File::create(output_path)?;
+        for pc in unresolved {
+            writeln!(file, "PC: {pc:x}")?;
+        }
+        file.write_all(report.to_info().as_bytes())?;
         Ok(())
     }
 }
@@ -182,6 +262,8 @@ unsafe extern "C" fn __libafl_targets_trace_pc_guard_impl(_guard: *mut u32, pc:
 
 #[cfg(test)]
 mod tests {
+    use alloc::{boxed::Box, vec};
+
     use super::*;
 
     #[test]
@@ -207,4 +289,35 @@ mod tests {
             println!("PC: {:x} -> {:?}", loc.pc, loc.function);
         }
     }
+
+    #[test]
+    fn test_instrumented_lines() {
+        unsafe extern "C" {
+            fn __sanitizer_cov_pcs_init(pcs_beg: *const usize, pcs_end: *const usize);
+        }
+
+        // A PC table with the entry of this function, flagged as function entry
+        let table: &'static [usize] =
+            Box::leak(vec![test_instrumented_lines as fn() as usize, 1].into_boxed_slice());
+        // # Safety
+        // The table is a valid, leaked PC table with one entry.
+        unsafe {
+            __sanitizer_cov_pcs_init(table.as_ptr(), table.as_ptr().add(table.len()));
+        }
+
+        let report = instrumented_lines();
+        let (filename, file) = report
+            .files()
+            .iter()
+            .find(|(filename, _)| filename.ends_with("sancov_pcguard_dump_cov.rs"))
+            .expect("the PC of this function is not resolved");
+        assert!(!file.lines.is_empty());
+        assert!(file.lines.values().all(|hits| *hits == 0));
+        assert!(
+            file.functions
+                .keys()
+                .any(|func| func.contains("test_instrumented_lines")),
+            "no function entry in {filename}"
+        );
+    }
 }
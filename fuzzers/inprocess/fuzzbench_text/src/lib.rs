@@ -314,13 +314,19 @@ fn run_testcases(filenames: &[&str], _dump_coverage_dir: Option<PathBuf>) {
     .unwrap();
 
     #[cfg(feature = "dump_cov")]
-    let mut stage = ReplayStage::with_hook(CoverageDumpHook::new(_dump_coverage_dir));
+    let mut stage = ReplayStage::with_hook(CoverageDumpHook::new(_dump_coverage_dir.clone()));
     #[cfg(not(feature = "dump_cov"))]
     let mut stage = ReplayStage::new();
 
     stage
         .perform(&mut fuzzer, &mut executor, &mut state, &mut mgr)
         .unwrap();
+
+    // The summed up coverage of all testcases, as lcov and HTML
+    #[cfg(feature = "dump_cov")]
+    if let Some(dump_coverage_dir) = _dump_coverage_dir {
+        stage.hook().write_report(dump_coverage_dir).unwrap();
+    }
 }
 
 /// The actual fuzzer
@@ -0,0 +1,227 @@
+//! Symbolizes `DrCov` traces, for example of a corpus replayed with `QEMU` or `FRIDA`, to source lines,
+//! using the `DWARF` debug info of the traced modules, and writes an lcov `.info` file and an HTML report.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use addr2line::Loader;
+use clap::Parser;
+use libafl_targets::{
+    drcov::{DrCovModuleEntry, DrCovReader},
+    lcov::LcovReport,
+};
+use object::{Object, ObjectKind};
+use walkdir::WalkDir;
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+#[command(
+    name = "drcov_lcov",
+    about,
+    long_about = "Converts DrCov traces to an lcov .info file and an HTML coverage report, using the DWARF debug info of the traced modules"
+)]
+pub struct Opt {
+    #[arg(
+        short,
+        long,
+        help = "DrCov traces or directories to read, one trace per testcase",
+        required = true
+    )]
+    pub inputs: Vec<PathBuf>,
+
+    #[arg(
+        short,
+        long,
+        help = "The directory to write coverage.info and the html report to",
+        default_value = "coverage"
+    )]
+    pub out_dir: PathBuf,
+
+    #[arg(
+        short,
+        long,
+        help = "Only symbolize modules whose path contains one of these strings"
+    )]
+    pub modules: Vec<String>,
+}
+
+/// The debug info of a traced module
+struct Module {
+    loader: Loader,
+    /// Whether the debug info uses the runtime addresses, like non-PIE executables,
+    /// instead of offsets into the module
+    absolute: bool,
+}
+
+impl Module {
+    fn load(path: &Path) -> Option<Self> {
+        let data = fs::read(path)
+            .map_err(|err| eprintln!("Could not read module {}: {err}", path.display()))
+            .ok()?;
+        let absolute = object::File::parse(&*data).ok()?.kind() == ObjectKind::Executable;
+        let loader = Loader::new(path)
+            .map_err(|err| eprintln!("Could not load debug info of {}: {err}", path.display()))
+            .ok()?;
+        Some(Self { loader, absolute })
+    }
+
+    /// Adds every line of the module with `0` hits, to report the lines never hit
+    fn add_all_lines(&self, report: &mut LcovReport) {
+        let Some(text) = self.loader.get_section_range(b".text") else {
+            return;
+        };
+        if let Ok(locations) = self.loader.find_location_range(text.begin, text.end) {
+            for (_, _, location) in locations {
+                if let (Some(file), Some(line)) = (location.file, location.line) {
+                    report.add_line(file, line, 0);
+                }
+            }
+        }
+    }
+
+    /// The source lines of a block, and the function it is in
+    fn block_lines(&self, start: u64, end: u64) -> BlockLines {
+        let mut lines = Vec::new();
+        if let Ok(locations) = self.loader.find_location_range(start, end) {
+            for (_, _, location) in locations {
+                if let (Some(file), Some(line)) = (location.file, location.line) {
+                    lines.push((file.to_string(), line));
+                }
+            }
+        }
+
+        let function = self.loader.find_frames(start).ok().and_then(|mut frames| {
+            let frame = frames.next().ok()??;
+            let name = frame.function?.demangle().ok()?.into_owned();
+            let location = frame.location?;
+            Some((location.file?.to_string(), name, location.line?))
+        });
+        BlockLines { lines, function }
+    }
+}
+
+/// The source lines of a basic block
+#[derive(Clone, Default)]
+struct BlockLines {
+    lines: Vec<(String, u32)>,
+    /// The file, name and line of the function
+    function: Option<(String, String, u32)>,
+}
+
+#[derive(Default)]
+struct Symbolizer {
+    modules: HashMap<PathBuf, Option<Module>>,
+    blocks: HashMap<(PathBuf, u64), BlockLines>,
+}
+
+impl Symbolizer {
+    /// Loads the debug info of a module the first time it is seen
+    fn module(
+        &mut self,
+        entry: &DrCovModuleEntry,
+        filters: &[String],
+        report: &mut LcovReport,
+    ) -> Option<&Module> {
+        self.modules
+            .entry(entry.path.clone())
+            .or_insert_with(|| {
+                let path = entry.path.to_string_lossy();
+                if !filters.is_empty() && !filters.iter().any(|filter| path.contains(filter)) {
+                    return None;
+                }
+                let module = Module::load(&entry.path)?;
+                module.add_all_lines(report);
+                Some(module)
+            })
+            .as_ref()
+    }
+
+    /// Adds the lines hit by one trace, each line and function once
+    fn add_trace(&mut self, drcov: &DrCovReader, filters: &[String], report: &mut LcovReport) {
+        let mut lines = HashSet::new();
+        let mut functions = HashSet::new();
+        for block in drcov.basic_blocks() {
+            let Some(entry) = drcov
+                .module_entries
+                .iter()
+                .find(|entry| entry.base <= block.start && block.start < entry.end)
+            else {
+                continue;
+            };
+            let offset = block.start - entry.base;
+            let key = (entry.path.clone(), offset);
+            if !self.blocks.contains_key(&key) {
+                let block_lines = self
+                    .module(entry, filters, report)
+                    .map(|module| {
+                        let start = if module.absolute {
+                            block.start
+                        } else {
+                            offset + module.loader.relative_address_base()
+                        };
+                        module.block_lines(start, start + (block.end - block.start))
+                    })
+                    .unwrap_or_default();
+                self.blocks.insert(key.clone(), block_lines);
+            }
+
+            let block_lines = &self.blocks[&key];
+            lines.extend(block_lines.lines.iter().cloned());
+            functions.extend(block_lines.function.iter().cloned());
+        }
+
+        for (file, line) in lines {
+            report.add_line(&file, line, 1);
+        }
+        for (file, name, line) in functions {
+            report.add_function(&file, &name, line, 1);
+        }
+    }
+}
+
+fn main() {
+    env_logger::init();
+    let opts = Opt::parse();
+
+    let mut symbolizer = Symbolizer::default();
+    let mut report = LcovReport::new();
+    let mut traces = 0;
+
+    for input in &opts.inputs {
+        for entry in WalkDir::new(input).into_iter().filter_map(Result::ok) {
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let Ok(drcov) = DrCovReader::read(entry.path()).map_err(|err| {
+                eprintln!(
+                    "Ignored coverage file {}, reason: {err:?}",
+                    entry.path().display()
+                );
+            }) else {
+                continue;
+            };
+            symbolizer.add_trace(&drcov, &opts.modules, &mut report);
+            traces += 1;
+        }
+    }
+
+    fs::create_dir_all(&opts.out_dir).expect("Could not create the output directory");
+    report
+        .write_info(opts.out_dir.join("coverage.info"))
+        .expect("Could not write coverage.info");
+    report
+        .write_html(opts.out_dir.join("html"))
+        .expect("Could not write the html report");
+
+    let (found, hit) = report.files().values().fold((0, 0), |(found, hit), file| {
+        (found + file.lines_found(), hit + file.lines_hit())
+    });
+    println!(
+        "Symbolized {traces} traces: {hit} of {found} lines in {} files hit, report in {}",
+        report.files().len(),
+        opts.out_dir.display()
+    );
+}
@@ -0,0 +1,4 @@
+fn main() {
+    // Export the sancov callbacks of `libafl_targets` to the shared libraries loaded in-process
+    println!("cargo:rustc-link-arg-bins=-rdynamic");
+}
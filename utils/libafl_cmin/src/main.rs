@@ -0,0 +1,449 @@
+//! Corpus distillation, like `afl-cmin`.
+//!
+//! Executes every input of a corpus directory once, and copies a subset of the inputs
+//! with the same coverage to the output directory.
+//! Like `afl-cmin`, inputs that crash or time out are dropped, unless `--keep-failing` is set.
+
+extern crate alloc;
+
+use alloc::ffi::CString;
+use core::{
+    ffi::{CStr, c_char, c_int, c_void},
+    ptr,
+    time::Duration,
+};
+use std::{fs, path::PathBuf};
+
+use clap::{Parser, ValueEnum};
+use libafl::{
+    Error, HasScheduler,
+    corpus::{Corpus, CorpusCoverageReport, InMemoryCorpus, StdGreedyCorpusMinimizer, Testcase},
+    events::{EventFirer, SimpleEventManager},
+    executors::{
+        Executor, ExitKind, HasObservers, StdChildArgs, command::CommandExecutor,
+        forkserver::ForkserverExecutor, inprocess_fork::InProcessForkExecutor,
+    },
+    feedbacks::ConstFeedback,
+    fuzzer::StdFuzzer,
+    inputs::{BytesInput, HasTargetBytes, Input},
+    monitors::SimpleMonitor,
+    observers::{HitcountsMapObserver, ObserversTuple, StdMapObserver},
+    schedulers::{QueueScheduler, RemovableScheduler, Scheduler},
+    state::{HasCorpus, StdState},
+};
+use libafl_bolts::{
+    AsSlice, StdTargetArgs, Truncate, current_nanos,
+    rands::StdRand,
+    shmem::{ShMem, ShMemProvider, UnixShMemProvider},
+    tuples::{Handled, tuple_list},
+};
+use libafl_targets::{EDGES_MAP_ALLOCATED_SIZE, EDGES_MAP_PTR, edges_max_num};
+
+type Edges = HitcountsMapObserver<StdMapObserver<'static, u8, false>>;
+type State = StdState<InMemoryCorpus<BytesInput>, BytesInput, StdRand, InMemoryCorpus<BytesInput>>;
+type Greedy<E> = StdGreedyCorpusMinimizer<Edges, E, BytesInput, Edges, State, u8>;
+#[cfg(feature = "z3")]
+type Optimal<E> = libafl::corpus::StdCorpusMinimizer<Edges, E, BytesInput, Edges, State, u8>;
+
+/// `LLVMFuzzerTestOneInput`
+type HarnessFn = unsafe extern "C" fn(*const u8, usize) -> c_int;
+/// `LLVMFuzzerInitialize`
+type InitFn = unsafe extern "C" fn(*mut c_int, *mut *mut *mut c_char) -> c_int;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum TargetKind {
+    /// An `afl-cc` or `libafl_cc` instrumented binary, run as forkserver
+    Forkserver,
+    /// A binary writing its coverage to `__AFL_SHM_ID`, started once per input
+    Command,
+    /// A `trace-pc-guard` instrumented shared library exporting `LLVMFuzzerTestOneInput`
+    Inprocess,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Mode {
+    /// Keep the cheapest input for each covered map entry, fast
+    Greedy,
+    /// Find the smallest corpus with the same coverage, needs the `z3` feature
+    Optimal,
+}
+
+/// The commandline args this tool accepts
+#[derive(Debug, Parser)]
+#[command(
+    name = "libafl_cmin",
+    about = "Minimizes a corpus to a subset with the same coverage, like afl-cmin"
+)]
+struct Opt {
+    #[arg(
+        help = "The directory to read the corpus from",
+        short = 'i',
+        long = "input",
+        required = true
+    )]
+    input: PathBuf,
+
+    #[arg(
+        help = "The directory to write the minimized corpus to",
+        short = 'o',
+        long = "output",
+        required = true
+    )]
+    output: PathBuf,
+
+    #[arg(
+        help = "How the target is run",
+        short = 'k',
+        long = "kind",
+        value_enum,
+        default_value = "forkserver"
+    )]
+    kind: TargetKind,
+
+    #[arg(
+        help = "The minimization algorithm",
+        short = 'm',
+        long = "mode",
+        value_enum,
+        default_value = "greedy"
+    )]
+    mode: Mode,
+
+    #[arg(
+        help = "Writes the map entries covered by each kept input to this JSON file",
+        short = 'r',
+        long = "report"
+    )]
+    report: Option<PathBuf>,
+
+    #[arg(
+        help = "Timeout for each individual execution, in milliseconds",
+        short = 't',
+        long = "timeout",
+        default_value = "1000"
+    )]
+    timeout: u64,
+
+    #[arg(
+        help = "The size of the coverage map for forkserver and command targets",
+        long = "map-size",
+        default_value = "65536"
+    )]
+    map_size: usize,
+
+    #[arg(
+        help = "Keep inputs that crash or time out, by default they are dropped like in afl-cmin",
+        long = "keep-failing",
+        default_value = "false"
+    )]
+    keep_failing: bool,
+
+    #[arg(
+        help = "If not set, the child's stdout and stderror will be redirected to /dev/null",
+        short = 'd',
+        long = "debug-child",
+        default_value = "false"
+    )]
+    debug_child: bool,
+
+    #[arg(
+        help = "The target and its arguments, `@@` is replaced by the input file, for in-process targets the shared library",
+        name = "TARGET",
+        num_args(1..),
+        allow_hyphen_values = true,
+        required = true
+    )]
+    target: Vec<String>,
+}
+
+/// The minimizers, created before the observer moves into the executor
+struct Minimizers<E> {
+    greedy: Greedy<E>,
+    #[cfg(feature = "z3")]
+    optimal: Optimal<E>,
+}
+
+impl<E> Minimizers<E> {
+    fn new(edges: &Edges, keep_failing: bool) -> Self {
+        Self {
+            greedy: Greedy::new(edges).with_remove_failing(!keep_failing),
+            #[cfg(feature = "z3")]
+            optimal: Optimal::new(edges),
+        }
+    }
+}
+
+pub fn main() -> Result<(), Error> {
+    env_logger::init();
+    let opt = Opt::parse();
+    let timeout = Duration::from_millis(opt.timeout);
+
+    let mut shmem_provider = UnixShMemProvider::new()?;
+    let map_size = if opt.kind == TargetKind::Inprocess {
+        EDGES_MAP_ALLOCATED_SIZE
+    } else {
+        opt.map_size
+    };
+    let mut shmem = shmem_provider.new_shmem(map_size)?;
+    // The shmem lives until the end of main
+    let edges_observer = unsafe {
+        HitcountsMapObserver::new(StdMapObserver::from_mut_ptr(
+            "edges",
+            shmem.as_mut_ptr(),
+            map_size,
+        ))
+    };
+    let edges_handle = edges_observer.handle();
+
+    let mut feedback = ConstFeedback::new(false);
+    let mut objective = ConstFeedback::new(false);
+    let mut state = StdState::new(
+        StdRand::with_seed(current_nanos()),
+        InMemoryCorpus::new(),
+        InMemoryCorpus::new(),
+        &mut feedback,
+        &mut objective,
+    )?;
+    let mut fuzzer = StdFuzzer::new(QueueScheduler::new(), feedback, objective);
+    let mut mgr = SimpleEventManager::new(SimpleMonitor::new(|s| log::info!("{s}")));
+
+    load_corpus(&opt, &mut fuzzer, &mut state)?;
+
+    match opt.kind {
+        TargetKind::Forkserver => {
+            unsafe {
+                shmem.write_to_env("__AFL_SHM_ID")?;
+            }
+            let minimizers = Minimizers::new(&edges_observer, opt.keep_failing);
+            let mut executor = ForkserverExecutor::builder()
+                .debug_child(opt.debug_child)
+                .shmem_provider(&mut shmem_provider)
+                .parse_afl_cmdline(&opt.target)
+                .coverage_map_size(map_size)
+                .timeout(timeout)
+                .build(tuple_list!(edges_observer))?;
+            if let Some(dynamic_map_size) = executor.coverage_map_size() {
+                executor.observers_mut()[&edges_handle]
+                    .as_mut()
+                    .truncate(dynamic_map_size);
+            }
+            distill(
+                &opt,
+                &minimizers,
+                &mut fuzzer,
+                &mut executor,
+                &mut state,
+                &mut mgr,
+            )
+        }
+        TargetKind::Command => {
+            unsafe {
+                shmem.write_to_env("__AFL_SHM_ID")?;
+            }
+            let minimizers = Minimizers::new(&edges_observer, opt.keep_failing);
+            let mut executor = CommandExecutor::builder()
+                .debug_child(opt.debug_child)
+                .parse_afl_cmdline(&opt.target)
+                .timeout(timeout)
+                .build(tuple_list!(edges_observer))?;
+            distill(
+                &opt,
+                &minimizers,
+                &mut fuzzer,
+                &mut executor,
+                &mut state,
+                &mut mgr,
+            )
+        }
+        TargetKind::Inprocess => {
+            // The guards get their map indexes when the library is loaded
+            unsafe {
+                EDGES_MAP_PTR = shmem.as_mut_ptr();
+            }
+            let harness_fn = load_library(&opt.target)?;
+            let minimizers = Minimizers::new(&edges_observer, opt.keep_failing);
+            let mut harness = |input: &BytesInput| {
+                let target = input.target_bytes();
+                let buf = target.as_slice();
+                unsafe {
+                    harness_fn(buf.as_ptr(), buf.len());
+                }
+                ExitKind::Ok
+            };
+            let mut executor = InProcessForkExecutor::new(
+                &mut harness,
+                tuple_list!(edges_observer),
+                &mut fuzzer,
+                &mut state,
+                &mut mgr,
+                timeout,
+                shmem_provider.clone(),
+            )?;
+            executor.observers_mut()[&edges_handle]
+                .as_mut()
+                .truncate(edges_max_num());
+            distill(
+                &opt,
+                &minimizers,
+                &mut fuzzer,
+                &mut executor,
+                &mut state,
+                &mut mgr,
+            )
+        }
+    }
+}
+
+/// Adds every file of the input directory to the corpus, without executing it
+fn load_corpus<Z>(opt: &Opt, fuzzer: &mut Z, state: &mut State) -> Result<(), Error>
+where
+    Z: HasScheduler<BytesInput, State>,
+{
+    let mut entries = fs::read_dir(&opt.input)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(fs::DirEntry::file_name);
+    for entry in entries {
+        let path = entry.path();
+        if !path.is_file() {
+            continue;
+        }
+        let mut testcase = Testcase::new(BytesInput::from_file(&path)?);
+        *testcase.filename_mut() = Some(entry.file_name().to_string_lossy().into_owned());
+        let id = state.corpus_mut().add(testcase)?;
+        fuzzer.scheduler_mut().on_add(state, id)?;
+    }
+    if state.corpus().count() == 0 {
+        return Err(Error::illegal_argument(format!(
+            "No inputs found in {}",
+            opt.input.display()
+        )));
+    }
+    Ok(())
+}
+
+/// Loads the in-process target, and runs its `LLVMFuzzerInitialize`, if any
+fn load_library(target: &[String]) -> Result<HarnessFn, Error> {
+    let args = target
+        .iter()
+        .map(|arg| CString::new(arg.as_str()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| Error::illegal_argument(format!("Invalid target argument: {err}")))?;
+    unsafe {
+        let handle = libc::dlopen(args[0].as_ptr(), libc::RTLD_NOW | libc::RTLD_GLOBAL);
+        if handle.is_null() {
+            return Err(Error::illegal_argument(format!(
+                "Could not load {}: {}",
+                target[0],
+                CStr::from_ptr(libc::dlerror()).to_string_lossy()
+            )));
+        }
+
+        let init = libc::dlsym(handle, c"LLVMFuzzerInitialize".as_ptr());
+        if !init.is_null() {
+            let init = core::mem::transmute::<*mut c_void, InitFn>(init);
+            let mut argv = args
+                .iter()
+                .map(|arg| arg.as_ptr().cast_mut())
+                .chain([ptr::null_mut()])
+                .collect::<Vec<_>>();
+            #[expect(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+            let mut argc = args.len() as c_int;
+            let mut argv_ptr = argv.as_mut_ptr();
+            init(&raw mut argc, &raw mut argv_ptr);
+        }
+
+        let harness = libc::dlsym(handle, c"LLVMFuzzerTestOneInput".as_ptr());
+        if harness.is_null() {
+            return Err(Error::illegal_argument(format!(
+                "{} does not export LLVMFuzzerTestOneInput",
+                target[0]
+            )));
+        }
+        Ok(core::mem::transmute::<*mut c_void, HarnessFn>(harness))
+    }
+}
+
+/// Minimizes the corpus, then writes the kept inputs and the report
+fn distill<CS, E, EM, Z>(
+    opt: &Opt,
+    minimizers: &Minimizers<E>,
+    fuzzer: &mut Z,
+    executor: &mut E,
+    state: &mut State,
+    mgr: &mut EM,
+) -> Result<(), Error>
+where
+    E: Executor<EM, BytesInput, State, Z> + HasObservers,
+    E::Observers: ObserversTuple<BytesInput, State>,
+    CS: Scheduler<BytesInput, State> + RemovableScheduler<BytesInput, State>,
+    EM: EventFirer<BytesInput, State>,
+    Z: HasScheduler<BytesInput, State, Scheduler = CS>,
+{
+    let total = state.corpus().count();
+    let report = match opt.mode {
+        Mode::Greedy => minimizers.greedy.minimize(fuzzer, executor, mgr, state)?,
+        #[cfg(feature = "z3")]
+        Mode::Optimal => {
+            // The optimal minimizer does not drop failing inputs, so remove them and record the coverage first
+            let mut report = minimizers
+                .greedy
+                .coverage_report(fuzzer, executor, mgr, state)?;
+            minimizers.optimal.minimize(fuzzer, executor, mgr, state)?;
+            report
+                .seeds
+                .retain(|seed| state.corpus().get(seed.id).is_ok());
+            report.removed = total - state.corpus().count();
+            report
+        }
+        #[cfg(not(feature = "z3"))]
+        Mode::Optimal => {
+            return Err(Error::illegal_argument(
+                "The optimal mode needs libafl_cmin built with the z3 feature",
+            ));
+        }
+    };
+
+    fs::create_dir_all(&opt.output)?;
+    for id in state.corpus().ids() {
+        let testcase = state.corpus().get(id)?.borrow();
+        let filename = testcase
+            .filename()
+            .clone()
+            .unwrap_or_else(|| id.to_string());
+        testcase
+            .input()
+            .as_ref()
+            .ok_or_else(|| Error::empty_optional("Testcase without input"))?
+            .to_file(opt.output.join(filename))?;
+    }
+    println!(
+        "Kept {} of {total} inputs in {}, {} inputs crashed or timed out",
+        state.corpus().count(),
+        opt.output.display(),
+        report.failing
+    );
+
+    if let Some(path) = &opt.report {
+        write_report(path, &report, state)?;
+    }
+    Ok(())
+}
+
+/// Writes the covered map entries of each kept input, by file name
+fn write_report(path: &PathBuf, report: &CorpusCoverageReport, state: &State) -> Result<(), Error> {
+    let mut seeds = Vec::with_capacity(report.seeds.len());
+    for seed in &report.seeds {
+        let filename = state.corpus().get(seed.id)?.borrow().filename().clone();
+        seeds.push(serde_json::json!({
+            "file": filename,
+            "edges": seed.edges,
+        }));
+    }
+    let json = serde_json::json!({
+        "removed": report.removed,
+        "seeds": seeds,
+    });
+    let json =
+        serde_json::to_string_pretty(&json).map_err(|err| Error::serialize(err.to_string()))?;
+    fs::write(path, json)?;
+    Ok(())
+}
@@ -0,0 +1,319 @@
+//! Renders the time series recorded by the `OnDiskTimeSeriesMonitor` to SVG charts,
+//! with one line per campaign, to compare campaigns side by side.
+
+use core::{fmt::Write as _, time::Duration};
+use std::{fs, path::PathBuf};
+
+use clap::Parser;
+use libafl::monitors::TimeSeries;
+use libafl_bolts::ClientId;
+
+const WIDTH: f64 = 800.0;
+const HEIGHT: f64 = 450.0;
+const MARGIN_LEFT: f64 = 80.0;
+const MARGIN_RIGHT: f64 = 20.0;
+const MARGIN_TOP: f64 = 40.0;
+const MARGIN_BOTTOM: f64 = 50.0;
+const TICKS: u32 = 5;
+const COLORS: [&str; 8] = [
+    "#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd", "#8c564b", "#e377c2", "#7f7f7f",
+];
+
+/// The commandline args this tool accepts
+#[derive(Debug, Parser)]
+#[command(
+    name = "libafl_plot",
+    about = "Plots the stats of one or several campaigns, recorded by the OnDiskTimeSeriesMonitor, to SVG charts"
+)]
+struct Opt {
+    #[arg(
+        help = "The time series files, as `label=path` or `path`",
+        name = "CAMPAIGNS",
+        required = true
+    )]
+    campaigns: Vec<String>,
+
+    #[arg(
+        help = "The stats to plot, one chart each",
+        short = 'm',
+        long = "metric",
+        default_values = ["edges", "executions", "corpus", "exec_sec"]
+    )]
+    metrics: Vec<String>,
+
+    #[arg(
+        help = "Plot the stats of this client, instead of the global stats",
+        short = 'c',
+        long = "client"
+    )]
+    client: Option<u32>,
+
+    #[arg(
+        help = "The directory to write the charts to",
+        short = 'o',
+        long = "out-dir",
+        default_value = "plots"
+    )]
+    out_dir: PathBuf,
+
+    #[arg(
+        help = "Only list the recorded stats and clients",
+        short = 'l',
+        long = "list"
+    )]
+    list: bool,
+}
+
+/// One line of a chart
+struct Line {
+    label: String,
+    points: Vec<(Duration, f64)>,
+}
+
+fn main() {
+    let opt = Opt::parse();
+
+    let mut campaigns = Vec::new();
+    for campaign in &opt.campaigns {
+        let (label, path) = parse_campaign(campaign);
+        let series = TimeSeries::read(&path)
+            .unwrap_or_else(|err| panic!("Could not read {}: {err:?}", path.display()));
+        campaigns.push((label, series));
+    }
+
+    if opt.list {
+        for (label, series) in &campaigns {
+            println!("{label}:");
+            println!("  stats: {}", series.names().join(", "));
+            let clients = series
+                .clients()
+                .iter()
+                .map(|client| client.0.to_string())
+                .collect::<Vec<_>>();
+            println!("  clients: {}", clients.join(", "));
+        }
+        return;
+    }
+
+    fs::create_dir_all(&opt.out_dir).expect("Could not create the output directory");
+    for metric in &opt.metrics {
+        let lines = campaigns
+            .iter()
+            .map(|(label, series)| Line {
+                label: label.clone(),
+                points: series.series(metric, opt.client.map(ClientId)),
+            })
+            .filter(|line| !line.points.is_empty())
+            .collect::<Vec<_>>();
+        if lines.is_empty() {
+            eprintln!("No campaign recorded {metric}, skipping it");
+            continue;
+        }
+
+        let path = opt.out_dir.join(chart_filename(metric));
+        fs::write(&path, render(metric, &lines)).expect("Could not write the chart");
+        println!("Wrote {}", path.display());
+    }
+}
+
+/// Splits a campaign argument into its label and path, the label defaults to the file name
+fn parse_campaign(campaign: &str) -> (String, PathBuf) {
+    if let Some((label, path)) = campaign.split_once('=') {
+        (label.to_string(), PathBuf::from(path))
+    } else {
+        let path = PathBuf::from(campaign);
+        let label = path.file_name().map_or_else(
+            || campaign.to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+        (label, path)
+    }
+}
+
+/// The file name of the chart of `metric`
+fn chart_filename(metric: &str) -> String {
+    let filename: String = metric
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("{filename}.svg")
+}
+
+/// Formats large values as `1.5k`, `2.3M`, ...
+fn format_value(value: f64) -> String {
+    let abs = value.abs();
+    if abs >= 1e9 {
+        format!("{:.1}G", value / 1e9)
+    } else if abs >= 1e6 {
+        format!("{:.1}M", value / 1e6)
+    } else if abs >= 1e3 {
+        format!("{:.1}k", value / 1e3)
+    } else if abs >= 10.0 || value.fract().abs() < f64::EPSILON {
+        format!("{value:.0}")
+    } else {
+        format!("{value:.2}")
+    }
+}
+
+/// Formats a run time as `1h30m`, `15m`, `40s`
+fn format_time(time: Duration) -> String {
+    let secs = time.as_secs();
+    if secs >= 3600 {
+        format!("{}h{:02}m", secs / 3600, secs % 3600 / 60)
+    } else if secs >= 60 {
+        format!("{}m", secs / 60)
+    } else {
+        format!("{secs}s")
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Renders a line chart of `metric` over time, with one line per campaign
+fn render(metric: &str, lines: &[Line]) -> String {
+    let max_time = lines
+        .iter()
+        .flat_map(|line| line.points.iter().map(|(time, _)| time.as_secs_f64()))
+        .fold(1.0, f64::max);
+    let max_value = lines
+        .iter()
+        .flat_map(|line| line.points.iter().map(|(_, value)| *value))
+        .fold(0.0, f64::max)
+        .max(1.0);
+
+    let plot_width = WIDTH - MARGIN_LEFT - MARGIN_RIGHT;
+    let plot_height = HEIGHT - MARGIN_TOP - MARGIN_BOTTOM;
+    let x = |time: f64| MARGIN_LEFT + time / max_time * plot_width;
+    let y = |value: f64| MARGIN_TOP + plot_height - value / max_value * plot_height;
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{WIDTH}\" height=\"{HEIGHT}\" font-family=\"sans-serif\" font-size=\"12\">"
+    )
+    .unwrap();
+    writeln!(
+        svg,
+        "<rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n<text x=\"{}\" y=\"24\" text-anchor=\"middle\" font-size=\"16\">{}</text>",
+        WIDTH / 2.0,
+        escape(metric)
+    )
+    .unwrap();
+
+    // Grid and axis labels
+    for tick in 0..=TICKS {
+        let fraction = f64::from(tick) / f64::from(TICKS);
+        let (tick_y, tick_x) = (y(fraction * max_value), x(fraction * max_time));
+        writeln!(
+            svg,
+            "<line x1=\"{MARGIN_LEFT}\" y1=\"{tick_y:.1}\" x2=\"{:.1}\" y2=\"{tick_y:.1}\" stroke=\"#ddd\"/>\n\
+             <text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"end\">{}</text>\n\
+             <text x=\"{tick_x:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>",
+            WIDTH - MARGIN_RIGHT,
+            MARGIN_LEFT - 6.0,
+            tick_y + 4.0,
+            format_value(fraction * max_value),
+            HEIGHT - MARGIN_BOTTOM + 18.0,
+            format_time(Duration::from_secs_f64(fraction * max_time))
+        )
+        .unwrap();
+    }
+    writeln!(
+        svg,
+        "<rect x=\"{MARGIN_LEFT}\" y=\"{MARGIN_TOP}\" width=\"{plot_width}\" height=\"{plot_height}\" fill=\"none\" stroke=\"black\"/>\n\
+         <text x=\"{}\" y=\"{}\" text-anchor=\"middle\">run time</text>",
+        MARGIN_LEFT + plot_width / 2.0,
+        HEIGHT - 10.0
+    )
+    .unwrap();
+
+    // One line and legend entry per campaign
+    for (idx, line) in lines.iter().enumerate() {
+        let color = COLORS[idx % COLORS.len()];
+        let points = line
+            .points
+            .iter()
+            .map(|(time, value)| format!("{:.1},{:.1}", x(time.as_secs_f64()), y(*value)))
+            .collect::<Vec<_>>()
+            .join(" ");
+        #[expect(clippy::cast_precision_loss)]
+        let legend_y = MARGIN_TOP + 16.0 + idx as f64 * 16.0;
+        writeln!(
+            svg,
+            "<polyline points=\"{points}\" fill=\"none\" stroke=\"{color}\" stroke-width=\"2\"/>\n\
+             <rect x=\"{:.1}\" y=\"{:.1}\" width=\"12\" height=\"3\" fill=\"{color}\"/>\n\
+             <text x=\"{:.1}\" y=\"{legend_y:.1}\">{}</text>",
+            MARGIN_LEFT + 10.0,
+            legend_y - 4.0,
+            MARGIN_LEFT + 28.0,
+            escape(&line.label)
+        )
+        .unwrap();
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+    use std::path::PathBuf;
+
+    use crate::{Line, chart_filename, format_time, format_value, parse_campaign, render};
+
+    #[test]
+    fn test_parse_campaign() {
+        assert_eq!(
+            parse_campaign("base=runs/a.ts"),
+            ("base".to_string(), PathBuf::from("runs/a.ts"))
+        );
+        assert_eq!(
+            parse_campaign("runs/b.ts"),
+            ("b.ts".to_string(), PathBuf::from("runs/b.ts"))
+        );
+        assert_eq!(chart_filename("perf_Mutate/x"), "perf_Mutate_x.svg");
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(format_value(3.0), "3");
+        assert_eq!(format_value(2.5), "2.50");
+        assert_eq!(format_value(42.4), "42");
+        assert_eq!(format_value(1500.0), "1.5k");
+        assert_eq!(format_value(2_300_000.0), "2.3M");
+        assert_eq!(format_value(4e9), "4.0G");
+        assert_eq!(format_time(Duration::from_secs(40)), "40s");
+        assert_eq!(format_time(Duration::from_secs(15 * 60 + 3)), "15m");
+        assert_eq!(format_time(Duration::from_mins(90)), "1h30m");
+    }
+
+    #[test]
+    fn test_render() {
+        let lines = [
+            Line {
+                label: "a<b".to_string(),
+                points: vec![(Duration::ZERO, 0.0), (Duration::from_secs(10), 100.0)],
+            },
+            Line {
+                label: "c".to_string(),
+                points: vec![(Duration::from_secs(5), 50.0)],
+            },
+        ];
+        let svg = render("edges", &lines);
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<polyline").count(), 2);
+        // The first point is at the origin of the plot, the last one at the top right
+        assert!(svg.contains("points=\"80.0,400.0 780.0,40.0\""));
+        assert!(svg.contains("points=\"430.0,220.0\""));
+        assert!(svg.contains(">a&lt;b</text>"));
+        assert!(svg.contains(">edges</text>"));
+        assert!(svg.contains(">100</text>"));
+        assert!(svg.contains(">10s</text>"));
+    }
+}